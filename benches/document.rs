@@ -0,0 +1,67 @@
+//! Rough timings of `Documenter` on a large value, without external dependencies.
+//!
+//! Run with `cargo bench --bench document`.
+
+use serde_yaml::{Mapping, Value};
+use std::hint::black_box;
+use std::time::Instant;
+use yaml_extras::document::{CommentPlacement, Documenter};
+
+/// A mapping of `width` keys on `depth` levels, with lists and descriptions on every other key
+fn generate(width: usize, depth: usize) -> (Value, Value) {
+    let mut value = Mapping::new();
+    let mut desc = Mapping::new();
+    for i in 0..width {
+        let key = Value::from(format!("key_{i}"));
+        if depth > 0 {
+            let (v, d) = generate(width, depth - 1);
+            value.insert(key.clone(), v);
+            desc.insert(key, d);
+        } else {
+            let v = match i % 3 {
+                0 => Value::from(i as u64),
+                1 => Value::from(format!("value number {i}")),
+                _ => Value::Sequence(vec![Value::from(1), Value::from("two"), Value::from(3.0)]),
+            };
+            value.insert(key.clone(), v);
+            if i % 2 == 0 {
+                desc.insert(key, Value::from(format!("Description of setting {i}")));
+            }
+        }
+    }
+    (Value::Mapping(value), Value::Mapping(desc))
+}
+
+fn bench(name: &str, iterations: u32, f: impl Fn() -> String) {
+    // Warm up
+    black_box(f());
+    let start = Instant::now();
+    for _ in 0..iterations {
+        black_box(f());
+    }
+    let elapsed = start.elapsed();
+    println!("{name:<24} {:>10.3?} per iteration", elapsed / iterations);
+}
+
+fn main() {
+    let (value, desc) = generate(10, 3);
+    let iterations = 20;
+
+    let d = Documenter::new();
+    bench("default", iterations, || d.apply_value(&value, Some(&desc)).unwrap());
+
+    let d = Documenter::new()
+        .comment_placement(CommentPlacement::Inline)
+        .align_comments(true);
+    bench("inline, aligned", iterations, || d.apply_value(&value, Some(&desc)).unwrap());
+
+    let d = Documenter::new()
+        .format_mapping(|args| {
+            let s = args.inner.join("\n");
+            if args.path.is_empty() { s } else { format!("\n{s}") }
+        });
+    bench("custom format_mapping", iterations, || d.apply_value(&value, Some(&desc)).unwrap());
+
+    let d = Documenter::new();
+    bench("markdown", iterations, || d.apply_markdown(&value, Some(&desc)).unwrap());
+}
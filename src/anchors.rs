@@ -0,0 +1,251 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::error::{Error, Result};
+
+use serde::ser;
+use serde_yaml::{Mapping, Value};
+
+use std::collections::{HashMap, HashSet};
+
+/// The number of nodes of `v`
+fn size(v: &Value) -> usize {
+    match v {
+        Value::Mapping(m) => 1 + m.iter().map(|(k, v)| size(k) + size(v)).sum::<usize>(),
+        Value::Sequence(s) => 1 + s.iter().map(size).sum::<usize>(),
+        Value::Tagged(t) => size(&t.value),
+        _ => 1,
+    }
+}
+
+/// A non-empty collection, possibly tagged
+pub(crate) fn is_collection(v: &Value) -> bool {
+    match v {
+        Value::Mapping(m) => !m.is_empty(),
+        Value::Sequence(s) => !s.is_empty(),
+        Value::Tagged(t) => is_collection(&t.value),
+        _ => false,
+    }
+}
+
+/// The children of a collection
+fn children(v: &Value) -> Box<dyn Iterator<Item = &Value> + '_> {
+    match v {
+        Value::Mapping(m) => Box::new(m.values()),
+        Value::Sequence(s) => Box::new(s.iter()),
+        Value::Tagged(t) => children(&t.value),
+        _ => Box::new(std::iter::empty()),
+    }
+}
+
+/// A scalar, without its trailing newline, the lines after the first one being indented
+/// with `indent` spaces
+fn scalar(v: &Value, indent: usize) -> Result<String> {
+    let s = serde_yaml::to_string(v)?;
+    let s = s.strip_suffix('\n').unwrap_or(&s);
+    let prefix = " ".repeat(indent);
+    Ok(s.split('\n')
+        .enumerate()
+        .map(|(i, line)| if i == 0 || line.is_empty() { line.to_owned() } else { format!("{prefix}{line}") })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+struct Emitter<'v> {
+    /// The subtrees that appear more than once
+    repeated: HashSet<&'v Value>,
+    /// The names of the anchors already written
+    names: HashMap<&'v Value, String>,
+    out: String,
+}
+
+impl<'v> Emitter<'v> {
+    /// Finds the nodes to anchor: aliased subtrees are not looked into
+    fn find_repeated(&mut self, v: &'v Value, min_size: usize, seen: &mut HashSet<&'v Value>) {
+        if is_collection(v) && size(v) >= min_size && !seen.insert(v) {
+            self.repeated.insert(v);
+            return;
+        }
+        for child in children(v) {
+            self.find_repeated(child, min_size, seen);
+        }
+    }
+
+    /// Writes a node after a key or a dash, `indent` being the one of its content
+    fn node(&mut self, v: &'v Value, indent: usize) -> Result<()> {
+        if self.repeated.contains(v) {
+            if let Some(name) = self.names.get(v) {
+                self.out.push_str(&format!(" *{name}\n"));
+                return Ok(());
+            }
+            let name = format!("a{}", self.names.len() + 1);
+            self.out.push_str(&format!(" &{name}"));
+            self.names.insert(v, name);
+        }
+        let inner = match v {
+            Value::Tagged(t) if is_collection(&t.value) => {
+                self.out.push_str(&format!(" {}", t.tag));
+                &t.value
+            },
+            v => v,
+        };
+        match inner {
+            Value::Mapping(m) if !m.is_empty() => {
+                self.out.push('\n');
+                self.mapping(m, indent, false)
+            },
+            Value::Sequence(s) if !s.is_empty() => {
+                self.out.push('\n');
+                self.sequence(s, indent, false)
+            },
+            _ => {
+                let s = scalar(v, indent - 2)?;
+                self.out.push_str(&format!(" {s}\n"));
+                Ok(())
+            },
+        }
+    }
+
+    /// Writes the entries of a mapping, the first one without indentation if `inline`
+    fn mapping(&mut self, m: &'v Mapping, indent: usize, inline: bool) -> Result<()> {
+        for (i, (k, v)) in m.iter().enumerate() {
+            if is_collection(k) || matches!(k, Value::Mapping(_) | Value::Sequence(_)) {
+                return Err(Error::Yaml(ser::Error::custom("only scalar keys can be written with anchors")));
+            }
+            let key = scalar(k, 0)?;
+            if key.contains('\n') {
+                return Err(Error::Yaml(ser::Error::custom("multiline keys can't be written with anchors")));
+            }
+            if i > 0 || !inline {
+                self.out.push_str(&" ".repeat(indent));
+            }
+            self.out.push_str(&format!("{key}:"));
+            self.node(v, indent + 2)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the items of a sequence, the first one without indentation if `inline`
+    fn sequence(&mut self, s: &'v [Value], indent: usize, inline: bool) -> Result<()> {
+        for (i, item) in s.iter().enumerate() {
+            if i > 0 || !inline {
+                self.out.push_str(&" ".repeat(indent));
+            }
+            self.out.push('-');
+            // Plain collections start on the same line as the dash
+            match item {
+                Value::Mapping(m) if !m.is_empty() && !self.repeated.contains(item) => {
+                    self.out.push(' ');
+                    self.mapping(m, indent + 2, true)?;
+                },
+                Value::Sequence(s) if !s.is_empty() && !self.repeated.contains(item) => {
+                    self.out.push(' ');
+                    self.sequence(s, indent + 2, true)?;
+                },
+                item => self.node(item, indent + 2)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Serializes a value to a YAML string, writing the subtrees that appear several times only
+/// once, with an anchor, and then as aliases
+///
+/// Only mappings and sequences of at least `min_size` nodes (counting keys, values and
+/// items) are anchored. Keys must be scalars.
+///
+/// # Example
+///
+/// ```
+/// let config: serde_yaml::Value = serde_yaml::from_str("
+/// web: {limits: {cpu: 2, memory: 4G}, port: 80}
+/// api: {limits: {cpu: 2, memory: 4G}, port: 81}
+/// ")?;
+/// let s = yaml_extras::to_string_with_anchors(&config, 3)?;
+/// assert_eq!(s, "\
+/// web:
+///   limits: &a1
+///     cpu: 2
+///     memory: 4G
+///   port: 80
+/// api:
+///   limits: *a1
+///   port: 81
+/// ");
+/// assert_eq!(serde_yaml::from_str::<serde_yaml::Value>(&s)?, config);
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn to_string_with_anchors(value: &Value, min_size: usize) -> Result<String> {
+    let mut emitter = Emitter {
+        repeated: HashSet::new(),
+        names: HashMap::new(),
+        out: String::new(),
+    };
+    let mut seen = HashSet::new();
+    seen.insert(value);
+    for child in children(value) {
+        emitter.find_repeated(child, min_size, &mut seen);
+    }
+    let root = match value {
+        Value::Tagged(t) if is_collection(&t.value) => {
+            emitter.out.push_str(&format!("{}\n", t.tag));
+            &t.value
+        },
+        v => v,
+    };
+    match root {
+        Value::Mapping(m) if !m.is_empty() => emitter.mapping(m, 0, false)?,
+        Value::Sequence(s) if !s.is_empty() => emitter.sequence(s, 0, false)?,
+        _ => return Ok(serde_yaml::to_string(value)?),
+    }
+    Ok(emitter.out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yaml;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn anchors() {
+        let v = yaml(r#"
+a: {x: [1, 2], y: "multi\nline\n"}
+b: [{x: [1, 2], y: "multi\nline\n"}, [1, 2], !t [1, 2], []]
+c: !t [1, 2]
+d: {z: {x: [1, 2], y: "multi\nline\n"}}
+'key: 1': 'yes'
+"#);
+        let s = to_string_with_anchors(&v, 3).unwrap();
+        assert_eq!(s, r#"a: &a1
+  x: &a2
+    - 1
+    - 2
+  y: |
+    multi
+    line
+b:
+  - *a1
+  - *a2
+  - &a3 !t
+    - 1
+    - 2
+  - []
+c: *a3
+d:
+  z: *a1
+'key: 1': yes
+"#);
+        assert_eq!(yaml(&s), v);
+
+        let s = to_string_with_anchors(&v, 100).unwrap();
+        assert!(!s.contains('&'));
+        assert_eq!(yaml(&s), v);
+        let v = yaml("[[[1, 2], [1, 2]], [[1, 2], [1, 2]], {? [1]: 2}]");
+        assert_eq!(yaml(&to_string_with_anchors(&v[0], 1).unwrap()), v[0]);
+        assert!(to_string_with_anchors(&v, 1).is_err());
+        assert_eq!(to_string_with_anchors(&yaml("!t 'x'"), 1).unwrap(), "!t x\n");
+    }
+}
@@ -0,0 +1,217 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Deserialization from a `Value` that remembers the path at which an error occurred.
+//!
+//! `Track` wraps the deserializer of `&Value` from `serde_yaml`, only taking over mappings,
+//! sequences and options so that the path of nested values is known.
+
+use crate::error::{Error, Result};
+use crate::path::key_token;
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Visitor};
+use serde_yaml::Value;
+
+use std::cell::RefCell;
+
+#[derive(Default)]
+struct State {
+    path: Vec<String>,
+    /// The path of the innermost error, which is the first one to be seen
+    error_path: Option<Vec<String>>,
+}
+
+#[derive(Clone, Copy)]
+struct Track<'de, 's> {
+    value: &'de Value,
+    state: &'s RefCell<State>,
+}
+
+impl<'s> Track<'_, 's> {
+    fn record<T>(&self, result: std::result::Result<T, serde_yaml::Error>) -> std::result::Result<T, serde_yaml::Error> {
+        if result.is_err() {
+            let mut state = self.state.borrow_mut();
+            if state.error_path.is_none() {
+                state.error_path = Some(state.path.clone());
+            }
+        }
+        result
+    }
+
+    /// Deserializes a nested value
+    fn nested<'de, T>(value: &'de Value, key: String, state: &'s RefCell<State>, seed: T) -> std::result::Result<T::Value, serde_yaml::Error>
+    where T: DeserializeSeed<'de> {
+        state.borrow_mut().path.push(key);
+        let result = seed.deserialize(Track { value, state });
+        state.borrow_mut().path.pop();
+        result
+    }
+}
+
+struct TrackMap<'de, 's> {
+    iter: serde_yaml::mapping::Iter<'de>,
+    value: Option<(String, &'de Value)>,
+    state: &'s RefCell<State>,
+}
+
+impl<'de> de::MapAccess<'de> for TrackMap<'de, '_> {
+    type Error = serde_yaml::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> std::result::Result<Option<K::Value>, Self::Error>
+    where K: DeserializeSeed<'de> {
+        match self.iter.next() {
+            Some((k, v)) => {
+                self.value = Some((key_token(k), v));
+                seed.deserialize(k).map(Some)
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> std::result::Result<V::Value, Self::Error>
+    where V: DeserializeSeed<'de> {
+        let (key, value) = self.value.take()
+            .ok_or_else(|| <serde_yaml::Error as de::Error>::custom("value requested before key"))?;
+        Track::nested(value, key, self.state, seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct TrackSeq<'de, 's> {
+    iter: std::iter::Enumerate<std::slice::Iter<'de, Value>>,
+    state: &'s RefCell<State>,
+}
+
+impl<'de> de::SeqAccess<'de> for TrackSeq<'de, '_> {
+    type Error = serde_yaml::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> std::result::Result<Option<T::Value>, Self::Error>
+    where T: DeserializeSeed<'de> {
+        match self.iter.next() {
+            Some((i, v)) => Track::nested(v, i.to_string(), self.state, seed).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// Forwards methods to the deserializer of `&Value`
+macro_rules! forward {
+    ($($method:ident($($arg:ident: $ty:ty),*)),*) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> std::result::Result<V::Value, Self::Error>
+            where V: Visitor<'de> {
+                self.record(de::Deserializer::$method(self.value, $($arg,)* visitor))
+            }
+        )*
+    };
+}
+
+/// Methods that accept a mapping or a sequence
+macro_rules! nested {
+    ($($method:ident($($arg:ident: $ty:ty),*)),*) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> std::result::Result<V::Value, Self::Error>
+            where V: Visitor<'de> {
+                let result = match self.value {
+                    Value::Mapping(m) => visitor.visit_map(TrackMap { iter: m.iter(), value: None, state: self.state }),
+                    Value::Sequence(s) => visitor.visit_seq(TrackSeq { iter: s.iter().enumerate(), state: self.state }),
+                    _ => de::Deserializer::$method(self.value, $($arg,)* visitor),
+                };
+                self.record(result)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Track<'de, '_> {
+    type Error = serde_yaml::Error;
+
+    forward!(deserialize_bool(), deserialize_i8(), deserialize_i16(), deserialize_i32(),
+             deserialize_i64(), deserialize_i128(), deserialize_u8(), deserialize_u16(),
+             deserialize_u32(), deserialize_u64(), deserialize_u128(), deserialize_f32(),
+             deserialize_f64(), deserialize_char(), deserialize_str(), deserialize_string(),
+             deserialize_bytes(), deserialize_byte_buf(), deserialize_unit(),
+             deserialize_unit_struct(name: &'static str),
+             deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+             deserialize_identifier(), deserialize_ignored_any());
+    nested!(deserialize_any(), deserialize_seq(), deserialize_tuple(len: usize),
+            deserialize_tuple_struct(name: &'static str, len: usize), deserialize_map(),
+            deserialize_struct(name: &'static str, fields: &'static [&'static str]));
+
+    fn deserialize_option<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    where V: Visitor<'de> {
+        let result = match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        };
+        self.record(result)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    where V: Visitor<'de> {
+        let result = visitor.visit_newtype_struct(self);
+        self.record(result)
+    }
+}
+
+/// Deserializes a value, errors giving the path of the value that couldn't be deserialized
+/// (relative to `value`), when it is known.
+pub(crate) fn from_value<T: DeserializeOwned>(value: &Value) -> Result<T> {
+    let state = RefCell::new(State::default());
+    T::deserialize(Track { value, state: &state })
+        .map_err(|source| Error::Deserialize {
+            path: state.into_inner()
+                .error_path
+                .unwrap_or_default()
+                .join("."),
+            source,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_derive::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Server {
+        host: String,
+        port: Option<u16>,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Config {
+        servers: Vec<Server>,
+        name: (String, u8),
+    }
+
+    fn error_path(yaml: &str) -> String {
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        match from_value::<Config>(&value) {
+            Err(Error::Deserialize { path, .. }) => path,
+            other => panic!("unexpected result {other:?}"),
+        }
+    }
+
+    #[test]
+    fn paths() {
+        let value: Value = serde_yaml::from_str("{servers: [{host: a, port: 80}, {host: b}], name: [x, 1]}").unwrap();
+        assert_eq!(from_value::<Config>(&value).unwrap(), Config {
+            servers: vec![Server { host: "a".into(), port: Some(80) }, Server { host: "b".into(), port: None }],
+            name: ("x".into(), 1),
+        });
+        assert_eq!(error_path("{servers: [{host: a}, {host: b, port: x}], name: [x, 1]}"), "servers.1.port");
+        assert_eq!(error_path("{servers: [{port: 1}], name: [x, 1]}"), "servers.0");
+        assert_eq!(error_path("{servers: [], name: [x, 300]}"), "name.1");
+        assert_eq!(error_path("{servers: []}"), "");
+    }
+}
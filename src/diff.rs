@@ -0,0 +1,570 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::error::{Error, Result};
+use crate::path::key_token;
+
+use serde_yaml::{Mapping, Number, Value};
+
+use std::cmp::Ordering;
+
+/// The differences between two values, see `diff`
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffValue {
+    /// The value is the same on both sides
+    Unchanged(Value),
+    /// The value is only in the second document
+    Added(Value),
+    /// The value is only in the first document
+    Removed(Value),
+    /// The value is different, and the two sides can't be compared key by key
+    Changed {
+        old: Value,
+        new: Value,
+    },
+    /// Both sides are mappings with some differences, given for each key, in the order of the
+    /// first document then the keys added by the second one
+    Mapping(Vec<(Value, DiffValue)>),
+    /// Both sides are sequences with some differences, compared item by item
+    Sequence(Vec<DiffValue>),
+}
+
+impl DiffValue {
+    /// Whether both sides are equal
+    pub fn is_unchanged(&self) -> bool {
+        matches!(self, DiffValue::Unchanged(_))
+    }
+
+    /// The difference for a key of a mapping or an index of a sequence
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use yaml_extras::DiffValue;
+    ///
+    /// let a: serde_yaml::Value = serde_yaml::from_str("{server: {port: 80, host: localhost}}")?;
+    /// let b: serde_yaml::Value = serde_yaml::from_str("{server: {port: 8080, host: localhost}}")?;
+    /// let diff = yaml_extras::diff(&a, &b);
+    /// let port = diff.get("server").and_then(|d| d.get("port"));
+    /// assert_eq!(port, Some(&DiffValue::Changed { old: 80.into(), new: 8080.into() }));
+    /// assert!(diff.get("server").and_then(|d| d.get("host")).unwrap().is_unchanged());
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn get(&self, key: &str) -> Option<&DiffValue> {
+        match self {
+            DiffValue::Mapping(entries) => entries.iter()
+                .find(|(k, _)| key_token(k) == key)
+                .map(|(_, d)| d),
+            DiffValue::Sequence(items) => key.parse::<usize>()
+                .ok()
+                .and_then(|i| items.get(i)),
+            _ => None,
+        }
+    }
+
+    /// The value in the first document, if any
+    pub fn before(&self) -> Option<Value> {
+        match self {
+            DiffValue::Unchanged(v) | DiffValue::Removed(v) | DiffValue::Changed { old: v, .. } => Some(v.clone()),
+            DiffValue::Added(_) => None,
+            DiffValue::Mapping(entries) => Some(Value::Mapping(entries.iter()
+                                                               .filter_map(|(k, d)| d.before().map(|v| (k.clone(), v)))
+                                                               .collect())),
+            DiffValue::Sequence(items) => Some(Value::Sequence(items.iter()
+                                                               .filter_map(|d| d.before())
+                                                               .collect())),
+        }
+    }
+
+    /// The value in the second document, if any
+    pub fn after(&self) -> Option<Value> {
+        match self {
+            DiffValue::Unchanged(v) | DiffValue::Added(v) | DiffValue::Changed { new: v, .. } => Some(v.clone()),
+            DiffValue::Removed(_) => None,
+            DiffValue::Mapping(entries) => Some(Value::Mapping(entries.iter()
+                                                               .filter_map(|(k, d)| d.after().map(|v| (k.clone(), v)))
+                                                               .collect())),
+            DiffValue::Sequence(items) => Some(Value::Sequence(items.iter()
+                                                               .filter_map(|d| d.after())
+                                                               .collect())),
+        }
+    }
+
+    /// Lists the added, removed and changed values with their paths, sequence indices being
+    /// displayed as numbers
+    pub fn changes(&self) -> Vec<(Vec<String>, &DiffValue)> {
+        let mut list = vec![];
+        self.collect_changes(&mut vec![], &mut list);
+        list
+    }
+
+    /// Displays the changes as text, grouped by path, e.g.:
+    ///
+    /// ```text
+    /// @@ server.port @@
+    /// - 80
+    /// + 8080
+    /// ```
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let a: serde_yaml::Value = serde_yaml::from_str("{port: 80, debug: true}")?;
+    /// let b: serde_yaml::Value = serde_yaml::from_str("{port: 8080, hosts: [a, b]}")?;
+    /// assert_eq!(yaml_extras::diff(&a, &b).render(), "\
+    /// @@ port @@
+    /// - 80
+    /// + 8080
+    /// @@ debug @@
+    /// - true
+    /// @@ hosts @@
+    /// + - a
+    /// + - b
+    /// ");
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn render(&self) -> String {
+        self.render_with(&|_, line| line)
+    }
+
+    /// Same as `render`, with removed lines in red, added lines in green and paths in bold.
+    /// Requires the `color` feature.
+    #[cfg(feature = "color")]
+    pub fn render_colored(&self) -> String {
+        use yansi::Paint;
+
+        self.render_with(&|c, line| match c {
+            '-' => line.red().to_string(),
+            '+' => line.green().to_string(),
+            _ => line.bold().to_string(),
+        })
+    }
+
+    /// Renders the changes, `paint` being called on each line with its first character
+    fn render_with(&self, paint: &dyn Fn(char, String) -> String) -> String {
+        let mut out = String::new();
+        for (path, change) in self.changes() {
+            let path = if path.is_empty() { "(root)".to_owned() } else { path.join(".") };
+            out.push_str(&paint('@', format!("@@ {path} @@")));
+            out.push('\n');
+            let (old, new) = match change {
+                DiffValue::Added(v) => (None, Some(v)),
+                DiffValue::Removed(v) => (Some(v), None),
+                DiffValue::Changed { old, new } => (Some(old), Some(new)),
+                _ => (None, None),
+            };
+            for (c, v) in [('-', old), ('+', new)] {
+                let Some(v) = v else {
+                    continue;
+                };
+                for line in yaml_lines(v).lines() {
+                    out.push_str(&paint(c, format!("{c} {line}")));
+                    out.push('\n');
+                }
+            }
+        }
+        out
+    }
+
+    fn collect_changes<'d>(&'d self, path: &mut Vec<String>, list: &mut Vec<(Vec<String>, &'d DiffValue)>) {
+        match self {
+            DiffValue::Unchanged(_) => (),
+            DiffValue::Added(_) | DiffValue::Removed(_) | DiffValue::Changed { .. } => list.push((path.clone(), self)),
+            DiffValue::Mapping(entries) => {
+                for (k, d) in entries {
+                    path.push(key_token(k));
+                    d.collect_changes(path, list);
+                    path.pop();
+                }
+            },
+            DiffValue::Sequence(items) => {
+                for (i, d) in items.iter().enumerate() {
+                    path.push(i.to_string());
+                    d.collect_changes(path, list);
+                    path.pop();
+                }
+            },
+        }
+    }
+}
+
+/// Displays a value as YAML
+fn yaml_lines(v: &Value) -> String {
+    serde_yaml::to_string(v)
+        .unwrap_or_else(|_| format!("{v:?}"))
+}
+
+/// Computes the differences between two values.
+///
+/// Mappings are compared key by key, whatever their order, and sequences item by item.
+///
+/// # Example
+///
+/// ```
+/// let a: serde_yaml::Value = serde_yaml::from_str("{port: 80, debug: true, hosts: [a, b]}")?;
+/// let b: serde_yaml::Value = serde_yaml::from_str("{port: 8080, hosts: [a, c], tls: true}")?;
+/// let diff = yaml_extras::diff(&a, &b);
+/// let changes: Vec<String> = diff.changes()
+///     .iter()
+///     .map(|(path, _)| path.join("."))
+///     .collect();
+/// assert_eq!(changes, ["port", "debug", "hosts.1", "tls"]);
+/// assert_eq!(diff.before(), Some(a));
+/// assert_eq!(diff.after(), Some(b));
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn diff(a: &Value, b: &Value) -> DiffValue {
+    if a == b {
+        return DiffValue::Unchanged(a.clone());
+    }
+    match (a, b) {
+        (Value::Mapping(a), Value::Mapping(b)) => {
+            let mut entries = vec![];
+            for (k, a_val) in a {
+                let d = match b.get(k) {
+                    Some(b_val) => diff(a_val, b_val),
+                    None => DiffValue::Removed(a_val.clone()),
+                };
+                entries.push((k.clone(), d));
+            }
+            for (k, b_val) in b.iter().filter(|(k, _)| !a.contains_key(*k)) {
+                entries.push((k.clone(), DiffValue::Added(b_val.clone())));
+            }
+            DiffValue::Mapping(entries)
+        },
+        (Value::Sequence(a), Value::Sequence(b)) => {
+            let mut items: Vec<DiffValue> = a.iter()
+                .zip(b)
+                .map(|(a, b)| diff(a, b))
+                .collect();
+            items.extend(a.iter().skip(b.len()).map(|v| DiffValue::Removed(v.clone())));
+            items.extend(b.iter().skip(a.len()).map(|v| DiffValue::Added(v.clone())));
+            DiffValue::Sequence(items)
+        },
+        _ => DiffValue::Changed {
+            old: a.clone(),
+            new: b.clone(),
+        },
+    }
+}
+
+/// Options of `semantically_equal`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EqualityOptions {
+    /// Maximum difference between two numbers considered equal. Without it, integers and
+    /// floats are still equal if they have the same value, e.g. `1` and `1.0`.
+    pub float_tolerance: Option<f64>,
+    /// Whether a key with a null value is equal to an absent key
+    pub null_as_absent: bool,
+}
+
+/// Compares two values, ignoring the order of mapping keys and, depending on `options`,
+/// small differences between numbers and null values.
+///
+/// # Example
+///
+/// ```
+/// use yaml_extras::EqualityOptions;
+///
+/// let a: serde_yaml::Value = serde_yaml::from_str("{ratio: 0.3, port: 80, proxy: null}")?;
+/// let b: serde_yaml::Value = serde_yaml::from_str("{port: 80.0, ratio: 0.30000001}")?;
+/// assert!(!yaml_extras::semantically_equal(&a, &b, EqualityOptions::default()));
+/// let options = EqualityOptions {
+///     float_tolerance: Some(1e-6),
+///     null_as_absent: true,
+/// };
+/// assert!(yaml_extras::semantically_equal(&a, &b, options));
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn semantically_equal(a: &Value, b: &Value, options: EqualityOptions) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => {
+            if x == y {
+                return true;
+            }
+            match (x.as_f64(), y.as_f64(), options.float_tolerance) {
+                (Some(x), Some(y), Some(tolerance)) => (x - y).abs() <= tolerance,
+                (Some(x), Some(y), None) => x == y,
+                _ => false,
+            }
+        },
+        (Value::Mapping(x), Value::Mapping(y)) => {
+            let present = |v: &Value| !(options.null_as_absent && v.is_null());
+            let contains = |x: &serde_yaml::Mapping, y: &serde_yaml::Mapping| x.iter()
+                .filter(|(_, v)| present(v))
+                .all(|(k, v)| match y.get(k) {
+                    Some(w) => semantically_equal(v, w, options),
+                    None => false,
+                });
+            contains(x, y) && y.iter()
+                .filter(|(_, v)| present(v))
+                .all(|(k, _)| x.get(k).is_some_and(present))
+        },
+        (Value::Sequence(x), Value::Sequence(y)) => x.len() == y.len()
+            && x.iter().zip(y).all(|(v, w)| semantically_equal(v, w, options)),
+        (Value::Tagged(x), Value::Tagged(y)) => x.tag == y.tag
+            && semantically_equal(&x.value, &y.value, options),
+        (a, b) => a == b,
+    }
+}
+
+/// The rank of the type of a value, for `compare`
+fn rank(v: &Value) -> u8 {
+    match v {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::Sequence(_) => 4,
+        Value::Mapping(_) => 5,
+        Value::Tagged(_) => 6,
+    }
+}
+
+/// A total order on values: by type (null, booleans, numbers, strings, sequences, mappings,
+/// then tagged values), then by value, numbers being compared whatever their representation
+pub(crate) fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
+        (Value::Number(x), Value::Number(y)) => match (x.as_i64(), y.as_i64()) {
+            (Some(x), Some(y)) => x.cmp(&y),
+            _ => x.as_f64().unwrap_or(f64::NAN).total_cmp(&y.as_f64().unwrap_or(f64::NAN)),
+        },
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        (Value::Sequence(x), Value::Sequence(y)) => x.iter()
+            .zip(y)
+            .map(|(v, w)| compare(v, w))
+            .find(|o| o.is_ne())
+            .unwrap_or_else(|| x.len().cmp(&y.len())),
+        (Value::Mapping(x), Value::Mapping(y)) => x.iter()
+            .zip(y)
+            .map(|((k, v), (l, w))| compare(k, l).then_with(|| compare(v, w)))
+            .find(|o| o.is_ne())
+            .unwrap_or_else(|| x.len().cmp(&y.len())),
+        (Value::Tagged(x), Value::Tagged(y)) => x.tag.to_string().cmp(&y.tag.to_string())
+            .then_with(|| compare(&x.value, &y.value)),
+        (a, b) => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// Options of `canonicalize`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CanonOptions {
+    /// Whether the keys of mappings are sorted (default is `true`)
+    pub sort_keys: bool,
+    /// Whether floats with an integer value, e.g. `1.0`, become integers, and `-0.0` becomes
+    /// `0` (default is `true`)
+    pub normalize_numbers: bool,
+    /// Whether strings that are booleans in YAML 1.1, e.g. `yes`, `Off` or `TRUE`, become
+    /// booleans (default is `false`)
+    pub yaml11_booleans: bool,
+    /// Whether keys with a null value are removed from mappings (default is `true`)
+    pub strip_nulls: bool,
+}
+
+impl Default for CanonOptions {
+    fn default() -> Self {
+        CanonOptions {
+            sort_keys: true,
+            normalize_numbers: true,
+            yaml11_booleans: false,
+            strip_nulls: true,
+        }
+    }
+}
+
+/// Rewrites a value in a deterministic form, so that values with the same meaning become
+/// equal, and serialize the same way
+///
+/// Mapping keys are sorted with numbers before strings (see `CanonOptions`). Keys that become
+/// equal, e.g. `1` and `1.0`, are an error, since only one of them could be kept. If an error
+/// occurs, `value` is left unchanged.
+///
+/// # Example
+///
+/// ```
+/// use yaml_extras::CanonOptions;
+///
+/// let mut a: serde_yaml::Value = serde_yaml::from_str("{port: 80.0, host: localhost, proxy: null}")?;
+/// let mut b: serde_yaml::Value = serde_yaml::from_str("{host: localhost, port: 80}")?;
+/// yaml_extras::canonicalize(&mut a, CanonOptions::default())?;
+/// yaml_extras::canonicalize(&mut b, CanonOptions::default())?;
+/// assert_eq!(serde_yaml::to_string(&a)?, serde_yaml::to_string(&b)?);
+/// assert_eq!(serde_yaml::to_string(&a)?, "host: localhost\nport: 80\n");
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn canonicalize(value: &mut Value, options: CanonOptions) -> Result<()> {
+    let mut canonical = value.clone();
+    canonicalize_at(&mut canonical, options, &mut vec![])?;
+    *value = canonical;
+    Ok(())
+}
+
+/// Floats with an integer value become integers, and `-0.0` becomes `0`
+pub(crate) fn canonical_number(n: &Number) -> Number {
+    match n.as_f64().filter(|_| n.is_f64()) {
+        Some(0.0) => Number::from(0),
+        Some(f) if f.fract() == 0.0 && f.abs() < 9.0e15 => Number::from(f as i64),
+        _ => n.clone(),
+    }
+}
+
+/// Rewrites a scalar in its canonical form
+fn canonical_scalar(value: &mut Value, options: CanonOptions) {
+    match value {
+        Value::Number(n) if options.normalize_numbers => *n = canonical_number(n),
+        Value::String(s) if options.yaml11_booleans => {
+            match s.to_lowercase().as_str() {
+                "y" | "yes" | "true" | "on" => *value = Value::Bool(true),
+                "n" | "no" | "false" | "off" => *value = Value::Bool(false),
+                _ => (),
+            }
+        },
+        _ => (),
+    }
+}
+
+fn canonicalize_at(value: &mut Value, options: CanonOptions, path: &mut Vec<String>) -> Result<()> {
+    match value {
+        Value::Sequence(s) => for (i, item) in s.iter_mut().enumerate() {
+            path.push(i.to_string());
+            let result = canonicalize_at(item, options, path);
+            path.pop();
+            result?;
+        },
+        Value::Mapping(m) => {
+            let mut entries: Vec<(Value, Value)> = vec![];
+            // The original keys, for errors
+            let mut originals: Vec<Value> = vec![];
+            for (k, mut v) in std::mem::take(m) {
+                if options.strip_nulls && v.is_null() {
+                    continue;
+                }
+                let mut key = k.clone();
+                canonicalize_at(&mut key, options, path)?;
+                if let Some(i) = entries.iter().position(|(other, _)| *other == key) {
+                    return Err(Error::Canonicalize(format!("keys '{}' and '{}' at '{}' are the same once canonicalized",
+                                                           key_token(&originals[i]), key_token(&k), path.join("."))));
+                }
+                path.push(key_token(&k));
+                let result = canonicalize_at(&mut v, options, path);
+                path.pop();
+                result?;
+                entries.push((key, v));
+                originals.push(k);
+            }
+            if options.sort_keys {
+                entries.sort_by(|(k, _), (l, _)| compare(k, l));
+            }
+            *m = entries.into_iter().collect::<Mapping>();
+        },
+        Value::Tagged(t) => canonicalize_at(&mut t.value, options, path)?,
+        scalar => canonical_scalar(scalar, options),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yaml;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn structural_diff() {
+        let a = yaml("{a: {b: 1, c: [1, 2, 3]}, d: x, e: {f: 1}}");
+        let b = yaml("{e: {f: 1}, a: {c: [1, 4], b: 1}, d: [x], g: null}");
+        let d = diff(&a, &b);
+        assert_eq!(d, DiffValue::Mapping(vec![
+            (yaml("a"), DiffValue::Mapping(vec![
+                (yaml("b"), DiffValue::Unchanged(yaml("1"))),
+                (yaml("c"), DiffValue::Sequence(vec![DiffValue::Unchanged(yaml("1")),
+                                                     DiffValue::Changed { old: yaml("2"), new: yaml("4") },
+                                                     DiffValue::Removed(yaml("3"))])),
+            ])),
+            (yaml("d"), DiffValue::Changed { old: yaml("x"), new: yaml("[x]") }),
+            (yaml("e"), DiffValue::Unchanged(yaml("{f: 1}"))),
+            (yaml("g"), DiffValue::Added(Value::Null)),
+        ]));
+        assert_eq!(d.get("a").and_then(|d| d.get("c")).and_then(|d| d.get("2")), Some(&DiffValue::Removed(yaml("3"))));
+        assert_eq!(d.get("z"), None);
+        // Key order is not significant
+        assert!(diff(&yaml("{a: 1, b: 2}"), &yaml("{b: 2, a: 1}")).is_unchanged());
+        assert_eq!(d.after(), Some(yaml("{a: {b: 1, c: [1, 4]}, d: [x], e: {f: 1}, g: null}")));
+    }
+
+    #[test]
+    fn render() {
+        let a = yaml("{a: {b: 1}, c: [1]}");
+        let b = yaml("{a: {b: {x: 1, y: 2}}, c: [1, 2]}");
+        assert_eq!(diff(&a, &b).render(), "@@ a.b @@\n- 1\n+ x: 1\n+ y: 2\n@@ c.1 @@\n+ 2\n");
+        assert_eq!(diff(&yaml("1"), &yaml("two")).render(), "@@ (root) @@\n- 1\n+ two\n");
+        assert_eq!(diff(&a, &a).render(), "");
+    }
+
+    #[test]
+    fn semantic_equality() {
+        let options = EqualityOptions::default();
+        assert!(semantically_equal(&yaml("{a: 1, b: [1, {c: 2}]}"), &yaml("{b: [1.0, {c: 2}], a: 1}"), options));
+        assert!(!semantically_equal(&yaml("{a: 1, b: null}"), &yaml("{a: 1}"), options));
+        assert!(!semantically_equal(&yaml("[1, 2]"), &yaml("[2, 1]"), options));
+        assert!(!semantically_equal(&yaml("!a 1"), &yaml("!b 1"), options));
+        let options = EqualityOptions {
+            null_as_absent: true,
+            ..options
+        };
+        assert!(semantically_equal(&yaml("{a: 1, b: null}"), &yaml("{a: 1, c: null}"), options));
+        assert!(!semantically_equal(&yaml("{a: 1}"), &yaml("{a: 1, b: 2}"), options));
+        assert!(!semantically_equal(&yaml("{a: 1, b: 2}"), &yaml("{a: 1}"), options));
+    }
+
+    #[test]
+    fn canonical_form() {
+        let mut v = yaml("{b: [2.0, -0.0, 1.5, 'yes', {z: 1, y: null}], a: !t {d: Off, c: 1e20}, 10: x, 9: y, null: z}");
+        canonicalize(&mut v, CanonOptions::default()).unwrap();
+        assert_eq!(serde_yaml::to_string(&v).unwrap(), serde_yaml::to_string(&yaml(
+            "{null: z, 9: y, 10: x, a: !t {c: 1e20, d: Off}, b: [2, 0, 1.5, 'yes', {z: 1}]}")).unwrap());
+
+        let options = CanonOptions {
+            sort_keys: false,
+            normalize_numbers: false,
+            yaml11_booleans: true,
+            strip_nulls: false,
+        };
+        let mut v = yaml("{b: [1.0, 'yes', 'NO', 'maybe'], a: null}");
+        canonicalize(&mut v, options).unwrap();
+        assert_eq!(serde_yaml::to_string(&v).unwrap(), serde_yaml::to_string(&yaml("{b: [1.0, true, false, maybe], a: null}")).unwrap());
+    }
+
+    #[test]
+    fn canonical_collisions() {
+        for (s, msg) in [("{a: {1: a, 1.0: b}}", "keys '1' and '1.0' at 'a' are the same once canonicalized"),
+                         ("{a: {1.0: b, 1: a}}", "keys '1.0' and '1' at 'a' are the same once canonicalized")] {
+            let mut v = yaml(s);
+            let err = canonicalize(&mut v, CanonOptions::default()).unwrap_err();
+            assert_eq!(err.to_string(), format!("impossible to canonicalize YAML value: {msg}"));
+            assert_eq!(v, yaml(s));
+        }
+        let options = CanonOptions { yaml11_booleans: true, ..CanonOptions::default() };
+        for s in ["[{yes: 1, true: 2}]", "[{true: 2, yes: 1}]"] {
+            let err = canonicalize(&mut yaml(s), options).unwrap_err();
+            assert!(err.to_string().contains("at '0'"), "{err}");
+        }
+        // Keys with a null value are removed before
+        let mut v = yaml("{1: a, 1.0: null}");
+        canonicalize(&mut v, CanonOptions::default()).unwrap();
+        assert_eq!(v, yaml("{1: a}"));
+    }
+
+    #[test]
+    fn ordering() {
+        let mut values: Vec<Value> = ["b", "2.5", "a", "[1, 2]", "[1]", "true", "~", "10", "!t 1", "{a: 1}"]
+            .iter()
+            .map(|s| yaml(s))
+            .collect();
+        values.sort_by(compare);
+        assert_eq!(Value::Sequence(values), yaml("[~, true, 2.5, 10, a, b, [1], [1, 2], {a: 1}, !t 1]"));
+        assert_eq!(compare(&yaml("1"), &yaml("1.0")), Ordering::Equal);
+    }
+}
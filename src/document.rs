@@ -3,10 +3,24 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use crate::error;
+use crate::path::key_token;
+use crate::Restructurer;
+use crate::width::{char_width, display_width};
+#[cfg(feature = "template")]
+use crate::template::Template;
 
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use serde_yaml::Value;
 
-#[derive(Debug, PartialEq)]
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+pub use crate::reflect::type_value;
+use crate::reflect::{type_info, value_at};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ValueType {
     Null,
     Bool,
@@ -17,17 +31,201 @@ pub enum ValueType {
     Tagged,
 }
 
+impl From<&Value> for ValueType {
+    fn from(v: &Value) -> Self {
+        match v {
+            Value::Null => ValueType::Null,
+            Value::Bool(_) => ValueType::Bool,
+            Value::Number(_) => ValueType::Number,
+            Value::String(_) => ValueType::String,
+            Value::Sequence(_) => ValueType::List,
+            Value::Mapping(_) => ValueType::Mapping,
+            Value::Tagged(_) => ValueType::Tagged,
+        }
+    }
+}
+
 impl ValueType {
     pub fn to_str(v: &ValueType) -> String {
         match v {
             ValueType::Null | ValueType::Mapping | ValueType::Tagged => String::new(),
-            __=> format!(" ({:?})", v)
+            _ => format!(" ({:?})", v)
+        }
+    }
+}
+
+/// Where the description of a key is displayed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentPlacement {
+    /// On its own line, above the key (default)
+    Above,
+    /// At the end of the key's line, e.g. `key: value  # description`
+    Inline,
+}
+
+/// Re-export of `yansi::Style`, used to configure colors
+#[cfg(feature = "color")]
+pub use yansi::Style;
+
+/// Styles used to colorize the different parts of the documentation, see `Documenter::colors`.
+#[cfg(feature = "color")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Styles {
+    pub key: Style,
+    pub ty: Style,
+    pub value: Style,
+    pub description: Style,
+}
+
+#[cfg(feature = "color")]
+impl Default for Styles {
+    fn default() -> Self {
+        Styles {
+            key: Style::new().bold().blue(),
+            ty: Style::new().dim(),
+            value: Style::new().green(),
+            description: Style::new().italic().yellow(),
+        }
+    }
+}
+
+/// The part of the output that is being displayed, so it can be colorized
+#[derive(Clone, Copy)]
+enum Part {
+    Key,
+    Type,
+    Value,
+    Description,
+}
+
+const INDENT: &str = "    ";
+const DESCRIPTION: &str = "__description__";
+const NULL: &str = "Null";
+const MIN: &str = "__min__";
+const MAX: &str = "__max__";
+const PATTERN: &str = "__pattern__";
+const SECTION: &str = "__section__";
+const VARIANTS: &str = "__variants__";
+
+/// Constraints on a value, read from the `__min__`, `__max__` and `__pattern__` keys of its
+/// description.
+///
+/// They are only displayed, but can be retrieved with `Documenter::constraints` to validate
+/// a configuration.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Constraints {
+    pub min: Option<Value>,
+    pub max: Option<Value>,
+    pub pattern: Option<String>,
+}
+
+impl Constraints {
+    /// Reads the constraints from the description of a value, if there are any
+    fn from_description(description: &Value) -> Option<Constraints> {
+        let m = description.as_mapping()?;
+        let constraints = Constraints {
+            min: m.get(MIN).cloned(),
+            max: m.get(MAX).cloned(),
+            pattern: m.get(PATTERN).and_then(|v| v.as_str()).map(|s| s.to_owned()),
+        };
+        if constraints == Constraints::default() {
+            None
+        } else {
+            Some(constraints)
+        }
+    }
+}
+
+/// A variant of an enum, read from the `__variants__` key of its description.
+///
+/// `__variants__` maps the name of each variant to its description, which is either a
+/// string or, for a struct variant, a mapping with the descriptions of its fields and an
+/// optional `__description__`. When the value is tagged with a variant (e.g. `!s3`), the
+/// descriptions of its fields are taken from the subtree of this variant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub name: String,
+    /// The fields of a struct variant, empty for other variants
+    pub fields: Vec<String>,
+    pub description: Option<String>,
+}
+
+impl std::fmt::Display for Variant {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.fields.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{} {{ {} }}", self.name, self.fields.join(", "))
+        }
+    }
+}
+
+/// Lists the alternatives of an enum, e.g. "one of: `local`, `s3 { bucket, region }`"
+fn variants_summary(variants: &[Variant]) -> String {
+    let list: Vec<String> = variants.iter()
+        .map(|v| format!("`{v}`"))
+        .collect();
+    format!("one of: {}", list.join(", "))
+}
+
+/// The name of the variant of a tagged value, without the `!`
+fn variant_name(value: &Value) -> Option<String> {
+    match value {
+        Value::Tagged(t) => Some(t.tag.to_string().trim_start_matches('!').to_owned()),
+        _ => None,
+    }
+}
+
+/// Displays a bound, which should be a scalar but is user-provided
+fn bound_to_string(v: &Value) -> String {
+    match v {
+        Value::Bool(_) | Value::Null | Value::Number(_) | Value::String(_) => default_format_scalar(v, NULL),
+        v => format!("{:?}", v),
+    }
+}
+
+impl std::fmt::Display for Constraints {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut parts = vec![];
+        if let Some(ref min) = self.min {
+            parts.push(format!("min: {}", bound_to_string(min)));
+        }
+        if let Some(ref max) = self.max {
+            parts.push(format!("max: {}", bound_to_string(max)));
         }
+        if let Some(ref pattern) = self.pattern {
+            parts.push(format!("pattern: {pattern}"));
+        }
+        write!(f, "{}", parts.join(", "))
     }
 }
 
-const INDENT: &'static str = "    ";
-const DESCRIPTION: &'static str = "__description__";
+/// A problem found while documenting a value, which doesn't prevent documenting it.
+///
+/// Warnings are passed to the closure set with `Documenter::on_warning`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocWarning {
+    /// A description was found for a path that doesn't exist in the value, e.g. because of a
+    /// typo. This is an error instead if `Documenter::strict` is set.
+    UnknownDescription(String),
+    /// A mapping key is not a string, so it is displayed with its debug representation
+    NonStringKey(String),
+    /// A value is nested deeper than the depth set with `Documenter::warning_depth`
+    DeepNesting {
+        path: String,
+        depth: usize,
+    },
+}
+
+impl std::fmt::Display for DocWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DocWarning::UnknownDescription(path) => write!(f, "description found for unknown path: {path}"),
+            DocWarning::NonStringKey(path) => write!(f, "key that is not a string: {path}"),
+            DocWarning::DeepNesting { path, depth } => write!(f, "value nested at depth {depth}: {path}"),
+        }
+    }
+}
 
 /// Arguments passed to a `Documenter`.`format_key` closure.
 ///
@@ -39,6 +237,26 @@ pub struct KeyArgs<'k> {
     pub path: &'k Vec<String>,
     pub key: &'k str,
     pub description: Option<&'k str>,
+    /// Where the description should be displayed
+    pub placement: CommentPlacement,
+    /// Whether the key line itself should be commented out
+    pub commented_out: bool,
+    /// The environment variable corresponding to this key, if enabled and if it is not a mapping
+    pub env_var: Option<&'k str>,
+    /// The constraints found in the description, if any
+    pub constraints: Option<&'k Constraints>,
+    /// The variants of an enum, if they are described
+    pub variants: &'k [Variant],
+    /// Whether this is an optional field of a type, see `Documenter::apply_type`
+    pub optional: bool,
+    /// A marker such as "new in this version", when compared with previous defaults
+    pub change: Option<&'k str>,
+    /// The default value, if it differs from the value (see `Documenter::apply_current`)
+    pub overridden: Option<&'k str>,
+    /// The title of a section starting at this key, if any
+    pub section: Option<&'k str>,
+    /// The display column at which inline comments start, if they should be aligned
+    pub comment_column: Option<usize>,
     /// A representation of the type
     pub ty: &'k str,
     /// A representation of the value
@@ -59,17 +277,299 @@ pub struct InnerArgs<'a> {
     pub path: &'a Vec<String>,
 }
 
-fn default_format_key(k: KeyArgs) -> String {
+/// Computes the HTML anchor used for a path in Markdown output.
+///
+/// Keys are separated by `-`, and characters that are not alphanumeric, `-` or `_`
+/// are replaced by `_`.
+///
+/// # Example
+///
+/// ```
+/// let path = vec!["server".to_owned(), "listen address".to_owned()];
+/// assert_eq!(yaml_extras::document::anchor(&path), "server-listen_address");
+/// ```
+pub fn anchor(path: &[String]) -> String {
+    path.iter()
+        .map(|k| k.chars()
+             .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+             .collect::<String>())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Lists the references to other paths, like `{{some.path}}`, in a description
+fn references(s: &str) -> Vec<&str> {
+    let mut list = vec![];
+    let mut rest = s;
+    while let Some(i) = rest.find("{{") {
+        rest = &rest[i + 2..];
+        let Some(end) = rest.find("}}") else {
+            break;
+        };
+        let path = &rest[..end];
+        if !path.is_empty() && path.chars().all(|c| c.is_alphanumeric() || "._-".contains(c)) {
+            list.push(path);
+            rest = &rest[end + 2..];
+        }
+    }
+    list
+}
+
+/// Replaces the references to other paths in a description with `replace`
+fn resolve_references(s: &str, replace: &dyn Fn(&str) -> String) -> String {
+    let mut result = s.to_owned();
+    for path in references(s) {
+        result = result.replacen(&format!("{{{{{path}}}}}"), &replace(path), 1);
+    }
+    result
+}
+
+/// Rewrites Markdown links to `#some.path` so they point to the anchor of this path, with
+/// `target` giving the link for a path. References like `{{some.path}}` become links too.
+fn resolve_markdown_links(s: &str, target: &dyn Fn(&[String]) -> String) -> String {
+    let s = resolve_references(s, &|p| format!("[`{p}`](#{p})"));
+    let mut result = String::new();
+    let mut rest = s.as_str();
+    while let Some(i) = rest.find("](#") {
+        let (before, after) = rest.split_at(i + 2);
+        result.push_str(before);
+        let after = &after[1..];
+        let end = after.find(')').unwrap_or(after.len());
+        let path: Vec<String> = after[..end].split('.')
+            .map(|s| s.to_owned())
+            .collect();
+        result.push_str(&target(&path));
+        rest = &after[end..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Rewrites Markdown links to `#some.path`, and references like `{{some.path}}`, as AsciiDoc
+/// cross references to this path
+fn resolve_asciidoc_links(s: &str) -> String {
+    let s = resolve_references(s, &|p| format!("[`{p}`](#{p})"));
+    let mut result = String::new();
+    let mut rest = s.as_str();
+    while let Some(i) = rest.find("](#") {
+        let (before, after) = (&rest[..i], &rest[i + 3..]);
+        let Some(start) = before.rfind('[') else {
+            result.push_str(&rest[..i + 3]);
+            rest = after;
+            continue;
+        };
+        let end = after.find(')').unwrap_or(after.len());
+        let path: Vec<String> = after[..end].split('.')
+            .map(|s| s.to_owned())
+            .collect();
+        result.push_str(&before[..start]);
+        result.push_str(&format!("<<{},{}>>", anchor(&path), &before[start + 1..]));
+        rest = after.get(end + 1..).unwrap_or("");
+    }
+    result.push_str(rest);
+    result
+}
+
+/// The lines of a document produced for a key, see `Documenter::apply_value_with_map`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceSpan {
+    pub path: Vec<String>,
+    /// The lines, starting from 0, including the comments above the key and its children
+    pub lines: std::ops::Range<usize>,
+}
+
+// Private use characters marking the output of keys, which can't be mistaken for content
+const START_MARK: char = '\u{E000}';
+const END_MARK: char = '\u{E001}';
+const END_PATH_MARK: char = '\u{E002}';
+const PATH_MARK: &str = "\u{E003}";
+
+/// Removes the markers added around keys, returning the spans they delimit
+fn extract_source_map(marked: &str) -> (String, Vec<SourceSpan>) {
+    let mut content = String::with_capacity(marked.len());
+    let mut spans = vec![];
+    let mut open = vec![];
+    let mut line = 0;
+    let mut chars = marked.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            START_MARK => {
+                let path: String = chars.by_ref()
+                    .take_while(|c| *c != END_PATH_MARK)
+                    .collect();
+                open.push((path, line));
+            },
+            END_MARK => if let Some((path, start)) = open.pop() {
+                spans.push(SourceSpan {
+                    path: path.split(PATH_MARK).map(|s| s.to_owned()).collect(),
+                    lines: start..line + 1,
+                });
+            },
+            '\n' => {
+                line += 1;
+                content.push(c);
+            },
+            c => content.push(c),
+        }
+    }
+    // Parents are closed after their children
+    spans.sort_by_key(|s| (s.lines.start, std::cmp::Reverse(s.lines.end)));
+    (content, spans)
+}
+
+/// Splits a line such as `key (Type): value` at the colon following the key
+fn split_key(line: &str) -> Option<(&str, &str)> {
+    match line.find(": ") {
+        Some(i) => Some((&line[..i], line[i + 2..].trim_end())),
+        None => line.strip_suffix(':').map(|k| (k, "")),
+    }
+}
+
+/// Splits a value from a comment at the end of its line, if any
+fn split_inline_comment(s: &str) -> (&str, Option<&str>) {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q && prev != '\\' => quote = None,
+            (None, '#') if prev == ' ' => return (s[..i].trim_end(), Some(s[i + 1..].trim())),
+            _ => (),
+        }
+        prev = c;
+    }
+    (s, None)
+}
+
+/// Sets the value at a path, creating the mappings leading to it if needed
+fn set_value(root: &mut Value, path: &[String], v: Value) {
+    let mut current = root;
+    for key in &path[..path.len() - 1] {
+        if !current.is_mapping() {
+            *current = Value::Mapping(serde_yaml::Mapping::new());
+        }
+        let m = current.as_mapping_mut().unwrap();
+        current = m.entry(Value::from(key.as_str()))
+            .or_insert_with(|| Value::Mapping(serde_yaml::Mapping::new()));
+    }
+    if let Value::Mapping(m) = current {
+        m.insert(Value::from(path[path.len() - 1].as_str()), v);
+    }
+}
+
+/// Displays a mapping key
+/// Writes a key, its comments and its value into `out`
+fn write_default_key(k: &KeyArgs, out: &mut String) {
+    use std::fmt::Write;
+
     let key = k.key;
     let ty = k.ty;
     let value = k.value;
     let indent = k.indent;
-    let the_description = if let Some(s) = k.description {
-        format!("{indent}# {s}\n")
-    } else {
-        "".to_owned()
-    };
-    format!("{the_description}{indent}{key}{ty}: {value}")
+    // Like in sshd_config, "#key" is a setting that can be uncommented, "# text" a comment
+    let prefix = if k.commented_out { "#" } else { "" };
+    // Writing to a String can't fail
+    let mut extra = String::new();
+    if let Some(var) = k.env_var {
+        let _ = writeln!(extra, "{indent}# env: {var}");
+    }
+    if let Some(c) = k.constraints {
+        let _ = writeln!(extra, "{indent}# {c}");
+    }
+    if !k.variants.is_empty() {
+        let _ = writeln!(extra, "{indent}# {}", variants_summary(k.variants));
+        for v in k.variants {
+            if let Some(ref s) = v.description {
+                let _ = writeln!(extra, "{indent}# - {}: {s}", v.name);
+            }
+        }
+    }
+    if k.optional {
+        let _ = writeln!(extra, "{indent}# optional");
+    }
+    if let Some(change) = k.change {
+        let _ = writeln!(extra, "{indent}# {change}");
+    }
+    if let Some(default) = k.overridden {
+        let _ = writeln!(extra, "{indent}# default: {default}");
+    }
+    if let Some(title) = k.section {
+        let line = "-".repeat(display_width(title));
+        let _ = write!(out, "{indent}# {line}\n{indent}# {title}\n{indent}# {line}\n");
+    }
+    match (k.description, k.placement) {
+        (Some(s), CommentPlacement::Inline) => {
+            // Nested values start on the next line, so the comment goes before them
+            let (first, rest) = match value.find('\n') {
+                Some(i) => value.split_at(i),
+                None => (value, ""),
+            };
+            let start = out.len();
+            let _ = write!(out, "{extra}{prefix}{indent}{key}{ty}: {first}");
+            let trimmed = out.trim_end().len();
+            out.truncate(trimmed.max(start + extra.len()));
+            let width = display_width(&out[start + extra.len()..]);
+            if let Some(c) = k.comment_column {
+                out.extend(std::iter::repeat_n(' ', c.saturating_sub(width)));
+            }
+            let _ = write!(out, "  # {s}{rest}");
+        },
+        (Some(s), CommentPlacement::Above) if k.section.is_some() => {
+            let _ = write!(out, "{extra}{indent}# {s}\n{prefix}{indent}{key}{ty}: {value}");
+        },
+        (Some(s), CommentPlacement::Above) => {
+            let _ = write!(out, "{indent}# {s}\n{extra}{prefix}{indent}{key}{ty}: {value}");
+        },
+        (None, _) => {
+            let _ = write!(out, "{extra}{prefix}{indent}{key}{ty}: {value}");
+        },
+    }
+}
+
+/// Displays a scalar the way it would be written in YAML, e.g. with quotes for strings that
+/// would otherwise be read as another type
+fn default_format_scalar(v: &Value, null: &str) -> String {
+    match v {
+        Value::Null => null.to_owned(),
+        // serde_yaml would use a block scalar, which doesn't fit on a line
+        Value::String(s) if s.contains(['\n', '\r']) => double_quoted(s),
+        // serde_yaml follows YAML 1.2, but these are booleans for YAML 1.1 parsers
+        Value::String(s) if YAML_1_1_BOOLS.contains(&s.as_str()) => format!("'{s}'"),
+        Value::Bool(_) | Value::Number(_) | Value::String(_) => match serde_yaml::to_string(v) {
+            Ok(s) => s.trim_end_matches('\n').to_owned(),
+            Err(_) => format!("{:?}", v),
+        },
+        _ => unreachable!{},
+    }
+}
+
+const YAML_1_1_BOOLS: &[&str] = &["y", "Y", "yes", "Yes", "YES", "n", "N", "no", "No", "NO",
+                                   "on", "On", "ON", "off", "Off", "OFF"];
+
+/// Displays the type of a value instead of the value, e.g. `<string>`
+fn placeholder(v: &Value) -> String {
+    match v {
+        Value::Tagged(t) => placeholder(&t.value),
+        v => format!("<{}>", format!("{:?}", ValueType::from(v)).to_lowercase()),
+    }
+}
+
+/// Displays a string as a YAML double-quoted scalar
+pub(crate) fn double_quoted(s: &str) -> String {
+    let mut result = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
 }
 
 fn default_format_mapping(args: InnerArgs) -> String {
@@ -83,18 +583,208 @@ fn default_format_mapping(args: InnerArgs) -> String {
 }
 
 fn default_format_list(args: InnerArgs) -> String {
-    format!("[{}]", args.inner.join(", "))
+    if args.path.is_empty() {
+        // A top-level list is displayed as a block, one item per line
+        args.inner.iter()
+            .map(|s| format!("- {s}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else {
+        format!("[{}]", args.inner.join(", "))
+    }
+}
+
+
+/// How a value changed compared to previous defaults, see `Documenter::previous_defaults`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// The key didn't exist
+    New,
+    /// The default value was different, and is included
+    DefaultChanged(Value),
+}
+
+/// A node of the documentation tree, see `Documenter::doc_tree`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocNode {
+    /// The keys leading to this node, empty for the root
+    pub path: Vec<String>,
+    /// The type of the value, or of what an optional field contains
+    pub ty: ValueType,
+    /// Whether this is an optional field whose default is null, when documenting a type
+    pub optional: bool,
+    /// The default value, including the values of the children
+    pub default: Value,
+    /// The description, in the selected language if there are translations
+    pub description: Option<String>,
+    /// The constraints given by the `__min__`, `__max__` and `__pattern__` keys of the
+    /// description, if any
+    pub constraints: Option<Constraints>,
+    /// The variants of an enum, from the `__variants__` key of its description
+    pub variants: Vec<Variant>,
+    /// The title of the section starting at this node, from the `__section__` key of its
+    /// description
+    pub section: Option<String>,
+    /// The environment variable for this value, if enabled (see `Documenter::env_var_prefix`)
+    pub env_var: Option<String>,
+    /// The change compared to previous defaults, if they were set
+    pub change: Option<Change>,
+    /// When documenting a configuration against its defaults (see
+    /// `Documenter::apply_current`), the default of a value that differs from it
+    pub overridden: Option<Value>,
+    /// The nodes of the fields of a mapping, empty for other values
+    pub children: Vec<DocNode>,
+}
+
+impl DocNode {
+    /// The last key of the path, or `None` for the root
+    pub fn key(&self) -> Option<&str> {
+        self.path.last()
+            .map(|s| s.as_str())
+    }
+
+    /// Whether this node is not a mapping, and thus has no children
+    pub fn is_leaf(&self) -> bool {
+        match self.default {
+            Value::Tagged(ref t) => !t.value.is_mapping(),
+            ref v => !v.is_mapping(),
+        }
+    }
+
+    /// Lists the leaves under this node, or the node itself if it is a leaf
+    pub fn leaves(&self) -> Vec<&DocNode> {
+        if self.is_leaf() {
+            return vec![self];
+        }
+        self.children.iter()
+            .flat_map(|c| c.leaves())
+            .collect()
+    }
+}
+
+/// Adds Markdown footnotes at the end of a page
+fn push_markdown_footnotes(content: &mut String, notes: &[String]) {
+    if !notes.is_empty() {
+        content.push('\n');
+    }
+    for (i, note) in notes.iter().enumerate() {
+        content.push_str(&format!("[^{}]: `{note}`\n", i + 1));
+    }
+}
+
+/// Link to the anchor of a path in the same page
+fn local_link(path: &[String]) -> String {
+    format!("#{}", anchor(path))
+}
+
+/// Result of `Documenter::apply_mdbook`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MdBook {
+    /// The fragment of `SUMMARY.md` listing the chapters
+    pub summary: String,
+    /// One chapter per top-level key, in order
+    pub chapters: Vec<Chapter>,
+}
+
+/// A Markdown file of an `MdBook`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    /// The title of the chapter: its top-level key, or `Overview` for `README.md`
+    pub title: String,
+    /// The file name, relative to the directory of `SUMMARY.md`
+    pub file: String,
+    /// The Markdown content of the file
+    pub content: String,
 }
 
+impl MdBook {
+    /// Writes the chapters in `dir`. The summary is not written, since it is only a
+    /// fragment of the `SUMMARY.md` of the book.
+    pub fn write_chapters(&self, dir: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        for chapter in &self.chapters {
+            std::fs::write(dir.as_ref().join(&chapter.file), &chapter.content)?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of `Documenter::coverage`, listing which paths are documented or not
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverageReport {
+    /// Dotted paths that have a description
+    pub documented: Vec<String>,
+    /// Dotted paths that don't have a description
+    pub undocumented: Vec<String>,
+}
+
+impl CoverageReport {
+    /// Total number of paths
+    pub fn total(&self) -> usize {
+        self.documented.len() + self.undocumented.len()
+    }
+
+    /// Percentage of documented paths, 100 if there are no paths at all
+    pub fn percentage(&self) -> f64 {
+        if self.total() == 0 {
+            100.0
+        } else {
+            100.0 * self.documented.len() as f64 / self.total() as f64
+        }
+    }
+}
+
+type ScalarFn<'d> = Arc<dyn Fn(&Value, &[String]) -> String + Send + Sync + 'd>;
+type KeyFn<'d> = Arc<dyn Fn(KeyArgs) -> error::Result<String> + Send + Sync + 'd>;
+type InnerFn<'d> = Arc<dyn Fn(InnerArgs) -> error::Result<String> + Send + Sync + 'd>;
+type TypeFn<'d> = Arc<dyn Fn(&Value) -> String + Send + Sync + 'd>;
+type WarningFn<'d> = Arc<dyn Fn(&DocWarning) + Send + Sync + 'd>;
 
 /// Contains the option for documenting YAML
+///
+/// The formatting closures are owned by the `Documenter`, so if they don't borrow anything
+/// a `Documenter<'static>` can be built once and stored wherever you want. Closures must
+/// be `Send + Sync`, so a `Documenter` can also be cloned and shared between threads.
+#[derive(Clone)]
 pub struct Documenter<'d,> {
-    indent: &'d str,
-    description_field: &'d str,
-    type_name: &'d dyn Fn(&ValueType) -> String,
-    format_key: &'d dyn Fn(KeyArgs) -> String,
-    format_mapping: &'d dyn Fn(InnerArgs) -> String,
-    format_list: &'d dyn Fn(InnerArgs) -> String,
+    indent: Cow<'d, str>,
+    description_field: Cow<'d, str>,
+    type_name: Arc<dyn Fn(&ValueType) -> String + Send + Sync + 'd>,
+    // `None` for the default formatting, which can be done without intermediate strings
+    format_key: Option<KeyFn<'d>>,
+    format_mapping: Option<InnerFn<'d>>,
+    format_list: Option<InnerFn<'d>>,
+    format_scalar: Option<ScalarFn<'d>>,
+    type_formats: BTreeMap<ValueType, TypeFn<'d>>,
+    null: Cow<'d, str>,
+    comment_placement: CommentPlacement,
+    depth_placements: BTreeMap<usize, CommentPlacement>,
+    commented_out: bool,
+    #[cfg(feature = "color")]
+    styles: Option<Styles>,
+    flat_descriptions: bool,
+    strict: bool,
+    on_warning: Option<WarningFn<'d>>,
+    warning_depth: usize,
+    max_depth: usize,
+    language: Option<Cow<'d, str>>,
+    fallback_languages: Vec<Cow<'d, str>>,
+    markdown_toc: bool,
+    align_comments: bool,
+    omit_defaults: bool,
+    max_value_len: Option<usize>,
+    truncation_footnotes: bool,
+    previous: Option<Arc<Value>>,
+    defaults: Option<Arc<Value>>,
+    source_map: bool,
+    env_prefix: Option<Cow<'d, str>>,
+    env_separator: Cow<'d, str>,
+    optional_fields: Arc<BTreeMap<Vec<String>, Option<ValueType>>>,
+}
+
+impl<'d> Default for Documenter<'d> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<'d> Documenter<'d> {
@@ -107,12 +797,38 @@ impl<'d> Documenter<'d> {
     /// ```
     pub fn new() -> Self {
         Documenter {
-            indent: INDENT,
-            description_field: DESCRIPTION,
-            type_name: &ValueType::to_str,
-            format_key: &default_format_key,
-            format_mapping: &default_format_mapping,
-            format_list: &default_format_list,
+            indent: Cow::Borrowed(INDENT),
+            description_field: Cow::Borrowed(DESCRIPTION),
+            type_name: Arc::new(ValueType::to_str),
+            format_key: None,
+            format_mapping: None,
+            format_list: None,
+            format_scalar: None,
+            type_formats: BTreeMap::new(),
+            null: Cow::Borrowed(NULL),
+            comment_placement: CommentPlacement::Above,
+            depth_placements: BTreeMap::new(),
+            commented_out: false,
+            #[cfg(feature = "color")]
+            styles: None,
+            flat_descriptions: false,
+            strict: false,
+            on_warning: None,
+            warning_depth: 8,
+            max_depth: error::DEFAULT_MAX_DEPTH,
+            language: None,
+            fallback_languages: vec![],
+            markdown_toc: false,
+            align_comments: false,
+            omit_defaults: false,
+            max_value_len: None,
+            truncation_footnotes: false,
+            previous: None,
+            defaults: None,
+            source_map: false,
+            env_prefix: None,
+            env_separator: Cow::Borrowed("_"),
+            optional_fields: Arc::new(BTreeMap::new()),
         }
     }
 
@@ -137,8 +853,8 @@ impl<'d> Documenter<'d> {
     ///
     /// By default you shouldn't need to change this, except if your YAML structure actually contains
     /// a field called `__description__`.
-    pub fn description_field(mut self, field: &'d str) -> Self {
-        self.description_field = field;
+    pub fn description_field(mut self, field: impl Into<Cow<'d, str>>) -> Self {
+        self.description_field = field.into();
         self
     }
 
@@ -150,26 +866,27 @@ impl<'d> Documenter<'d> {
     ///
     /// # Argument
     ///
-    /// * f: reference to a (&ValueType) -> String closure or function. It it responsible for
-    ///     returning the type name as string. Typically you will want to match on `yaml_extras::document::ValueType`
-    ///    and maybe call the `yaml_extras_document_ValueType::to_str` function, which is
+    /// * f: a (&ValueType) -> String closure or function. It it responsible for
+    ///   returning the type name as string. Typically you will want to match on `yaml_extras::document::ValueType`
+    ///   and maybe call the `yaml_extras_document_ValueType::to_str` function, which is
     ///   the default.
     ///
     /// # Example
     /// ```
     /// let yaml = serde_yaml::from_str("foo: 42").unwrap();
     /// let mut d = yaml_extras::Documenter::new()
-    ///     .type_name(&|t| format!(" (whatever)"));
+    ///     .type_name(|t| format!(" (whatever)"));
     ///
     /// let mut actual = d.apply_value(&yaml, None).unwrap();
     /// assert_eq!(actual, "foo (whatever): 42");
     ///
-    /// d = d.type_name(&|t| String::new());
+    /// d = d.type_name(|t| String::new());
     /// actual = d.apply_value(&yaml, None).unwrap();
     /// assert_eq!(actual, "foo: 42");
     /// ```
-    pub fn type_name(mut self, f: &'d dyn Fn(&ValueType) -> String) -> Self {
-        self.type_name = f;
+    pub fn type_name<F>(mut self, f: F) -> Self
+    where F: Fn(&ValueType) -> String + Send + Sync + 'd {
+        self.type_name = Arc::new(f);
         self
     }
 
@@ -182,271 +899,3518 @@ impl<'d> Documenter<'d> {
     /// bar: true"#).unwrap();
     /// let actual = yaml_extras::Documenter::new()
     ///     // Quite useless way to display the info
-    ///     .format_key(&|args| format!("{}!!!", args.key.to_uppercase()))
+    ///     .format_key(|args| format!("{}!!!", args.key.to_uppercase()))
     ///     .apply_value(&yaml, None)
     ///     .unwrap();
     ///
     /// assert_eq!(actual, "FOO!!!\nBAR!!!");
     /// ```
-    pub fn format_key(mut self, f: &'d dyn Fn(KeyArgs) -> String) -> Self {
-        self.format_key = f;
+    ///
+    /// The closure is given the rendered value as a string, so with a custom closure each
+    /// nested mapping is rendered into its own string before being passed to its parent,
+    /// instead of being written directly into the output.
+    pub fn format_key<F>(mut self, f: F) -> Self
+    where F: Fn(KeyArgs) -> String + Send + Sync + 'd {
+        self.format_key = Some(Arc::new(move |k| Ok(f(k))));
+        self
+    }
+
+    /// Same as `format_key`, but with a closure that can fail.
+    ///
+    /// The error will be returned as is by `apply_value`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str::<serde_yaml::Value>("foo: 42").unwrap();
+    /// let res = yaml_extras::Documenter::new()
+    ///     .try_format_key(|args| if args.key == "foo" {
+    ///         Err(yaml_extras::Error::Document("no foo allowed".into()))
+    ///     } else {
+    ///         Ok(args.key.to_owned())
+    ///     })
+    ///     .apply_value(&yaml, None);
+    ///
+    /// assert!(res.is_err());
+    /// ```
+    pub fn try_format_key<F>(mut self, f: F) -> Self
+    where F: Fn(KeyArgs) -> error::Result<String> + Send + Sync + 'd {
+        self.format_key = Some(Arc::new(f));
         self
     }
 
     /// Change the way `Mappings` are displayed.
-    pub fn format_mapping(mut self, f: &'d dyn Fn(InnerArgs) -> String) -> Self {
-        self.format_mapping = f;
+    ///
+    /// Like with `format_key`, the entries are rendered into separate strings before being
+    /// given to the closure.
+    pub fn format_mapping<F>(mut self, f: F) -> Self
+    where F: Fn(InnerArgs) -> String + Send + Sync + 'd {
+        self.format_mapping = Some(Arc::new(move |args| Ok(f(args))));
+        self
+    }
+
+    /// Same as `format_mapping`, but with a closure that can fail.
+    pub fn try_format_mapping<F>(mut self, f: F) -> Self
+    where F: Fn(InnerArgs) -> error::Result<String> + Send + Sync + 'd {
+        self.format_mapping = Some(Arc::new(f));
         self
     }
 
     /// Change the way `Sequences` are displayed.
-    pub fn format_list(mut self, f: &'d dyn Fn(InnerArgs) -> String) -> Self {
-        self.format_list = f;
+    ///
+    /// The items are rendered into separate strings before being given to the closure.
+    pub fn format_list<F>(mut self, f: F) -> Self
+    where F: Fn(InnerArgs) -> String + Send + Sync + 'd {
+        self.format_list = Some(Arc::new(move |args| Ok(f(args))));
+        self
+    }
+
+    /// Same as `format_list`, but with a closure that can fail.
+    pub fn try_format_list<F>(mut self, f: F) -> Self
+    where F: Fn(InnerArgs) -> error::Result<String> + Send + Sync + 'd {
+        self.format_list = Some(Arc::new(f));
         self
     }
     
-    /// Change the indent. Default: 4 spaces.
+    /// Change the way scalar values (booleans, numbers, strings and null) are displayed.
+    ///
+    /// The closure receives the value and its path in the structure.
     ///
     /// # Example
     ///
     /// ```
-    /// let d = yaml_extras::Documenter::new()
-    ///     .indent("\t");
+    /// let yaml = serde_yaml::from_str::<serde_yaml::Value>(r#"foo: bar
+    /// baz: true"#).unwrap();
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .type_name(|_| String::new())
+    ///     .format_scalar(|v, _path| match v {
+    ///         serde_yaml::Value::String(s) => format!("\"{s}\""),
+    ///         serde_yaml::Value::Bool(true) => "yes".to_owned(),
+    ///         serde_yaml::Value::Bool(false) => "no".to_owned(),
+    ///         v => serde_yaml::to_string(v).unwrap().trim_end().to_owned(),
+    ///     })
+    ///     .apply_value(&yaml, None)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(actual, "foo: \"bar\"\nbaz: yes");
     /// ```
-    pub fn indent(mut self, indent: &'d str) -> Self {
-        self.indent = indent;
+    pub fn format_scalar<F>(mut self, f: F) -> Self
+    where F: Fn(&Value, &[String]) -> String + Send + Sync + 'd {
+        self.format_scalar = Some(Arc::new(f));
         self
     }
 
-    fn indent_str(&self, struct_path: &Vec<String>) -> String {
-        let mut content = String::new();
-        for _ in 0..struct_path.len() {
-            content.push_str(self.indent);
-        }
-        content
+    /// Change the way scalar values of a given type are displayed, e.g. to display all
+    /// floats with a fixed precision.
+    ///
+    /// This takes precedence over the closure set with `format_scalar`, which is still used
+    /// for other types.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use yaml_extras::document::ValueType;
+    ///
+    /// let yaml = serde_yaml::from_str("ratio: 0.5\nverbose: true").unwrap();
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .type_name(|_| String::new())
+    ///     .format_type(ValueType::Number, |v| format!("{:.2}", v.as_f64().unwrap_or_default()))
+    ///     .format_type(ValueType::Bool, |v| if v.as_bool() == Some(true) { "yes" } else { "no" }.to_owned())
+    ///     .apply_value(&yaml, None)?;
+    ///
+    /// assert_eq!(actual, "ratio: 0.50\nverbose: yes");
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn format_type<F>(mut self, ty: ValueType, f: F) -> Self
+    where F: Fn(&Value) -> String + Send + Sync + 'd {
+        self.type_formats.insert(ty, Arc::new(f));
+        self
     }
 
-
-    fn document_val(&self, val: &Value, description: Option<&Value>, struct_path: &mut Vec<String>) -> error::Result<String> {
-        let indent = self.indent_str(struct_path);
-
-        match val {
-            Value::Mapping(ref m) => {
-                let mut list = vec![];
-
-                for (key, value) in m.iter() {
-                    let ty = match value {
-                        Value::Null => ValueType::Null,
-                        Value::Bool(_) => ValueType::Bool,
-                        Value::Number(_) => ValueType::Number,
-                        Value::String(_) => ValueType::String,
-                        Value::Sequence(_) => ValueType::List,
-                        Value::Mapping(_) => ValueType::Mapping,
-                        Value::Tagged(_) => ValueType::Tagged,
-                    };
-                    // Try displaying the description, if it exists
-                    let desc_value = description.and_then(|d| d.as_mapping())
-                        .and_then(|m| m.get(key));
-                    let mut the_description: Option<&str> = None;
-                    if let Some(inner) = desc_value {
-                        match inner {
-                            Value::String(s) => {
-                                the_description = Some(s);
-                            }
-                            Value::Mapping(m) => {
-                                // Try to see if there is a description field for this mapping
-                                let desc = m.get(self.description_field)
-                                    .and_then(|v| v.as_str());
-                                if let Some(s) = desc {
-                                    the_description = Some(s);
-                                }
-                            }
-                            _ => {
-                                
-                            }
-                        }
-                    }
-                    
-                    
-                    // Display the key name
-                    let k = if key.is_string() {
-                        key.as_str().unwrap().to_owned()
-                    } else {
-                        format!("{:?}", key)
-                    };
-                    struct_path.push(k.to_owned());
-                    let v = self.document_val(value, desc_value, struct_path)?;
-                    struct_path.pop();
-
-                    let key_args = KeyArgs {yaml_value: value,
-                                            path: struct_path,
-                                            indent: &indent,
-                                            key: &k,
-                                            description: the_description,
-                                            ty: &(*self.type_name)(&ty),
-                                            value: &v};
-                    list.push((*self.format_key)(key_args));
-                }
-                let args = InnerArgs {
-                    inner: &list,
-                    path: struct_path,
-                    indent: &indent,
-                };
-                Ok((*self.format_mapping)(args))
-            },
-            Value::Sequence(ref s) => {
-                struct_path.push("-".to_owned());
-                let mut list = vec![];
-                for v in s.iter() {
-                    list.push(self.document_val(v, None, struct_path)?);
-                }
-                struct_path.pop();
-                let args = InnerArgs {
-                    inner: &list,
-                    path: struct_path,
-                    indent: &indent,
-                };
-                Ok((*self.format_list)(args))
-            },
-            Value::Bool(b) => { Ok(format!("{b}")) },
-            Value::String(ref s) => { Ok(format!("{s}")) },
-            Value::Null => { Ok("Null".to_owned()) },
-            Value::Tagged(ref t) => { self.document_val(&t.value, description, struct_path) },
-            Value::Number(ref n) => {
-                if let Some(i) = n.as_i64() {
-                    Ok(format!("{i}"))
-                } else if let Some(f) = n.as_f64() {
-                    Ok(format!("{f}"))
-                } else {
-                    unreachable!{};
-                }
-            }
-        }
+    /// Change the way null values are displayed. Default: "Null".
+    ///
+    /// This has no effect if a closure was set with `format_scalar`, or with `format_type`
+    /// for `ValueType::Null`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("foo: null").unwrap();
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .null_repr("~")
+    ///     .apply_value(&yaml, None)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(actual, "foo: ~");
+    /// ```
+    pub fn null_repr(mut self, null: impl Into<Cow<'d, str>>) -> Self {
+        self.null = null.into();
+        self
     }
 
-
-    /// Uses a YAML representation from a default struct to document an API or options
-    /// in a YAML-looking way
-    ///
-    /// The idea is to first generate a yaml representation with YourStruct::default()
-    /// to get a mostly automated API description.
+    /// Change the indent. Default: 4 spaces.
     ///
-    /// # Arguments
+    /// # Example
     ///
-    /// * `value`: should correspond to a `serde_yaml` Value with the default values of your
-    ///   structure
-    /// * `description`: an optional `serde_yaml` value mirroring the `value` but with descriptions for
-    ///    fields you want to document. Use `__description__` inside a `Mapping` to document the
-    ///   upper-level field.
+    /// ```
+    /// let d = yaml_extras::Documenter::new()
+    ///     .indent("\t");
+    /// ```
+    pub fn indent(mut self, indent: impl Into<Cow<'d, str>>) -> Self {
+        self.indent = indent.into();
+        self
+    }
+
+    /// Change where descriptions are displayed. Default: `CommentPlacement::Above`.
     ///
     /// # Example
     ///
     /// ```
-    ///         let desc_yaml = r#"
-    /// foo:
-    ///     __description__: Description for foo
-    ///     bar: Description for bar
-    /// "#;
-    /// 
-    ///         let yaml = r#"
+    /// use yaml_extras::document::CommentPlacement;
+    ///
+    /// let yaml = serde_yaml::from_str("foo: 42").unwrap();
+    /// let desc = serde_yaml::from_str("foo: The answer").unwrap();
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .comment_placement(CommentPlacement::Inline)
+    ///     .apply_value(&yaml, Some(&desc))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(actual, "foo (Number): 42  # The answer");
+    /// ```
+    pub fn comment_placement(mut self, placement: CommentPlacement) -> Self {
+        self.comment_placement = placement;
+        self
+    }
+
+    /// Set to `true` to align the inline descriptions of the keys of a same mapping, see
+    /// `comment_placement`. Alignment uses the display width, so it works with wide
+    /// (e.g. CJK) characters. Default: `false`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("port: 8080\nname: サーバー").unwrap();
+    /// let desc = serde_yaml::from_str("port: Port\nname: Name").unwrap();
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .comment_placement(yaml_extras::document::CommentPlacement::Inline)
+    ///     .align_comments(true)
+    ///     .apply_value(&yaml, Some(&desc))?;
+    ///
+    /// assert_eq!(actual, "port (Number): 8080      \u{23} Port\nname (String): サーバー  \u{23} Name");
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn align_comments(mut self, align: bool) -> Self {
+        self.align_comments = align;
+        self
+    }
+
+    /// Change where descriptions are displayed for keys at a given depth only, overriding
+    /// `comment_placement`. Top-level keys have a depth of 0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use yaml_extras::document::CommentPlacement;
+    ///
+    /// // Sections get a comment above, their fields an inline one
+    /// let d = yaml_extras::Documenter::new()
+    ///     .comment_placement(CommentPlacement::Inline)
+    ///     .comment_placement_at_depth(0, CommentPlacement::Above);
+    /// ```
+    pub fn comment_placement_at_depth(mut self, depth: usize, placement: CommentPlacement) -> Self {
+        self.depth_placements.insert(depth, placement);
+        self
+    }
+
+    /// Set to `true` to comment out every key, generating the classic "uncomment to override"
+    /// sample configuration file. Default: `false`.
+    ///
+    /// Keys are prefixed with `#` (without space) while descriptions keep their `# ` prefix,
+    /// so the two can still be told apart.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("port: 22").unwrap();
+    /// let desc = serde_yaml::from_str("port: Port to listen on").unwrap();
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .type_name(|_| String::new())
+    ///     .commented_out(true)
+    ///     .apply_value(&yaml, Some(&desc))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(actual, "# Port to listen on\n#port: 22");
+    /// ```
+    pub fn commented_out(mut self, commented_out: bool) -> Self {
+        self.commented_out = commented_out;
+        self
+    }
+
+    /// Colorize the output with ANSI escape codes, e.g. for printing to a terminal.
+    ///
+    /// Keys, types, scalar values and descriptions are painted before being passed to
+    /// the formatting closures. Requires the `color` feature.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use yaml_extras::document::{Style, Styles};
+    ///
+    /// let d = yaml_extras::Documenter::new()
+    ///     .colors(Styles {
+    ///         key: Style::new().red(),
+    ///         ..Styles::default()
+    ///     });
+    /// ```
+    #[cfg(feature = "color")]
+    pub fn colors(mut self, styles: Styles) -> Self {
+        self.styles = Some(styles);
+        self
+    }
+
+    #[cfg(feature = "color")]
+    fn paint(&self, part: Part, s: String) -> String {
+        use yansi::Paint;
+
+        let Some(styles) = &self.styles else {
+            return s;
+        };
+        let style = match part {
+            Part::Key => styles.key,
+            Part::Type => styles.ty,
+            Part::Value => styles.value,
+            Part::Description => styles.description,
+        };
+        if s.is_empty() {
+            s
+        } else {
+            s.paint(style).to_string()
+        }
+    }
+
+    #[cfg(not(feature = "color"))]
+    fn paint(&self, _part: Part, s: String) -> String {
+        s
+    }
+
+    /// Set to `true` if descriptions are given as a flat mapping of dotted paths to strings,
+    /// instead of a structure mirroring the documented value. Default: `false`.
+    ///
+    /// The description is then restructured (see `Restructurer`) before being used. A path that
+    /// is also the prefix of other paths documents the corresponding mapping.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("foo: {bar: 42}").unwrap();
+    /// let desc = serde_yaml::from_str(r#"
+    /// foo: Description for foo
+    /// foo.bar: Description for bar
+    /// "#).unwrap();
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .flat_descriptions(true)
+    ///     .apply_value(&yaml, Some(&desc))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(actual, "# Description for foo
+    /// foo: \n    # Description for bar
+    ///     bar (Number): 42");
+    /// ```
+    pub fn flat_descriptions(mut self, flat: bool) -> Self {
+        self.flat_descriptions = flat;
+        self
+    }
+
+    /// Set to `true` to return an error if the description contains paths that are not
+    /// present in the documented value, e.g. because of a typo. Default: `false`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("foo: 42").unwrap();
+    /// let desc = serde_yaml::from_str("fooo: Description for foo").unwrap();
+    /// let res = yaml_extras::Documenter::new()
+    ///     .strict(true)
+    ///     .apply_value(&yaml, Some(&desc));
+    ///
+    /// assert!(res.is_err());
+    /// ```
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets a closure called for each problem that doesn't prevent documenting a value (see
+    /// `DocWarning`), e.g. to log them or to fail a CI job.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let yaml = serde_yaml::from_str("foo: 42").unwrap();
+    /// let desc = serde_yaml::from_str("fooo: Description for foo").unwrap();
+    /// let warnings = Arc::new(Mutex::new(vec![]));
+    /// let w = warnings.clone();
+    /// yaml_extras::Documenter::new()
+    ///     .on_warning(move |warning| w.lock().unwrap().push(warning.to_string()))
+    ///     .apply_value(&yaml, Some(&desc))?;
+    ///
+    /// assert_eq!(*warnings.lock().unwrap(), vec!["description found for unknown path: fooo"]);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn on_warning<F>(mut self, f: F) -> Self
+    where F: Fn(&DocWarning) + Send + Sync + 'd {
+        self.on_warning = Some(Arc::new(f));
+        self
+    }
+
+    /// Values nested deeper than this depth get a `DocWarning::DeepNesting` warning, see
+    /// `on_warning`. Top-level keys have a depth of 0. Default: 8.
+    pub fn warning_depth(mut self, depth: usize) -> Self {
+        self.warning_depth = depth;
+        self
+    }
+
+    /// Values (or descriptions) nested deeper than this depth are rejected with
+    /// `Error::DepthExceeded`, instead of risking a stack overflow. Default: 128.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("a: {b: {c: 1}}").unwrap();
+    /// let res = yaml_extras::Documenter::new()
+    ///     .max_depth(2)
+    ///     .apply_value(&yaml, None);
+    /// assert!(matches!(res, Err(yaml_extras::Error::DepthExceeded { .. })));
+    /// ```
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Select the language of descriptions. Default: none.
+    ///
+    /// Descriptions can then be mappings from language codes to strings. For a mapping
+    /// containing other fields, it is the description field that contains these translations.
+    /// If there is no translation for this language, fallback languages are tried in order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("foo: {bar: 42, baz: true}").unwrap();
+    /// let desc = serde_yaml::from_str(r#"
     /// foo:
-    ///     bar: 42
-    /// "#;
-    /// 
-    ///         let expected = "# Description for foo
+    ///     __description__:
+    ///         en: Description for foo
+    ///         fr: Description de foo
+    ///     bar:
+    ///         en: Description for bar
+    ///     baz: Description for baz
+    /// "#).unwrap();
+    ///
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .language("fr")
+    ///     .fallback_language("en")
+    ///     .apply_value(&yaml, Some(&desc))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(actual, "# Description de foo
     /// foo: \n    # Description for bar
-    ///     bar (Number): 42";
-    /// 
-    ///         let value: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
-    ///         let desc: serde_yaml::Value = serde_yaml::from_str(&desc_yaml).unwrap();
-    ///         let s = yaml_extras::Documenter::new()
-    ///             .apply_value(&value, Some(&desc))
-    ///             .unwrap();
-    ///         assert_eq!(s, expected);
+    ///     bar (Number): 42\n    # Description for baz
+    ///     baz (Bool): true");
     /// ```
-    pub fn apply_value(&self, value: &Value, description: Option<&Value>) -> error::Result<String> {
+    pub fn language(mut self, language: impl Into<Cow<'d, str>>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Add a language to try, after the ones previously set, when a translation is missing
+    pub fn fallback_language(mut self, language: impl Into<Cow<'d, str>>) -> Self {
+        self.fallback_languages.push(language.into());
+        self
+    }
+
+    /// Gets the translation of a description, if it is localized
+    fn localize<'v>(&self, description: &'v Value) -> Option<&'v str> {
+        match description {
+            Value::String(s) => Some(s),
+            Value::Mapping(m) => self.language.iter()
+                .chain(self.fallback_languages.iter())
+                .find_map(|l| m.get(l.as_ref()))
+                .and_then(|v| v.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Lists the paths in `description` that don't exist in `value`
+    fn orphan_descriptions(&self, value: &Value, description: &Value, struct_path: &mut Vec<String>, orphans: &mut Vec<String>) {
+        let Some(d) = description.as_mapping() else {
+            return;
+        };
+        let value = match value {
+            Value::Tagged(t) => &t.value,
+            v => v,
+        };
+        if !value.is_mapping() {
+            // Not nested descriptions but translations
+            return;
+        }
+        for (key, inner) in d.iter() {
+            if key.as_str() == Some(self.description_field.as_ref())
+                || [MIN, MAX, PATTERN, SECTION, VARIANTS].iter().any(|k| key.as_str() == Some(k)) {
+                continue;
+            }
+            struct_path.push(key_token(key));
+            match value.as_mapping().and_then(|m| m.get(key)) {
+                Some(v) => self.orphan_descriptions(v, inner, struct_path, orphans),
+                None => orphans.push(struct_path.join(".")),
+            }
+            struct_path.pop();
+        }
+    }
+
+    /// Gets the description string for a value from the matching part of the description document
+    fn description_of<'v>(&self, desc_value: Option<&'v Value>, value: &Value) -> Option<&'v str> {
+        let desc_value = desc_value?;
+        let is_mapping = match value {
+            Value::Tagged(t) => t.value.is_mapping(),
+            v => v.is_mapping(),
+        };
+        // For a leaf, a mapping can only contain translations
+        let local = if is_mapping {
+            None
+        } else {
+            self.localize(desc_value)
+        };
+        // Try to see if there is a description field for this mapping
+        local.or_else(|| desc_value.as_mapping()
+                      .and_then(|m| m.get(self.description_field.as_ref()))
+                      .and_then(|v| self.localize(v)))
+    }
+
+    /// Checks the description if needed, then documents the value
+    fn document_root(&self, value: &Value, description: Option<&Value>) -> error::Result<String> {
+        let root = self.tree(value, description)?;
+        self.render_root(&root)
+    }
+
+    /// Renders a tree, with footnotes for the truncated values and the description of the root
+    fn render_root(&self, root: &DocNode) -> error::Result<String> {
+        let mut notes = vec![];
+        let mut content = self.document_node(root, &mut notes)?;
+        for (i, note) in notes.iter().enumerate() {
+            content.push_str(&format!("\n# [{}] {note}", i + 1));
+        }
+        match root.description {
+            Some(ref s) => {
+                let s = self.paint(Part::Description, resolve_references(s, &|p| p.to_owned()));
+                Ok(format!("# {s}\n{content}"))
+            },
+            None => Ok(content),
+        }
+    }
+
+    /// Builds the documentation tree that is rendered by the `apply_*` methods, e.g. to
+    /// feed it to your own templates.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("server: {port: 8080}").unwrap();
+    /// let desc = serde_yaml::from_str("server: {port: Port to listen on}").unwrap();
+    /// let root = yaml_extras::Documenter::new()
+    ///     .doc_tree(&yaml, Some(&desc))?;
+    ///
+    /// let port = &root.children[0].children[0];
+    /// assert_eq!(port.path, vec!["server", "port"]);
+    /// assert_eq!(port.default, serde_yaml::Value::from(8080));
+    /// assert_eq!(port.description.as_deref(), Some("Port to listen on"));
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn doc_tree(&self, value: &Value, description: Option<&Value>) -> error::Result<DocNode> {
+        let description = self.prepare_description(description)?;
+        self.tree(value, description.as_deref())
+    }
+
+    /// Checks the (already prepared) description if needed, then builds the tree
+    fn tree(&self, value: &Value, description: Option<&Value>) -> error::Result<DocNode> {
+        error::check_depth(value, self.max_depth)?;
+        if let Some(d) = description {
+            // Descriptions of leaves are one level deeper, e.g. with `__description__`
+            error::check_depth(d, self.max_depth + 1)?;
+        }
         let mut struct_path = vec![];
-        self.document_val(value, description, &mut struct_path)
+        let mut orphans = vec![];
+        if let (true, Some(d)) = (self.strict || self.on_warning.is_some(), description) {
+            self.orphan_descriptions(value, d, &mut struct_path, &mut orphans);
+        }
+        if self.strict && !orphans.is_empty() {
+            return Err(error::Error::Document(format!("descriptions found for unknown paths: {}",
+                                                      orphans.join(", "))));
+        }
+        if let Some(ref warn) = self.on_warning {
+            for path in orphans {
+                warn(&DocWarning::UnknownDescription(path));
+            }
+            self.value_warnings(value, &mut struct_path, warn.as_ref());
+        }
+        let root = self.node(value, description, self.previous.as_deref(), &mut struct_path);
+        let mut paths = BTreeSet::new();
+        let mut dangling = vec![];
+        Self::collect_paths(&root, &mut paths);
+        Self::dangling_references(&root, &paths, &mut dangling);
+        if !dangling.is_empty() {
+            return Err(error::Error::Document(format!("references to unknown paths: {}",
+                                                      dangling.join(", "))));
+        }
+        Ok(root)
+    }
+
+    /// Emits the warnings about the value itself
+    fn value_warnings(&self, value: &Value, struct_path: &mut Vec<String>, warn: &dyn Fn(&DocWarning)) {
+        // Only the first path that is too deep is reported, not the ones below it
+        if struct_path.len() == self.warning_depth + 2 {
+            warn(&DocWarning::DeepNesting {
+                path: struct_path.join("."),
+                depth: self.warning_depth + 1,
+            });
+        }
+        match value {
+            Value::Mapping(m) => for (k, v) in m.iter() {
+                struct_path.push(key_token(k));
+                if !k.is_string() {
+                    warn(&DocWarning::NonStringKey(struct_path.join(".")));
+                }
+                self.value_warnings(v, struct_path, warn);
+                struct_path.pop();
+            },
+            Value::Sequence(s) => for v in s.iter() {
+                struct_path.push("-".to_owned());
+                self.value_warnings(v, struct_path, warn);
+                struct_path.pop();
+            },
+            Value::Tagged(t) => self.value_warnings(&t.value, struct_path, warn),
+            _ => (),
+        }
+    }
+
+    fn collect_paths(node: &DocNode, paths: &mut BTreeSet<String>) {
+        for child in &node.children {
+            paths.insert(child.path.join("."));
+            Self::collect_paths(child, paths);
+        }
+    }
+
+    fn dangling_references(node: &DocNode, paths: &BTreeSet<String>, dangling: &mut Vec<String>) {
+        if let Some(ref s) = node.description {
+            dangling.extend(references(s).into_iter()
+                            .filter(|p| !paths.contains(*p))
+                            .map(|p| p.to_owned()));
+        }
+        for child in &node.children {
+            Self::dangling_references(child, paths, dangling);
+        }
+    }
+
+    /// Builds a node. `previous` is the previous default of this value, which is only used
+    /// if `previous_defaults` was set.
+    fn node(&self, value: &Value, description: Option<&Value>, previous: Option<&Value>, struct_path: &mut Vec<String>) -> DocNode {
+        let mut children = vec![];
+        let inner = match value {
+            Value::Tagged(t) => &t.value,
+            v => v,
+        };
+        if let Value::Mapping(m) = inner {
+            let previous_m = match previous {
+                Some(Value::Tagged(t)) => t.value.as_mapping(),
+                Some(v) => v.as_mapping(),
+                None => None,
+            };
+            // The fields of a variant are described in its subtree
+            let variant_desc = variant_name(value)
+                .and_then(|name| description.and_then(|d| d.get(VARIANTS))
+                          .and_then(|v| v.get(name.as_str())));
+            for (key, value) in m.iter() {
+                let desc_value = variant_desc.and_then(|d| d.as_mapping())
+                    .and_then(|m| m.get(key))
+                    .or_else(|| description.and_then(|d| d.as_mapping())
+                             .and_then(|m| m.get(key)));
+                struct_path.push(key_token(key));
+                children.push(self.node(value, desc_value, previous_m.and_then(|m| m.get(key)), struct_path));
+                struct_path.pop();
+            }
+        }
+        let env_var = if struct_path.is_empty() {
+            None
+        } else {
+            self.leaf_env_var(struct_path, value)
+        };
+        let overridden = match self.defaults {
+            Some(ref defaults) if !struct_path.is_empty() && !inner.is_mapping() => {
+                value_at(defaults, struct_path).filter(|d| *d != value)
+                    .cloned()
+            },
+            _ => None,
+        };
+        let change = match (&self.previous, previous) {
+            (None, _) => None,
+            _ if struct_path.is_empty() => None,
+            (Some(_), None) => Some(Change::New),
+            (Some(_), Some(p)) => {
+                let is_mapping = match value {
+                    Value::Tagged(t) => t.value.is_mapping(),
+                    v => v.is_mapping(),
+                };
+                if !is_mapping && p != value {
+                    Some(Change::DefaultChanged(p.clone()))
+                } else {
+                    None
+                }
+            },
+        };
+        let optional = match value {
+            Value::Null => self.optional_fields.get(struct_path.as_slice()),
+            _ => None,
+        };
+        DocNode {
+            path: struct_path.clone(),
+            ty: optional.cloned()
+                .flatten()
+                .unwrap_or_else(|| ValueType::from(value)),
+            optional: optional.is_some(),
+            default: value.clone(),
+            description: self.description_of(description, value)
+                .map(|s| s.to_owned()),
+            constraints: description.and_then(Constraints::from_description),
+            variants: description.map(|d| self.variants_of(d, value))
+                .unwrap_or_default(),
+            section: description.and_then(|d| d.as_mapping())
+                .and_then(|m| m.get(SECTION))
+                .and_then(|v| self.localize(v))
+                .map(|s| s.to_owned()),
+            env_var,
+            change,
+            overridden,
+            children,
+        }
+    }
+
+    /// Reads the variants of an enum from its description. The fields of the variant of
+    /// `value` also include the ones it contains.
+    fn variants_of(&self, description: &Value, value: &Value) -> Vec<Variant> {
+        let Some(m) = description.get(VARIANTS).and_then(|v| v.as_mapping()) else {
+            return vec![];
+        };
+        let current = variant_name(value);
+        m.iter()
+            .map(|(name, desc)| {
+                let name = key_token(name);
+                let mut fields = vec![];
+                if current.as_ref() == Some(&name) {
+                    if let Value::Tagged(t) = value {
+                        if let Some(inner) = t.value.as_mapping() {
+                            fields.extend(inner.keys().map(key_token));
+                        }
+                    }
+                }
+                let description = match desc {
+                    Value::Mapping(d) => {
+                        for k in d.keys() {
+                            let k = key_token(k);
+                            if k != self.description_field && !k.starts_with("__") && !fields.contains(&k) {
+                                fields.push(k);
+                            }
+                        }
+                        d.get(self.description_field.as_ref())
+                            .and_then(|v| self.localize(v))
+                    },
+                    d => self.localize(d),
+                };
+                Variant {
+                    name,
+                    fields,
+                    description: description.map(|s| s.to_owned()),
+                }
+            })
+            .collect()
+    }
+
+    /// The marker displayed for a change compared to previous defaults
+    fn change_marker(&self, node: &DocNode) -> Option<String> {
+        match node.change {
+            Some(Change::New) => Some("new in this version".to_owned()),
+            Some(Change::DefaultChanged(_)) if self.omit_defaults => Some("default changed".to_owned()),
+            Some(Change::DefaultChanged(ref p)) => Some(format!("default changed from {}",
+                                                                self.leaf_string(p, &mut node.path.clone()))),
+            None => None,
+        }
+    }
+
+    /// Lists the paths of the previous defaults which don't exist anymore in `value`.
+    ///
+    /// Returns an empty list if `previous_defaults` was not set.
+    pub fn removed_paths(&self, value: &Value) -> Vec<String> {
+        let mut removed = vec![];
+        if let Some(ref previous) = self.previous {
+            Self::collect_removed(value, previous, &mut vec![], &mut removed);
+        }
+        removed
+    }
+
+    fn collect_removed(value: &Value, previous: &Value, struct_path: &mut Vec<String>, removed: &mut Vec<String>) {
+        let (Some(m), Some(previous_m)) = (value.as_mapping(), previous.as_mapping()) else {
+            return;
+        };
+        for (key, p) in previous_m.iter() {
+            struct_path.push(key_token(key));
+            match m.get(key) {
+                Some(v) => Self::collect_removed(v, p, struct_path, removed),
+                None => removed.push(struct_path.join(".")),
+            }
+            struct_path.pop();
+        }
+    }
+
+    /// Lists the constraints found in a description, with the path they apply to
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let desc = serde_yaml::from_str("port: {__description__: Port, __min__: 1, __max__: 65535}").unwrap();
+    /// let constraints = yaml_extras::Documenter::new()
+    ///     .constraints(&desc)?;
+    ///
+    /// assert_eq!(constraints[0].0, "port");
+    /// assert_eq!(constraints[0].1.max, Some(65535.into()));
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn constraints(&self, description: &Value) -> error::Result<Vec<(String, Constraints)>> {
+        let description = self.prepare_description(Some(description))?;
+        let mut list = vec![];
+        if let Some(d) = description {
+            Self::collect_constraints(&d, &mut vec![], &mut list);
+        }
+        Ok(list)
+    }
+
+    fn collect_constraints(description: &Value, struct_path: &mut Vec<String>, list: &mut Vec<(String, Constraints)>) {
+        let Some(m) = description.as_mapping() else {
+            return;
+        };
+        for (key, inner) in m.iter() {
+            struct_path.push(key_token(key));
+            if let Some(c) = Constraints::from_description(inner) {
+                list.push((struct_path.join("."), c));
+            }
+            Self::collect_constraints(inner, struct_path, list);
+            struct_path.pop();
+        }
+    }
+
+    /// Computes which paths of `value` are documented by `description`
+    ///
+    /// Every key of every mapping counts as a path, so a mapping containing other fields
+    /// needs its own description (see `description_field`) to be fully covered.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("foo: {bar: 42, baz: true}").unwrap();
+    /// let desc = serde_yaml::from_str("foo: {bar: Description for bar}").unwrap();
+    /// let report = yaml_extras::Documenter::new()
+    ///     .coverage(&yaml, &desc)?;
+    ///
+    /// assert_eq!(report.documented, vec!["foo.bar"]);
+    /// assert_eq!(report.undocumented, vec!["foo", "foo.baz"]);
+    /// assert!(report.percentage() < 50.0);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn coverage(&self, value: &Value, description: &Value) -> error::Result<CoverageReport> {
+        let description = self.prepare_description(Some(description))?;
+        let root = self.node(value, description.as_deref(), None, &mut vec![]);
+        let mut report = CoverageReport {
+            documented: vec![],
+            undocumented: vec![],
+        };
+        Self::compute_coverage(&root, &mut report);
+        Ok(report)
+    }
+
+    fn compute_coverage(node: &DocNode, report: &mut CoverageReport) {
+        for child in &node.children {
+            if child.description.is_some() {
+                report.documented.push(child.path.join("."));
+            } else {
+                report.undocumented.push(child.path.join("."));
+            }
+            Self::compute_coverage(child, report);
+        }
+    }
+
+    /// Display the environment variable corresponding to each path that is not a
+    /// mapping, starting with this prefix. Default: none, environment variables are not
+    /// displayed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("server: {port: 8080}").unwrap();
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .env_var_prefix("MYAPP")
+    ///     .apply_value(&yaml, None)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(actual, "server: \n    # env: MYAPP_SERVER_PORT\n    port (Number): 8080");
+    /// ```
+    pub fn env_var_prefix(mut self, prefix: impl Into<Cow<'d, str>>) -> Self {
+        self.env_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Change the separator between the prefix and keys in environment variables. Default: "_".
+    pub fn env_var_separator(mut self, separator: impl Into<Cow<'d, str>>) -> Self {
+        self.env_separator = separator.into();
+        self
+    }
+
+    /// Computes the environment variable corresponding to a path, or `None` if no prefix
+    /// was set with `env_var_prefix`.
+    ///
+    /// Keys are uppercased, and characters that are not ASCII letters or digits are replaced
+    /// by `_`, since other characters are not portable in environment variable names.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let d = yaml_extras::Documenter::new()
+    ///     .env_var_prefix("MYAPP")
+    ///     .env_var_separator("__");
+    /// let path = vec!["server".to_owned(), "listen-port".to_owned()];
+    /// assert_eq!(d.env_var(&path).unwrap(), "MYAPP__SERVER__LISTEN_PORT");
+    /// ```
+    pub fn env_var(&self, path: &[String]) -> Option<String> {
+        let prefix = self.env_prefix.as_ref()?;
+        let mut parts = vec![prefix.to_string()];
+        parts.extend(path.iter()
+                     .map(|k| k.chars()
+                          .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+                          .collect::<String>()));
+        Some(parts.join(&self.env_separator))
+    }
+
+    /// Environment variable for a path, if the value can be set this way
+    fn leaf_env_var(&self, path: &[String], value: &Value) -> Option<String> {
+        let is_mapping = match value {
+            Value::Tagged(t) => t.value.is_mapping(),
+            v => v.is_mapping(),
+        };
+        if is_mapping {
+            None
+        } else {
+            self.env_var(path)
+        }
+    }
+
+    /// Set to `true` to only display keys, types and descriptions, e.g. if default values
+    /// depend on the environment or are secret. Values are replaced by their type, such as
+    /// `<string>`. Default: `false`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("db: {password: hunter2, hosts: [a, b]}").unwrap();
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .omit_defaults(true)
+    ///     .apply_value(&yaml, None)?;
+    ///
+    /// assert_eq!(actual, "db: \n    password (String): <string>\n    hosts (List): <list>");
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn omit_defaults(mut self, omit: bool) -> Self {
+        self.omit_defaults = omit;
+        self
+    }
+
+    /// Truncates the displayed values that are longer than `max` characters, with an
+    /// ellipsis. Only the values of lists and scalars are truncated, not of mappings.
+    /// Default: no limit.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("template: Hello, {name}! How are you?").unwrap();
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .truncate_values(12)
+    ///     .apply_value(&yaml, None)?;
+    ///
+    /// assert_eq!(actual, "template (String): Hello, {name…");
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn truncate_values(mut self, max: usize) -> Self {
+        self.max_value_len = Some(max);
+        self
+    }
+
+    /// Set to `true` to display the full value of truncated values in footnotes, see
+    /// `truncate_values`. Default: `false`.
+    pub fn truncation_footnotes(mut self, footnotes: bool) -> Self {
+        self.truncation_footnotes = footnotes;
+        self
+    }
+
+    /// Compares the documented value with the default value of a previous version, marking
+    /// keys that are new or whose default changed. See also `removed_paths`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let old = serde_yaml::from_str("port: 80").unwrap();
+    /// let yaml = serde_yaml::from_str("port: 8080\nhost: localhost").unwrap();
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .previous_defaults(old)
+    ///     .apply_value(&yaml, None)?;
+    ///
+    /// assert_eq!(actual, "\u{23} default changed from 80
+    /// port (Number): 8080
+    /// \u{23} new in this version
+    /// host (String): localhost");
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn previous_defaults(mut self, previous: Value) -> Self {
+        self.previous = Some(Arc::new(previous));
+        self
+    }
+
+    /// Set to `true` to add a table of contents listing the top-level sections at the beginning
+    /// of the Markdown output. Default: `false`.
+    pub fn markdown_toc(mut self, toc: bool) -> Self {
+        self.markdown_toc = toc;
+        self
+    }
+
+    /// Renders the documentation as Markdown.
+    ///
+    /// Each mapping gets a heading and each other value an item in a list, with its type
+    /// and default value. Every path gets a stable HTML anchor (see `anchor`), so in
+    /// descriptions links to `#some.path` are rewritten to point to the anchor of
+    /// `some.path`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("server: {port: 8080, host: localhost}").unwrap();
+    /// let desc = serde_yaml::from_str(r#"
+    /// server:
+    ///     __description__: Server options
+    ///     port: Port to listen on, see also [host](#server.host)
+    /// "#).unwrap();
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .markdown_toc(true)
+    ///     .apply_markdown(&yaml, Some(&desc))?;
+    ///
+    /// assert!(actual.contains("* [server](#server)\n"));
+    /// assert!(actual.contains("<a id=\"server\"></a>\n## `server`\n\nServer options\n"));
+    /// assert!(actual.contains("* <a id=\"server-port\"></a>`port` (Number, default: `8080`): \
+    ///                          Port to listen on, see also [host](#server-host)\n"));
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn apply_markdown(&self, value: &Value, description: Option<&Value>) -> error::Result<String> {
+        let description = self.prepare_description(description)?;
+        let root = self.tree(value, description.as_deref())?;
+        let mut content = String::new();
+        if let Some(ref s) = root.description {
+            content.push_str(&resolve_markdown_links(s, &local_link));
+            content.push_str("\n\n");
+        }
+        if self.markdown_toc && !root.is_leaf() {
+            content.push_str("## Contents\n\n");
+            for child in root.children.iter().filter(|c| !c.is_leaf()) {
+                content.push_str(&format!("* [{}](#{})\n",
+                                          child.key().unwrap_or_default(),
+                                          anchor(&child.path)));
+            }
+            content.push('\n');
+        }
+        let mut notes = vec![];
+        self.markdown_node(&root, &mut content, &local_link, 0, &mut notes);
+        push_markdown_footnotes(&mut content, &notes);
+        Ok(content)
+    }
+
+    /// Renders the documentation as an mdBook: one chapter per top-level mapping, plus
+    /// a `README.md` chapter for the description of the root and its other values, if
+    /// there are any.
+    ///
+    /// Chapters are rendered like with `apply_markdown`, and links to `#some.path` point
+    /// to the chapter containing this path. File names are the anchors of the keys, with a
+    /// number added if several keys would have the same file.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("server: {port: 8080}\nclient: {retries: 3}").unwrap();
+    /// let book = yaml_extras::Documenter::new()
+    ///     .apply_mdbook(&yaml, None)?;
+    ///
+    /// assert_eq!(book.summary, "- [server](server.md)\n- [client](client.md)\n");
+    /// assert_eq!(book.chapters[0].content,
+    ///            "<a id=\"server\"></a>\n# `server`\n\n* <a id=\"server-port\"></a>`port` (Number, default: `8080`)\n");
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn apply_mdbook(&self, value: &Value, description: Option<&Value>) -> error::Result<MdBook> {
+        let description = self.prepare_description(description)?;
+        let root = self.tree(value, description.as_deref())?;
+        if root.is_leaf() {
+            return Err(error::Error::Document("an mdBook can only be generated from a mapping".to_owned()));
+        }
+        let (mappings, leaves): (Vec<_>, Vec<_>) = root.children.iter()
+            .partition(|c| !c.is_leaf());
+        // Different keys may have the same anchor, or differ only by case, which is the same
+        // file on some systems, so a number is added to the names already taken
+        let mut taken = vec!["readme".to_owned()];
+        let mut files: BTreeMap<&str, String> = BTreeMap::new();
+        for c in &mappings {
+            let name = anchor(&c.path);
+            let mut file = name.clone();
+            let mut n = 1;
+            while taken.contains(&file.to_lowercase()) {
+                n += 1;
+                file = format!("{name}-{n}");
+            }
+            taken.push(file.to_lowercase());
+            files.insert(c.key().unwrap_or_default(), format!("{file}.md"));
+        }
+        let link = |path: &[String]| {
+            let file = path.first()
+                .and_then(|k| files.get(k.as_str()))
+                .map(|f| f.as_str())
+                .unwrap_or("README.md");
+            format!("{file}#{}", anchor(path))
+        };
+
+        let mut chapters = vec![];
+        let mut content = String::new();
+        if let Some(ref s) = root.description {
+            content.push_str(&resolve_markdown_links(s, &link));
+            content.push_str("\n\n");
+        }
+        let mut notes = vec![];
+        self.markdown_leaves(&leaves, &mut content, &link, 0, &mut notes);
+        push_markdown_footnotes(&mut content, &notes);
+        if !content.is_empty() {
+            chapters.push(Chapter {
+                title: "Overview".to_owned(),
+                file: "README.md".to_owned(),
+                content,
+            });
+        }
+        for node in mappings {
+            let k = node.key().unwrap_or_default();
+            let mut content = format!("<a id=\"{}\"></a>\n# `{k}`\n\n", anchor(&node.path));
+            if let Some(ref s) = node.description {
+                content.push_str(&resolve_markdown_links(s, &link));
+                content.push_str("\n\n");
+            }
+            let mut notes = vec![];
+            self.markdown_node(node, &mut content, &link, 1, &mut notes);
+            push_markdown_footnotes(&mut content, &notes);
+            chapters.push(Chapter {
+                title: k.to_owned(),
+                file: files[k].clone(),
+                content,
+            });
+        }
+        let summary = chapters.iter()
+            .map(|c| format!("- [{}]({})\n", c.title, c.file))
+            .collect();
+        Ok(MdBook {
+            summary,
+            chapters,
+        })
+    }
+
+    /// Renders the Markdown for a value. `level_offset` is subtracted from the depth to get
+    /// the level of headings.
+    fn markdown_node(&self, node: &DocNode, content: &mut String, link: &dyn Fn(&[String]) -> String, level_offset: usize, notes: &mut Vec<String>) {
+        if node.is_leaf() {
+            content.push_str(&format!("`{}`\n", self.leaf_string(&node.default, &mut node.path.clone())));
+            return;
+        }
+        // Leaves first, so they are not displayed under the heading of a nested mapping
+        let (mappings, leaves): (Vec<_>, Vec<_>) = node.children.iter()
+            .partition(|c| !c.is_leaf());
+        self.markdown_leaves(&leaves, content, link, level_offset, notes);
+        for child in mappings {
+            if !content.is_empty() && !content.ends_with("\n\n") {
+                content.push('\n');
+            }
+            let level = "#".repeat((child.path.len() + 1 - level_offset).min(6));
+            if let Some(ref title) = child.section {
+                content.push_str(&format!("{level} {title}\n\n"));
+            }
+            content.push_str(&format!("<a id=\"{}\"></a>\n{level} `{}`\n\n",
+                                      anchor(&child.path),
+                                      child.key().unwrap_or_default()));
+            if let Some(ref s) = child.description {
+                content.push_str(&resolve_markdown_links(s, link));
+                content.push_str("\n\n");
+            }
+            if !child.variants.is_empty() {
+                let mut summary = variants_summary(&child.variants);
+                summary[..1].make_ascii_uppercase();
+                content.push_str(&format!("{summary}.\n\n"));
+                for v in &child.variants {
+                    if let Some(ref s) = v.description {
+                        content.push_str(&format!("* `{}`: {}\n", v.name, resolve_markdown_links(s, link)));
+                    }
+                }
+                if !content.ends_with("\n\n") {
+                    content.push('\n');
+                }
+            }
+            self.markdown_node(child, content, link, level_offset, notes);
+        }
+    }
+
+    /// Renders leaves as a Markdown list
+    fn markdown_leaves(&self, leaves: &[&DocNode], content: &mut String, link: &dyn Fn(&[String]) -> String, level_offset: usize, notes: &mut Vec<String>) {
+        for leaf in leaves {
+            if let Some(ref title) = leaf.section {
+                if !content.is_empty() && !content.ends_with("\n\n") {
+                    content.push('\n');
+                }
+                let level = "#".repeat((leaf.path.len() + 1 - level_offset).min(6));
+                content.push_str(&format!("{level} {title}\n\n"));
+            }
+            let summary = self.leaf_summary(leaf, &mut |full| {
+                notes.push(full.to_owned());
+                format!("[^{}]", notes.len())
+            });
+            content.push_str(&format!("* <a id=\"{}\"></a>`{}` ({summary})",
+                                      anchor(&leaf.path),
+                                      leaf.key().unwrap_or_default()));
+            if let Some(ref s) = leaf.description {
+                content.push_str(": ");
+                content.push_str(&resolve_markdown_links(s, link));
+            }
+            content.push('\n');
+            for v in &leaf.variants {
+                if let Some(ref s) = v.description {
+                    content.push_str(&format!("    * `{}`: {}\n", v.name, resolve_markdown_links(s, link)));
+                }
+            }
+        }
+    }
+
+    /// Type, default value and environment variable of a leaf, for document outputs.
+    /// `footnote` gives the marker of a footnote containing a value that was truncated.
+    fn leaf_summary(&self, node: &DocNode, footnote: &mut dyn FnMut(&str) -> String) -> String {
+        let ty = (self.type_name)(&node.ty);
+        let ty = ty.trim()
+            .trim_start_matches('(')
+            .trim_end_matches(')');
+        let mut parts = vec![];
+        if !ty.is_empty() {
+            parts.push(ty.to_owned());
+        }
+        if node.optional {
+            parts.push("optional".to_owned());
+        }
+        if !self.omit_defaults {
+            let (value, full) = self.truncate(self.leaf_string(&node.default, &mut node.path.clone()));
+            let note = full.map(|f| footnote(&f))
+                .unwrap_or_default();
+            // When documenting a configuration, the value is not the default
+            let label = if self.defaults.is_some() { "value" } else { "default" };
+            parts.push(format!("{label}: `{value}`{note}"));
+            if let Some(ref d) = node.overridden {
+                parts.push(format!("default: `{}`", self.leaf_string(d, &mut node.path.clone())));
+            }
+        }
+        if let Some(ref var) = node.env_var {
+            parts.push(format!("env: `{var}`"));
+        }
+        if !node.variants.is_empty() {
+            parts.push(variants_summary(&node.variants));
+        }
+        match node.change {
+            Some(Change::New) => parts.push("new in this version".to_owned()),
+            Some(Change::DefaultChanged(_)) if self.omit_defaults => parts.push("default changed".to_owned()),
+            Some(Change::DefaultChanged(ref p)) => parts.push(format!("default changed from `{}`",
+                                                                       self.leaf_string(p, &mut node.path.clone()))),
+            None => (),
+        }
+        parts.join(", ")
+    }
+
+    /// Renders the documentation as AsciiDoc, e.g. to include it in an Asciidoctor or
+    /// Antora pipeline.
+    ///
+    /// Each mapping gets a section and the other values are items of a description list.
+    /// Like with `apply_markdown`, every path gets an anchor, and links to `#some.path` in
+    /// descriptions are rewritten as cross references.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("server: {port: 8080, host: localhost}").unwrap();
+    /// let desc = serde_yaml::from_str(r#"
+    /// server:
+    ///     __description__: Server options
+    ///     port: Port to listen on, see also [host](#server.host)
+    /// "#).unwrap();
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .apply_asciidoc(&yaml, Some(&desc))?;
+    ///
+    /// assert_eq!(actual, "[[server]]
+    /// == `server`
+    ///
+    /// Server options
+    ///
+    /// [[server-port]]`port` (Number, default: `8080`)::
+    /// Port to listen on, see also <<server-host,host>>
+    /// [[server-host]]`host` (String, default: `localhost`)::
+    /// ");
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn apply_asciidoc(&self, value: &Value, description: Option<&Value>) -> error::Result<String> {
+        let description = self.prepare_description(description)?;
+        let root = self.tree(value, description.as_deref())?;
+        let mut content = String::new();
+        if let Some(ref s) = root.description {
+            content.push_str(&resolve_asciidoc_links(s));
+            content.push_str("\n\n");
+        }
+        self.asciidoc_node(&root, &mut content);
+        Ok(content)
+    }
+
+    fn asciidoc_node(&self, node: &DocNode, content: &mut String) {
+        if node.is_leaf() {
+            content.push_str(&format!("`{}`\n", self.leaf_string(&node.default, &mut node.path.clone())));
+            return;
+        }
+        // Leaves first, so they are not displayed in the section of a nested mapping
+        let (mappings, leaves): (Vec<_>, Vec<_>) = node.children.iter()
+            .partition(|c| !c.is_leaf());
+        for leaf in leaves {
+            if let Some(ref title) = leaf.section {
+                if !content.is_empty() && !content.ends_with("\n\n") {
+                    content.push('\n');
+                }
+                let level = "=".repeat((leaf.path.len() + 1).min(6));
+                content.push_str(&format!("[discrete]\n{level} {title}\n\n"));
+            }
+            let summary = self.leaf_summary(leaf, &mut |full| format!("footnote:[`{}`]", full.replace(']', "\\]")));
+            content.push_str(&format!("[[{}]]`{}` ({summary})::\n",
+                                      anchor(&leaf.path),
+                                      leaf.key().unwrap_or_default()));
+            if let Some(ref s) = leaf.description {
+                content.push_str(&resolve_asciidoc_links(s));
+                content.push('\n');
+            }
+        }
+        for child in mappings {
+            if !content.is_empty() && !content.ends_with("\n\n") {
+                content.push('\n');
+            }
+            let level = "=".repeat((child.path.len() + 1).min(6));
+            if let Some(ref title) = child.section {
+                content.push_str(&format!("[discrete]\n{level} {title}\n\n"));
+            }
+            content.push_str(&format!("[[{}]]\n{level} `{}`\n\n",
+                                      anchor(&child.path),
+                                      child.key().unwrap_or_default()));
+            if let Some(ref s) = child.description {
+                content.push_str(&resolve_asciidoc_links(s));
+                content.push_str("\n\n");
+            }
+            self.asciidoc_node(child, content);
+        }
+    }
+
+    /// Exports the documentation as CSV, with one row per leaf path and the columns
+    /// `path`, `type`, `default` and `description`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("foo: {bar: 42, baz: [1, 2]}").unwrap();
+    /// let desc = serde_yaml::from_str("foo: {bar: The answer, baz: Some numbers}").unwrap();
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .apply_csv(&yaml, Some(&desc))?;
+    ///
+    /// assert_eq!(actual, r#"path,type,default,description
+    /// foo.bar,Number,42,The answer
+    /// foo.baz,List,"[1, 2]",Some numbers
+    /// "#);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn apply_csv(&self, value: &Value, description: Option<&Value>) -> error::Result<String> {
+        self.export_table(value, description, ",", |field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_owned()
+            }
+        })
+    }
+
+    /// Same as `apply_csv`, but with tab-separated values.
+    ///
+    /// Tabs and line breaks inside fields are replaced by spaces.
+    pub fn apply_tsv(&self, value: &Value, description: Option<&Value>) -> error::Result<String> {
+        self.export_table(value, description, "\t", |field| field.replace(['\t', '\n', '\r'], " "))
+    }
+
+    fn export_table(&self, value: &Value, description: Option<&Value>, separator: &str, escape: impl Fn(&str) -> String) -> error::Result<String> {
+        let description = self.prepare_description(description)?;
+        let root = self.tree(value, description.as_deref())?;
+        let mut header = vec!["path", "type", "default", "description"];
+        if self.env_prefix.is_some() {
+            header.push("env");
+        }
+        if self.previous.is_some() {
+            header.push("change");
+        }
+        let mut content = header.join(separator);
+        content.push('\n');
+        for leaf in root.leaves() {
+            let mut row = vec![leaf.path.join("."),
+                               format!("{:?}", leaf.ty),
+                               self.leaf_string(&leaf.default, &mut leaf.path.clone()),
+                               leaf.description.as_ref()
+                                   .map(|s| resolve_references(s, &|p| p.to_owned()))
+                                   .unwrap_or_default()];
+            if self.env_prefix.is_some() {
+                row.push(leaf.env_var.clone().unwrap_or_default());
+            }
+            if self.previous.is_some() {
+                row.push(self.change_marker(leaf).unwrap_or_default());
+            }
+            let row: Vec<String> = row.iter()
+                .map(|f| escape(f))
+                .collect();
+            content.push_str(&row.join(separator));
+            content.push('\n');
+        }
+        Ok(content)
+    }
+
+    /// Renders the documentation with a template, for layouts that can't be obtained by
+    /// customizing the other outputs, e.g. listing the keys that changed at the end.
+    ///
+    /// The template uses a subset of the Jinja syntax: `{{ var.field }}`,
+    /// `{% for var in list [if [not] var.field] %}…{% endfor %}`,
+    /// `{% if [not] var.field %}…{% else %}…{% endif %}`, `{# comments #}`, and `{%-`/`-%}`
+    /// (or `{{-`/`-}}`) to strip the whitespace before or after a tag. `root` is the root of
+    /// the documentation tree (see `doc_tree`), and the fields of a node are:
+    ///
+    /// * `children` and `leaves`, lists of nodes (see `DocNode`);
+    /// * `path`, `key`, `anchor` and `depth`;
+    /// * `type`, `default`, `description` (with cross-references replaced by their path),
+    ///   `section`, `constraints`, `env` and `change`, which are empty when not relevant.
+    ///
+    /// Empty fields and lists are false in conditions. Filters, expressions, `elif`, `set`,
+    /// macros and includes are not supported, and values are not escaped. An invalid
+    /// template, or one using unknown variables or fields, is an `Error::Document`.
+    ///
+    /// Requires the `template` feature.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("port: 8080\nhost: localhost\nworkers: 4").unwrap();
+    /// let desc = serde_yaml::from_str("port: Port\nhost: Host\nworkers: Number of threads").unwrap();
+    /// let previous = serde_yaml::from_str("port: 80\nhost: localhost").unwrap();
+    /// let template = "\
+    /// {% for leaf in root.leaves if not leaf.change -%}
+    /// * {{ leaf.path }}: {{ leaf.description }}
+    /// {% endfor -%}
+    /// Changes:
+    /// {% for leaf in root.leaves if leaf.change -%}
+    /// * {{ leaf.path }}: {{ leaf.change }}
+    /// {% endfor %}";
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .previous_defaults(previous)
+    ///     .apply_template(&yaml, Some(&desc), template)?;
+    ///
+    /// assert_eq!(actual, "* host: Host
+    /// Changes:
+    /// * port: default changed from 80
+    /// * workers: new in this version
+    /// ");
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    #[cfg(feature = "template")]
+    pub fn apply_template(&self, value: &Value, description: Option<&Value>, template: &str) -> error::Result<String> {
+        let template = Template::parse(template)?;
+        let description = self.prepare_description(description)?;
+        let root = self.tree(value, description.as_deref())?;
+        template.render(&root, &|node, field| self.template_field(node, field))
+    }
+
+    /// The fields of a node, as they are displayed by a template
+    #[cfg(feature = "template")]
+    fn template_field(&self, node: &DocNode, field: &str) -> Option<String> {
+        let s = match field {
+            "path" => node.path.join("."),
+            "key" => node.key().unwrap_or_default().to_owned(),
+            "anchor" => anchor(&node.path),
+            "depth" => node.path.len().to_string(),
+            "type" => format!("{:?}", node.ty),
+            "default" => self.leaf_string(&node.default, &mut node.path.clone()),
+            "description" => node.description.as_ref()
+                .map(|s| resolve_references(s, &|p| p.to_owned()))
+                .unwrap_or_default(),
+            "section" => node.section.clone().unwrap_or_default(),
+            "constraints" => node.constraints.as_ref()
+                .map(|c| c.to_string())
+                .unwrap_or_default(),
+            "env" => node.env_var.clone().unwrap_or_default(),
+            "optional" => if node.optional { "optional".to_owned() } else { String::new() },
+            "variants" => node.variants.iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            "change" => self.change_marker(node).unwrap_or_default(),
+            "overridden" => node.overridden.as_ref()
+                .map(|d| self.leaf_string(d, &mut node.path.clone()))
+                .unwrap_or_default(),
+            _ => return None,
+        };
+        Some(s)
+    }
+
+    /// Displays a value on a single line
+    fn leaf_string(&self, value: &Value, struct_path: &mut Vec<String>) -> String {
+        if self.omit_defaults {
+            return placeholder(value);
+        }
+        match value {
+            Value::Sequence(s) => {
+                struct_path.push("-".to_owned());
+                let inner: Vec<String> = s.iter()
+                    .map(|v| self.leaf_string(v, struct_path))
+                    .collect();
+                struct_path.pop();
+                format!("[{}]", inner.join(", "))
+            },
+            Value::Mapping(m) => {
+                let inner: Vec<String> = m.iter()
+                    .map(|(k, v)| {
+                        let k = key_token(k);
+                        struct_path.push(k.clone());
+                        let v = self.leaf_string(v, struct_path);
+                        struct_path.pop();
+                        format!("{k}: {v}")
+                    })
+                    .collect();
+                format!("{{{}}}", inner.join(", "))
+            },
+            Value::Tagged(t) => self.leaf_string(&t.value, struct_path),
+            scalar => self.scalar_string(scalar, struct_path),
+        }
+    }
+
+    /// Displays a scalar with the closures set with `format_type` or `format_scalar`, if any
+    fn scalar_string(&self, scalar: &Value, struct_path: &[String]) -> String {
+        if let Some(f) = self.type_formats.get(&ValueType::from(scalar)) {
+            return f(scalar);
+        }
+        match self.format_scalar {
+            Some(ref f) => f(scalar, struct_path),
+            None => default_format_scalar(scalar, &self.null),
+        }
+    }
+
+    /// Restructures the description if needed
+    fn prepare_description<'v>(&self, description: Option<&'v Value>) -> error::Result<Option<Cow<'v, Value>>> {
+        match description {
+            Some(d) if self.flat_descriptions => Ok(Some(Cow::Owned(self.restructure_description(d)?))),
+            Some(d) => Ok(Some(Cow::Borrowed(d))),
+            None => Ok(None),
+        }
+    }
+
+    /// Converts a flat description mapping to a nested one
+    fn restructure_description(&self, description: &Value) -> error::Result<Value> {
+        let Some(m) = description.as_mapping() else {
+            return Ok(description.clone());
+        };
+        let keys: Vec<&str> = m.keys()
+            .filter_map(|k| k.as_str())
+            .collect();
+        let mut flat = serde_yaml::Mapping::new();
+        for (k, v) in m.iter() {
+            let new_key = match k.as_str() {
+                // This path contains other ones, so it is actually the description of a mapping
+                Some(s) if v.is_string() && keys.iter()
+                    .any(|o| o.strip_prefix(s).is_some_and(|rest| rest.len() > 1 && rest.starts_with('.'))) => {
+                    Value::String(format!("{s}.{}", self.description_field))
+                },
+                _ => k.clone(),
+            };
+            flat.insert(new_key, v.clone());
+        }
+        let mut restructured = Value::Mapping(flat);
+        Restructurer::new()
+            .apply_value(&mut restructured)?;
+        Ok(restructured)
+    }
+
+    fn placement_at(&self, depth: usize) -> CommentPlacement {
+        self.depth_placements.get(&depth)
+            .copied()
+            .unwrap_or(self.comment_placement)
+    }
+
+    fn indent_str(&self, struct_path: &[String]) -> String {
+        let mut content = String::new();
+        for _ in 0..struct_path.len() {
+            content.push_str(&self.indent);
+        }
+        content
+    }
+
+
+    /// Truncates a value if it is too long (see `truncate_values`), also returning the full
+    /// value if it should be displayed in a footnote
+    fn truncate(&self, s: String) -> (String, Option<String>) {
+        match self.max_value_len {
+            Some(max) if display_width(&s) > max => {
+                let mut width = 0;
+                let mut truncated: String = s.chars()
+                    .take_while(|c| {
+                        width += char_width(*c);
+                        width <= max
+                    })
+                    .collect();
+                truncated.push('…');
+                let full = if self.truncation_footnotes {
+                    Some(s)
+                } else {
+                    None
+                };
+                (truncated, full)
+            },
+            _ => (s, None),
+        }
+    }
+
+    /// Renders a node in a YAML-looking way, with descriptions as comments. The full values
+    /// of truncated leaves are added to `notes`.
+    fn document_node(&self, node: &DocNode, notes: &mut Vec<String>) -> error::Result<String> {
+        let mut out = String::new();
+        self.write_node(node, notes, &mut out)?;
+        Ok(out)
+    }
+
+    /// Same as `document_node`, writing into `out`.
+    ///
+    /// Only with the default `format_key` and `format_mapping`, and without truncation
+    /// footnotes, are nested mappings written directly into `out`. Otherwise, since the hooks
+    /// take the rendered children as strings, each level is rendered separately and then
+    /// joined.
+    fn write_node(&self, node: &DocNode, notes: &mut Vec<String>, out: &mut String) -> error::Result<()> {
+        if node.is_leaf() {
+            return self.write_val(&node.default, &mut node.path.clone(), notes, out);
+        }
+        // Footnotes are numbered in the order of the output, so values that may have one are
+        // rendered before their key
+        let streaming = self.format_key.is_none() && self.format_mapping.is_none()
+            && !(self.max_value_len.is_some() && self.truncation_footnotes);
+        let indent = self.indent_str(&node.path);
+        let placement = self.placement_at(node.path.len());
+        let mut entries = vec![];
+        for child in &node.children {
+            let v = if streaming && !child.is_leaf() {
+                None
+            } else {
+                Some(self.child_value(child, notes)?)
+            };
+            let k = self.paint(Part::Key, child.key().unwrap_or_default().to_owned());
+            let the_description = child.description.as_ref()
+                .map(|s| self.paint(Part::Description, resolve_references(s, &|p| p.to_owned())));
+            let ty = self.paint(Part::Type, (*self.type_name)(&child.ty));
+            entries.push((child, k, ty, v, the_description));
+        }
+        // Inline comments are aligned on the longest line that has one. Mappings that are not
+        // rendered yet start on the next line.
+        let comment_column = match placement {
+            CommentPlacement::Inline if self.align_comments => entries.iter()
+                .filter(|(_, _, _, _, desc)| desc.is_some())
+                .map(|(_, k, ty, v, _)| {
+                    let first = v.as_deref()
+                        .and_then(|v| v.split('\n').next())
+                        .unwrap_or_default();
+                    let prefix = if self.commented_out { "#" } else { "" };
+                    display_width(format!("{prefix}{indent}{k}{ty}: {first}").trim_end())
+                })
+                .max(),
+            _ => None,
+        };
+        let mut list = vec![];
+        // With the default `format_mapping`, nested mappings start on a new line
+        if streaming && !node.path.is_empty() {
+            out.push('\n');
+        }
+        for (i, (child, k, ty, v, the_description)) in entries.iter().enumerate() {
+            let change = self.change_marker(child);
+            let overridden = child.overridden.as_ref()
+                .map(|d| self.leaf_string(d, &mut child.path.clone()));
+            let key_args = KeyArgs {yaml_value: &child.default,
+                                    path: &node.path,
+                                    indent: &indent,
+                                    key: k,
+                                    description: the_description.as_deref(),
+                                    placement,
+                                    commented_out: self.commented_out,
+                                    env_var: child.env_var.as_deref(),
+                                    change: change.as_deref(),
+                                    overridden: overridden.as_deref(),
+                                    section: child.section.as_deref(),
+                                    comment_column,
+                                    constraints: child.constraints.as_ref(),
+                                    variants: &child.variants,
+                                    optional: child.optional,
+                                    ty,
+                                    value: v.as_deref().unwrap_or_default()};
+            if streaming {
+                if i > 0 {
+                    out.push('\n');
+                }
+                self.start_mark(child, out);
+                write_default_key(&key_args, out);
+                if v.is_none() {
+                    self.write_node(child, notes, out)?;
+                }
+                self.end_mark(out);
+                continue;
+            }
+            let mut s = String::new();
+            self.start_mark(child, &mut s);
+            match self.format_key {
+                Some(ref f) => s.push_str(&f(key_args)?),
+                None => write_default_key(&key_args, &mut s),
+            }
+            self.end_mark(&mut s);
+            list.push(s);
+        }
+        if !streaming {
+            let args = InnerArgs {
+                inner: &list,
+                path: &node.path,
+                indent: &indent,
+            };
+            match self.format_mapping {
+                Some(ref f) => out.push_str(&f(args)?),
+                None => out.push_str(&default_format_mapping(args)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Renders the value of a key, possibly truncated
+    fn child_value(&self, child: &DocNode, notes: &mut Vec<String>) -> error::Result<String> {
+        let plain = match self.max_value_len {
+            Some(_) if child.is_leaf() => Some(self.leaf_string(&child.default, &mut child.path.clone())),
+            _ => None,
+        };
+        let too_long = |s: &String| self.max_value_len
+            .is_some_and(|max| display_width(s) > max);
+        match plain {
+            Some(s) if too_long(&s) => {
+                let (truncated, full) = self.truncate(s);
+                let mut v = self.paint(Part::Value, truncated);
+                if let Some(full) = full {
+                    notes.push(full);
+                    v.push_str(&format!(" [{}]", notes.len()));
+                }
+                Ok(v)
+            },
+            _ => self.document_node(child, notes),
+        }
+    }
+
+    /// Marks the start of the output of a key, see `apply_value_with_map`
+    fn start_mark(&self, node: &DocNode, out: &mut String) {
+        if self.source_map {
+            out.push(START_MARK);
+            out.push_str(&node.path.join(PATH_MARK));
+            out.push(END_PATH_MARK);
+        }
+    }
+
+    /// Marks the end of the output of a key, see `apply_value_with_map`
+    fn end_mark(&self, out: &mut String) {
+        if self.source_map {
+            out.push(END_MARK);
+        }
+    }
+
+    /// Renders a value without description, e.g. the items of a list, writing into `out`
+    fn write_val(&self, val: &Value, struct_path: &mut Vec<String>, notes: &mut Vec<String>, out: &mut String) -> error::Result<()> {
+        if self.omit_defaults && !val.is_mapping() {
+            out.push_str(&self.paint(Part::Value, placeholder(val)));
+            return Ok(());
+        }
+        match val {
+            Value::Mapping(_) => {
+                // Compared with itself, since values inside lists are not compared
+                let node = self.node(val, None, Some(val), struct_path);
+                self.write_node(&node, notes, out)
+            },
+            Value::Sequence(ref s) => {
+                let top_level = struct_path.is_empty();
+                struct_path.push("-".to_owned());
+                let result = match self.format_list {
+                    // Same as `default_format_list`, without rendering items separately
+                    None if !top_level => {
+                        out.push('[');
+                        for (i, v) in s.iter().enumerate() {
+                            if i > 0 {
+                                out.push_str(", ");
+                            }
+                            self.write_val(v, struct_path, notes, out)?;
+                        }
+                        out.push(']');
+                        Ok(())
+                    },
+                    ref f => {
+                        let mut list = vec![];
+                        for v in s.iter() {
+                            let mut item = String::new();
+                            self.write_val(v, struct_path, notes, &mut item)?;
+                            list.push(item);
+                        }
+                        struct_path.pop();
+                        let indent = self.indent_str(struct_path);
+                        let args = InnerArgs {
+                            inner: &list,
+                            path: struct_path,
+                            indent: &indent,
+                        };
+                        let s = match f {
+                            Some(f) => f(args)?,
+                            None => default_format_list(args),
+                        };
+                        out.push_str(&s);
+                        return Ok(());
+                    },
+                };
+                struct_path.pop();
+                result
+            },
+            Value::Tagged(ref t) => self.write_val(&t.value, struct_path, notes, out),
+            scalar => {
+                out.push_str(&self.paint(Part::Value, self.scalar_string(scalar, struct_path)));
+                Ok(())
+            },
+        }
+    }
+
+
+    /// Uses a YAML representation from a default struct to document an API or options
+    /// in a YAML-looking way
+    ///
+    /// The idea is to first generate a yaml representation with YourStruct::default()
+    /// to get a mostly automated API description.
+    ///
+    /// # Arguments
+    ///
+    /// * `value`: should correspond to a `serde_yaml` Value with the default values of your
+    ///   structure
+    /// * `description`: an optional `serde_yaml` value mirroring the `value` but with descriptions for
+    ///   fields you want to document. Use `__description__` inside a `Mapping` to document the
+    ///   upper-level field. The `__description__` of the top-level mapping (or the description
+    ///   itself if it is a string) is displayed as a header. A `__section__` key starts a
+    ///   section with this title before the field. Other fields can be referenced with
+    ///   `{{some.path}}`, which is an error if this path doesn't exist.
+    ///
+    /// # Example
+    ///
+    /// ```
+    ///         let desc_yaml = r#"
+    /// foo:
+    ///     __description__: Description for foo
+    ///     bar: Description for bar
+    /// "#;
+    /// 
+    ///         let yaml = r#"
+    /// foo:
+    ///     bar: 42
+    /// "#;
+    /// 
+    ///         let expected = "# Description for foo
+    /// foo: \n    # Description for bar
+    ///     bar (Number): 42";
+    /// 
+    ///         let value: serde_yaml::Value = serde_yaml::from_str(yaml).unwrap();
+    ///         let desc: serde_yaml::Value = serde_yaml::from_str(desc_yaml).unwrap();
+    ///         let s = yaml_extras::Documenter::new()
+    ///             .apply_value(&value, Some(&desc))
+    ///             .unwrap();
+    ///         assert_eq!(s, expected);
+    /// ```
+    pub fn apply_value(&self, value: &Value, description: Option<&Value>) -> error::Result<String> {
+        let description = self.prepare_description(description)?;
+        self.document_root(value, description.as_deref())
+    }
+
+    /// Same as `apply_value`, but also returns the lines corresponding to each key, e.g. to
+    /// highlight the documentation of the key under the cursor in an editor.
+    ///
+    /// This works with custom `format_key`, `format_mapping` and `format_list` closures, as
+    /// long as they include the strings of their keys or items unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("server: {port: 8080}\ndebug: false").unwrap();
+    /// let desc = serde_yaml::from_str("server: {port: Port}").unwrap();
+    /// let (doc, spans) = yaml_extras::Documenter::new()
+    ///     .apply_value_with_map(&yaml, Some(&desc))?;
+    ///
+    /// assert_eq!(doc, "server: \n    \u{23} Port\n    port (Number): 8080\ndebug (Bool): false");
+    /// assert_eq!(spans[0].path, vec!["server"]);
+    /// assert_eq!(spans[0].lines, 0..3);
+    /// assert_eq!(spans[1].path, vec!["server", "port"]);
+    /// assert_eq!(spans[1].lines, 1..3);
+    /// assert_eq!(spans[2].lines, 3..4);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn apply_value_with_map(&self, value: &Value, description: Option<&Value>) -> error::Result<(String, Vec<SourceSpan>)> {
+        let mut documenter = self.clone();
+        documenter.source_map = true;
+        let marked = documenter.apply_value(value, description)?;
+        Ok(extract_source_map(&marked))
+    }
+
+    /// Same as `apply_value`, but renders each top-level key as its own document, e.g. to
+    /// display each section of a configuration as a separate help page.
+    ///
+    /// Returns the keys with their documentation, in the order of `value`. The
+    /// description of the root, if any, is not included.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("server: {port: 8080}\ndebug: false").unwrap();
+    /// let desc = serde_yaml::from_str("server: {port: Port}\ndebug: Debug mode").unwrap();
+    /// let pages = yaml_extras::Documenter::new()
+    ///     .apply_value_split(&yaml, Some(&desc))?;
+    ///
+    /// assert_eq!(pages, vec![
+    ///     ("server".to_owned(), "server: \n    \u{23} Port\n    port (Number): 8080".to_owned()),
+    ///     ("debug".to_owned(), "\u{23} Debug mode\ndebug (Bool): false".to_owned()),
+    /// ]);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn apply_value_split(&self, value: &Value, description: Option<&Value>) -> error::Result<Vec<(String, String)>> {
+        let description = self.prepare_description(description)?;
+        let mut root = self.tree(value, description.as_deref())?;
+        if root.is_leaf() {
+            return Err(error::Error::Document(format!("only mappings can be split, found {:?}", value)));
+        }
+        let children = std::mem::take(&mut root.children);
+        // A root with only one child, without the whole value
+        let mut page = DocNode {
+            description: None,
+            default: Value::Mapping(serde_yaml::Mapping::new()),
+            ..root
+        };
+        let mut pages = vec![];
+        for child in children {
+            let key = child.key().unwrap_or_default().to_owned();
+            page.children = vec![child];
+            pages.push((key, self.render_root(&page)?));
+        }
+        Ok(pages)
+    }
+
+    /// Documents a type, using its default value.
+    ///
+    /// The documented keys match what deserializing the type actually accepts, see
+    /// `type_value` for details. Keys are documented in the order in which they are declared.
+    ///
+    /// Optional fields whose default is `None` are documented with the type they contain,
+    /// when it can be found, and an "optional" marker.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use serde_derive::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default)]
+    /// struct Config {
+    ///     #[serde(rename = "server-name")]
+    ///     name: String,
+    ///     #[serde(skip_serializing_if = "Option::is_none")]
+    ///     port: Option<u16>,
+    /// }
+    ///
+    /// let desc = serde_yaml::from_str("port: Port to listen on").unwrap();
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .apply_type::<Config>(Some(&desc))?;
+    ///
+    /// assert_eq!(actual, "server-name (String): ''\n# Port to listen on\n# optional\nport (Number): Null");
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn apply_type<T>(&self, description: Option<&Value>) -> error::Result<String>
+    where T: Serialize + DeserializeOwned + Default {
+        let (value, optional) = type_info::<T>()?;
+        let mut documenter = self.clone();
+        documenter.optional_fields = Arc::new(optional.into_iter().collect());
+        documenter.apply_value(&value, description)
+    }
+
+    /// Documents a configuration against its defaults: each key is displayed with its
+    /// current value, followed by its default when they differ. Keys that are missing in
+    /// `current` are displayed with their default value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let defaults = serde_yaml::from_str("port: 8080\nhost: localhost").unwrap();
+    /// let current = serde_yaml::from_str("port: 80").unwrap();
+    /// let desc = serde_yaml::from_str("port: Port to listen on").unwrap();
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .apply_current(&current, &defaults, Some(&desc))?;
+    ///
+    /// assert_eq!(actual, "\u{23} Port to listen on\n\u{23} default: 8080\nport (Number): 80\nhost (String): localhost");
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn apply_current(&self, current: &Value, defaults: &Value, description: Option<&Value>) -> error::Result<String> {
+        let mut value = defaults.clone();
+        crate::merge(&mut value, current)?;
+        let mut documenter = self.clone();
+        documenter.defaults = Some(Arc::new(defaults.clone()));
+        documenter.apply_value(&value, description)
+    }
+
+    /// Same as `apply_value`, but with several description documents.
+    ///
+    /// The descriptions are merged, with documents coming later in the list taking precedence
+    /// over the previous ones: this allows e.g. an application to add or override descriptions
+    /// of options provided by a library.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let yaml = serde_yaml::from_str("foo: {bar: 42, baz: true}").unwrap();
+    /// let base = serde_yaml::from_str(r#"
+    /// foo:
+    ///     __description__: Base description for foo
+    ///     bar: Base description for bar
+    /// "#).unwrap();
+    /// let overrides = serde_yaml::from_str(r#"
+    /// foo:
+    ///     bar: Overriden description for bar
+    ///     baz: New description for baz
+    /// "#).unwrap();
+    ///
+    /// let actual = yaml_extras::Documenter::new()
+    ///     .apply_value_layered(&yaml, &[&base, &overrides])
+    ///     .unwrap();
+    ///
+    /// assert_eq!(actual, "# Base description for foo
+    /// foo: \n    # Overriden description for bar
+    ///     bar (Number): 42\n    # New description for baz
+    ///     baz (Bool): true");
+    /// ```
+    pub fn apply_value_layered(&self, value: &Value, descriptions: &[&Value]) -> error::Result<String> {
+        let mut merged: Option<Value> = None;
+        for d in descriptions {
+            let d = if self.flat_descriptions {
+                self.restructure_description(d)?
+            } else {
+                (*d).clone()
+            };
+            match merged {
+                Some(ref mut m) => self.merge_descriptions(m, &d),
+                None => merged = Some(d),
+            }
+        }
+        self.document_root(value, merged.as_ref())
+    }
+
+    /// Reads back a document produced by `apply_value` or `apply_type`, returning the value
+    /// and the descriptions. This allows users to edit a generated sample configuration,
+    /// including its comments, and to extract the edited descriptions.
+    ///
+    /// The documenter must be configured as it was to produce the document (indent, type
+    /// names, null representation…), with the default `format_key`, `format_mapping` and
+    /// `format_list` closures. Comments above a key, or at the end of its line, are read
+    /// as its description, except for the lines generated from other sources such as
+    /// environment variables, constraints or changes. Section banners are read back as
+    /// `__section__` keys.
+    ///
+    /// Some information can't be recovered: the description of the root is read as the one
+    /// of the first key, truncated values are read as they are displayed, and mappings
+    /// inside lists are not supported.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let doc = "\
+    /// \u{23} Server options
+    /// server: 
+    ///     \u{23} Port to listen on
+    ///     port (Number): 8080
+    ///     hosts (List): [localhost, example.com]";
+    /// let (value, desc) = yaml_extras::Documenter::new()
+    ///     .parse_documented(doc)?;
+    ///
+    /// assert_eq!(value, serde_yaml::from_str::<serde_yaml::Value>("server: {port: 8080, hosts: [localhost, example.com]}")?);
+    /// assert_eq!(desc, serde_yaml::from_str::<serde_yaml::Value>("server: {__description__: Server options, port: Port to listen on}")?);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn parse_documented(&self, doc: &str) -> error::Result<(Value, Value)> {
+        let types = [ValueType::Null, ValueType::Bool, ValueType::Number, ValueType::String,
+                     ValueType::List, ValueType::Mapping, ValueType::Tagged];
+        let mut type_names: Vec<String> = types.iter()
+            .map(|t| (self.type_name)(t))
+            .filter(|s| !s.is_empty())
+            .collect();
+        // Longest first, in case a name ends with another one
+        type_names.sort_by_key(|s| std::cmp::Reverse(s.len()));
+
+        let mut value = Value::Mapping(serde_yaml::Mapping::new());
+        let mut description = Value::Mapping(serde_yaml::Mapping::new());
+        let mut path: Vec<String> = vec![];
+        let mut comments: Vec<String> = vec![];
+        for (n, line) in doc.lines().enumerate() {
+            let err = |msg: &str| error::Error::Document(format!("line {}: {msg}", n + 1));
+            let trimmed = line.trim_start();
+            if trimmed.is_empty() {
+                continue;
+            }
+            // Commented out keys start with "#" followed by their indentation
+            let commented_key = line.strip_prefix('#')
+                .filter(|l| self.commented_out
+                        && (l.len() - l.trim_start_matches(' ').len()) % self.indent.len().max(1) == 0
+                        && split_key(l.trim_start()).is_some());
+            let line = match (commented_key, trimmed.strip_prefix('#')) {
+                (Some(l), _) => l,
+                (None, Some(comment)) => {
+                    comments.push(comment.strip_prefix(' ').unwrap_or(comment).to_owned());
+                    continue;
+                },
+                (None, None) => line,
+            };
+            let content = line.trim_start_matches(' ');
+            let depth = (line.len() - content.len()) / self.indent.len().max(1);
+            if depth > path.len() {
+                return Err(err("unexpected indentation"));
+            }
+            path.truncate(depth);
+
+            let (key, rest) = split_key(content)
+                .ok_or_else(|| err("expected a key"))?;
+            let key = type_names.iter()
+                .find_map(|t| key.strip_suffix(t.as_str()))
+                .unwrap_or(key);
+            let (text, inline) = split_inline_comment(rest);
+            if let Some(c) = inline {
+                comments.push(c.to_owned());
+            }
+            path.push(key.to_owned());
+            let v = if text.is_empty() {
+                Value::Mapping(serde_yaml::Mapping::new())
+            } else if text == self.null {
+                Value::Null
+            } else {
+                serde_yaml::from_str(text)
+                    .map_err(|e| err(&e.to_string()))?
+            };
+            let is_mapping = v.is_mapping();
+            set_value(&mut value, &path, v);
+
+            let (section, lines) = self.read_comments(std::mem::take(&mut comments));
+            if let Some(section) = section {
+                let mut p = path.clone();
+                p.push(SECTION.to_owned());
+                set_value(&mut description, &p, Value::from(section));
+            }
+            if !lines.is_empty() {
+                let mut p = path.clone();
+                if is_mapping {
+                    p.push(self.description_field.to_string());
+                }
+                set_value(&mut description, &p, Value::from(lines.join("\n")));
+            }
+        }
+        Ok((value, description))
+    }
+
+    /// Splits the comments above a key between a section title and the lines of its
+    /// description, leaving out the lines that were generated from other sources
+    fn read_comments(&self, comments: Vec<String>) -> (Option<String>, Vec<String>) {
+        let is_banner = |s: &str| !s.is_empty() && s.chars().all(|c| c == '-');
+        let mut section = None;
+        let mut lines = vec![];
+        let mut in_variants = false;
+        let mut i = 0;
+        while i < comments.len() {
+            let c = comments[i].as_str();
+            if is_banner(c) && comments.get(i + 2).is_some_and(|s| is_banner(s)) {
+                section = Some(comments[i + 1].clone());
+                i += 3;
+                continue;
+            }
+            i += 1;
+            let generated = (self.env_prefix.is_some() && c.starts_with("env: "))
+                || ["min: ", "max: ", "pattern: ", "one of: "].iter().any(|p| c.starts_with(p))
+                || c == "optional"
+                || (in_variants && c.starts_with("- "))
+                || (self.previous.is_some() && (c == "new in this version" || c.starts_with("default changed")));
+            in_variants = c.starts_with("one of: ") || (in_variants && c.starts_with("- "));
+            if !generated {
+                lines.push(c.to_owned());
+            }
+        }
+        (section, lines)
+    }
+
+    /// Merge two description documents, `other` taking precedence.
+    ///
+    /// Contrary to a plain `merge`, a string describing a mapping is not lost when merged
+    /// with the mapping's descriptions but moved to its description field.
+    fn merge_descriptions(&self, base: &mut Value, other: &Value) {
+        let field = Value::String(self.description_field.to_string());
+        match (base, other) {
+            (Value::Mapping(b), Value::Mapping(o)) => {
+                for (key, o_val) in o.iter() {
+                    match b.get_mut(key) {
+                        Some(b_val) => self.merge_descriptions(b_val, o_val),
+                        None => {
+                            b.insert(key.clone(), o_val.clone());
+                        },
+                    }
+                }
+            },
+            (Value::Mapping(b), Value::String(_)) => {
+                b.insert(field, other.clone());
+            },
+            (base @ Value::String(_), Value::Mapping(o)) => {
+                let mut o = o.clone();
+                if !o.contains_key(&field) {
+                    o.insert(field, base.clone());
+                }
+                *base = Value::Mapping(o);
+            },
+            (base, _) => {
+                *base = other.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_yaml::Value;
+
+    #[test]
+    fn document_simple() {
+        let desc_yaml = r#"
+foo:
+    __description__: Description for foo.
+    bar: Description for bar"#;
+
+        let yaml = r#"
+foo:
+    bar: 42
+"#;
+
+        let expected = r#"# Description for foo.
+foo: 
+    # Description for bar
+    bar (Number): 42"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str(desc_yaml).unwrap();
+        let s = Documenter::new()
+            .apply_value(&value, Some(&desc)).unwrap();
+        assert_eq!(s, expected);
+    }
+
+    #[test]
+    fn indent() {
+        let desc_yaml = r#"
+foo:
+    __description__: Description for foo
+    bar: Description for bar
+"#;
+
+        let yaml = r#"
+foo:
+    bar: 42
+"#;
+
+        let expected = r#"# Description for foo
+foo: 
+....# Description for bar
+....bar (Number): 42"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str(desc_yaml).unwrap();
+        let s = Documenter::new()
+            .indent("....")
+            .apply_value(&value, Some(&desc)).unwrap();
+        assert_eq!(s, expected);
+    }
+
+    #[test]
+    fn description() {
+        let desc_yaml = r#"
+foo:
+    ___description___: Description for foo
+    __description__: Description for __description__
+"#;
+
+        let yaml = r#"
+foo:
+    __description__: 42
+"#;
+
+        let expected = r#"# Description for foo
+foo: 
+....# Description for __description__
+....__description__ (Number): 42"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str(desc_yaml).unwrap();
+        let s = Documenter::new()
+            .indent("....")
+            .description_field("___description___")
+            .apply_value(&value, Some(&desc)).unwrap();
+        assert_eq!(s, expected);
+    }
+
+    #[test]
+    fn fallible_formatters() {
+        let yaml = r#"
+foo:
+    bar: [1, 2]
+"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let res = Documenter::new()
+            .try_format_list(|_| Err(error::Error::Document("no lists".into())))
+            .apply_value(&value, None);
+        assert!(matches!(res, Err(error::Error::Document(_))));
+
+        let res = Documenter::new()
+            .try_format_mapping(|args| Ok(args.inner.join(";")))
+            .apply_value(&value, None)
+            .unwrap();
+        assert_eq!(res, "foo:     bar (List): [1, 2]");
+    }
+
+    #[test]
+    fn owned_documenter() {
+        struct Config {
+            documenter: Documenter<'static>,
+        }
+
+        fn build(suffix: String) -> Config {
+            Config {
+                documenter: Documenter::new()
+                    .indent(String::from("  "))
+                    .format_key(move |args| format!("{}{}", args.key, suffix)),
+            }
+        }
+
+        let value: Value = serde_yaml::from_str("foo: 42").unwrap();
+        let config = build("!".to_owned());
+        let s = config.documenter.apply_value(&value, None).unwrap();
+        assert_eq!(s, "foo!");
+    }
+
+    #[test]
+    fn shared_documenter() {
+        fn assert_send_sync<T: Send + Sync + Clone>(_: &T) {}
+
+        let d = Documenter::new()
+            .format_key(|args| args.key.to_uppercase());
+        assert_send_sync(&d);
+
+        let value: Value = serde_yaml::from_str("foo: 42").unwrap();
+        let d2 = d.clone();
+        let handle = std::thread::spawn(move || d2.apply_value(&value, None).unwrap());
+        assert_eq!(handle.join().unwrap(), "FOO");
+    }
+
+    #[test]
+    fn inline_comments() {
+        let desc_yaml = r#"
+foo:
+    __description__: Description for foo
+    bar: Description for bar
+baz: Description for baz
+"#;
+
+        let yaml = r#"
+foo:
+    bar: 42
+baz: true
+"#;
+
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str(desc_yaml).unwrap();
+        let s = Documenter::new()
+            .comment_placement(CommentPlacement::Inline)
+            .apply_value(&value, Some(&desc)).unwrap();
+        let expected = r#"foo:  # Description for foo
+    bar (Number): 42  # Description for bar
+baz (Bool): true  # Description for baz"#;
+        assert_eq!(s, expected);
+
+        let s = Documenter::new()
+            .comment_placement(CommentPlacement::Inline)
+            .comment_placement_at_depth(0, CommentPlacement::Above)
+            .apply_value(&value, Some(&desc)).unwrap();
+        let expected = r#"# Description for foo
+foo: 
+    bar (Number): 42  # Description for bar
+# Description for baz
+baz (Bool): true"#;
+        assert_eq!(s, expected);
+    }
+
+    #[test]
+    fn commented_out() {
+        let desc_yaml = r#"
+foo:
+    __description__: Description for foo
+    bar: Description for bar
+"#;
+
+        let yaml = r#"
+foo:
+    bar: 42
+    baz: true
+"#;
+
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str(desc_yaml).unwrap();
+        let s = Documenter::new()
+            .commented_out(true)
+            .apply_value(&value, Some(&desc)).unwrap();
+        let expected = r#"# Description for foo
+#foo: 
+    # Description for bar
+#    bar (Number): 42
+#    baz (Bool): true"#;
+        assert_eq!(s, expected);
+    }
+
+    #[cfg(feature = "color")]
+    #[test]
+    fn colors() {
+        let value: Value = serde_yaml::from_str("foo: 42").unwrap();
+        let desc: Value = serde_yaml::from_str("foo: The answer").unwrap();
+        let styles = Styles {
+            key: Style::new().red(),
+            ty: Style::new(),
+            value: Style::new().green(),
+            description: Style::new().bold(),
+        };
+        let s = Documenter::new()
+            .colors(styles)
+            .apply_value(&value, Some(&desc)).unwrap();
+        assert_eq!(s, "# \u{1b}[1mThe answer\u{1b}[0m\n\u{1b}[31mfoo\u{1b}[0m (Number): \u{1b}[32m42\u{1b}[0m");
+    }
+
+    #[test]
+    fn flat_descriptions() {
+        let desc_yaml = r#"
+foo: Description for foo
+foo.bar: Description for bar
+foo.baz:
+    __description__: Description for baz
+foo.baz.qux: Description for qux
+"#;
+
+        let yaml = r#"
+foo:
+    bar: 42
+    baz:
+        qux: true
+"#;
+
+        let expected = r#"# Description for foo
+foo: 
+    # Description for bar
+    bar (Number): 42
+    # Description for baz
+    baz: 
+        # Description for qux
+        qux (Bool): true"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str(desc_yaml).unwrap();
+        let s = Documenter::new()
+            .flat_descriptions(true)
+            .apply_value(&value, Some(&desc)).unwrap();
+        assert_eq!(s, expected);
+    }
+
+    #[test]
+    fn layered_descriptions() {
+        let base = r#"
+foo:
+    __description__: Description for foo
+    bar: Description for bar
+baz: Description for baz
+"#;
+        let overrides = r#"
+foo: New description for foo
+baz:
+    qux: Description for qux
+"#;
+
+        let yaml = r#"
+foo:
+    bar: 42
+baz:
+    qux: true
+"#;
+
+        let expected = r#"# New description for foo
+foo: 
+    # Description for bar
+    bar (Number): 42
+# Description for baz
+baz: 
+    # Description for qux
+    qux (Bool): true"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let base: Value = serde_yaml::from_str(base).unwrap();
+        let overrides: Value = serde_yaml::from_str(overrides).unwrap();
+        let s = Documenter::new()
+            .apply_value_layered(&value, &[&base, &overrides]).unwrap();
+        assert_eq!(s, expected);
+    }
+
+    #[test]
+    fn strict() {
+        let desc_yaml = r#"
+foo:
+    __description__: Description for foo
+    bar: Description for bar
+    baz: Description for baz
+qux:
+    quux: Description for quux
+"#;
+
+        let yaml = r#"
+foo:
+    bar: 42
+"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str(desc_yaml).unwrap();
+        assert!(Documenter::new()
+                .apply_value(&value, Some(&desc))
+                .is_ok());
+        let res = Documenter::new()
+            .strict(true)
+            .apply_value(&value, Some(&desc));
+        match res {
+            Err(error::Error::Document(s)) => assert_eq!(s, "descriptions found for unknown paths: foo.baz, qux"),
+            _ => panic!("expected an error, got {res:?}"),
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use pretty_assertions::{assert_eq, assert_ne};
-    use serde_yaml::Value;
+    #[test]
+    fn coverage() {
+        let desc_yaml = r#"
+foo:
+    __description__: Description for foo
+    bar: Description for bar
+baz:
+    qux: Description for qux
+"#;
+
+        let yaml = r#"
+foo:
+    bar: 42
+    quux: []
+baz:
+    qux: true
+"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str(desc_yaml).unwrap();
+        let report = Documenter::new()
+            .coverage(&value, &desc)
+            .unwrap();
+        assert_eq!(report.documented, vec!["foo", "foo.bar", "baz.qux"]);
+        assert_eq!(report.undocumented, vec!["foo.quux", "baz"]);
+        assert_eq!(report.total(), 5);
+        assert_eq!(report.percentage(), 60.0);
+
+        let report = Documenter::new()
+            .coverage(&Value::Null, &desc)
+            .unwrap();
+        assert_eq!(report.total(), 0);
+        assert_eq!(report.percentage(), 100.0);
+    }
 
     #[test]
-    fn document_simple() {
+    fn localized() {
         let desc_yaml = r#"
 foo:
-    __description__: Description for foo.
-    bar: Description for bar"#;
+    __description__:
+        en: Description for foo
+        fr: Description de foo
+    bar:
+        en: Description for bar
+        fr: Description de bar
+    baz:
+        de: Beschreibung von baz
+"#;
 
         let yaml = r#"
 foo:
     bar: 42
+    baz: true
 "#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str(desc_yaml).unwrap();
+        let d = Documenter::new()
+            .strict(true)
+            .language("fr");
+        let expected = r#"# Description de foo
+foo: 
+    # Description de bar
+    bar (Number): 42
+    baz (Bool): true"#;
+        assert_eq!(d.apply_value(&value, Some(&desc)).unwrap(), expected);
 
-        let expected = r#"# Description for foo.
+        let d = d.language("it")
+            .fallback_language("de")
+            .fallback_language("en");
+        let expected = r#"# Description for foo
 foo: 
     # Description for bar
-    bar (Number): 42"#;
-        let value: Value = serde_yaml::from_str(&yaml).unwrap();
-        let desc: Value = serde_yaml::from_str(&desc_yaml).unwrap();
+    bar (Number): 42
+    # Beschreibung von baz
+    baz (Bool): true"#;
+        assert_eq!(d.apply_value(&value, Some(&desc)).unwrap(), expected);
+    }
+
+    #[test]
+    fn format_scalar() {
+        let yaml = r#"
+foo:
+    bar: A long string
+    baz: [1, 2]
+"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
         let s = Documenter::new()
-            .apply_value(&value, Some(&desc)).unwrap();
+            .format_scalar(|v, path| format!("{}={v:?}", path.join(".")))
+            .apply_value(&value, None)
+            .unwrap();
+        let expected = r#"foo: 
+    bar (String): foo.bar=String("A long string")
+    baz (List): [foo.baz.-=Number(1), foo.baz.-=Number(2)]"#;
         assert_eq!(s, expected);
     }
 
     #[test]
-    fn indent() {
+    fn format_type() {
+        let value: Value = serde_yaml::from_str("foo: {bar: text, baz: [1, 2.5], qux: ~}").unwrap();
+        let d = Documenter::new()
+            .format_scalar(|v, path| format!("{}={v:?}", path.join(".")))
+            .format_type(ValueType::Number, |v| format!("{:.1}", v.as_f64().unwrap()));
+        let expected = r#"foo: 
+    bar (String): foo.bar=String("text")
+    baz (List): [1.0, 2.5]
+    qux: foo.qux=Null"#;
+        assert_eq!(d.apply_value(&value, None).unwrap(), expected);
+
+        // Later calls replace formatters of the same type
+        let s = d.format_type(ValueType::Null, |_| "~".to_owned())
+            .format_type(ValueType::Null, |_| "none".to_owned())
+            .apply_markdown(&value, None)
+            .unwrap();
+        assert!(s.contains("`qux` (default: `none`)"));
+        assert!(s.contains("`baz` (List, default: `[1.0, 2.5]`)"));
+    }
+
+    #[test]
+    fn null_repr() {
+        let yaml = r#"
+foo:
+    bar: null
+    baz: [1, null]
+"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let s = Documenter::new()
+            .apply_value(&value, None)
+            .unwrap();
+        let expected = r#"foo: 
+    bar: Null
+    baz (List): [1, Null]"#;
+        assert_eq!(s, expected);
+
+        let s = Documenter::new()
+            .null_repr(format!("not {}", "set"))
+            .apply_value(&value, None)
+            .unwrap();
+        let expected = r#"foo: 
+    bar: not set
+    baz (List): [1, not set]"#;
+        assert_eq!(s, expected);
+    }
+
+    #[test]
+    fn non_mapping_roots() {
+        let desc: Value = serde_yaml::from_str("The answer").unwrap();
+        let s = Documenter::new()
+            .apply_value(&Value::from(42), Some(&desc))
+            .unwrap();
+        assert_eq!(s, "# The answer\n42");
+
+        let yaml = r#"
+- foo
+- bar: 42
+  baz: true
+- [1, 2]
+"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str("__description__: A list").unwrap();
+        let s = Documenter::new()
+            .apply_value(&value, Some(&desc))
+            .unwrap();
+        let expected = r#"# A list
+- foo
+- 
+    bar (Number): 42
+    baz (Bool): true
+- [1, 2]"#;
+        assert_eq!(s, expected);
+    }
+
+    #[test]
+    fn root_description() {
+        let desc_yaml = r#"
+__description__: My configuration
+foo: Description for foo
+"#;
+        let value: Value = serde_yaml::from_str("foo: 42").unwrap();
+        let desc: Value = serde_yaml::from_str(desc_yaml).unwrap();
+        let s = Documenter::new()
+            .strict(true)
+            .apply_value(&value, Some(&desc))
+            .unwrap();
+        assert_eq!(s, "# My configuration\n# Description for foo\nfoo (Number): 42");
+    }
+
+    #[test]
+    fn csv() {
         let desc_yaml = r#"
 foo:
     __description__: Description for foo
-    bar: Description for bar
+    bar: Description, with a comma
+baz: "Description with \"quotes\"\tand a tab"
 "#;
 
         let yaml = r#"
 foo:
     bar: 42
+    qux: null
+baz: "a\tb"
 "#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str(desc_yaml).unwrap();
+        let d = Documenter::new();
+        let expected = "path,type,default,description
+foo.bar,Number,42,\"Description, with a comma\"
+foo.qux,Null,Null,
+baz,String,\"\"\"a\\tb\"\"\",\"Description with \"\"quotes\"\"\tand a tab\"
+";
+        assert_eq!(d.apply_csv(&value, Some(&desc)).unwrap(), expected);
 
-        let expected = r#"# Description for foo
-foo: 
-....# Description for bar
-....bar (Number): 42"#;
-        let value: Value = serde_yaml::from_str(&yaml).unwrap();
-        let desc: Value = serde_yaml::from_str(&desc_yaml).unwrap();
-        let s = Documenter::new()
-            .indent("....")
-            .apply_value(&value, Some(&desc)).unwrap();
-        assert_eq!(s, expected);
+        let expected = "path\ttype\tdefault\tdescription
+foo.bar\tNumber\t42\tDescription, with a comma
+foo.qux\tNull\tNull\t
+baz\tString\t\"a\\tb\"\tDescription with \"quotes\" and a tab
+";
+        assert_eq!(d.apply_tsv(&value, Some(&desc)).unwrap(), expected);
     }
 
     #[test]
-    fn description() {
+    fn markdown() {
         let desc_yaml = r#"
+__description__: My options
 foo:
-    ___description___: Description for foo
-    __description__: Description for __description__
+    __description__: Description for foo, see [baz](#baz)
+    bar: Description for bar
+    nested:
+        __description__: Nested options
+        qux: Description for qux
 "#;
 
         let yaml = r#"
+top: true
 foo:
-    __description__: 42
+    nested:
+        qux: [1, 2]
+    bar: 42
+baz:
+    quux: a string
 "#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str(desc_yaml).unwrap();
+        let expected = r#"My options
 
-        let expected = r#"# Description for foo
-foo: 
-....# Description for __description__
-....__description__ (Number): 42"#;
-        let value: Value = serde_yaml::from_str(&yaml).unwrap();
-        let desc: Value = serde_yaml::from_str(&desc_yaml).unwrap();
+## Contents
+
+* [foo](#foo)
+* [baz](#baz)
+
+* <a id="top"></a>`top` (Bool, default: `true`)
+
+<a id="foo"></a>
+## `foo`
+
+Description for foo, see [baz](#baz)
+
+* <a id="foo-bar"></a>`bar` (Number, default: `42`): Description for bar
+
+<a id="foo-nested"></a>
+### `nested`
+
+Nested options
+
+* <a id="foo-nested-qux"></a>`qux` (List, default: `[1, 2]`): Description for qux
+
+<a id="baz"></a>
+## `baz`
+
+* <a id="baz-quux"></a>`quux` (String, default: `a string`)
+"#;
         let s = Documenter::new()
-            .indent("....")
-            .description_field("___description___")
-            .apply_value(&value, Some(&desc)).unwrap();
+            .markdown_toc(true)
+            .apply_markdown(&value, Some(&desc))
+            .unwrap();
         assert_eq!(s, expected);
     }
+
+    #[test]
+    fn env_vars() {
+        let yaml = r#"
+server:
+    port: 8080
+    allowed-hosts: [localhost]
+"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str("server: {port: Port to listen on}").unwrap();
+        let d = Documenter::new()
+            .env_var_prefix("MYAPP");
+        let expected = r#"server: 
+    # Port to listen on
+    # env: MYAPP_SERVER_PORT
+    port (Number): 8080
+    # env: MYAPP_SERVER_ALLOWED_HOSTS
+    allowed-hosts (List): [localhost]"#;
+        assert_eq!(d.apply_value(&value, Some(&desc)).unwrap(), expected);
+
+        let expected = "path,type,default,description,env
+server.port,Number,8080,Port to listen on,MYAPP_SERVER_PORT
+server.allowed-hosts,List,[localhost],,MYAPP_SERVER_ALLOWED_HOSTS
+";
+        assert_eq!(d.apply_csv(&value, Some(&desc)).unwrap(), expected);
+
+        let md = d.apply_markdown(&value, Some(&desc)).unwrap();
+        assert!(md.contains("`port` (Number, default: `8080`, env: `MYAPP_SERVER_PORT`): Port to listen on\n"));
+
+        let path = |keys: &[&str]| keys.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+        assert_eq!(d.env_var(&path(&["café", "ポート", "x1"])).as_deref(), Some("MYAPP_CAF______X1"));
+    }
+
+    #[test]
+    fn constraints() {
+        let yaml = r#"
+port: 8080
+name: server
+"#;
+        let desc = r#"
+port:
+    __description__: Port to listen on
+    __min__: 1
+    __max__: 65535
+name:
+    __pattern__: "^[a-z]+$"
+"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str(desc).unwrap();
+        let expected = r#"# Port to listen on
+# min: 1, max: 65535
+port (Number): 8080
+# pattern: ^[a-z]+$
+name (String): server"#;
+        let d = Documenter::new()
+            .strict(true);
+        assert_eq!(d.apply_value(&value, Some(&desc)).unwrap(), expected);
+
+        let constraints = d.constraints(&desc).unwrap();
+        assert_eq!(constraints, vec![
+            ("port".to_owned(), Constraints {
+                min: Some(1.into()),
+                max: Some(65535.into()),
+                pattern: None,
+            }),
+            ("name".to_owned(), Constraints {
+                min: None,
+                max: None,
+                pattern: Some("^[a-z]+$".to_owned()),
+            }),
+        ]);
+    }
+
+    #[test]
+    fn asciidoc() {
+        let yaml = r#"
+name: foo
+server:
+    port: 8080
+    tls:
+        enabled: false
+"#;
+        let desc = r#"
+__description__: My configuration
+name: The name, not to confuse with [the port](#server.port) nor [TLS](#server.tls)
+server:
+    tls:
+        __description__: TLS options
+"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str(desc).unwrap();
+        let expected = "My configuration
+
+[[name]]`name` (String, default: `foo`)::
+The name, not to confuse with <<server-port,the port>> nor <<server-tls,TLS>>
+
+[[server]]
+== `server`
+
+[[server-port]]`port` (Number, default: `8080`)::
+
+[[server-tls]]
+=== `tls`
+
+TLS options
+
+[[server-tls-enabled]]`enabled` (Bool, default: `false`)::
+";
+        assert_eq!(Documenter::new().apply_asciidoc(&value, Some(&desc)).unwrap(), expected);
+    }
+
+    #[test]
+    fn mdbook() {
+        let yaml = r#"
+name: foo
+server:
+    port: 8080
+    tls:
+        enabled: false
+client:
+    retries: 3
+"#;
+        let desc = r#"
+__description__: My configuration
+server:
+    port: Port to listen on
+client:
+    retries: Number of retries before giving up on [the server](#server.port)
+"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str(desc).unwrap();
+        let book = Documenter::new().apply_mdbook(&value, Some(&desc)).unwrap();
+        assert_eq!(book.summary, "- [Overview](README.md)
+- [server](server.md)
+- [client](client.md)
+");
+        let files: Vec<_> = book.chapters.iter()
+            .map(|c| c.file.as_str())
+            .collect();
+        assert_eq!(files, vec!["README.md", "server.md", "client.md"]);
+        assert_eq!(book.chapters[0].content, "My configuration
+
+* <a id=\"name\"></a>`name` (String, default: `foo`)
+");
+        assert_eq!(book.chapters[1].content, "<a id=\"server\"></a>
+# `server`
+
+* <a id=\"server-port\"></a>`port` (Number, default: `8080`): Port to listen on
+
+<a id=\"server-tls\"></a>
+## `tls`
+
+* <a id=\"server-tls-enabled\"></a>`enabled` (Bool, default: `false`)
+");
+        assert_eq!(book.chapters[2].content, "<a id=\"client\"></a>
+# `client`
+
+* <a id=\"client-retries\"></a>`retries` (Number, default: `3`): \
+Number of retries before giving up on [the server](server.md#server-port)
+");
+    }
+
+    #[test]
+    fn mdbook_file_names() {
+        let value: Value = serde_yaml::from_str("
+a.b: {x: 1}
+a_b: {x: 2}
+a?b: {x: 3}
+README: {x: 4}
+A_B: {x: 5}
+a_b-2: {x: 6}
+").unwrap();
+        let book = Documenter::new().apply_mdbook(&value, None).unwrap();
+        let files: Vec<_> = book.chapters.iter()
+            .map(|c| (c.title.as_str(), c.file.as_str()))
+            .collect();
+        assert_eq!(files, vec![("a.b", "a_b.md"), ("a_b", "a_b-2.md"), ("a?b", "a_b-3.md"),
+                               ("README", "README-2.md"), ("A_B", "A_B-4.md"), ("a_b-2", "a_b-2-2.md")]);
+        assert!(book.chapters[1].content.contains("`x` (Number, default: `2`)"));
+
+        let value: Value = serde_yaml::from_str("{1: {x: 1}, true: {y: 2}}").unwrap();
+        let book = Documenter::new().apply_mdbook(&value, None).unwrap();
+        let files: Vec<_> = book.chapters.iter()
+            .map(|c| (c.title.as_str(), c.file.as_str()))
+            .collect();
+        assert_eq!(files, vec![("1", "1.md"), ("true", "true.md")]);
+    }
+
+    #[test]
+    fn doc_tree() {
+        let yaml = r#"
+name: foo
+server:
+    port: 8080
+    hosts: [a, b]
+"#;
+        let desc = r#"
+__description__: My configuration
+server:
+    __description__: Server options
+    port:
+        __description__: Port to listen on
+        __max__: 65535
+"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str(desc).unwrap();
+        let root = Documenter::new()
+            .env_var_prefix("APP")
+            .doc_tree(&value, Some(&desc))
+            .unwrap();
+        assert_eq!(root.path, Vec::<String>::new());
+        assert_eq!(root.key(), None);
+        assert_eq!(root.description.as_deref(), Some("My configuration"));
+        assert_eq!(root.env_var, None);
+
+        let server = &root.children[1];
+        assert!(!server.is_leaf());
+        assert_eq!(server.ty, ValueType::Mapping);
+        assert_eq!(server.description.as_deref(), Some("Server options"));
+        assert_eq!(server.env_var, None);
+
+        let leaves: Vec<_> = root.leaves()
+            .iter()
+            .map(|l| l.path.join("."))
+            .collect();
+        assert_eq!(leaves, vec!["name", "server.port", "server.hosts"]);
+
+        let port = &server.children[0];
+        assert_eq!(port.key(), Some("port"));
+        assert_eq!(port.env_var.as_deref(), Some("APP_SERVER_PORT"));
+        assert_eq!(port.constraints.as_ref().and_then(|c| c.max.clone()), Some(65535.into()));
+        let hosts = &server.children[1];
+        assert!(hosts.is_leaf());
+        assert_eq!(hosts.ty, ValueType::List);
+        assert!(hosts.children.is_empty());
+
+        // Non-string keys are written as in paths
+        let value: Value = serde_yaml::from_str("{1: {true: x}}").unwrap();
+        let root = Documenter::new().doc_tree(&value, None).unwrap();
+        assert_eq!(root.children[0].children[0].path, vec!["1", "true"]);
+    }
+
+    #[test]
+    fn previous_defaults() {
+        let old = r#"
+port: 80
+removed: true
+tls:
+    enabled: false
+"#;
+        let yaml = r#"
+port: 8080
+tls:
+    enabled: false
+    cert: cert.pem
+logging:
+    level: info
+"#;
+        let old: Value = serde_yaml::from_str(old).unwrap();
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let d = Documenter::new()
+            .previous_defaults(old);
+        let expected = "# default changed from 80
+port (Number): 8080
+tls: \n    enabled (Bool): false
+    # new in this version
+    cert (String): cert.pem
+# new in this version
+logging: \n    # new in this version
+    level (String): info";
+        assert_eq!(d.apply_value(&value, None).unwrap(), expected);
+        assert_eq!(d.removed_paths(&value), vec!["removed"]);
+
+        let md = d.apply_markdown(&value, None).unwrap();
+        assert!(md.contains("`port` (Number, default: `8080`, default changed from `80`)\n"));
+        let csv = d.apply_csv(&value, None).unwrap();
+        assert!(csv.starts_with("path,type,default,description,change\nport,Number,8080,,default changed from 80\n"));
+
+        // Values in lists are never compared
+        let list: Value = serde_yaml::from_str("items: [{a: 1}]").unwrap();
+        let d = Documenter::new()
+            .previous_defaults(list.clone());
+        assert_eq!(d.apply_value(&list, None).unwrap(), "items (List): [\n        a (Number): 1]");
+    }
+
+    #[test]
+    fn faithful_scalars() {
+        let yaml = r#"
+float: 1.0
+infinite: .inf
+int: 42
+quoted_bool: "yes"
+quoted_number: "1.0"
+empty: ""
+plain: some text
+multiline: "first line\nsecond \"line\""
+"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let expected = r#"float (Number): 1.0
+infinite (Number): .inf
+int (Number): 42
+quoted_bool (String): 'yes'
+quoted_number (String): '1.0'
+empty (String): ''
+plain (String): some text
+multiline (String): "first line\nsecond \"line\"""#;
+        assert_eq!(Documenter::new().apply_value(&value, None).unwrap(), expected);
+    }
+
+    #[test]
+    fn omit_defaults() {
+        let yaml = r#"
+secret: hunter2
+port: 8080
+server:
+    hosts: [{name: a}]
+    tls: !Enabled true
+"#;
+        let old: Value = serde_yaml::from_str("secret: changeme").unwrap();
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let d = Documenter::new()
+            .previous_defaults(old)
+            .omit_defaults(true);
+        let md = d.apply_markdown(&value, None).unwrap();
+        assert!(md.contains("`secret` (String, default changed)\n"));
+        assert!(md.contains("`hosts` (List, new in this version)\n"));
+        assert!(md.contains("`tls` (new in this version)\n"));
+        assert!(!md.contains("hunter2") && !md.contains("changeme"));
+
+        let expected = "path,type,default,description
+secret,String,<string>,
+port,Number,<number>,
+server.hosts,List,<list>,
+server.tls,Tagged,<bool>,
+";
+        assert_eq!(Documenter::new().omit_defaults(true).apply_csv(&value, None).unwrap(), expected);
+    }
+
+    #[test]
+    fn sections() {
+        let yaml = r#"
+host: localhost
+port: 8080
+verbose: false
+"#;
+        let desc = r#"
+host:
+    __section__: Networking
+    __description__: Host to bind to
+port: Port to listen on
+verbose:
+    __section__: Logging
+"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str(desc).unwrap();
+        let d = Documenter::new()
+            .strict(true);
+        let expected = "# ----------
+# Networking
+# ----------
+# Host to bind to
+host (String): localhost
+# Port to listen on
+port (Number): 8080
+# -------
+# Logging
+# -------
+verbose (Bool): false";
+        assert_eq!(d.apply_value(&value, Some(&desc)).unwrap(), expected);
+
+        let expected = "## Networking
+
+* <a id=\"host\"></a>`host` (String, default: `localhost`): Host to bind to
+* <a id=\"port\"></a>`port` (Number, default: `8080`): Port to listen on
+
+## Logging
+
+* <a id=\"verbose\"></a>`verbose` (Bool, default: `false`)
+";
+        assert_eq!(d.apply_markdown(&value, Some(&desc)).unwrap(), expected);
+    }
+
+    #[test]
+    fn references() {
+        let yaml = r#"
+server:
+    port: 8080
+    host: localhost
+"#;
+        let desc = r#"
+server:
+    port: Port to listen on, see also {{server.host}}
+    host: "Template syntax is kept: {{ name }}"
+"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let desc: Value = serde_yaml::from_str(desc).unwrap();
+        let d = Documenter::new();
+        let yaml = d.apply_value(&value, Some(&desc)).unwrap();
+        assert!(yaml.contains("# Port to listen on, see also server.host\n"));
+        assert!(yaml.contains("# Template syntax is kept: {{ name }}\n"));
+        let md = d.apply_markdown(&value, Some(&desc)).unwrap();
+        assert!(md.contains("Port to listen on, see also [`server.host`](#server-host)\n"));
+        let adoc = d.apply_asciidoc(&value, Some(&desc)).unwrap();
+        assert!(adoc.contains("Port to listen on, see also <<server-host,`server.host`>>\n"));
+
+        let desc: Value = serde_yaml::from_str("server: {port: 'See {{server.hots}}'}").unwrap();
+        let err = d.apply_value(&value, Some(&desc)).unwrap_err();
+        assert_eq!(err.to_string(), "impossible to document YAML value: references to unknown paths: server.hots");
+    }
+
+    #[test]
+    fn truncation() {
+        let yaml = r#"
+short: abc
+template: "Dear {name}, your order is ready"
+list: [1, 2, 3, 4, 5, 6]
+"#;
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let d = Documenter::new()
+            .truncate_values(10);
+        let expected = "short (String): abc
+template (String): Dear {name…
+list (List): [1, 2, 3, …";
+        assert_eq!(d.apply_value(&value, None).unwrap(), expected);
+
+        let d = d.truncation_footnotes(true);
+        let expected = "short (String): abc
+template (String): Dear {name… [1]
+list (List): [1, 2, 3, … [2]
+# [1] Dear {name}, your order is ready
+# [2] [1, 2, 3, 4, 5, 6]";
+        assert_eq!(d.apply_value(&value, None).unwrap(), expected);
+
+        let expected = "* <a id=\"short\"></a>`short` (String, default: `abc`)
+* <a id=\"template\"></a>`template` (String, default: `Dear {name…`[^1])
+* <a id=\"list\"></a>`list` (List, default: `[1, 2, 3, …`[^2])
+
+[^1]: `Dear {name}, your order is ready`
+[^2]: `[1, 2, 3, 4, 5, 6]`
+";
+        assert_eq!(d.apply_markdown(&value, None).unwrap(), expected);
+
+        let adoc = d.apply_asciidoc(&value, None).unwrap();
+        assert!(adoc.contains("(List, default: `[1, 2, 3, …`footnote:[`[1, 2, 3, 4, 5, 6\\]`])::\n"));
+    }
+
+
+    #[cfg(feature = "template")]
+    #[test]
+    fn template() {
+        let yaml: Value = serde_yaml::from_str("server: {port: 8080, name: ~}\ndebug: false").unwrap();
+        let desc: Value = serde_yaml::from_str(r#"
+server:
+    __description__: Server options
+    port: Port, see {{debug}}
+"#).unwrap();
+        let template = "\
+{% for node in root.children -%}
+[{{ node.key }}]{% if node.description %} {{ node.description }}{% endif %}
+{% for leaf in node.leaves -%}
+{{ leaf.key }} ({{ leaf.type }}) = {{ leaf.default }}{% if not leaf.description %} (undocumented){% else %}: {{ leaf.description }}{% endif %}
+{% endfor -%}
+{% endfor %}";
+        let expected = "\
+[server] Server options
+port (Number) = 8080: Port, see debug
+name (Null) = Null (undocumented)
+[debug]
+debug (Bool) = false (undocumented)
+";
+        let actual = Documenter::new()
+            .apply_template(&yaml, Some(&desc), template)
+            .unwrap();
+        assert_eq!(actual, expected);
+
+        let err = Documenter::new()
+            .apply_template(&yaml, None, "{{ root.nope }}")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "impossible to document YAML value: invalid template: unknown field 'root.nope'");
+        for (template, msg) in [("{{ leaf.path }}", "unknown variable 'leaf'"),
+                                ("{% for x in root.key %}{% endfor %}", "'root.key' is not a list"),
+                                ("{% if root.children.key %}{% endif %}", "unknown field 'root.children.key'"),
+                                ("{% for x in root.leaves %}", "missing 'endfor'")] {
+            let err = Documenter::new()
+                .apply_template(&yaml, None, template)
+                .unwrap_err();
+            assert_eq!(err.to_string(), format!("impossible to document YAML value: invalid template: {msg}"));
+        }
+    }
+
+
+    #[test]
+    fn variants() {
+        let yaml: Value = serde_yaml::from_str("storage: !s3 {bucket: data, region: eu}\nlog: stdout").unwrap();
+        let desc: Value = serde_yaml::from_str(r#"
+storage:
+    __description__: Where to store files
+    __variants__:
+        local:
+            __description__: On disk
+            path: Directory
+        s3:
+            __description__: On S3
+            bucket: Name of the bucket
+log:
+    __description__: Log output
+    __variants__:
+        stdout: ~
+        file: Log to a file
+"#).unwrap();
+        let d = Documenter::new();
+        let expected = "\
+# Where to store files
+# one of: `local { path }`, `s3 { bucket, region }`
+# - local: On disk
+# - s3: On S3
+storage: 
+    # Name of the bucket
+    bucket (String): data
+    region (String): eu
+# Log output
+# one of: `stdout`, `file`
+# - file: Log to a file
+log (String): stdout";
+        assert_eq!(d.apply_value(&yaml, Some(&desc)).unwrap(), expected);
+
+        let markdown = d.apply_markdown(&yaml, Some(&desc)).unwrap();
+        assert!(markdown.contains("* <a id=\"log\"></a>`log` (String, default: `stdout`, one of: `stdout`, `file`): \
+                                   Log output\n    * `file`: Log to a file\n"));
+        assert!(markdown.contains("Where to store files\n\nOne of: `local { path }`, `s3 { bucket, region }`.\n\n\
+                                   * `local`: On disk\n* `s3`: On S3\n\n"));
+
+        let report = d.coverage(&yaml, &desc).unwrap();
+        assert_eq!(report.undocumented, vec!["storage.region"]);
+        d.clone().strict(true).apply_value(&yaml, Some(&desc)).unwrap();
+    }
+
+
+    #[test]
+    fn optional_fields() {
+        #[derive(serde_derive::Serialize, serde_derive::Deserialize, Default)]
+        struct Config {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            port: Option<u16>,
+            name: Option<String>,
+            nothing: (),
+        }
+
+        let desc: Value = serde_yaml::from_str("port: Port").unwrap();
+        let d = Documenter::new();
+        let expected = "\
+# Port
+# optional
+port (Number): Null
+# optional
+name (String): Null
+nothing: Null";
+        assert_eq!(d.apply_type::<Config>(Some(&desc)).unwrap(), expected);
+
+        // Values that are not documented from a type are not affected
+        let yaml = type_value::<Config>().unwrap();
+        assert_eq!(d.apply_value(&yaml, None).unwrap(), "port: Null\nname: Null\nnothing: Null");
+    }
+
+
+    #[test]
+    fn warnings() {
+        use std::sync::Mutex;
+
+        let yaml: Value = serde_yaml::from_str("a: {b: {c: {d: 1}}, 1: x}\nlist: [{e: {f: 2}}]").unwrap();
+        let desc: Value = serde_yaml::from_str("a: {bb: Typo}").unwrap();
+        let warnings = Arc::new(Mutex::new(vec![]));
+        let w = warnings.clone();
+        let d = Documenter::new()
+            .warning_depth(1)
+            .on_warning(move |warning| w.lock().unwrap().push(warning.clone()));
+        d.apply_value(&yaml, Some(&desc)).unwrap();
+        assert_eq!(*warnings.lock().unwrap(), vec![
+            DocWarning::UnknownDescription("a.bb".to_owned()),
+            DocWarning::DeepNesting { path: "a.b.c".to_owned(), depth: 2 },
+            DocWarning::NonStringKey("a.1".to_owned()),
+            DocWarning::DeepNesting { path: "list.-.e".to_owned(), depth: 2 },
+        ]);
+
+        // Strict mode still returns an error
+        assert!(d.strict(true).apply_value(&yaml, Some(&desc)).is_err());
+    }
+
+
+    #[test]
+    fn parse_documented() {
+        let yaml: Value = serde_yaml::from_str(r#"
+server:
+    port: 8080
+    name: "a # b"
+    tls: ~
+    hosts: [a, b]
+debug: false
+"#).unwrap();
+        let desc: Value = serde_yaml::from_str(r#"
+server:
+    __description__: Server options
+    __section__: Network
+    port:
+        __description__: Port to listen on
+        __min__: 1
+    name: Name
+debug: Debug mode
+"#).unwrap();
+        let expected_desc: Value = serde_yaml::from_str(r#"
+server:
+    __section__: Network
+    __description__: Server options
+    port: Port to listen on
+    name: Name
+debug: Debug mode
+"#).unwrap();
+        for d in [Documenter::new(),
+                  Documenter::new().comment_placement(CommentPlacement::Inline).align_comments(true),
+                  Documenter::new().commented_out(true).env_var_prefix("APP").null_repr("<none>")] {
+            let doc = d.apply_value(&yaml, Some(&desc)).unwrap();
+            let (value, description) = d.parse_documented(&doc).unwrap();
+            assert_eq!(value, yaml);
+            assert_eq!(description, expected_desc);
+        }
+
+        let err = Documenter::new().parse_documented("foo: \n        bar: 1").unwrap_err();
+        assert_eq!(err.to_string(), "impossible to document YAML value: line 2: unexpected indentation");
+    }
+
+
+    #[test]
+    fn current_config() {
+        let defaults: Value = serde_yaml::from_str("server: {port: 8080, hosts: [a]}\ndebug: false").unwrap();
+        let current: Value = serde_yaml::from_str("server: {hosts: [a, b]}\ndebug: false\nextra: 1").unwrap();
+        let d = Documenter::new();
+        let expected = "\
+server: 
+    port (Number): 8080
+    # default: [a]
+    hosts (List): [a, b]
+debug (Bool): false
+extra (Number): 1";
+        assert_eq!(d.apply_current(&current, &defaults, None).unwrap(), expected);
+
+        let mut d2 = d.clone();
+        d2.defaults = Some(Arc::new(defaults.clone()));
+        let mut value = defaults.clone();
+        crate::merge(&mut value, &current).unwrap();
+        let markdown = d2.apply_markdown(&value, None).unwrap();
+        assert!(markdown.contains("`hosts` (List, value: `[a, b]`, default: `[a]`)"));
+        assert!(markdown.contains("`port` (Number, value: `8080`)"));
+    }
+
+
+    #[test]
+    fn split() {
+        let yaml: Value = serde_yaml::from_str("a: {b: \"long string\"}\nc: [1, 2]").unwrap();
+        let desc: Value = serde_yaml::from_str("__description__: Root\na: {__description__: First}").unwrap();
+        let pages = Documenter::new()
+            .truncate_values(6)
+            .truncation_footnotes(true)
+            .apply_value_split(&yaml, Some(&desc))
+            .unwrap();
+        assert_eq!(pages, vec![
+            ("a".to_owned(), "# First\na: \n    b (String): long s… [1]\n# [1] long string".to_owned()),
+            ("c".to_owned(), "c (List): [1, 2]".to_owned()),
+        ]);
+
+        assert!(Documenter::new().apply_value_split(&Value::from(1), None).is_err());
+    }
+
+
+    #[test]
+    fn source_map() {
+        let yaml: Value = serde_yaml::from_str("a: {b: 1, c: {d: 2}}\nlist: [{e: 3}]\nf: 4").unwrap();
+        let desc: Value = serde_yaml::from_str("a: {__description__: A, c: {d: D}}\nf: F").unwrap();
+        let d = Documenter::new()
+            .comment_placement_at_depth(0, CommentPlacement::Inline);
+        let (doc, spans) = d.apply_value_with_map(&yaml, Some(&desc)).unwrap();
+        assert_eq!(doc, d.apply_value(&yaml, Some(&desc)).unwrap());
+        let path = |s: &str| s.split('.').map(|s| s.to_owned()).collect::<Vec<_>>();
+        assert_eq!(spans, vec![
+            SourceSpan { path: path("a"), lines: 0..5 },
+            SourceSpan { path: path("a.b"), lines: 1..2 },
+            SourceSpan { path: path("a.c"), lines: 2..5 },
+            SourceSpan { path: path("a.c.d"), lines: 3..5 },
+            SourceSpan { path: path("list"), lines: 5..7 },
+            SourceSpan { path: path("list.-.e"), lines: 6..7 },
+            SourceSpan { path: path("f"), lines: 7..8 },
+        ]);
+    }
+
+
+    #[test]
+    fn streaming() {
+        let yaml: Value = serde_yaml::from_str("a: {b: 1, c: {d: [1, {e: 2}]}}\nlist: [{e: 3}, 4]\nf: 4").unwrap();
+        let desc: Value = serde_yaml::from_str("a: {__description__: A, c: {d: D}}\nf: F").unwrap();
+        // Same as the default, but goes through the hook
+        let buffered = Documenter::new()
+            .format_mapping(|args| {
+                let s = args.inner.join("\n");
+                if args.path.is_empty() { s } else { format!("\n{s}") }
+            });
+        for depth in [0, 1] {
+            let d = Documenter::new()
+                .comment_placement_at_depth(depth, CommentPlacement::Inline)
+                .align_comments(true);
+            let b = buffered.clone()
+                .comment_placement_at_depth(depth, CommentPlacement::Inline)
+                .align_comments(true);
+            assert_eq!(d.apply_value(&yaml, Some(&desc)).unwrap(),
+                       b.apply_value(&yaml, Some(&desc)).unwrap());
+            assert_eq!(d.apply_value_with_map(&yaml, Some(&desc)).unwrap(),
+                       b.apply_value_with_map(&yaml, Some(&desc)).unwrap());
+        }
+    }
 }
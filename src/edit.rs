@@ -0,0 +1,502 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Editing of YAML files that keeps their formatting (requires the `edit` feature).
+//!
+//! A `Document` keeps the source of a YAML document next to its value. Its modifications only
+//! rewrite the source of the values they change, so the comments, blank lines and quoting of
+//! the rest of the document are kept. New values are written in block style, unless they are
+//! inside a flow collection or replace a value that is on the same line as its key.
+//!
+//! Each modification checks that the new source gives the expected value; when the formatting
+//! can't be kept (e.g. inside an alias), an error is returned and the document is unchanged.
+//!
+//! # Example
+//!
+//! ```
+//! use yaml_extras::edit::Document;
+//!
+//! let mut doc = Document::parse("\
+//! ## Server settings
+//! server:
+//!   host: 'localhost'  # or 0.0.0.0
+//!   port: 80
+//!
+//! ## Logging
+//! log: {level: info}
+//! ")?;
+//! doc.set("server.port", 8080)?;
+//! doc.set("server.tls.cert", "cert.pem")?;
+//! doc.set("log.file", "app.log")?;
+//! doc.remove("server.host")?;
+//! assert_eq!(doc.as_str(), "\
+//! ## Server settings
+//! server:
+//!   port: 8080
+//!   tls:
+//!     cert: cert.pem
+//!
+//! ## Logging
+//! log: {level: info, file: app.log}
+//! ");
+//! # Ok::<(), yaml_extras::Error>(())
+//! ```
+
+use crate::anchors::{is_collection, to_string_with_anchors};
+use crate::document::double_quoted;
+use crate::error::{Error, Result};
+use crate::path::{self, get_tokens, key_token, tokens};
+use crate::span::Spanned;
+
+use serde_yaml::{Mapping, Value};
+
+use std::fmt;
+use std::path::Path;
+
+/// The text of a scalar on a single line
+fn scalar(v: &Value) -> Result<Option<String>> {
+    let s = serde_yaml::to_string(v)?;
+    let s = s.strip_suffix('\n').unwrap_or(&s);
+    Ok((!s.contains('\n')).then(|| s.to_owned()))
+}
+
+/// A value in flow style
+fn flow(v: &Value) -> Result<String> {
+    Ok(match v {
+        Value::Mapping(m) => format!("{{{}}}", m.iter()
+            .map(|(k, v)| Ok(format!("{}: {}", flow(k)?, flow(v)?)))
+            .collect::<Result<Vec<_>>>()?
+            .join(", ")),
+        Value::Sequence(s) => format!("[{}]", s.iter()
+            .map(flow)
+            .collect::<Result<Vec<_>>>()?
+            .join(", ")),
+        Value::Tagged(t) => format!("{} {}", t.tag, flow(&t.value)?),
+        Value::String(s) => match scalar(v)? {
+            Some(plain) if plain.starts_with(['\'', '"']) || !plain.contains([',', '[', ']', '{', '}']) => plain,
+            _ => double_quoted(s),
+        },
+        v => scalar(v)?.unwrap_or_default(),
+    })
+}
+
+/// A value on a single line of a block collection
+fn inline(v: &Value) -> Result<String> {
+    if is_collection(v) {
+        return flow(v);
+    }
+    match scalar(v)? {
+        Some(s) => Ok(s),
+        None => flow(v),
+    }
+}
+
+/// A collection in block style, the lines after the first one being indented with `indent`
+/// spaces
+fn block(v: &Value, indent: usize) -> Result<String> {
+    let s = to_string_with_anchors(v, usize::MAX)?;
+    let prefix = " ".repeat(indent);
+    Ok(s.trim_end_matches('\n')
+        .split('\n')
+        .enumerate()
+        .map(|(i, line)| if i == 0 || line.is_empty() { line.to_owned() } else { format!("{prefix}{line}") })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// The value itself, for a tagged value
+fn untagged(v: &Value) -> &Value {
+    match v {
+        Value::Tagged(t) => &t.value,
+        v => v,
+    }
+}
+
+/// A YAML document that can be modified while keeping its formatting
+#[derive(Debug, Clone)]
+pub struct Document {
+    source: String,
+    spanned: Spanned,
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Document {
+    /// Parses a YAML document
+    pub fn parse<S: Into<String>>(source: S) -> Result<Self> {
+        let source = source.into();
+        let spanned = Spanned::parse(&source)?;
+        Ok(Document { source, spanned })
+    }
+
+    /// Reads and parses a YAML file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)
+            .map_err(|source| Error::Io { path: path.display().to_string(), source })?;
+        Self::parse(source)
+    }
+
+    /// Writes the document to a file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, &self.source)
+            .map_err(|source| Error::Io { path: path.display().to_string(), source })
+    }
+
+    /// The source of the document
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// The value of the document
+    pub fn value(&self) -> &Value {
+        self.spanned.value()
+    }
+
+    /// The value at `path`, if it exists
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        path::get(self.value(), path)
+    }
+
+    /// Sets the value at `path`, creating the missing mappings, and returns the previous value
+    /// if there was one (see `path::set`)
+    pub fn set<V: Into<Value>>(&mut self, path: &str, new: V) -> Result<Option<Value>> {
+        let new = new.into();
+        let mut expected = self.value().clone();
+        let old = path::set(&mut expected, path, new.clone())?;
+        let keys = tokens(path);
+        let mut existing = keys.len();
+        while get_tokens(self.value(), &keys[..existing]).is_none() {
+            existing -= 1;
+        }
+        let (parent, missing) = keys.split_at(existing);
+        let mut new = new;
+        for key in missing[1.min(missing.len())..].iter().rev() {
+            let mut m = Mapping::new();
+            m.insert(key.as_str().into(), new);
+            new = Value::Mapping(m);
+        }
+        match (missing.first(), get_tokens(self.value(), parent).map(untagged)) {
+            (Some(key), Some(Value::Mapping(m))) => {
+                let empty = m.is_empty();
+                self.insert(parent, empty, key, &new, expected, path)?
+            },
+            (Some(key), _) => {
+                // Null, replaced by a mapping
+                let mut m = Mapping::new();
+                m.insert(key.as_str().into(), new);
+                self.replace(parent, &Value::Mapping(m), expected, path)?
+            },
+            (None, _) => self.replace(parent, &new, expected, path)?,
+        }
+        Ok(old)
+    }
+
+    /// Removes the value at `path` and returns it, if it exists (see `path::remove`)
+    pub fn remove(&mut self, path: &str) -> Result<Option<Value>> {
+        let mut expected = self.value().clone();
+        let Some(old) = path::remove(&mut expected, path) else { return Ok(None) };
+        let keys = tokens(path);
+        let parent = &keys[..keys.len() - 1];
+        match get_tokens(self.value(), parent).map(untagged) {
+            Some(Value::Mapping(m)) if m.len() == 1 => {
+                self.replace(parent, &Value::Mapping(Mapping::new()), expected, path)?;
+                return Ok(Some(old));
+            },
+            Some(Value::Sequence(s)) if s.len() == 1 => {
+                self.replace(parent, &Value::Sequence(vec![]), expected, path)?;
+                return Ok(Some(old));
+            },
+            _ => (),
+        }
+        let span = self.spanned.value_span(&keys).ok_or_else(|| self.error(path))?;
+        let key_span = self.spanned.key_span(&keys);
+        let mut start = key_span.unwrap_or(span).start.index;
+        let mut end = self.end(&keys, span.end.index);
+        if self.is_flow(parent) {
+            // Along with a comma
+            let after = self.source[end..].trim_start();
+            if let Some(after) = after.strip_prefix(',') {
+                end = self.source.len() - after.trim_start_matches([' ', '\t']).len();
+            } else {
+                let before = self.source[..start].trim_end();
+                if before.ends_with(',') {
+                    start = before.len() - 1;
+                }
+            }
+            self.splice(start, end, "", expected, path)?;
+        } else {
+            let line_start = self.source[..start].rfind('\n').map_or(0, |i| i + 1);
+            let prefix = self.source[line_start..start].trim();
+            let line_end = self.line_end(end);
+            if prefix.is_empty() || (prefix == "-" && key_span.is_none()) {
+                let line_end = (line_end + 1).min(self.source.len());
+                self.splice(line_start, line_end, "", expected, path)?;
+            } else {
+                // The next entry takes the place of the removed one, if it is aligned with it
+                let next_line = &self.source[(line_end + 1).min(self.source.len())..];
+                let indent = next_line.len() - next_line.trim_start_matches(' ').len();
+                let end = if indent == start - line_start { line_end + 1 + indent } else { line_end };
+                self.splice(start, end, "", expected, path)?;
+            }
+        }
+        Ok(Some(old))
+    }
+
+    fn error(&self, path: &str) -> Error {
+        Error::Path(format!("can't keep the formatting when editing '{path}'"))
+    }
+
+    /// The index of the content of the node starting at `index`, after its anchor and tag
+    fn content_start(&self, index: usize) -> usize {
+        let bytes = self.source.as_bytes();
+        let mut i = index;
+        while bytes.get(i).is_some_and(|b| matches!(b, b'&' | b'!')) {
+            while bytes.get(i).is_some_and(|b| !b.is_ascii_whitespace()) {
+                i += 1;
+            }
+            while bytes.get(i).is_some_and(|b| matches!(b, b' ' | b'\t')) {
+                i += 1;
+            }
+        }
+        i
+    }
+
+    /// Whether the value at `keys` is a flow collection
+    fn is_flow(&self, keys: &[String]) -> bool {
+        self.spanned.value_span(keys)
+            .is_some_and(|span| matches!(self.source.as_bytes().get(self.content_start(span.start.index)), Some(b'{' | b'[')))
+    }
+
+    /// Whether the value at `keys` is a block collection
+    fn is_block(&self, keys: &[String]) -> bool {
+        get_tokens(self.value(), keys).is_some_and(is_collection) && !self.is_flow(keys)
+    }
+
+    /// The end of the value at `keys`, whose span ends at `end`: the end of a block collection
+    /// is the one of its last node, not the start of what follows it
+    fn end(&self, keys: &[String], end: usize) -> usize {
+        if self.is_block(keys) {
+            self.spanned.descendants_end(keys).map_or(end, |end| end.index)
+        } else {
+            end
+        }
+    }
+
+    /// The index of the end of the line of `index`, at its line break if there is one
+    fn line_end(&self, index: usize) -> usize {
+        if self.source[..index].ends_with('\n') {
+            index - 1
+        } else {
+            self.source[index..].find('\n').map_or(self.source.len(), |i| index + i)
+        }
+    }
+
+    /// Replaces the value at `keys` by `new`
+    fn replace(&mut self, keys: &[String], new: &Value, expected: Value, path: &str) -> Result<()> {
+        let Some(span) = self.spanned.value_span(keys) else {
+            if !keys.is_empty() || !self.value().is_null() {
+                return Err(self.error(path));
+            }
+            // Empty document, maybe with comments
+            let mut text = if is_collection(new) { block(new, 0)? } else { inline(new)? };
+            if !self.source.is_empty() && !self.source.ends_with('\n') {
+                text.insert(0, '\n');
+            }
+            text.push('\n');
+            let len = self.source.len();
+            return self.splice(len, len, &text, expected, path);
+        };
+        let start = span.start.index;
+        // Block scalars end after their last line break
+        let end = start + self.source[start..self.end(keys, span.end.index)].trim_end_matches('\n').len();
+        let in_flow = !keys.is_empty() && self.is_flow(&keys[..keys.len() - 1]);
+        let mut text = if in_flow {
+            flow(new)?
+        } else if self.is_block(keys) && is_collection(new) {
+            block(new, span.start.column - 1)?
+        } else {
+            inline(new)?
+        };
+        if start == end && self.source[..start].ends_with([':', '-']) {
+            text.insert(0, ' ');
+        }
+        self.splice(start, end, &text, expected, path)
+    }
+
+    /// Inserts `key` with the value `new` in the mapping at `keys`
+    fn insert(&mut self, keys: &[String], empty: bool, key: &str, new: &Value, expected: Value, path: &str) -> Result<()> {
+        let span = self.spanned.value_span(keys).ok_or_else(|| self.error(path))?;
+        let key_text = inline(&Value::from(key))?;
+        if self.is_flow(keys) {
+            let text = format!("{}{key_text}: {}", if empty { "" } else { ", " }, flow(new)?);
+            let close = span.end.index.saturating_sub(1);
+            return self.splice(close, close, &text, expected, path);
+        }
+        // After the line of the last entry, with the same indentation as the first one
+        let first = get_tokens(self.value(), keys)
+            .map(untagged)
+            .and_then(Value::as_mapping)
+            .and_then(|m| m.keys().next())
+            .ok_or_else(|| self.error(path))?;
+        let mut first_keys = keys.to_vec();
+        first_keys.push(key_token(first));
+        let indent = self.spanned.key_span(&first_keys).ok_or_else(|| self.error(path))?.start.column - 1;
+        let end = self.spanned.descendants_end(keys).ok_or_else(|| self.error(path))?.index;
+        let pos = self.line_end(end);
+        let value_text = if is_collection(new) {
+            format!("\n{}{}", " ".repeat(indent + 2), block(new, indent + 2)?)
+        } else {
+            format!(" {}", inline(new)?)
+        };
+        let text = format!("\n{}{key_text}:{value_text}", " ".repeat(indent));
+        self.splice(pos, pos, &text, expected, path)
+    }
+
+    /// Replaces the source from `start` to `end` by `text`, if this gives `expected`
+    fn splice(&mut self, start: usize, end: usize, text: &str, expected: Value, path: &str) -> Result<()> {
+        let source = format!("{}{text}{}", &self.source[..start], &self.source[end..]);
+        let spanned = Spanned::parse(&source)
+            .ok()
+            .filter(|spanned| *spanned.value() == expected)
+            .ok_or_else(|| self.error(path))?;
+        self.source = source;
+        self.spanned = spanned;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yaml;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn replacing() {
+        let mut doc = Document::parse("\
+a: \"quoted\"   # comment
+b:
+  - x
+  - {y: 1, z: [1, 2]}  # flow
+c:
+  d: 1
+
+  e: 2
+").unwrap();
+        assert_eq!(doc.set("b.1.z.0", "hello, world").unwrap(), Some(yaml("1")));
+        doc.set("b.0", yaml("{k: v}")).unwrap();
+        doc.set("c", yaml("{f: [1, 2], g: \"two\\nlines\"}")).unwrap();
+        assert_eq!(doc.as_str(), "\
+a: \"quoted\"   # comment
+b:
+  - {k: v}
+  - {y: 1, z: [\"hello, world\", 2]}  # flow
+c:
+  f:
+    - 1
+    - 2
+  g: |-
+    two
+    lines
+");
+        doc.set("a", yaml("[1]")).unwrap();
+        doc.set("", yaml("[1]")).unwrap();
+        assert_eq!(doc.as_str(), "- 1\n");
+    }
+
+    #[test]
+    fn inserting() {
+        let mut doc = Document::parse("\
+# top
+a:
+  b: |
+    text
+c: null
+d: {}
+seq:
+  - name: x
+    port: 1 # inline
+").unwrap();
+        doc.set("a.e", 1).unwrap();
+        doc.set("c.f.g", true).unwrap();
+        doc.set("d.h", 2).unwrap();
+        doc.set("seq.0.tags", yaml("[t]")).unwrap();
+        doc.set("new.nested", yaml("{i: 3}")).unwrap();
+        assert_eq!(doc.as_str(), "\
+# top
+a:
+  b: |
+    text
+  e: 1
+c: {f: {g: true}}
+d: {h: 2}
+seq:
+  - name: x
+    port: 1 # inline
+    tags:
+      - t
+new:
+  nested:
+    i: 3
+");
+        assert!(doc.set("seq.1", 1).is_err());
+        assert_eq!(doc.value()["new"]["nested"]["i"], yaml("3"));
+
+        let mut doc = Document::parse("# only a comment").unwrap();
+        doc.set("a.b", 1).unwrap();
+        assert_eq!(doc.as_str(), "# only a comment\na:\n  b: 1\n");
+    }
+
+    #[test]
+    fn removing() {
+        let mut doc = Document::parse("\
+a: 1
+# about b
+b:
+  c: 1
+  d: [1, 2, 3]
+  e: {f: 1, g: 2}
+seq:
+  - 1
+  - x: 1
+    y: 2
+single: {h: 1}
+").unwrap();
+        assert_eq!(doc.remove("a").unwrap(), Some(yaml("1")));
+        assert_eq!(doc.remove("missing").unwrap(), None);
+        doc.remove("b.c").unwrap();
+        doc.remove("b.d.0").unwrap();
+        doc.remove("b.d.1").unwrap();
+        doc.remove("b.e.g").unwrap();
+        doc.remove("seq.1.x").unwrap();
+        doc.remove("seq.0").unwrap();
+        doc.remove("single.h").unwrap();
+        assert_eq!(doc.value(), &yaml("{b: {d: [2], e: {f: 1}}, seq: [{y: 2}], single: {}}"));
+        assert_eq!(doc.as_str(), "\
+# about b
+b:
+  d: [2]
+  e: {f: 1}
+seq:
+  - y: 2
+single: {}
+");
+    }
+
+    #[test]
+    fn aliases() {
+        let mut doc = Document::parse("a: &x {b: 1}\nc: *x\n").unwrap();
+        let err = doc.set("c.b", 2).unwrap_err();
+        assert_eq!(err.to_string(), "impossible to access YAML path: can't keep the formatting when editing 'c.b'");
+        assert_eq!(doc.as_str(), "a: &x {b: 1}\nc: *x\n");
+        doc.set("c", 3).unwrap();
+        assert_eq!(doc.as_str(), "a: &x {b: 1}\nc: 3\n");
+    }
+}
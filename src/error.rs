@@ -10,8 +10,120 @@ pub enum Error {
     Merge(String),
     #[error("impossible to restructure YAML map: {0}")]
     Restructure(String),
+    #[error("impossible to document YAML value: {0}")]
+    Document(String),
+    #[error("impossible to apply patch: {0}")]
+    Patch(String),
+    #[error("impossible to access YAML path: {0}")]
+    Path(String),
+    #[error("impossible to migrate YAML value: {0}")]
+    Migrate(String),
+    #[error("impossible to resolve includes: {0}")]
+    Include(String),
+    #[error("impossible to interpolate variables: {0}")]
+    Interpolate(String),
+    #[error("impossible to canonicalize YAML value: {0}")]
+    Canonicalize(String),
+    #[error("{source} (line {line}, column {column})")]
+    Located {
+        /// The line of the value the error is about, starting at 1
+        line: usize,
+        /// The column of the value, starting at 1
+        column: usize,
+        #[source]
+        source: Box<Error>,
+    },
+    #[error("impossible to merge YAML values at '{path}': {base} in the base, {overlay} in the overlay")]
+    TypeMismatch {
+        /// The path of the values, empty for the root
+        path: String,
+        /// A short name of the base's type, e.g. `mapping` or `string`
+        base: String,
+        /// A short name of the overlay's type
+        overlay: String,
+    },
+    #[error("impossible to deserialize '{path}': {source}")]
+    Deserialize {
+        /// The path of the value that couldn't be deserialized, empty for the root or if it
+        /// is unknown
+        path: String,
+        #[source]
+        source: serde_yaml::Error,
+    },
+    #[error("impossible to read {path}")]
+    Io {
+        /// The file that could not be read
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("maximum depth of {limit} exceeded at '{path}'")]
+    DepthExceeded {
+        /// The dotted path of the first value nested too deep
+        path: String,
+        /// The maximum depth, e.g. set with `Merger::max_depth`
+        limit: usize,
+    },
     #[error("YAML error")]
     Yaml(#[from] serde_yaml::Error)
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
+
+/// Default maximum depth of values, which is also the limit used by `serde_yaml` when parsing
+pub(crate) const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Checks that `value` is not nested deeper than `limit`, without recursion so that it
+/// doesn't overflow the stack itself
+pub(crate) fn check_depth(value: &serde_yaml::Value, limit: usize) -> Result<()> {
+    use serde_yaml::Value;
+
+    let mut path: Vec<String> = vec![];
+    // Values with their depth and the key leading to them
+    let mut stack = vec![(value, 0, String::new())];
+    while let Some((v, depth, key)) = stack.pop() {
+        if depth > 0 {
+            path.truncate(depth - 1);
+            path.push(key);
+        }
+        if depth > limit {
+            return Err(Error::DepthExceeded {
+                path: path.join("."),
+                limit,
+            });
+        }
+        // Children are pushed in reverse order so that they are checked in order
+        let start = stack.len();
+        match v {
+            Value::Mapping(m) => for (k, inner) in m.iter() {
+                let k = match k {
+                    Value::String(s) => s.clone(),
+                    k => format!("{k:?}"),
+                };
+                stack.push((inner, depth + 1, k));
+            },
+            Value::Sequence(s) => for (i, inner) in s.iter().enumerate() {
+                stack.push((inner, depth + 1, i.to_string()));
+            },
+            // Same depth and path
+            Value::Tagged(t) => stack.push((&t.value, depth, path.last().cloned().unwrap_or_default())),
+            _ => (),
+        }
+        stack[start..].reverse();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn depth() {
+        let v: serde_yaml::Value = serde_yaml::from_str("{a: {b: [1, !t {c: {d: 1}}]}, e: 1}").unwrap();
+        assert!(check_depth(&v, 5).is_ok());
+        let err = check_depth(&v, 4).unwrap_err();
+        assert_eq!(err.to_string(), "maximum depth of 4 exceeded at 'a.b.1.c.d'");
+    }
+}
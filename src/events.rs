@@ -0,0 +1,256 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Reading of the parser's events, to know where nodes, anchors and aliases are in the source
+
+use crate::error::{Error, Result};
+use crate::path::key_token;
+
+use serde::de;
+use serde_yaml::Value;
+
+use std::collections::BTreeMap;
+use std::mem::MaybeUninit;
+
+/// A position in the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    /// The offset in bytes, starting at 0
+    pub index: usize,
+    /// The line, starting at 1
+    pub line: usize,
+    /// The column, in characters, starting at 1
+    pub column: usize,
+}
+
+impl From<unsafe_libyaml::yaml_mark_t> for Location {
+    fn from(mark: unsafe_libyaml::yaml_mark_t) -> Self {
+        Location {
+            index: mark.index as usize,
+            line: mark.line as usize + 1,
+            column: mark.column as usize + 1,
+        }
+    }
+}
+
+/// The part of the source where a node is, from `start` included to `end` excluded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// The location of the first character of the node
+    pub start: Location,
+    /// The location just after the node
+    pub end: Location,
+}
+
+/// A parser event, with the anchor of the node if any
+enum Event {
+    Scalar { value: String, plain: bool, anchor: Option<String> },
+    Alias { anchor: String },
+    MappingStart { anchor: Option<String> },
+    SequenceStart { anchor: Option<String> },
+    End,
+}
+
+/// Reads a string owned by libyaml, if there is one
+///
+/// # Safety
+///
+/// `s` must be null or a valid nul-terminated string
+unsafe fn string(s: *const u8) -> Option<String> {
+    if s.is_null() {
+        return None;
+    }
+    let s = std::ffi::CStr::from_ptr(s.cast());
+    Some(s.to_string_lossy().into_owned())
+}
+
+/// The events of the first document of `source`
+fn events(source: &str) -> Result<Vec<(Event, Span)>> {
+    let mut events = vec![];
+    // libyaml keeps pointers to the parser, so it must not move
+    let mut parser = Box::new(MaybeUninit::<unsafe_libyaml::yaml_parser_t>::uninit());
+    let parser = parser.as_mut_ptr();
+    // Safety: the parser is initialized before being used and deleted once, the input outlives
+    // it, and each event is deleted once after being read
+    unsafe {
+        if unsafe_libyaml::yaml_parser_initialize(parser).fail {
+            return Err(Error::Yaml(de::Error::custom("impossible to initialize the YAML parser")));
+        }
+        unsafe_libyaml::yaml_parser_set_input_string(parser, source.as_ptr(), source.len() as u64);
+        let result = loop {
+            let mut event = MaybeUninit::<unsafe_libyaml::yaml_event_t>::uninit();
+            if unsafe_libyaml::yaml_parser_parse(parser, event.as_mut_ptr()).fail {
+                let failed = &*parser;
+                let problem = string(failed.problem.cast()).unwrap_or_else(|| "invalid YAML".to_owned());
+                let mark = Location::from(failed.problem_mark);
+                break Err(Error::Located {
+                    line: mark.line,
+                    column: mark.column,
+                    source: Box::new(Error::Yaml(de::Error::custom(problem))),
+                });
+            }
+            let event = event.as_mut_ptr();
+            let span = Span {
+                start: (*event).start_mark.into(),
+                end: (*event).end_mark.into(),
+            };
+            let converted = match (*event).type_ {
+                unsafe_libyaml::YAML_SCALAR_EVENT => {
+                    let scalar = (*event).data.scalar;
+                    let bytes = std::slice::from_raw_parts(scalar.value, scalar.length as usize);
+                    Some(Event::Scalar {
+                        value: String::from_utf8_lossy(bytes).into_owned(),
+                        plain: scalar.style == unsafe_libyaml::YAML_PLAIN_SCALAR_STYLE,
+                        anchor: string(scalar.anchor),
+                    })
+                },
+                unsafe_libyaml::YAML_ALIAS_EVENT => Some(Event::Alias {
+                    anchor: string((*event).data.alias.anchor).unwrap_or_default(),
+                }),
+                unsafe_libyaml::YAML_MAPPING_START_EVENT => Some(Event::MappingStart {
+                    anchor: string((*event).data.mapping_start.anchor),
+                }),
+                unsafe_libyaml::YAML_SEQUENCE_START_EVENT => Some(Event::SequenceStart {
+                    anchor: string((*event).data.sequence_start.anchor),
+                }),
+                unsafe_libyaml::YAML_MAPPING_END_EVENT | unsafe_libyaml::YAML_SEQUENCE_END_EVENT => Some(Event::End),
+                _ => None,
+            };
+            let done = matches!((*event).type_, unsafe_libyaml::YAML_DOCUMENT_END_EVENT | unsafe_libyaml::YAML_STREAM_END_EVENT);
+            unsafe_libyaml::yaml_event_delete(event);
+            if let Some(converted) = converted {
+                events.push((converted, span));
+            }
+            if done {
+                break Ok(events);
+            }
+        };
+        unsafe_libyaml::yaml_parser_delete(parser);
+        result
+    }
+}
+
+/// A collection whose children are being read
+struct Frame {
+    /// `None` for the children of a mapping key, which aren't recorded
+    path: Option<Vec<String>>,
+    start: Location,
+    mapping: bool,
+    /// For a mapping, the key (`None` if there is none, or if it isn't a scalar) and its span
+    /// while its value is expected
+    key: Option<(Option<String>, Span)>,
+    /// For a sequence, the index of the next item
+    index: usize,
+    /// Whether this collection is a mapping key
+    is_key: bool,
+}
+
+/// Updates the parent of a node that was just read
+fn end_node(stack: &mut [Frame], is_key: bool) {
+    match stack.last_mut() {
+        // The value of the key is still to be read
+        _ if is_key => (),
+        Some(parent) if parent.mapping => parent.key = None,
+        Some(parent) => parent.index += 1,
+        None => (),
+    }
+}
+
+/// An anchor or an alias, with the path of its node (`None` if it is in a mapping key)
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Anchoring {
+    Anchor { name: String, path: Option<Vec<String>> },
+    Alias { name: String, path: Option<Vec<String>>, span: Span },
+}
+
+/// The anchor or alias of an event, if any
+fn anchoring(event: &Event, path: Option<Vec<String>>, span: Span) -> Option<Anchoring> {
+    match event {
+        Event::Scalar { anchor: Some(name), .. }
+        | Event::MappingStart { anchor: Some(name) }
+        | Event::SequenceStart { anchor: Some(name) } => Some(Anchoring::Anchor { name: name.clone(), path }),
+        Event::Alias { anchor } => Some(Anchoring::Alias { name: anchor.clone(), path, span }),
+        _ => None,
+    }
+}
+
+/// The locations of the nodes of a document
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Nodes {
+    /// The spans of the values, by path
+    pub(crate) spans: BTreeMap<Vec<String>, Span>,
+    /// The spans of the keys, by the path of their value
+    pub(crate) keys: BTreeMap<Vec<String>, Span>,
+    /// The anchors and aliases, in the order of the source
+    pub(crate) anchors: Vec<Anchoring>,
+}
+
+/// Reads the nodes of the first document of `source`
+///
+/// Values that are inside an alias aren't listed.
+pub(crate) fn nodes(source: &str) -> Result<Nodes> {
+    let mut nodes = Nodes::default();
+    let mut stack: Vec<Frame> = vec![];
+    for (event, span) in events(source)? {
+        if let Event::End = event {
+            let Some(frame) = stack.pop() else { continue };
+            if let Some(path) = frame.path {
+                nodes.spans.insert(path, Span { start: frame.start, end: span.end });
+            }
+            end_node(&mut stack, frame.is_key);
+            continue;
+        }
+        // The path of this node, `None` if it isn't recorded
+        let path = match stack.last_mut() {
+            None => Some(vec![]),
+            Some(Frame { path: None, .. }) => None,
+            Some(frame) if frame.mapping && frame.key.is_none() => {
+                // A key
+                let key = match &event {
+                    Event::Scalar { value, plain: true, .. } => serde_yaml::from_str(value)
+                        .map(|key: Value| key_token(&key))
+                        .ok(),
+                    Event::Scalar { value, plain: false, .. } => Some(value.clone()),
+                    _ => None,
+                };
+                frame.key = Some((key, span));
+                nodes.anchors.extend(anchoring(&event, None, span));
+                if let Event::MappingStart { .. } | Event::SequenceStart { .. } = event {
+                    stack.push(Frame { path: None, start: span.start, mapping: false, key: None, index: 0, is_key: true });
+                }
+                continue;
+            },
+            Some(Frame { path: Some(path), mapping: true, key: Some((key, key_span)), .. }) => key.as_ref()
+                .map(|key| {
+                    let mut path = path.clone();
+                    path.push(key.clone());
+                    nodes.keys.insert(path.clone(), *key_span);
+                    path
+                }),
+            Some(Frame { path: Some(path), index, .. }) => {
+                let mut path = path.clone();
+                path.push(index.to_string());
+                Some(path)
+            },
+        };
+        nodes.anchors.extend(anchoring(&event, path.clone(), span));
+        match event {
+            Event::MappingStart { .. } | Event::SequenceStart { .. } => stack.push(Frame {
+                path,
+                start: span.start,
+                mapping: matches!(event, Event::MappingStart { .. }),
+                key: None,
+                index: 0,
+                is_key: false,
+            }),
+            _ => {
+                if let Some(path) = path {
+                    nodes.spans.insert(path, span);
+                }
+                end_node(&mut stack, false);
+            },
+        }
+    }
+    Ok(nodes)
+}
@@ -0,0 +1,246 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::diff::canonical_number;
+
+use serde_yaml::Value;
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// A SHA-256 digest (FIPS 180-4)
+///
+/// It is implemented here rather than taken from a crate like `sha2`, since it is the only
+/// primitive needed and fits in a few lines; the tests check it against the NIST examples.
+struct Sha256 {
+    state: [u32; 8],
+    buffer: Vec<u8>,
+    len: u64,
+}
+
+impl Sha256 {
+    fn new() -> Self {
+        Sha256 {
+            state: [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19],
+            buffer: Vec::with_capacity(64),
+            len: 0,
+        }
+    }
+
+    fn compress(&mut self, block: &[u8]) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (s, v) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+        while !data.is_empty() {
+            let n = (64 - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            if self.buffer.len() == 64 {
+                let block = std::mem::take(&mut self.buffer);
+                self.compress(&block);
+                self.buffer = block;
+                self.buffer.clear();
+            }
+        }
+    }
+
+    fn finish(mut self) -> [u8; 32] {
+        let bits = self.len.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.buffer.len() != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bits.to_be_bytes());
+        let mut digest = [0; 32];
+        for (bytes, s) in digest.chunks_exact_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&s.to_be_bytes());
+        }
+        digest
+    }
+}
+
+/// Writes an unambiguous encoding of the canonical form of `v` to `out`
+///
+/// Mapping entries are encoded separately and sorted by their encoding, so that the result
+/// doesn't depend on key order, even for keys that are equal once canonicalized.
+fn encode(v: &Value, out: &mut Vec<u8>) {
+    let bytes = |out: &mut Vec<u8>, b: &[u8]| {
+        out.extend_from_slice(&(b.len() as u64).to_be_bytes());
+        out.extend_from_slice(b);
+    };
+    match v {
+        Value::Null => out.extend_from_slice(b"n"),
+        Value::Bool(b) => out.extend_from_slice(if *b { b"t" } else { b"f" }),
+        Value::Number(n) => {
+            let n = canonical_number(n);
+            match (n.as_i64(), n.as_u64(), n.as_f64()) {
+                (Some(i), _, _) => {
+                    out.extend_from_slice(b"i");
+                    out.extend_from_slice(&i.to_be_bytes());
+                },
+                (None, Some(u), _) => {
+                    out.extend_from_slice(b"u");
+                    out.extend_from_slice(&u.to_be_bytes());
+                },
+                (_, _, f) => {
+                    out.extend_from_slice(b"d");
+                    let f = f.filter(|f| !f.is_nan()).unwrap_or(f64::NAN);
+                    out.extend_from_slice(&f.to_bits().to_be_bytes());
+                },
+            }
+        },
+        Value::String(s) => {
+            out.extend_from_slice(b"s");
+            bytes(out, s.as_bytes());
+        },
+        Value::Sequence(s) => {
+            out.extend_from_slice(b"[");
+            out.extend_from_slice(&(s.len() as u64).to_be_bytes());
+            for item in s {
+                encode(item, out);
+            }
+        },
+        Value::Mapping(m) => {
+            let mut entries: Vec<Vec<u8>> = m.iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| {
+                    let mut entry = vec![];
+                    encode(k, &mut entry);
+                    encode(v, &mut entry);
+                    entry
+                })
+                .collect();
+            entries.sort();
+            out.extend_from_slice(b"{");
+            out.extend_from_slice(&(entries.len() as u64).to_be_bytes());
+            for entry in entries {
+                out.extend_from_slice(&entry);
+            }
+        },
+        Value::Tagged(t) => {
+            out.extend_from_slice(b"!");
+            bytes(out, t.tag.to_string().trim_start_matches('!').as_bytes());
+            encode(&t.value, out);
+        },
+    }
+}
+
+/// A SHA-256 digest of a value, computed over its canonical form (see `canonicalize` with
+/// the default options), so that it doesn't depend on key order or formatting
+///
+/// Unlike `canonicalize`, it accepts keys that are equal once canonicalized, e.g. `1` and
+/// `1.0`: both entries are kept, and the digest doesn't depend on their order either.
+///
+/// # Example
+///
+/// ```
+/// let a: serde_yaml::Value = serde_yaml::from_str("{port: 80, host: localhost}")?;
+/// let b: serde_yaml::Value = serde_yaml::from_str("
+/// host: 'localhost'
+/// port: 80.0
+/// ")?;
+/// assert_eq!(yaml_extras::hash_value(&a), yaml_extras::hash_value(&b));
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn hash_value(value: &Value) -> [u8; 32] {
+    let mut encoded = vec![];
+    encode(value, &mut encoded);
+    let mut hasher = Sha256::new();
+    hasher.update(&encoded);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yaml;
+    use pretty_assertions::assert_eq;
+
+    fn hex(digest: [u8; 32]) -> String {
+        digest.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    fn sha256(data: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(data);
+        hex(hasher.finish())
+    }
+
+    #[test]
+    fn digests() {
+        assert_eq!(sha256(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(sha256(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(sha256(&[b'a'; 1000]), "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3");
+        // NIST examples
+        assert_eq!(sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+                   "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+        assert_eq!(sha256(b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu"),
+                   "cf5b16a778af8380036ce59e7b0492370b249b11e8f07a51afac45037afee9d1");
+        assert_eq!(sha256(&vec![b'a'; 1_000_000]), "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0");
+        // Updates in several parts
+        let mut hasher = Sha256::new();
+        for part in [&b"ab"[..], b"cdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomno", b"pnopq"] {
+            hasher.update(part);
+        }
+        assert_eq!(hex(hasher.finish()), "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+    }
+
+    #[test]
+    fn value_hashes() {
+        let h = |s| hash_value(&yaml(s));
+        assert_eq!(h("{a: 1, b: [x, {c: null}]}"), h("{b: [x, {}], a: 1.0}"));
+        assert_ne!(h("{a: 1}"), h("{a: '1'}"));
+        assert_ne!(h("[a, b]"), h("[b, a]"));
+        assert_ne!(h("[ab]"), h("[a, b]"));
+        assert_ne!(h("!t x"), h("x"));
+        assert_ne!(h("{a: [1]}"), h("{a: 1}"));
+    }
+
+    #[test]
+    fn colliding_keys() {
+        let h = |s| hash_value(&yaml(s));
+        assert_eq!(h("{a: {1: x, 1.0: y}}"), h("{a: {1.0: y, 1: x}}"));
+        assert_eq!(h("{a: {1: x, 1.0: y}}"), h("{a: {1: y, 1.0: x}}"));
+        assert_ne!(h("{a: {1: x, 1.0: y}}"), h("{a: {1: x}}"));
+        assert_ne!(h("{a: {1: x, 1.0: y}}"), h("{a: {1: y}}"));
+    }
+}
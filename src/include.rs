@@ -0,0 +1,219 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::error::{Error, Result};
+use crate::path::{self, VisitAction};
+
+use serde_yaml::Value;
+
+use std::path::{Path, PathBuf};
+
+/// Replaces `!include path.yaml` tags by the content of the YAML file they refer to
+///
+/// Relative paths are resolved from the directory of the file containing the tag, or from the
+/// base directory for the value given to `apply`. Included files can include other files.
+/// Optionally, `!include_str path` is replaced by the content of a file as a string and
+/// `!include_env VAR` by the value of an environment variable.
+///
+/// # Example
+///
+/// ```no_run
+/// let mut config: serde_yaml::Value = serde_yaml::from_str("
+/// database: !include database.yaml
+/// motd: !include_str motd.txt
+/// ")?;
+/// yaml_extras::Includer::new()
+///     .base_dir("/etc/myapp")
+///     .include_str(true)
+///     .apply(&mut config)?;
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Includer {
+    base_dir: PathBuf,
+    max_depth: usize,
+    include_str: bool,
+    include_env: bool,
+}
+
+impl Default for Includer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Includer {
+    /// Creates a new Includer with default values
+    pub fn new() -> Self {
+        Includer {
+            base_dir: PathBuf::from("."),
+            max_depth: 16,
+            include_str: false,
+            include_env: false,
+        }
+    }
+
+    /// Sets the directory from which the relative paths of the value given to `apply` are
+    /// resolved (default is the current directory)
+    pub fn base_dir<P: Into<PathBuf>>(mut self, base_dir: P) -> Self {
+        self.base_dir = base_dir.into();
+        self
+    }
+
+    /// Sets the maximum number of nested includes (default is 16)
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Set to `true` to replace `!include_str path` by the content of the file as a string
+    /// (default is `false`)
+    pub fn include_str(mut self, include_str: bool) -> Self {
+        self.include_str = include_str;
+        self
+    }
+
+    /// Set to `true` to replace `!include_env VAR` by the value of the environment variable
+    /// `VAR` (default is `false`)
+    pub fn include_env(mut self, include_env: bool) -> Self {
+        self.include_env = include_env;
+        self
+    }
+
+    /// Resolves the includes of `value`
+    ///
+    /// On error, `value` is left unmodified.
+    pub fn apply(&self, value: &mut Value) -> Result<()> {
+        let mut resolved = value.clone();
+        self.resolve(&mut resolved, &self.base_dir, &mut vec![])?;
+        *value = resolved;
+        Ok(())
+    }
+
+    /// Reads a YAML file and resolves its includes
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<Value> {
+        self.load_with_chain(path.as_ref(), &mut vec![])
+    }
+
+    /// Loads a file, `chain` being the files that include it
+    fn load_with_chain(&self, path: &Path, chain: &mut Vec<(PathBuf, String)>) -> Result<Value> {
+        load_with_chain(path, chain, self.max_depth, Error::Include, |mut value, chain| {
+            self.resolve(&mut value, path.parent().unwrap_or(Path::new("")), chain)?;
+            Ok(value)
+        })
+    }
+
+    /// Resolves the includes of `value`, whose relative paths start at `dir`
+    fn resolve(&self, value: &mut Value, dir: &Path, chain: &mut Vec<(PathBuf, String)>) -> Result<()> {
+        let mut result = Ok(());
+        path::walk_mut(value, |path, value| {
+            let Value::Tagged(t) = value else { return VisitAction::Continue };
+            let tag = t.tag.to_string();
+            let tag = tag.trim_start_matches('!');
+            let enabled = match tag {
+                "include" => true,
+                "include_str" => self.include_str,
+                "include_env" => self.include_env,
+                _ => false,
+            };
+            if !enabled {
+                return VisitAction::Continue;
+            }
+            let Some(arg) = t.value.as_str() else {
+                result = Err(Error::Include(format!("!{tag} at '{}' should be followed by a string", path.join("."))));
+                return VisitAction::Stop;
+            };
+            let included = match tag {
+                "include" => self.load_with_chain(&dir.join(arg), chain),
+                "include_str" => std::fs::read_to_string(dir.join(arg))
+                    .map(Value::String)
+                    .map_err(|source| Error::Io { path: dir.join(arg).display().to_string(), source }),
+                _ => std::env::var(arg)
+                    .map(Value::String)
+                    .map_err(|_| Error::Include(format!("undefined environment variable '{arg}' at '{}'", path.join(".")))),
+            };
+            match included {
+                Ok(included) => {
+                    *value = included;
+                    VisitAction::SkipChildren
+                },
+                Err(err) => {
+                    result = Err(err);
+                    VisitAction::Stop
+                },
+            }
+        });
+        result
+    }
+}
+
+/// Reads the YAML file at `path` and gives it to `process`, with `chain` (the files that
+/// include it) completed with this file
+///
+/// Include cycles, and more than `max_depth` nested includes, are reported with `error`.
+pub(crate) fn load_with_chain<F>(path: &Path, chain: &mut Vec<(PathBuf, String)>, max_depth: usize,
+                                 error: fn(String) -> Error, process: F) -> Result<Value>
+where F: FnOnce(Value, &mut Vec<(PathBuf, String)>) -> Result<Value> {
+    let io_error = |source| Error::Io { path: path.display().to_string(), source };
+    let canonical = std::fs::canonicalize(path).map_err(io_error)?;
+    if let Some(pos) = chain.iter().position(|(p, _)| *p == canonical) {
+        let names: Vec<&str> = chain[pos..].iter()
+            .map(|(_, name)| name.as_str())
+            .collect();
+        return Err(error(format!("include cycle: {} -> {}", names.join(" -> "), path.display())));
+    }
+    if chain.len() >= max_depth {
+        return Err(error(format!("more than {max_depth} nested includes at {}", path.display())));
+    }
+    let s = std::fs::read_to_string(path).map_err(io_error)?;
+    let value: Value = serde_yaml::from_str(&s)?;
+    chain.push((canonical, path.display().to_string()));
+    let result = process(value, chain);
+    chain.pop();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yaml;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn includes() {
+        let dir = std::env::temp_dir().join(format!("yaml_extras_include_tags_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("conf")).unwrap();
+        std::fs::write(dir.join("conf/db.yaml"), "{host: localhost, tls: !include tls.yaml}").unwrap();
+        std::fs::write(dir.join("conf/tls.yaml"), "{cert: !include_str ../cert.pem}").unwrap();
+        std::fs::write(dir.join("cert.pem"), "CERT\n").unwrap();
+        std::fs::write(dir.join("main.yaml"), "{db: !include conf/db.yaml, name: !include_env CARGO_PKG_NAME, other: !tag x}").unwrap();
+
+        let includer = Includer::new()
+            .include_str(true)
+            .include_env(true);
+        assert_eq!(includer.load(dir.join("main.yaml")).unwrap(),
+                   yaml("{db: {host: localhost, tls: {cert: \"CERT\\n\"}}, name: yaml_extras, other: !tag x}"));
+        assert_eq!(Includer::new().load(dir.join("main.yaml")).unwrap(),
+                   yaml("{db: {host: localhost, tls: {cert: !include_str ../cert.pem}}, name: !include_env CARGO_PKG_NAME, other: !tag x}"));
+
+        let mut v = yaml("[!include conf/tls.yaml]");
+        Includer::new().base_dir(&dir).apply(&mut v).unwrap();
+        assert_eq!(v, yaml("[{cert: !include_str ../cert.pem}]"));
+        let err = Includer::new().base_dir(&dir).max_depth(1).load(dir.join("main.yaml")).unwrap_err();
+        assert_eq!(err.to_string(), format!("impossible to resolve includes: more than 1 nested includes at {}",
+                                            dir.join("conf/db.yaml").display()));
+        let mut v = yaml("{a: !include [x]}");
+        assert_eq!(Includer::new().apply(&mut v).unwrap_err().to_string(),
+                   "impossible to resolve includes: !include at 'a' should be followed by a string");
+        assert_eq!(v, yaml("{a: !include [x]}"));
+
+        std::fs::write(dir.join("a.yaml"), "{b: !include b.yaml}").unwrap();
+        std::fs::write(dir.join("b.yaml"), "[!include a.yaml]").unwrap();
+        let err = Includer::new().load(dir.join("a.yaml")).unwrap_err();
+        let name = |f: &str| dir.join(f).display().to_string();
+        assert_eq!(err.to_string(), format!("impossible to resolve includes: include cycle: {} -> {} -> {}",
+                                            name("a.yaml"), name("b.yaml"), name("a.yaml")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,269 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::error::{Error, Result};
+use crate::path::{self, key_token, VisitAction};
+
+use serde_yaml::{Mapping, Value};
+
+use std::collections::BTreeMap;
+
+/// A variable reference that was replaced, see `Interpolator::apply`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Substitution {
+    /// The path of the string, or of the key, containing the reference
+    pub path: Vec<String>,
+    /// The name of the variable
+    pub variable: String,
+    /// The value of the variable, `None` if it is undefined and has no default
+    pub value: Option<String>,
+    /// Whether the default value was used
+    pub from_default: bool,
+}
+
+/// Expands references to environment variables inside string scalars
+///
+/// `${VAR}` is replaced by the value of `VAR`, and `${VAR:-default}` by `default` when `VAR`
+/// is undefined or empty. `$${` gives a literal `${`. Undefined variables without a default
+/// are replaced by an empty string, unless the interpolator is strict.
+///
+/// # Example
+///
+/// ```
+/// let mut config: serde_yaml::Value = serde_yaml::from_str("
+/// db: postgres://${DB_HOST}:${DB_PORT:-5432}/app
+/// price: $${not_a_variable}
+/// ")?;
+/// let substitutions = yaml_extras::Interpolator::new()
+///     .var("DB_HOST", "localhost")
+///     .apply(&mut config)?;
+/// assert_eq!(config["db"], "postgres://localhost:5432/app");
+/// assert_eq!(config["price"], "${not_a_variable}");
+/// assert_eq!(substitutions.len(), 2);
+/// assert!(substitutions[1].from_default);
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Interpolator {
+    keys: bool,
+    strict: bool,
+    env: bool,
+    vars: BTreeMap<String, String>,
+}
+
+impl Default for Interpolator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpolator {
+    /// Creates a new Interpolator with default values, which behaves like `interpolate_env`
+    pub fn new() -> Self {
+        Interpolator {
+            keys: false,
+            strict: false,
+            env: true,
+            vars: BTreeMap::new(),
+        }
+    }
+
+    /// Set to `true` to also expand the references in string keys (default is `false`)
+    pub fn keys(mut self, keys: bool) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    /// Set to `true` to return an error for undefined variables without a default (default
+    /// is `false`)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut config: serde_yaml::Value = serde_yaml::from_str("home: ${SURELY_UNDEFINED_VARIABLE}")?;
+    /// let err = yaml_extras::Interpolator::new()
+    ///     .strict(true)
+    ///     .apply(&mut config)
+    ///     .unwrap_err();
+    /// assert_eq!(err.to_string(), "impossible to interpolate variables: undefined variable 'SURELY_UNDEFINED_VARIABLE' in 'home'");
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Set to `false` to not read the environment, only the variables set with `var`
+    /// (default is `true`)
+    pub fn env(mut self, env: bool) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Defines a variable, taking precedence over the environment
+    pub fn var<S: Into<String>, T: Into<String>>(mut self, name: S, value: T) -> Self {
+        self.vars.insert(name.into(), value.into());
+        self
+    }
+
+    fn lookup(&self, name: &str) -> Option<String> {
+        self.vars.get(name)
+            .cloned()
+            .or_else(|| if self.env { std::env::var(name).ok() } else { None })
+    }
+
+    /// Expands the references of `s`, found at `path`
+    fn expand(&self, s: &str, path: &[String], substitutions: &mut Vec<Substitution>) -> Result<String> {
+        let mut expanded = String::with_capacity(s.len());
+        let mut rest = s;
+        while let Some(i) = rest.find("${") {
+            if rest[..i].ends_with('$') {
+                expanded.push_str(&rest[..i - 1]);
+                expanded.push_str("${");
+                rest = &rest[i + 2..];
+                continue;
+            }
+            let Some(len) = rest[i + 2..].find('}') else { break };
+            let reference = &rest[i + 2..i + 2 + len];
+            let (name, default) = match reference.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (reference, None),
+            };
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                expanded.push_str(&rest[..i + 2]);
+                rest = &rest[i + 2..];
+                continue;
+            }
+            let (value, from_default) = match (self.lookup(name), default) {
+                (Some(value), Some(default)) if value.is_empty() => (Some(default.to_owned()), true),
+                (None, Some(default)) => (Some(default.to_owned()), true),
+                (value, _) => (value, false),
+            };
+            if value.is_none() && self.strict {
+                return Err(Error::Interpolate(format!("undefined variable '{name}' in '{}'", path.join("."))));
+            }
+            expanded.push_str(&rest[..i]);
+            expanded.push_str(value.as_deref().unwrap_or_default());
+            substitutions.push(Substitution {
+                path: path.to_vec(),
+                variable: name.to_owned(),
+                value,
+                from_default,
+            });
+            rest = &rest[i + 3 + len..];
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+
+    /// Expands the references of the string keys of `m`
+    fn expand_keys(&self, m: &mut Mapping, path: &[String], substitutions: &mut Vec<Substitution>) -> Result<()> {
+        if !m.keys().any(|k| k.as_str().is_some_and(|k| k.contains("${"))) {
+            return Ok(());
+        }
+        let mut expanded = Mapping::with_capacity(m.len());
+        for (k, v) in std::mem::take(m) {
+            let k = match k {
+                Value::String(s) => {
+                    let mut key_path = path.to_vec();
+                    key_path.push(key_token(&Value::String(s.clone())));
+                    Value::String(self.expand(&s, &key_path, substitutions)?)
+                },
+                k => k,
+            };
+            expanded.insert(k, v);
+        }
+        *m = expanded;
+        Ok(())
+    }
+
+    /// Expands the references of `value` and returns the substitutions that were made
+    ///
+    /// On error, `value` is left unmodified.
+    pub fn apply(&self, value: &mut Value) -> Result<Vec<Substitution>> {
+        let mut expanded = value.clone();
+        let mut substitutions = vec![];
+        let mut result = Ok(());
+        path::walk_mut(&mut expanded, |path, value| {
+            let value = match value {
+                Value::Tagged(t) => &mut t.value,
+                v => v,
+            };
+            result = match value {
+                Value::String(s) => self.expand(s, path, &mut substitutions)
+                    .map(|expanded| *s = expanded),
+                Value::Mapping(m) if self.keys => self.expand_keys(m, path, &mut substitutions),
+                _ => Ok(()),
+            };
+            match result {
+                Ok(()) => VisitAction::Continue,
+                Err(_) => VisitAction::Stop,
+            }
+        });
+        result?;
+        *value = expanded;
+        Ok(substitutions)
+    }
+}
+
+/// Expands references to environment variables inside string scalars
+///
+/// This is the same as `Interpolator::new().apply(value)`.
+pub fn interpolate_env(value: &mut Value) -> Result<Vec<Substitution>> {
+    Interpolator::new().apply(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yaml;
+    use pretty_assertions::assert_eq;
+
+    fn interpolator() -> Interpolator {
+        Interpolator::new()
+            .env(false)
+            .var("HOST", "example.org")
+            .var("EMPTY", "")
+    }
+
+    #[test]
+    fn expansion() {
+        let mut v = yaml(r#"
+a: "${HOST}:${PORT:-80}"
+b: ["x${HOST}y", !tag "${EMPTY:-default}", "${EMPTY}|${MISSING}"]
+c: "${} ${not valid} ${HOST $$${HOST} $${HOST}"
+${HOST}: 1
+"#);
+        let substitutions = interpolator().apply(&mut v).unwrap();
+        assert_eq!(v, yaml(r#"
+a: "example.org:80"
+b: ["xexample.orgy", !tag "default", "|"]
+c: "${} ${not valid} ${HOST $${HOST} ${HOST}"
+${HOST}: 1
+"#));
+        assert_eq!(substitutions.iter().map(|s| s.variable.as_str()).collect::<Vec<_>>(), ["HOST", "PORT", "HOST", "EMPTY", "EMPTY", "MISSING"]);
+        assert_eq!(substitutions[5], Substitution {
+            path: vec!["b".into(), "2".into()],
+            variable: "MISSING".into(),
+            value: None,
+            from_default: false,
+        });
+        assert!(substitutions[3].from_default && !substitutions[4].from_default);
+    }
+
+    #[test]
+    fn keys_and_strict() {
+        let mut v = yaml("{servers: {'${HOST}': {port: '${PORT:-80}'}}}");
+        let substitutions = interpolator().keys(true).apply(&mut v).unwrap();
+        assert_eq!(v, yaml("{servers: {example.org: {port: '80'}}}"));
+        assert_eq!(substitutions[0].path, ["servers", "${HOST}"]);
+        assert_eq!(substitutions[1].path, ["servers", "example.org", "port"]);
+
+        let original = yaml("{a: '${HOST}', b: {c: '${MISSING}'}}");
+        let mut v = original.clone();
+        let err = interpolator().strict(true).apply(&mut v).unwrap_err();
+        assert_eq!(err.to_string(), "impossible to interpolate variables: undefined variable 'MISSING' in 'b.c'");
+        assert_eq!(v, original);
+    }
+}
@@ -46,10 +46,51 @@
 mod error;
 mod restructure;
 mod merge;
+mod diff;
+mod de;
+mod reflect;
+mod width;
+mod migrate;
+mod interpolate;
+mod include;
+mod anchors;
+#[cfg(feature = "spans")]
+mod events;
+mod hash;
+mod stats;
+mod schema;
+#[cfg(feature = "template")]
+mod template;
 
 pub mod document;
+pub mod patch;
+pub mod path;
+pub mod query;
+#[cfg(feature = "spans")]
+pub mod span;
+#[cfg(feature = "edit")]
+pub mod edit;
 
 pub use error::{Result, Error};
 pub use restructure::Restructurer;
 pub use document::Documenter;
-pub use merge::merge;
+pub use anchors::to_string_with_anchors;
+pub use hash::hash_value;
+pub use schema::{to_json_schema, SchemaOptions};
+pub use stats::{stats, Stats};
+pub use include::Includer;
+pub use interpolate::{interpolate_env, Interpolator, Substitution};
+pub use migrate::{MigrationReport, Migrations, Migrator, Notice, RenameConflict};
+pub use diff::{canonicalize, diff, semantically_equal, CanonOptions, DiffValue, EqualityOptions};
+pub use merge::{apply_defaults, apply_profile, expand_merge_keys, merge, merge3, merge_at, merge_files, merge_into, merge_str, merged, par_merge_all};
+#[cfg(feature = "spans")]
+pub use merge::merged_with_anchors;
+pub use merge::{shape_violations, unknown_keys, Merger};
+pub use merge::{MergeChange, MergeConflict, MergeReport, MergeStats, PriorityMerge, ShapeViolation, ThreeWayMerge};
+pub use merge::{KeyOrder, NullHandling, Precedence, Protection, SequenceStrategy, TypeConflict};
+
+/// Parses a YAML value in tests
+#[cfg(test)]
+pub(crate) fn yaml(s: &str) -> serde_yaml::Value {
+    serde_yaml::from_str(s).unwrap()
+}
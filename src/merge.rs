@@ -2,36 +2,2061 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use crate::error::{Result, Error};
+use crate::error::{check_depth, Result, Error, DEFAULT_MAX_DEPTH};
+use crate::document::ValueType;
+#[cfg(feature = "spans")]
+use crate::events::{self, Anchoring};
+use crate::include;
+use crate::patch::{self, PatchOp};
+use crate::path::{self, key_token};
 
-use serde_yaml::Value;
+use serde::de::DeserializeOwned;
+use serde_yaml::{Mapping, Value};
 
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-/// Merge two YAML representations into another
+
+/// How sequences of the overlay are combined with sequences of the base
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum SequenceStrategy {
+    /// The overlay's sequence replaces the base's one (default)
+    #[default]
+    Replace,
+    /// The overlay's items are added after the base's ones
+    Append,
+    /// The overlay's items are added before the base's ones
+    Prepend,
+    /// The overlay's items are added after the base's ones, unless they are already present
+    /// (see `Merger::union_key` to compare only part of the items)
+    Union,
+    /// Items at the same index are merged, extra items of the overlay are added at the end
+    MergeByIndex,
+    /// Mappings with the same value for this key (e.g. `name` or `id`) are merged, other
+    /// items of the overlay are added at the end
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use yaml_extras::{Merger, SequenceStrategy};
+    ///
+    /// let mut base: serde_yaml::Value = serde_yaml::from_str("
+    /// services:
+    ///   - {name: web, image: nginx, port: 80}
+    ///   - {name: db, image: postgres}")?;
+    /// let overlay: serde_yaml::Value = serde_yaml::from_str("
+    /// services:
+    ///   - {name: web, port: 8080}
+    ///   - {name: cache, image: redis}")?;
+    /// Merger::new()
+    ///     .sequences(SequenceStrategy::MergeByKey("name".into()))
+    ///     .apply(&mut base, &overlay)?;
+    ///
+    /// let expected: serde_yaml::Value = serde_yaml::from_str("
+    /// services:
+    ///   - {name: web, image: nginx, port: 8080}
+    ///   - {name: db, image: postgres}
+    ///   - {name: cache, image: redis}")?;
+    /// assert_eq!(base, expected);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    MergeByKey(String),
+}
+
+/// What a null value in the overlay does to the base
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NullHandling {
+    /// Null is a value like the others and replaces the base's one (default)
+    #[default]
+    Overwrite,
+    /// Null values of the overlay are skipped, leaving the base untouched
+    Ignore,
+    /// Null values of the overlay remove the key from the base
+    Delete,
+}
+
+/// Which document wins when both have a value at the same path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Precedence {
+    /// The overlay's values replace the base's ones (default)
+    #[default]
+    OverlayWins,
+    /// The base's values are kept, only missing keys are added from the overlay
+    BaseWins,
+}
+
+/// What to do when the base and the overlay have different kinds of values (mapping,
+/// sequence or scalar) at the same path
 ///
-pub fn merge(value: &mut Value, other: &Value) -> Result<()> {
-    if let (Some(v), Some(o))  = (value.as_mapping_mut(), other.as_mapping()) {
-        for (o_key, o_val) in o.iter() {
-            if !o_val.is_mapping() {
-                v.insert(o_key.clone(), o_val.clone());
+/// The default, `OverlayWins`, is what `merge` always did when the overlay had a scalar
+/// or a sequence. Before 0.3, a mapping in the overlay over another kind of value was an
+/// error; use `Error` to keep rejecting such conflicts (all of them, in both directions).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TypeConflict {
+    /// Returns an error
+    Error,
+    /// The overlay's value replaces the base's one (default)
+    #[default]
+    OverlayWins,
+    /// The base's value is kept
+    BaseWins,
+}
+
+/// Where keys of the overlay that are missing in the base are added. Keys of the base always
+/// keep their order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyOrder {
+    /// New keys are added at the end of the mapping, in the overlay's order (default)
+    #[default]
+    Append,
+    /// New keys are added after the key preceding them in the overlay that is also in the
+    /// base, or at the start if there is none
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use yaml_extras::{KeyOrder, Merger};
+    ///
+    /// let mut base: serde_yaml::Value = serde_yaml::from_str("{host: localhost, debug: false}")?;
+    /// let overlay: serde_yaml::Value = serde_yaml::from_str("{host: example.org, port: 80}")?;
+    /// Merger::new()
+    ///     .key_order(KeyOrder::Relative)
+    ///     .apply(&mut base, &overlay)?;
+    /// assert_eq!(serde_yaml::to_string(&base)?, "host: example.org\nport: 80\ndebug: false\n");
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    Relative,
+}
+
+/// What happens to changes of the overlay to protected paths, see `Merger::deny` and
+/// `Merger::allow`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Protection {
+    /// Returns an error (default)
+    #[default]
+    Error,
+    /// The change is skipped, and listed in `MergeReport::rejected`
+    Ignore,
+}
+
+/// Whether a path can be modified by the overlay
+#[derive(Debug, PartialEq, Eq)]
+enum Access {
+    Free,
+    /// Only protected paths nested under this one, which can be merged but not replaced
+    Nested,
+    Denied,
+}
+
+/// Whether `rule` matches `path` or one of its parents, `*` matching any key
+fn covers(rule: &[String], path: &[String]) -> bool {
+    rule.len() <= path.len() && rule.iter().zip(path).all(|(r, p)| r == "*" || r == p)
+}
+
+/// Whether `rule` matches a path nested under `path`
+fn leads_to(rule: &[String], path: &[String]) -> bool {
+    path.len() < rule.len() && rule.iter().zip(path).all(|(r, p)| r == "*" || r == p)
+}
+
+/// A short name of the type of a value, for errors
+fn type_name(v: &Value) -> String {
+    match v {
+        Value::Null => "null".to_owned(),
+        Value::Bool(_) => "boolean".to_owned(),
+        Value::Number(_) => "number".to_owned(),
+        Value::String(_) => "string".to_owned(),
+        Value::Sequence(_) => "sequence".to_owned(),
+        Value::Mapping(_) => "mapping".to_owned(),
+        Value::Tagged(t) => format!("value tagged {}", t.tag),
+    }
+}
+
+/// The kind of a value, for type conflicts. Nulls never conflict.
+fn kind(v: &Value) -> Option<Cow<'static, str>> {
+    match v {
+        Value::Null => None,
+        Value::Mapping(_) => Some("mapping".into()),
+        Value::Sequence(_) => Some("sequence".into()),
+        Value::Tagged(t) => Some(format!("value tagged {}", t.tag).into()),
+        _ => Some("scalar".into()),
+    }
+}
+
+/// Whether a value can be given to a combinator
+fn is_scalar(v: &Value) -> bool {
+    matches!(v, Value::Bool(_) | Value::Number(_) | Value::String(_))
+}
+
+type CombineFn<'m> = Arc<dyn Fn(&Value, &Value) -> Value + Send + Sync + 'm>;
+type KeyFn<'m> = Arc<dyn Fn(&Value) -> Value + Send + Sync + 'm>;
+type NodeFn<'m> = Arc<dyn Fn(&[String], Option<&Value>, &Value) + Send + Sync + 'm>;
+
+/// Merge YAML representations, deep-merging mappings.
+///
+/// # Example
+///
+/// ```
+/// use yaml_extras::{Merger, NullHandling, SequenceStrategy};
+///
+/// let mut base: serde_yaml::Value = serde_yaml::from_str("
+/// server: {host: localhost, port: 8080}
+/// plugins: [auth]
+/// log: debug")?;
+/// let overlay: serde_yaml::Value = serde_yaml::from_str("
+/// server: {port: 80}
+/// plugins: [cache]
+/// log: null")?;
+///
+/// Merger::new()
+///     .sequences(SequenceStrategy::Append)
+///     .nulls(NullHandling::Delete)
+///     .apply(&mut base, &overlay)?;
+///
+/// let expected: serde_yaml::Value = serde_yaml::from_str("
+/// server: {host: localhost, port: 80}
+/// plugins: [auth, cache]")?;
+/// assert_eq!(base, expected);
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+///
+/// Keys of the base keep their order, even when their value is replaced; keys that are
+/// missing in the base are added at the end, unless `KeyOrder::Relative` is used.
+///
+/// Like `Restructurer`, this struct mainly stores the options. Like `Documenter`, it owns its
+/// closures, which must be `Send + Sync`.
+#[derive(Clone)]
+pub struct Merger<'m> {
+    sequences: SequenceStrategy,
+    nulls: NullHandling,
+    precedence: Precedence,
+    shallow: bool,
+    conflicts: TypeConflict,
+    delete_tag: String,
+    max_depth: usize,
+    key_order: KeyOrder,
+    allowed: Vec<Vec<String>>,
+    denied: Vec<Vec<String>>,
+    protection: Protection,
+    strict: bool,
+    case_insensitive: bool,
+    include_key: String,
+    max_include_depth: usize,
+    profiles_key: String,
+    combinators: BTreeMap<Vec<String>, CombineFn<'m>>,
+    union_key: Option<KeyFn<'m>>,
+    before_node: Option<NodeFn<'m>>,
+    after_node: Option<NodeFn<'m>>,
+}
+
+impl Default for Merger<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Merger<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Merger")
+            .field("sequences", &self.sequences)
+            .field("nulls", &self.nulls)
+            .field("precedence", &self.precedence)
+            .field("shallow", &self.shallow)
+            .field("conflicts", &self.conflicts)
+            .field("delete_tag", &self.delete_tag)
+            .field("max_depth", &self.max_depth)
+            .field("key_order", &self.key_order)
+            .field("allowed", &self.allowed)
+            .field("denied", &self.denied)
+            .field("protection", &self.protection)
+            .field("strict", &self.strict)
+            .field("case_insensitive", &self.case_insensitive)
+            .field("include_key", &self.include_key)
+            .field("max_include_depth", &self.max_include_depth)
+            .field("profiles_key", &self.profiles_key)
+            .field("combinators", &self.combinators.keys().collect::<Vec<_>>())
+            .field("union_key", &self.union_key.is_some())
+            .field("before_node", &self.before_node.is_some())
+            .field("after_node", &self.after_node.is_some())
+            .finish()
+    }
+}
+
+impl<'m> Merger<'m> {
+    /// Creates a new Merger with default values, which behaves like `merge`
+    pub fn new() -> Self {
+        Merger {
+            sequences: SequenceStrategy::Replace,
+            nulls: NullHandling::Overwrite,
+            precedence: Precedence::OverlayWins,
+            shallow: false,
+            conflicts: TypeConflict::OverlayWins,
+            delete_tag: "delete".to_owned(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            key_order: KeyOrder::Append,
+            allowed: vec![],
+            denied: vec![],
+            protection: Protection::Error,
+            strict: false,
+            case_insensitive: false,
+            include_key: "extends".to_owned(),
+            max_include_depth: 16,
+            profiles_key: "profiles".to_owned(),
+            combinators: BTreeMap::new(),
+            union_key: None,
+            before_node: None,
+            after_node: None,
+        }
+    }
+
+    /// Sets how sequences present in both documents are merged (default is
+    /// `SequenceStrategy::Replace`)
+    pub fn sequences(mut self, strategy: SequenceStrategy) -> Self {
+        self.sequences = strategy;
+        self
+    }
+
+    /// Sets how null values of the overlay are handled (default is `NullHandling::Overwrite`)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use yaml_extras::{Merger, NullHandling};
+    ///
+    /// let mut base: serde_yaml::Value = serde_yaml::from_str("{a: 1, b: 2}")?;
+    /// let overlay: serde_yaml::Value = serde_yaml::from_str("{a: null, b: 3}")?;
+    /// Merger::new()
+    ///     .nulls(NullHandling::Ignore)
+    ///     .apply(&mut base, &overlay)?;
+    /// assert_eq!(base, serde_yaml::from_str::<serde_yaml::Value>("{a: 1, b: 3}")?);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn nulls(mut self, nulls: NullHandling) -> Self {
+        self.nulls = nulls;
+        self
+    }
+
+    /// Sets which document wins when both have a value at the same path (default is
+    /// `Precedence::OverlayWins`)
+    ///
+    /// With `Precedence::BaseWins`, values of the base are never modified, so this can be
+    /// used to fill in defaults for missing keys; `NullHandling::Delete` then behaves like
+    /// `NullHandling::Ignore`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use yaml_extras::{Merger, Precedence};
+    ///
+    /// let mut config: serde_yaml::Value = serde_yaml::from_str("{server: {port: 80}}")?;
+    /// let defaults: serde_yaml::Value = serde_yaml::from_str("{server: {host: localhost, port: 8080}}")?;
+    /// Merger::new()
+    ///     .precedence(Precedence::BaseWins)
+    ///     .apply(&mut config, &defaults)?;
+    /// assert_eq!(config, serde_yaml::from_str::<serde_yaml::Value>("{server: {port: 80, host: localhost}}")?);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn precedence(mut self, precedence: Precedence) -> Self {
+        self.precedence = precedence;
+        self
+    }
+
+    /// Set to `true` to only merge the top-level keys: mappings nested in the overlay then
+    /// replace the base's ones instead of being merged with them (default is `false`)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut base: serde_yaml::Value = serde_yaml::from_str("{db: {host: localhost, port: 5432}, debug: true}")?;
+    /// let overlay: serde_yaml::Value = serde_yaml::from_str("{db: {socket: /run/db.sock}}")?;
+    /// yaml_extras::Merger::new()
+    ///     .shallow(true)
+    ///     .apply(&mut base, &overlay)?;
+    /// assert_eq!(base, serde_yaml::from_str::<serde_yaml::Value>("{db: {socket: /run/db.sock}, debug: true}")?);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn shallow(mut self, shallow: bool) -> Self {
+        self.shallow = shallow;
+        self
+    }
+
+    /// Sets what happens when the base and the overlay have different kinds of values at the
+    /// same path, e.g. a mapping and a string (default is `TypeConflict::OverlayWins`)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use yaml_extras::{Merger, TypeConflict};
+    ///
+    /// let mut base: serde_yaml::Value = serde_yaml::from_str("{db: {host: localhost}}")?;
+    /// let overlay: serde_yaml::Value = serde_yaml::from_str("{db: postgres://localhost}")?;
+    /// let res = Merger::new()
+    ///     .type_conflicts(TypeConflict::Error)
+    ///     .apply(&mut base, &overlay);
+    /// assert!(res.is_err());
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn type_conflicts(mut self, policy: TypeConflict) -> Self {
+        self.conflicts = policy;
+        self
+    }
+
+    /// Sets the tag marking keys of the overlay that should be removed from the base, without
+    /// the `!` (default is `delete`). An empty string disables this.
+    ///
+    /// Unlike `NullHandling::Delete`, this allows to also set some values to null. Like it,
+    /// this does nothing with `Precedence::BaseWins`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut base: serde_yaml::Value = serde_yaml::from_str("{proxy: {host: example.org}, timeout: 30}")?;
+    /// let overlay: serde_yaml::Value = serde_yaml::from_str("{proxy: !delete , timeout: null}")?;
+    /// yaml_extras::Merger::new()
+    ///     .apply(&mut base, &overlay)?;
+    /// assert_eq!(base, serde_yaml::from_str::<serde_yaml::Value>("{timeout: null}")?);
+    ///
+    /// let overlay: serde_yaml::Value = serde_yaml::from_str("{timeout: !unset }")?;
+    /// yaml_extras::Merger::new()
+    ///     .delete_tag("unset")
+    ///     .apply(&mut base, &overlay)?;
+    /// assert_eq!(base, serde_yaml::from_str::<serde_yaml::Value>("{}")?);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn delete_tag<S: Into<String>>(mut self, tag: S) -> Self {
+        self.delete_tag = tag.into();
+        self
+    }
+
+    /// Sets the maximum depth of the merged values, beyond which `Error::DepthExceeded` is
+    /// returned instead of risking a stack overflow (default is 128)
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Sets where keys missing in the base are added (default is `KeyOrder::Append`)
+    pub fn key_order(mut self, order: KeyOrder) -> Self {
+        self.key_order = order;
+        self
+    }
+
+    /// Set to `true` to return an error listing the keys of the overlay that are missing in
+    /// the base, e.g. because of a typo (default is `false`)
+    ///
+    /// Keys marked for deletion are accepted. To check the overlay against another document,
+    /// such as the defaults, see `unknown_keys`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut base: serde_yaml::Value = serde_yaml::from_str("{compiler: {command: cargo}}")?;
+    /// let overlay: serde_yaml::Value = serde_yaml::from_str("{compilr: {command: make}, compiler: {comand: make}}")?;
+    /// let err = yaml_extras::Merger::new()
+    ///     .strict(true)
+    ///     .apply(&mut base, &overlay)
+    ///     .unwrap_err();
+    /// assert_eq!(err.to_string(), "impossible to mege YAML values: unknown keys in the overlay: \
+    ///                              compilr, compiler.comand");
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets the top-level key listing the files that a file loaded by `load` extends (default
+    /// is `extends`). An empty string disables this.
+    pub fn include_key<S: Into<String>>(mut self, key: S) -> Self {
+        self.include_key = key.into();
+        self
+    }
+
+    /// Sets the maximum number of nested files extended by a file loaded by `load` (default
+    /// is 16)
+    pub fn max_include_depth(mut self, depth: usize) -> Self {
+        self.max_include_depth = depth;
+        self
+    }
+
+    /// Sets the top-level key containing the profiles used by `apply_profile` (default is
+    /// `profiles`)
+    pub fn profiles_key<S: Into<String>>(mut self, key: S) -> Self {
+        self.profiles_key = key.into();
+        self
+    }
+
+    /// Set to `true` to match string keys of the overlay with the keys of the base that only
+    /// differ by their case, keeping the base's spelling (default is `false`)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut base: serde_yaml::Value = serde_yaml::from_str("{Path: /usr/bin, Server: {Port: 80}}")?;
+    /// let overlay: serde_yaml::Value = serde_yaml::from_str("{PATH: /opt/bin, server: {port: 8080}}")?;
+    /// yaml_extras::Merger::new()
+    ///     .case_insensitive(true)
+    ///     .apply(&mut base, &overlay)?;
+    /// assert_eq!(base, serde_yaml::from_str::<serde_yaml::Value>("{Path: /opt/bin, Server: {Port: 8080}}")?);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Allows the overlay to modify `path` (see the `path` module, `*` matching any key) and
+    /// the values nested under it. Once a path is allowed, all the paths that aren't are
+    /// protected.
+    ///
+    /// Mappings leading to allowed paths can still be merged, but not replaced or removed.
+    /// Overlay values that are the same as the base's ones are never rejected.
+    pub fn allow<S: AsRef<str>>(mut self, path: S) -> Self {
+        self.allowed.push(path::tokens(path.as_ref()));
+        self
+    }
+
+    /// Protects `path` (see the `path` module, `*` matching any key) and the values nested
+    /// under it from modifications by the overlay, even if it is allowed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use yaml_extras::{Merger, Protection};
+    ///
+    /// let mut base: serde_yaml::Value = serde_yaml::from_str("{security: {tls: true}, name: main}")?;
+    /// let tenant: serde_yaml::Value = serde_yaml::from_str("{security: {tls: false}, name: tenant}")?;
+    /// let merger = Merger::new().deny("security");
+    /// assert!(merger.apply(&mut base.clone(), &tenant).is_err());
+    ///
+    /// let report = merger.protection(Protection::Ignore)
+    ///     .apply_with_report(&mut base, &tenant)?;
+    /// assert_eq!(base, serde_yaml::from_str::<serde_yaml::Value>("{security: {tls: true}, name: tenant}")?);
+    /// assert_eq!(report.rejected[0].path, ["security"]);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn deny<S: AsRef<str>>(mut self, path: S) -> Self {
+        self.denied.push(path::tokens(path.as_ref()));
+        self
+    }
+
+    /// Sets what happens to changes of protected paths (default is `Protection::Error`)
+    pub fn protection(mut self, protection: Protection) -> Self {
+        self.protection = protection;
+        self
+    }
+
+    /// Whether the overlay can modify `path`
+    fn access(&self, path: &[String]) -> Access {
+        if self.denied.iter().any(|r| covers(r, path)) {
+            Access::Denied
+        } else if !self.allowed.is_empty() && !self.allowed.iter().any(|r| covers(r, path)) {
+            if self.allowed.iter().any(|r| leads_to(r, path)) {
+                Access::Nested
             } else {
-                /// If the contained hashmap  is already present, merge the hashmap
-                if v.contains_key(o_key) {
-                    merge(v.get_mut(o_key).unwrap(), o_val)?;
-                } else {
-                    v.insert(o_key.clone(), o_val.clone());
+                Access::Denied
+            }
+        } else if self.denied.iter().any(|r| leads_to(r, path)) {
+            Access::Nested
+        } else {
+            Access::Free
+        }
+    }
+
+    /// Handles a change to a protected path, `new` being `None` for deletions
+    fn reject(&self, old: Option<&Value>, new: Option<&Value>, state: &mut State) -> Result<()> {
+        if old == new {
+            return Ok(());
+        }
+        match self.protection {
+            Protection::Error => Err(Error::Merge(format!("'{}' is write-protected", state.path.join(".")))),
+            Protection::Ignore => {
+                state.reject(old, new);
+                Ok(())
+            },
+        }
+    }
+
+    /// Sets how two scalars found at `path` (see the `path` module) are combined, instead of
+    /// the overlay's one replacing the base's one.
+    ///
+    /// The closure receives the base's value then the overlay's one, and is only called when
+    /// both are booleans, numbers or strings; it also applies with `Precedence::BaseWins`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use serde_yaml::Value;
+    ///
+    /// let mut base: serde_yaml::Value = serde_yaml::from_str("{env: {PATH: /usr/bin}, limits: {memory: 512}}")?;
+    /// let overlay: serde_yaml::Value = serde_yaml::from_str("{env: {PATH: /opt/bin}, limits: {memory: 256}}")?;
+    /// yaml_extras::Merger::new()
+    ///     .combine("env.PATH", |base, overlay| match (base, overlay) {
+    ///         (Value::String(b), Value::String(o)) => format!("{b}:{o}").into(),
+    ///         (_, o) => o.clone(),
+    ///     })
+    ///     .combine("limits.memory", |base, overlay| match (base.as_u64(), overlay.as_u64()) {
+    ///         (Some(b), Some(o)) => b.max(o).into(),
+    ///         _ => overlay.clone(),
+    ///     })
+    ///     .apply(&mut base, &overlay)?;
+    /// assert_eq!(base, serde_yaml::from_str::<Value>("{env: {PATH: /usr/bin:/opt/bin}, limits: {memory: 512}}")?);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn combine<S, F>(mut self, path: S, f: F) -> Self
+    where S: AsRef<str>,
+          F: Fn(&Value, &Value) -> Value + Send + Sync + 'm {
+        self.combinators.insert(path::tokens(path.as_ref()), Arc::new(f));
+        self
+    }
+
+    /// Sets the closure extracting the key used to compare items with
+    /// `SequenceStrategy::Union`, instead of comparing whole items: an item of the overlay
+    /// is then skipped if an item with the same key is already present.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use yaml_extras::{Merger, SequenceStrategy};
+    ///
+    /// let mut base: serde_yaml::Value = serde_yaml::from_str("
+    /// users: [{name: alice, note: admin}]")?;
+    /// let overlay: serde_yaml::Value = serde_yaml::from_str("
+    /// users: [{name: alice, note: from LDAP}, {name: bob}]")?;
+    /// Merger::new()
+    ///     .sequences(SequenceStrategy::Union)
+    ///     .union_key(|item| item["name"].clone())
+    ///     .apply(&mut base, &overlay)?;
+    /// assert_eq!(base, serde_yaml::from_str::<serde_yaml::Value>("
+    /// users: [{name: alice, note: admin}, {name: bob}]")?);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn union_key<F>(mut self, f: F) -> Self
+    where F: Fn(&Value) -> Value + Send + Sync + 'm {
+        self.union_key = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets a closure called before merging each key of the overlay, with its path, the
+    /// base's value if any and the overlay's value, e.g. for logging or tracing
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::Mutex;
+    ///
+    /// let mut base: serde_yaml::Value = serde_yaml::from_str("{db: {port: 5432}}")?;
+    /// let overlay: serde_yaml::Value = serde_yaml::from_str("{db: {port: 5433}, debug: true}")?;
+    /// let log = Mutex::new(vec![]);
+    /// yaml_extras::Merger::new()
+    ///     .before_node(|path, _, _| log.lock().unwrap().push(format!("> {}", path.join("."))))
+    ///     .after_node(|path, v, _| log.lock().unwrap().push(format!("< {} = {:?}", path.join("."), v.and_then(|v| v.as_u64()))))
+    ///     .apply(&mut base, &overlay)?;
+    /// assert_eq!(log.into_inner().unwrap(), ["> db", "> db.port", "< db.port = Some(5433)",
+    ///                                        "< db = None", "> debug", "< debug = None"]);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn before_node<F>(mut self, f: F) -> Self
+    where F: Fn(&[String], Option<&Value>, &Value) + Send + Sync + 'm {
+        self.before_node = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets a closure called after merging each key of the overlay, even if it failed, with
+    /// its path, the resulting value if any and the overlay's value
+    pub fn after_node<F>(mut self, f: F) -> Self
+    where F: Fn(&[String], Option<&Value>, &Value) + Send + Sync + 'm {
+        self.after_node = Some(Arc::new(f));
+        self
+    }
+
+    /// Whether a value of the overlay is marked with the deletion tag
+    fn is_deletion(&self, v: &Value) -> bool {
+        match v {
+            Value::Tagged(t) if !self.delete_tag.is_empty() => {
+                t.tag.to_string().trim_start_matches('!') == self.delete_tag
+            },
+            _ => false,
+        }
+    }
+
+    /// The key of `v` matching `o_key`, which is `o_key` itself unless keys are matched
+    /// case-insensitively
+    fn base_key<'k>(&self, v: &Mapping, o_key: &'k Value) -> Cow<'k, Value> {
+        match o_key {
+            Value::String(o) if self.case_insensitive && !v.contains_key(o_key) => {
+                let o = o.to_lowercase();
+                v.keys()
+                    .find(|k| k.as_str().is_some_and(|k| k.to_lowercase() == o))
+                    .map_or(Cow::Borrowed(o_key), |k| Cow::Owned(k.clone()))
+            },
+            _ => Cow::Borrowed(o_key),
+        }
+    }
+
+    /// The keys of `overlay` missing in `reference`, see `unknown_keys`
+    fn unknown_keys(&self, reference: &Value, overlay: &Value) -> Vec<Vec<String>> {
+        let mut unknown = vec![];
+        self.find_unknown_keys(reference, overlay, &mut vec![], &mut unknown);
+        unknown
+    }
+
+    fn find_unknown_keys(&self, reference: &Value, overlay: &Value, path: &mut Vec<String>, unknown: &mut Vec<Vec<String>>) {
+        match (reference, overlay) {
+            (Value::Mapping(r), Value::Mapping(o)) => {
+                for (k, o_val) in o {
+                    if self.is_deletion(o_val) {
+                        continue;
+                    }
+                    let k = self.base_key(r, k);
+                    path.push(key_token(&k));
+                    match r.get(&*k) {
+                        Some(r_val) => self.find_unknown_keys(r_val, o_val, path, unknown),
+                        None => unknown.push(path.clone()),
+                    }
+                    path.pop();
+                }
+            },
+            (Value::Tagged(r), Value::Tagged(o)) if r.tag == o.tag => {
+                self.find_unknown_keys(&r.value, &o.value, path, unknown);
+            },
+            _ => (),
+        }
+    }
+
+    /// A copy of a value of the overlay without the keys marked for deletion
+    fn without_deletions(&self, v: &Value) -> Value {
+        match v {
+            Value::Mapping(m) => Value::Mapping(m.iter()
+                                                .filter(|(_, v)| !self.is_deletion(v))
+                                                .map(|(k, v)| (k.clone(), self.without_deletions(v)))
+                                                .collect()),
+            v => v.clone(),
+        }
+    }
+
+    /// Merges `other` into `value`. Both need to be mappings.
+    pub fn apply(&self, value: &mut Value, other: &Value) -> Result<()> {
+        self.apply_with_state(value, other, &mut State::default())
+    }
+
+    /// Same as `apply`, also returning the JSON patch that restores the previous value, e.g.
+    /// to undo the merge (see `patch::apply_with_inverse`)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut base: serde_yaml::Value = serde_yaml::from_str("{a: 1, b: {c: 2}}")?;
+    /// let original = base.clone();
+    /// let overlay: serde_yaml::Value = serde_yaml::from_str("{a: !delete , b: {c: 3, d: 4}}")?;
+    /// let undo = yaml_extras::Merger::new()
+    ///     .apply_with_inverse(&mut base, &overlay)?;
+    /// yaml_extras::patch::apply(&mut base, &undo)?;
+    /// assert_eq!(base, original);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn apply_with_inverse(&self, value: &mut Value, other: &Value) -> Result<Vec<PatchOp>> {
+        let old = value.clone();
+        self.apply(value, other)?;
+        Ok(patch::diff(value, &old))
+    }
+
+    /// Merges `other` at `path` (dotted or a JSON Pointer) inside `value`, creating the missing
+    /// mappings. An empty path is the same as `apply`.
+    ///
+    /// This behaves as if `other` was nested in mappings with the keys of `path`, so an
+    /// existing value at that path that is not a mapping is handled according to the
+    /// type conflict policy.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut config: serde_yaml::Value = serde_yaml::from_str("{plugins: {auth: {enabled: false}}}")?;
+    /// let fragment: serde_yaml::Value = serde_yaml::from_str("{enabled: true, ttl: 60}")?;
+    /// let merger = yaml_extras::Merger::new();
+    /// merger.apply_at(&mut config, "plugins.cache", &fragment)?;
+    /// merger.apply_at(&mut config, "plugins.auth", &fragment)?;
+    /// assert_eq!(config, serde_yaml::from_str::<serde_yaml::Value>("
+    /// plugins:
+    ///   auth: {enabled: true, ttl: 60}
+    ///   cache: {enabled: true, ttl: 60}")?);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn apply_at(&self, value: &mut Value, path: &str, other: &Value) -> Result<()> {
+        let nested = path::tokens(path)
+            .into_iter()
+            .rev()
+            .fold(other.clone(), |inner, key| {
+                let mut m = Mapping::new();
+                m.insert(key.into(), inner);
+                Value::Mapping(m)
+            });
+        self.apply(value, &nested)
+    }
+
+    /// Same as `apply`, returning the result instead of modifying `value`
+    pub fn merged(&self, value: &Value, other: &Value) -> Result<Value> {
+        let mut merged = value.clone();
+        self.apply(&mut merged, other)?;
+        Ok(merged)
+    }
+
+    /// Parses `s` as YAML and merges it into `value`
+    pub fn apply_str(&self, value: &mut Value, s: &str) -> Result<()> {
+        let other: Value = serde_yaml::from_str(s)?;
+        self.apply(value, &other)
+    }
+
+    /// Parses and merges two YAML documents, the overlay being able to use aliases of the
+    /// anchors defined in the base.
+    ///
+    /// Both documents are parsed separately, so errors are reported at their location in
+    /// their own source. Aliases of the overlay that aren't defined in it are resolved using
+    /// the base's anchors as they are in the base document, before merging. They can't be used
+    /// in mapping keys.
+    ///
+    /// This requires the `spans` feature.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let base = "
+    /// defaults: &defaults
+    ///   retries: 3
+    ///   timeout: 10
+    /// jobs:
+    ///   build: *defaults";
+    /// let overlay = "
+    /// jobs:
+    ///   deploy: *defaults";
+    /// let merged = yaml_extras::Merger::new()
+    ///     .merged_with_anchors(base, overlay)?;
+    /// assert_eq!(merged["jobs"]["deploy"]["retries"], 3);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    #[cfg(feature = "spans")]
+    pub fn merged_with_anchors(&self, base: &str, overlay: &str) -> Result<Value> {
+        let mut value: Value = serde_yaml::from_str(base)?;
+        let base_nodes = events::nodes(base)?;
+        let mut anchors: BTreeMap<&str, &Value> = BTreeMap::new();
+        for anchoring in &base_nodes.anchors {
+            if let Anchoring::Anchor { name, path: Some(path) } = anchoring {
+                if let Some(v) = path::get_tokens(&value, path) {
+                    anchors.insert(name, v);
+                }
+            }
+        }
+
+        // The aliases of the base's anchors are replaced with nulls of the same length, to
+        // keep the locations, then with the anchored values once parsed
+        let nodes = events::nodes(overlay)?;
+        let mut source = overlay.to_owned();
+        // The anchors of the overlay, with the path of their node
+        let mut defined: BTreeMap<&str, Option<&Vec<String>>> = BTreeMap::new();
+        // What an alias is replaced with: a value of the base, or the node of the overlay it
+        // refers to, which may contain aliases of the base
+        enum Target<'n> {
+            Value(Value),
+            Node(&'n [String]),
+        }
+        let mut resolved: Vec<(&[String], Target)> = vec![];
+        for anchoring in &nodes.anchors {
+            match anchoring {
+                Anchoring::Anchor { name, path } => {
+                    defined.insert(name, path.as_ref());
+                },
+                Anchoring::Alias { name, path, span } if !defined.contains_key(name.as_str()) => {
+                    let Some(v) = anchors.get(name.as_str()) else { continue };
+                    let Some(path) = path else {
+                        return Err(Error::Merge(format!("alias '*{name}' of the base can't be used in a key (line {}, column {})",
+                                                        span.start.line, span.start.column)));
+                    };
+                    let range = span.start.index..span.end.index;
+                    let len = source[range.clone()].len();
+                    source.replace_range(range, &format!("{:<len$}", "~"));
+                    resolved.push((path, Target::Value((*v).clone())));
+                },
+                Anchoring::Alias { name, path: Some(path), .. } => {
+                    if let Some(Some(target)) = defined.get(name.as_str()) {
+                        resolved.push((path, Target::Node(target)));
+                    }
+                },
+                Anchoring::Alias { .. } => (),
+            }
+        }
+        let mut other: Value = serde_yaml::from_str(&source)?;
+        for (path, v) in resolved {
+            let v = match v {
+                Target::Value(v) => v,
+                Target::Node(target) => match path::get_tokens(&other, target) {
+                    Some(v) => v.clone(),
+                    None => continue,
+                },
+            };
+            if let Some(alias) = path::get_tokens_mut(&mut other, path) {
+                *alias = v;
+            }
+        }
+        self.apply(&mut value, &other)?;
+        Ok(value)
+    }
+
+    /// Reads YAML files and merges them into `value`, in order
+    pub fn apply_files<I, P>(&self, value: &mut Value, paths: I) -> Result<()>
+    where I: IntoIterator<Item = P>,
+          P: AsRef<Path> {
+        for path in paths {
+            let path = path.as_ref();
+            let s = std::fs::read_to_string(path)
+                .map_err(|source| Error::Io { path: path.display().to_string(), source })?;
+            self.apply_str(value, &s)?;
+        }
+        Ok(())
+    }
+
+    /// Merges `overlays` in order into `value`, like calling `apply` for each of them. If an
+    /// error occurs, `value` is left unchanged.
+    pub fn apply_all(&self, value: &mut Value, overlays: &[Value]) -> Result<()> {
+        let mut merged = value.clone();
+        for overlay in overlays {
+            self.apply(&mut merged, overlay)?;
+        }
+        *value = merged;
+        Ok(())
+    }
+
+    /// Same as `apply_all`, but merges the different top-level keys in parallel.
+    ///
+    /// This uses scoped standard threads, up to the available parallelism, rather than a
+    /// thread pool like `rayon`, to keep the crate free of heavy dependencies; it is only
+    /// worth it for many or large overlays. The result is the same as with `apply_all`, but
+    /// the `before_node` and `after_node` closures may be called in a different order, and
+    /// the error returned may be another one if several overlays are invalid.
+    ///
+    /// The overlays are merged sequentially if the root of `value` or of an overlay is not a
+    /// mapping, with `KeyOrder::Relative` or `strict`, or if a top-level key that is deleted
+    /// comes back in a later overlay, since keys then depend on each other.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut base: serde_yaml::Value = serde_yaml::from_str("{a: {b: 1}, c: 2}")?;
+    /// let overlays: Vec<serde_yaml::Value> = (0..100)
+    ///     .map(|i| serde_yaml::from_str(&format!("{{a: {{tenant{i}: {i}}}, d{}: true}}", i % 10)))
+    ///     .collect::<Result<_, _>>()?;
+    /// yaml_extras::Merger::new()
+    ///     .par_apply_all(&mut base, &overlays)?;
+    /// assert_eq!(base["a"].as_mapping().unwrap().len(), 101);
+    /// assert_eq!(base.as_mapping().unwrap().len(), 12);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn par_apply_all(&self, value: &mut Value, overlays: &[Value]) -> Result<()> {
+        let Some(base) = value.as_mapping() else {
+            return self.apply_all(value, overlays);
+        };
+        if self.key_order == KeyOrder::Relative || self.strict || overlays.iter().any(|o| !o.is_mapping()) {
+            return self.apply_all(value, overlays);
+        }
+
+        // Each top-level key is merged separately, in the order they would be added. Keys
+        // of the overlays are matched like in `merge_mapping`, against the keys added so far.
+        // Each key is mapped to the index of its section
+        let mut keys: Mapping = base.keys().enumerate().map(|(i, k)| (k.clone(), i.into())).collect();
+        let mut sections: Vec<Vec<(usize, &Value, &Value)>> = vec![vec![]; keys.len()];
+        let mut removed: Vec<Value> = vec![];
+        for (i, overlay) in overlays.iter().filter_map(Value::as_mapping).enumerate() {
+            for (o_key, o_val) in overlay {
+                let key = self.base_key(&keys, o_key).into_owned();
+                let removal = self.is_deletion(o_val) || (o_val.is_null() && self.nulls == NullHandling::Delete);
+                if removed.contains(&key) && !removal {
+                    return self.apply_all(value, overlays);
+                }
+                if removal {
+                    removed.push(key.clone());
                 }
+                let index = match keys.get(&key).and_then(Value::as_u64) {
+                    Some(index) => index as usize,
+                    None => {
+                        keys.insert(key, sections.len().into());
+                        sections.push(vec![]);
+                        sections.len() - 1
+                    },
+                };
+                sections[index].push((i, o_key, o_val));
             }
         }
-        return Ok(())
+        let sections: Vec<_> = keys.keys().zip(sections).collect();
+        // Checked once here, since sections are merged with `merge_mapping`
+        check_depth(value, self.max_depth)?;
+        for overlay in overlays {
+            check_depth(overlay, self.max_depth)?;
+        }
+        let merge_section = |key: &Value, entries: &[(usize, &Value, &Value)]| -> Result<Mapping> {
+            let mut section = Mapping::new();
+            if let Some(v) = base.get(key) {
+                section.insert(key.clone(), v.clone());
+            }
+            for group in entries.chunk_by(|a, b| a.0 == b.0) {
+                let overlay: Mapping = group.iter()
+                    .map(|(_, k, v)| ((*k).clone(), (*v).clone()))
+                    .collect();
+                self.merge_mapping(&mut section, &overlay, &mut State::default())?;
+            }
+            Ok(section)
+        };
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = sections.len().div_ceil(threads).max(1);
+        let sections = std::thread::scope(|scope| {
+            let handles: Vec<_> = sections.chunks(chunk_size)
+                .map(|chunk| scope.spawn(|| chunk.iter()
+                                         .map(|(key, entries)| merge_section(key, entries))
+                                         .collect::<Result<Vec<_>>>()))
+                .collect();
+            handles.into_iter()
+                .map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                .collect::<Result<Vec<_>>>()
+        })?;
+        *value = Value::Mapping(sections.into_iter().flatten().flatten().collect());
+        Ok(())
+    }
+
+    /// Resolves the YAML merge keys (`<<`) of `value`: the mapping, or the mappings, given
+    /// to `<<` are merged together, the first ones having precedence, then the other keys of
+    /// the mapping are merged on top of them.
+    ///
+    /// By default, this is a deep merge; use `shallow(true)` to get the behaviour described by
+    /// the YAML merge key specification. On error, `value` is left unmodified.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut config: serde_yaml::Value = serde_yaml::from_str("
+    /// defaults: &defaults
+    ///   log: {level: info, file: app.log}
+    ///   port: 80
+    /// tls: &tls
+    ///   port: 443
+    /// prod:
+    ///   <<: [*tls, *defaults]
+    ///   log: {level: warn}
+    /// ")?;
+    /// yaml_extras::Merger::new()
+    ///     .expand_merge_keys(&mut config)?;
+    /// assert_eq!(config["prod"], serde_yaml::from_str::<serde_yaml::Value>("{log: {level: warn, file: app.log}, port: 443}")?);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn expand_merge_keys(&self, value: &mut Value) -> Result<()> {
+        let mut expanded = value.clone();
+        self.expand_merge_keys_at(&mut expanded, &mut vec![])?;
+        *value = expanded;
+        Ok(())
+    }
+
+    fn expand_merge_keys_at(&self, value: &mut Value, path: &mut Vec<String>) -> Result<()> {
+        match value {
+            Value::Tagged(t) => self.expand_merge_keys_at(&mut t.value, path),
+            Value::Sequence(s) => {
+                for (i, item) in s.iter_mut().enumerate() {
+                    path.push(i.to_string());
+                    self.expand_merge_keys_at(item, path)?;
+                    path.pop();
+                }
+                Ok(())
+            },
+            Value::Mapping(m) => {
+                for (k, v) in m.iter_mut() {
+                    path.push(key_token(k));
+                    self.expand_merge_keys_at(v, path)?;
+                    path.pop();
+                }
+                let Some(sources) = m.shift_remove("<<") else { return Ok(()) };
+                let sources = match sources {
+                    Value::Mapping(_) => vec![sources],
+                    Value::Sequence(list) if list.iter().all(Value::is_mapping) => list,
+                    _ => return Err(Error::Merge(format!("'<<' at '{}' should be a mapping or a list of mappings",
+                                                         path.join(".")))),
+                };
+                let mut merged = Value::Mapping(Mapping::new());
+                for source in sources.iter().rev() {
+                    self.apply(&mut merged, source)?;
+                }
+                self.apply(&mut merged, &Value::Mapping(std::mem::take(m)))?;
+                if let Value::Mapping(merged) = merged {
+                    *m = merged;
+                }
+                Ok(())
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Merges the profile `name`, found in the profiles section of `value` (see
+    /// `profiles_key`), into `value`, then removes the profiles section.
+    ///
+    /// If there is no such profile, an error is returned and `value` is left unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut config: serde_yaml::Value = serde_yaml::from_str("
+    /// log: debug
+    /// db: {host: localhost}
+    /// profiles:
+    ///   prod: {log: warn, db: {host: db.example.org}}
+    ///   test: {db: {host: 127.0.0.1}}")?;
+    /// yaml_extras::Merger::new()
+    ///     .apply_profile(&mut config, "prod")?;
+    /// assert_eq!(config, serde_yaml::from_str::<serde_yaml::Value>("{log: warn, db: {host: db.example.org}}")?);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn apply_profile(&self, value: &mut Value, name: &str) -> Result<()> {
+        let mut merged = value.clone();
+        let profile = merged.as_mapping_mut()
+            .and_then(|m| m.shift_remove(self.profiles_key.as_str()))
+            .and_then(|mut profiles| profiles.as_mapping_mut()?.shift_remove(name))
+            .ok_or_else(|| Error::Merge(format!("unknown profile '{name}'")))?;
+        self.apply(&mut merged, &profile)?;
+        *value = merged;
+        Ok(())
+    }
+
+    /// Merges documents by increasing priority, so that a document with a higher priority
+    /// wins over the ones with a lower priority. Documents with the same priority are merged
+    /// in the order they are given.
+    ///
+    /// The result tells which document (by its index in `sources`) provided the value of
+    /// each path, see `PriorityMerge::source`. If several documents give the same value,
+    /// this is the one with the highest priority.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let file: serde_yaml::Value = serde_yaml::from_str("{port: 80, host: localhost, debug: false}")?;
+    /// let env: serde_yaml::Value = serde_yaml::from_str("{port: 8080}")?;
+    /// let flags: serde_yaml::Value = serde_yaml::from_str("{port: 9000, debug: true}")?;
+    /// let merged = yaml_extras::Merger::new()
+    ///     .merge_by_priority([(10, flags), (0, file), (5, env)])?;
+    /// assert_eq!(merged.value, serde_yaml::from_str::<serde_yaml::Value>("{port: 9000, host: localhost, debug: true}")?);
+    /// assert_eq!(merged.source(&["port"]), Some(0));
+    /// assert_eq!(merged.source(&["host"]), Some(1));
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn merge_by_priority<P, I>(&self, sources: I) -> Result<PriorityMerge>
+    where P: Ord,
+          I: IntoIterator<Item = (P, Value)> {
+        let mut sources: Vec<(usize, P, Value)> = sources.into_iter()
+            .enumerate()
+            .map(|(i, (priority, value))| (i, priority, value))
+            .collect();
+        // Stable, so ties keep their order
+        sources.sort_by(|a, b| a.1.cmp(&b.1));
+        let mut merged = PriorityMerge {
+            value: Value::Mapping(Mapping::new()),
+            sources: BTreeMap::new(),
+        };
+        for (i, _, value) in sources {
+            let report = self.apply_with_report(&mut merged.value, &value)?;
+            for change in report.removed {
+                merged.sources.retain(|path, _| !path.starts_with(&change.path));
+            }
+            // A document that gives the same value as the current one also provides it
+            let same = report.unchanged.into_iter()
+                .filter(|change| path::get_tokens(&value, &change.path) == change.new.as_ref());
+            for change in report.added.into_iter().chain(report.overwritten).chain(same) {
+                merged.sources.retain(|path, _| !path.starts_with(&change.path));
+                merged.sources.insert(change.path, i);
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Reads a YAML file, merged on top of the files it extends.
+    ///
+    /// The files to extend are given by the include key (see `include_key`), either as a
+    /// path or a list of paths relative to the directory of the file. They are loaded the
+    /// same way, merged in order, then the file itself is merged on top of them, without the
+    /// include key. Cycles are reported as an error with the full include chain, as well as
+    /// more than `max_include_depth` nested files.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// // With base.yaml containing `{log: info, db: {host: localhost}}`, and
+    /// // prod.yaml containing `{extends: base.yaml, db: {host: db.example.org}}`
+    /// let config = yaml_extras::Merger::new().load("prod.yaml")?;
+    /// assert_eq!(config, serde_yaml::from_str::<serde_yaml::Value>("{log: info, db: {host: db.example.org}}")?);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<Value> {
+        self.load_with_chain(path.as_ref(), &mut vec![])
+    }
+
+    /// Loads a file, `chain` being the files that include it
+    fn load_with_chain(&self, path: &Path, chain: &mut Vec<(PathBuf, String)>) -> Result<Value> {
+        include::load_with_chain(path, chain, self.max_include_depth, Error::Merge, |mut value, chain| {
+            let includes = match value.as_mapping_mut() {
+                Some(m) if !self.include_key.is_empty() => m.shift_remove(self.include_key.as_str()),
+                _ => None,
+            };
+            let includes = match includes {
+                None => return Ok(value),
+                Some(Value::String(s)) => vec![s],
+                Some(Value::Sequence(list)) => list.into_iter()
+                    .map(|item| match item {
+                        Value::String(s) => Ok(s),
+                        _ => Err(Error::Merge(format!("'{}' in {} should only contain paths", self.include_key, path.display()))),
+                    })
+                    .collect::<Result<_>>()?,
+                Some(_) => return Err(Error::Merge(format!("'{}' in {} should be a path or a list of paths",
+                                                           self.include_key, path.display()))),
+            };
+            let dir = path.parent().unwrap_or(Path::new(""));
+            let mut merged = Value::Mapping(Mapping::new());
+            for include in includes {
+                let parent = self.load_with_chain(&dir.join(include), chain)?;
+                self.apply(&mut merged, &parent)?;
+            }
+            self.apply(&mut merged, &value)?;
+            Ok(merged)
+        })
+    }
+
+    /// Merges `overlays` in order into a copy of `base`, then deserializes the result
+    ///
+    /// If deserialization fails, the error is an `Error::Deserialize` with the path of the
+    /// failing key, when it is known.
+    pub fn merge_into<'o, T, I>(&self, base: &Value, overlays: I) -> Result<T>
+    where T: DeserializeOwned,
+          I: IntoIterator<Item = &'o Value> {
+        let mut value = base.clone();
+        for overlay in overlays {
+            self.apply(&mut value, overlay)?;
+        }
+        crate::de::from_value(&value)
+    }
+
+    /// Same as `apply`, also returning which paths were added, overwritten, removed or left
+    /// unchanged by the overlay.
+    ///
+    /// Sequences are reported as a whole, whatever the `SequenceStrategy`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use yaml_extras::{Merger, NullHandling};
+    ///
+    /// let mut base: serde_yaml::Value = serde_yaml::from_str("{server: {host: localhost, port: 80}, debug: true}")?;
+    /// let overlay: serde_yaml::Value = serde_yaml::from_str("{server: {host: localhost, port: 8080, tls: true}, debug: null}")?;
+    /// let report = Merger::new()
+    ///     .nulls(NullHandling::Delete)
+    ///     .apply_with_report(&mut base, &overlay)?;
+    ///
+    /// let paths = |changes: &[yaml_extras::MergeChange]| changes.iter()
+    ///     .map(|c| c.path.join("."))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(paths(&report.added), ["server.tls"]);
+    /// assert_eq!(paths(&report.overwritten), ["server.port"]);
+    /// assert_eq!(paths(&report.unchanged), ["server.host"]);
+    /// assert_eq!(paths(&report.removed), ["debug"]);
+    /// assert_eq!(report.overwritten[0].old, Some(80.into()));
+    /// assert_eq!(report.overwritten[0].new, Some(8080.into()));
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn apply_with_report(&self, value: &mut Value, other: &Value) -> Result<MergeReport> {
+        let mut state = State {
+            report: Some(MergeReport::default()),
+            ..State::default()
+        };
+        self.apply_with_state(value, other, &mut state)?;
+        Ok(state.report.unwrap_or_default())
+    }
+
+    /// Same as `apply`, also returning the number of added, overwritten, unchanged and
+    /// removed keys, and of type conflicts.
+    ///
+    /// This is cheaper than `apply_with_report`, since no path or value is copied.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut base: serde_yaml::Value = serde_yaml::from_str("{a: 1, b: {c: 2}, d: 3}")?;
+    /// let overlay: serde_yaml::Value = serde_yaml::from_str("{a: 1, b: {c: 3, e: 4}, d: [3]}")?;
+    /// let stats = yaml_extras::Merger::new()
+    ///     .apply_with_stats(&mut base, &overlay)?;
+    /// assert_eq!(stats, yaml_extras::MergeStats {
+    ///     added: 1,
+    ///     overwritten: 2,
+    ///     unchanged: 1,
+    ///     removed: 0,
+    ///     type_conflicts: 1,
+    ///     rejected: 0,
+    /// });
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn apply_with_stats(&self, value: &mut Value, other: &Value) -> Result<MergeStats> {
+        let mut state = State {
+            stats: Some(MergeStats::default()),
+            ..State::default()
+        };
+        self.apply_with_state(value, other, &mut state)?;
+        Ok(state.stats.unwrap_or_default())
+    }
+
+    /// Same as `apply`, but checks that the result matches `shape`, leaving `value` unchanged
+    /// if it doesn't.
+    ///
+    /// `shape` is a document with the expected types of values, such as the defaults of the
+    /// configuration, see `shape_violations`. The error lists all the mismatching paths.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let shape: serde_yaml::Value = serde_yaml::from_str("{server: {port: 80, hosts: [localhost]}}")?;
+    /// let mut config = shape.clone();
+    /// let overlay: serde_yaml::Value = serde_yaml::from_str("{server: {port: eighty, hosts: [1]}}")?;
+    /// let err = yaml_extras::Merger::new()
+    ///     .apply_checked(&mut config, &overlay, &shape)
+    ///     .unwrap_err();
+    /// assert_eq!(err.to_string(), "impossible to mege YAML values: result doesn't match the expected shape: \
+    ///                              'server.port' should be a Number, found a String, \
+    ///                              'server.hosts.0' should be a String, found a Number");
+    /// assert_eq!(config, shape);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn apply_checked(&self, value: &mut Value, other: &Value, shape: &Value) -> Result<()> {
+        let merged = self.merged(value, other)?;
+        let violations = shape_violations(&merged, shape);
+        if !violations.is_empty() {
+            let list: Vec<String> = violations.iter()
+                .map(|v| v.to_string())
+                .collect();
+            return Err(Error::Merge(format!("result doesn't match the expected shape: {}", list.join(", "))));
+        }
+        *value = merged;
+        Ok(())
+    }
+
+    fn apply_with_state(&self, value: &mut Value, other: &Value, state: &mut State) -> Result<()> {
+        check_depth(value, self.max_depth)?;
+        check_depth(other, self.max_depth)?;
+        if self.strict {
+            let unknown = self.unknown_keys(value, other);
+            if !unknown.is_empty() {
+                let list: Vec<String> = unknown.iter()
+                    .map(|path| path.join("."))
+                    .collect();
+                return Err(Error::Merge(format!("unknown keys in the overlay: {}", list.join(", "))));
+            }
+        }
+        if let (Some(v), Some(o)) = (value.as_mapping_mut(), other.as_mapping()) {
+            return self.merge_mapping(v, o, state);
+        }
+        Err(Error::TypeMismatch {
+            path: String::new(),
+            base: type_name(value),
+            overlay: type_name(other),
+        })
+    }
+
+    fn merge_mapping(&self, v: &mut Mapping, o: &Mapping, state: &mut State) -> Result<()> {
+        if self.key_order == KeyOrder::Relative {
+            return self.merge_mapping_relative(v, o, state);
+        }
+        for (o_key, o_val) in o.iter() {
+            let key = self.base_key(v, o_key);
+            state.path.push(key_token(&key));
+            self.merge_key(v, &key, o_val, state)?;
+            state.path.pop();
+        }
+        Ok(())
+    }
+
+    /// Same as `merge_mapping`, then moves the new keys after the base key preceding them
+    /// in the overlay
+    fn merge_mapping_relative(&self, v: &mut Mapping, o: &Mapping, state: &mut State) -> Result<()> {
+        let base_keys: Vec<Value> = v.keys().cloned().collect();
+        // New keys with the base key they follow
+        let mut new_keys: Vec<(Option<Cow<Value>>, Cow<Value>)> = vec![];
+        let mut anchor = None;
+        for (o_key, o_val) in o.iter() {
+            let key = self.base_key(v, o_key);
+            let existed = v.contains_key(&*key);
+            state.path.push(key_token(&key));
+            self.merge_key(v, &key, o_val, state)?;
+            state.path.pop();
+            if existed {
+                anchor = Some(key);
+            } else if v.contains_key(&*key) {
+                new_keys.push((anchor.clone(), key));
+            }
+        }
+        if new_keys.is_empty() {
+            return Ok(());
+        }
+        let mut old = std::mem::take(v);
+        let mut move_key = |k: &Value| if let Some(val) = old.swap_remove(k) {
+            v.insert(k.clone(), val);
+        };
+        for (_, k) in new_keys.iter().filter(|(anchor, _)| anchor.is_none()) {
+            move_key(k);
+        }
+        for base_key in &base_keys {
+            move_key(base_key);
+            for (_, k) in new_keys.iter().filter(|(anchor, _)| anchor.as_deref() == Some(base_key)) {
+                move_key(k);
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges the value of a key of the overlay
+    fn merge_key(&self, v: &mut Mapping, o_key: &Value, o_val: &Value, state: &mut State) -> Result<()> {
+        if let Some(f) = &self.before_node {
+            f(&state.path, v.get(o_key), o_val);
+        }
+        let result = self.merge_entry(v, o_key, o_val, state);
+        if let Some(f) = &self.after_node {
+            f(&state.path, v.get(o_key), o_val);
+        }
+        result
+    }
+
+    /// Same as `merge_key`, without the hooks
+    fn merge_entry(&self, v: &mut Mapping, o_key: &Value, o_val: &Value, state: &mut State) -> Result<()> {
+        let access = self.access(&state.path);
+        if access != Access::Free {
+            if o_val.is_null() && self.nulls == NullHandling::Ignore {
+                return Ok(());
+            }
+            let deleted = self.is_deletion(o_val) || (o_val.is_null() && self.nulls == NullHandling::Delete);
+            let new = if deleted { None } else { Some(o_val) };
+            match (access, v.get_mut(o_key), o_val) {
+                (Access::Nested, Some(Value::Mapping(v_map)), Value::Mapping(o_map)) if !self.shallow => {
+                    return self.merge_mapping(v_map, o_map, state);
+                },
+                (Access::Nested, None, Value::Mapping(o_map)) if !self.shallow => {
+                    let mut new = Mapping::new();
+                    self.merge_mapping(&mut new, o_map, state)?;
+                    if !new.is_empty() {
+                        v.insert(o_key.clone(), Value::Mapping(new));
+                    }
+                    return Ok(());
+                },
+                (_, old, _) => return self.reject(old.map(|old| &*old), new, state),
+            }
+        }
+        if self.is_deletion(o_val) {
+            if self.precedence == Precedence::OverlayWins {
+                if let Some(old) = v.shift_remove(o_key) {
+                    state.record(Some(&old), None);
+                }
+            }
+            return Ok(());
+        }
+        if o_val.is_null() {
+            match self.nulls {
+                NullHandling::Overwrite => (),
+                NullHandling::Ignore => return Ok(()),
+                NullHandling::Delete => {
+                    if self.precedence == Precedence::OverlayWins {
+                        if let Some(old) = v.shift_remove(o_key) {
+                            state.record(Some(&old), None);
+                        }
+                    }
+                    return Ok(());
+                },
+            }
+        }
+        match v.get_mut(o_key) {
+            Some(v_val) => self.merge_value(v_val, o_val, state),
+            None => {
+                let o_val = self.without_deletions(o_val);
+                state.record(None, Some(&o_val));
+                v.insert(o_key.clone(), o_val);
+                Ok(())
+            },
+        }
+    }
+
+    /// Merges two values found at the same key
+    fn merge_value(&self, v: &mut Value, o: &Value, state: &mut State) -> Result<()> {
+        // Values with the same tag are merged like untagged ones
+        if let (Value::Tagged(v), Value::Tagged(o)) = (&mut *v, o) {
+            if v.tag == o.tag {
+                return self.merge_value(&mut v.value, &o.value, state);
+            }
+        }
+        match (kind(v), kind(o)) {
+            (Some(v_kind), Some(o_kind)) if v_kind != o_kind => {
+                if let Some(ref mut stats) = state.stats {
+                    stats.type_conflicts += 1;
+                }
+                match self.conflicts {
+                    TypeConflict::Error => {
+                        return Err(Error::TypeMismatch {
+                            path: state.path.join("."),
+                            base: type_name(v),
+                            overlay: type_name(o),
+                        });
+                    },
+                    TypeConflict::BaseWins => {
+                        state.record(Some(v), Some(v));
+                        return Ok(());
+                    },
+                    TypeConflict::OverlayWins => (),
+                }
+            },
+            _ => (),
+        }
+        match (v, o) {
+            // If the contained hashmap is already present, merge the hashmap
+            (Value::Mapping(v), Value::Mapping(o)) if !self.shallow => self.merge_mapping(v, o, state),
+            (v, o) if is_scalar(v) && is_scalar(o) && self.combinators.contains_key(&state.path) => {
+                let combined = self.combinators[&state.path](v, o);
+                state.record(Some(v), Some(&combined));
+                *v = combined;
+                Ok(())
+            },
+            (v, _) if self.precedence == Precedence::BaseWins => {
+                state.record(Some(v), Some(v));
+                Ok(())
+            },
+            (Value::Sequence(v), Value::Sequence(o)) => {
+                let tracked = state.report.is_some() || state.stats.is_some();
+                let old = tracked.then(|| Value::Sequence(v.clone()));
+                // Items are not reported separately, only their type conflicts are counted
+                let report = state.report.take();
+                let stats = state.stats.replace(MergeStats::default());
+                let result = self.merge_sequence(v, o, state);
+                let items = std::mem::replace(&mut state.stats, stats);
+                state.report = report;
+                if let (Some(stats), Some(items)) = (&mut state.stats, items) {
+                    stats.type_conflicts += items.type_conflicts;
+                }
+                result?;
+                if let Some(old) = old {
+                    state.record(Some(&old), Some(&Value::Sequence(v.clone())));
+                }
+                Ok(())
+            },
+            (v, o) => {
+                state.record(Some(v), Some(o));
+                *v = o.clone();
+                Ok(())
+            },
+        }
+    }
+
+    fn merge_sequence(&self, v: &mut Vec<Value>, o: &[Value], state: &mut State) -> Result<()> {
+        match &self.sequences {
+            SequenceStrategy::Replace => *v = o.to_vec(),
+            SequenceStrategy::Append => v.extend(o.iter().cloned()),
+            SequenceStrategy::Prepend => {
+                v.splice(0..0, o.iter().cloned());
+            },
+            SequenceStrategy::Union => match &self.union_key {
+                None => for item in o {
+                    if !v.contains(item) {
+                        v.push(item.clone());
+                    }
+                },
+                Some(f) => {
+                    let mut keys: Vec<Value> = v.iter().map(|item| f(item)).collect();
+                    for item in o {
+                        let key = f(item);
+                        if !keys.contains(&key) {
+                            keys.push(key);
+                            v.push(item.clone());
+                        }
+                    }
+                },
+            },
+            SequenceStrategy::MergeByIndex => {
+                for (i, item) in o.iter().enumerate() {
+                    match v.get_mut(i) {
+                        Some(v_item) => {
+                            state.path.push(i.to_string());
+                            let result = self.merge_value(v_item, item, state);
+                            state.path.pop();
+                            result?;
+                        },
+                        None => v.push(item.clone()),
+                    }
+                }
+            },
+            SequenceStrategy::MergeByKey(key) => {
+                for item in o {
+                    let id = item.get(key.as_str());
+                    let matching = id.and_then(|id| v.iter()
+                                               .position(|v_item| v_item.get(key.as_str()) == Some(id)));
+                    match matching {
+                        Some(i) => {
+                            state.path.push(i.to_string());
+                            let result = self.merge_value(&mut v[i], item, state);
+                            state.path.pop();
+                            result?;
+                        },
+                        None => v.push(item.clone()),
+                    }
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+/// A path affected by a merge, see `Merger::apply_with_report`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeChange {
+    /// The path of the key, with sequence indices displayed as numbers
+    pub path: Vec<String>,
+    /// The value in the base, if any
+    pub old: Option<Value>,
+    /// The value after the merge, if any
+    pub new: Option<Value>,
+}
+
+/// The paths affected by a merge, see `Merger::apply_with_report`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MergeReport {
+    /// Keys of the overlay that were missing in the base
+    pub added: Vec<MergeChange>,
+    /// Keys whose value was changed by the overlay
+    pub overwritten: Vec<MergeChange>,
+    /// Keys of the overlay whose value was already the one in the base (or was kept because
+    /// of `Precedence::BaseWins`)
+    pub unchanged: Vec<MergeChange>,
+    /// Keys removed by a null value with `NullHandling::Delete`
+    pub removed: Vec<MergeChange>,
+    /// Changes to protected paths that were skipped, see `Protection::Ignore`
+    pub rejected: Vec<MergeChange>,
+}
+
+/// Counters of the keys affected by a merge, see `Merger::apply_with_stats`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MergeStats {
+    /// Keys of the overlay that were missing in the base
+    pub added: usize,
+    /// Keys whose value was changed by the overlay
+    pub overwritten: usize,
+    /// Keys of the overlay whose value was already the one in the base, or was kept
+    pub unchanged: usize,
+    /// Keys removed by the overlay
+    pub removed: usize,
+    /// Values with different kinds in the base and the overlay, see `TypeConflict`
+    pub type_conflicts: usize,
+    /// Changes to protected paths that were skipped
+    pub rejected: usize,
+}
+
+/// What is tracked during a merge
+#[derive(Default)]
+struct State {
+    path: Vec<String>,
+    report: Option<MergeReport>,
+    stats: Option<MergeStats>,
+}
+
+impl State {
+    /// Adds the current path to the report and the statistics, if they are tracked
+    fn record(&mut self, old: Option<&Value>, new: Option<&Value>) {
+        if let Some(ref mut stats) = self.stats {
+            let counter = match (old, new) {
+                (None, _) => &mut stats.added,
+                (_, None) => &mut stats.removed,
+                (Some(old), Some(new)) if old == new => &mut stats.unchanged,
+                _ => &mut stats.overwritten,
+            };
+            *counter += 1;
+        }
+        let Some(ref mut report) = self.report else {
+            return;
+        };
+        let old = old.cloned();
+        let new = new.cloned();
+        let list = match (&old, &new) {
+            (None, _) => &mut report.added,
+            (_, None) => &mut report.removed,
+            (Some(old), Some(new)) if old == new => &mut report.unchanged,
+            _ => &mut report.overwritten,
+        };
+        list.push(MergeChange { path: self.path.clone(), old, new });
+    }
+
+    /// Adds a change to a protected path to the report and the statistics
+    fn reject(&mut self, old: Option<&Value>, new: Option<&Value>) {
+        if let Some(ref mut stats) = self.stats {
+            stats.rejected += 1;
+        }
+        if let Some(ref mut report) = self.report {
+            report.rejected.push(MergeChange {
+                path: self.path.clone(),
+                old: old.cloned(),
+                new: new.cloned(),
+            });
+        }
+    }
+}
+
+/// Merge two YAML representations into another
+///
+/// This is the same as `Merger::new().apply(value, other)`.
+pub fn merge(value: &mut Value, other: &Value) -> Result<()> {
+    Merger::new().apply(value, other)
+}
+
+/// Merge two YAML representations into a new one
+///
+/// This is the same as `Merger::new().merged(value, other)`.
+///
+/// # Example
+///
+/// ```
+/// let base: serde_yaml::Value = serde_yaml::from_str("{a: {b: 1}}")?;
+/// let overlay: serde_yaml::Value = serde_yaml::from_str("{a: {c: 2}}")?;
+/// let merged = yaml_extras::merged(&base, &overlay)?;
+/// assert_eq!(merged, serde_yaml::from_str::<serde_yaml::Value>("{a: {b: 1, c: 2}}")?);
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn merged(value: &Value, other: &Value) -> Result<Value> {
+    Merger::new().merged(value, other)
+}
+
+/// Merges `overlays` in order into a copy of `base`, then deserializes the result
+///
+/// This is the same as `Merger::new().merge_into(base, overlays)`.
+///
+/// # Example
+///
+/// ```
+/// use serde_derive::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Server {
+///     host: String,
+///     port: u16,
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct Config {
+///     server: Server,
+/// }
+///
+/// let defaults: serde_yaml::Value = serde_yaml::from_str("{server: {host: localhost, port: 80}}")?;
+/// let local: serde_yaml::Value = serde_yaml::from_str("{server: {port: 8080}}")?;
+/// let config: Config = yaml_extras::merge_into(&defaults, [&local])?;
+/// assert_eq!(config.server.port, 8080);
+///
+/// let typo: serde_yaml::Value = serde_yaml::from_str("{server: {port: eighty}}")?;
+/// let err = yaml_extras::merge_into::<Config, _>(&defaults, [&typo]).err().unwrap();
+/// assert!(matches!(err, yaml_extras::Error::Deserialize { ref path, .. } if path == "server.port"));
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn merge_into<'o, T, I>(base: &Value, overlays: I) -> Result<T>
+where T: DeserializeOwned,
+      I: IntoIterator<Item = &'o Value> {
+    Merger::new().merge_into(base, overlays)
+}
+
+/// Parses and merges two YAML documents, the overlay being able to use aliases of the
+/// anchors defined in the base
+///
+/// This is the same as `Merger::new().merged_with_anchors(base, overlay)`.
+#[cfg(feature = "spans")]
+pub fn merged_with_anchors(base: &str, overlay: &str) -> Result<Value> {
+    Merger::new().merged_with_anchors(base, overlay)
+}
+
+/// Parses a YAML string and merges it into `value`
+///
+/// This is the same as `Merger::new().apply_str(value, s)`.
+///
+/// # Example
+///
+/// ```
+/// let mut config: serde_yaml::Value = serde_yaml::from_str("{port: 80, host: localhost}")?;
+/// yaml_extras::merge_str(&mut config, "port: 8080")?;
+/// assert_eq!(config, serde_yaml::from_str::<serde_yaml::Value>("{port: 8080, host: localhost}")?);
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn merge_str(value: &mut Value, s: &str) -> Result<()> {
+    Merger::new().apply_str(value, s)
+}
+
+/// Reads YAML files and merges them into `value`, in order
+///
+/// This is the same as `Merger::new().apply_files(value, paths)`.
+///
+/// # Example
+///
+/// ```no_run
+/// let mut config = serde_yaml::Value::Mapping(Default::default());
+/// yaml_extras::merge_files(&mut config, ["/etc/myapp.yaml", "myapp.yaml"])?;
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn merge_files<I, P>(value: &mut Value, paths: I) -> Result<()>
+where I: IntoIterator<Item = P>,
+      P: AsRef<Path> {
+    Merger::new().apply_files(value, paths)
+}
+
+/// Inserts the keys of `defaults` that are missing in `value`, at any depth, without modifying
+/// the values already present
+///
+/// A key whose value is null is present: it is kept as is, while a missing key gets the
+/// default value, even when it is null. This is the same as
+/// `Merger::new().precedence(Precedence::BaseWins).apply(value, defaults)`.
+///
+/// # Example
+///
+/// ```
+/// let mut config: serde_yaml::Value = serde_yaml::from_str("{server: {port: 8080, tls: null}}")?;
+/// let defaults: serde_yaml::Value = serde_yaml::from_str("
+/// server: {host: localhost, port: 80, tls: {cert: server.pem}}
+/// log: info
+/// ")?;
+/// yaml_extras::apply_defaults(&mut config, &defaults)?;
+/// assert_eq!(config, serde_yaml::from_str::<serde_yaml::Value>("
+/// server: {port: 8080, tls: null, host: localhost}
+/// log: info
+/// ")?);
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn apply_defaults(value: &mut Value, defaults: &Value) -> Result<()> {
+    Merger::new()
+        .precedence(Precedence::BaseWins)
+        .apply(value, defaults)
+}
+
+/// Resolves the YAML merge keys (`<<`) of `value`, deep-merging the mappings they refer to
+///
+/// This is the same as `Merger::new().expand_merge_keys(value)`.
+pub fn expand_merge_keys(value: &mut Value) -> Result<()> {
+    Merger::new().expand_merge_keys(value)
+}
+
+/// Merges the profile `name` of the `profiles` section of `value` into it, then removes the
+/// profiles section
+///
+/// This is the same as `Merger::new().apply_profile(value, name)`.
+pub fn apply_profile(value: &mut Value, name: &str) -> Result<()> {
+    Merger::new().apply_profile(value, name)
+}
+
+/// Merges `other` at `path` inside `value`, creating the missing mappings
+///
+/// This is the same as `Merger::new().apply_at(value, path, other)`.
+pub fn merge_at(value: &mut Value, path: &str, other: &Value) -> Result<()> {
+    Merger::new().apply_at(value, path, other)
+}
+
+/// Merges `overlays` in order into `value`, merging the top-level keys in parallel
+///
+/// This is the same as `Merger::new().par_apply_all(value, overlays)`.
+pub fn par_merge_all(value: &mut Value, overlays: &[Value]) -> Result<()> {
+    Merger::new().par_apply_all(value, overlays)
+}
+
+/// A value whose type differs from the one expected by a shape, see `shape_violations`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapeViolation {
+    /// The path of the value, with sequence indices displayed as numbers
+    pub path: Vec<String>,
+    /// The type of the value at the same path in the shape
+    pub expected: ValueType,
+    /// The type of the value
+    pub found: ValueType,
+}
+
+impl std::fmt::Display for ShapeViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "'{}' should be a {:?}, found a {:?}", self.path.join("."), self.expected, self.found)
+    }
+}
+
+/// Lists the paths of `value` whose type differs from the one at the same path in `shape`.
+///
+/// Nulls of `shape` accept any value and nulls of `value` are always accepted. The items of
+/// a sequence are checked against the first item of the shape's sequence, if any. Keys that
+/// are missing from `shape` are not checked.
+pub fn shape_violations(value: &Value, shape: &Value) -> Vec<ShapeViolation> {
+    let mut violations = vec![];
+    check_shape(value, shape, &mut vec![], &mut violations);
+    violations
+}
+
+fn check_shape(value: &Value, shape: &Value, path: &mut Vec<String>, violations: &mut Vec<ShapeViolation>) {
+    if value.is_null() || shape.is_null() {
+        return;
+    }
+    let (expected, found) = (ValueType::from(shape), ValueType::from(value));
+    if expected != found {
+        violations.push(ShapeViolation { path: path.clone(), expected, found });
+        return;
+    }
+    match (value, shape) {
+        (Value::Mapping(v), Value::Mapping(s)) => {
+            for (k, v_val) in v {
+                if let Some(s_val) = s.get(k) {
+                    path.push(key_token(k));
+                    check_shape(v_val, s_val, path, violations);
+                    path.pop();
+                }
+            }
+        },
+        (Value::Sequence(v), Value::Sequence(s)) => {
+            if let Some(s_item) = s.first() {
+                for (i, item) in v.iter().enumerate() {
+                    path.push(i.to_string());
+                    check_shape(item, s_item, path, violations);
+                    path.pop();
+                }
+            }
+        },
+        (Value::Tagged(v), Value::Tagged(s)) => check_shape(&v.value, &s.value, path, violations),
+        _ => (),
+    }
+}
+
+/// Lists the paths of the keys of `overlay` that are missing in `reference`, without the keys
+/// nested under them.
+///
+/// Keys marked with the `!delete` tag are ignored.
+///
+/// # Example
+///
+/// ```
+/// let defaults: serde_yaml::Value = serde_yaml::from_str("{compiler: {command: cargo, args: []}}")?;
+/// let overlay: serde_yaml::Value = serde_yaml::from_str("{compiler: {comand: make, args: [-j]}}")?;
+/// assert_eq!(yaml_extras::unknown_keys(&defaults, &overlay), [["compiler", "comand"]]);
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn unknown_keys(reference: &Value, overlay: &Value) -> Vec<Vec<String>> {
+    Merger::new().unknown_keys(reference, overlay)
+}
+
+/// A path changed differently on both sides of a three-way merge, see `merge3`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeConflict {
+    pub path: Vec<String>,
+    /// The value in the common ancestor, if any
+    pub base: Option<Value>,
+    /// Our value, if any, which is the one kept in the result
+    pub ours: Option<Value>,
+    /// Their value, if any
+    pub theirs: Option<Value>,
+}
+
+/// The result of `merge3`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThreeWayMerge {
+    /// The merged value, with our side for conflicting paths
+    pub value: Value,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// The result of `Merger::merge_by_priority`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriorityMerge {
+    /// The merged value
+    pub value: Value,
+    /// The index of the document that provided the value of each path. Paths nested under
+    /// one of these are only listed if another document changed them.
+    pub sources: BTreeMap<Vec<String>, usize>,
+}
+
+impl PriorityMerge {
+    /// The index of the document that provided the value at `path`, if it exists. For a
+    /// mapping merged from several documents, this is the one that added it.
+    pub fn source<S: AsRef<str>>(&self, path: &[S]) -> Option<usize> {
+        let path: Vec<String> = path.iter()
+            .map(|s| s.as_ref().to_owned())
+            .collect();
+        (1..=path.len())
+            .rev()
+            .find_map(|n| self.sources.get(&path[..n]))
+            .copied()
+    }
+}
+
+/// Three-way merge of two versions of a document with their common ancestor `base`.
+///
+/// A path changed on one side only takes the changed value; a path changed on both sides
+/// is a conflict, unless both made the same change. Mappings are merged key by key, other
+/// values (including sequences) as a whole.
+///
+/// # Example
+///
+/// ```
+/// let base: serde_yaml::Value = serde_yaml::from_str("{host: localhost, port: 80, debug: false}")?;
+/// let ours: serde_yaml::Value = serde_yaml::from_str("{host: example.org, port: 8080, debug: false}")?;
+/// let theirs: serde_yaml::Value = serde_yaml::from_str("{host: localhost, port: 8000, debug: true}")?;
+///
+/// let merged = yaml_extras::merge3(&base, &ours, &theirs);
+/// let expected: serde_yaml::Value = serde_yaml::from_str("{host: example.org, port: 8080, debug: true}")?;
+/// assert_eq!(merged.value, expected);
+/// assert_eq!(merged.conflicts.len(), 1);
+/// assert_eq!(merged.conflicts[0].path, ["port"]);
+/// assert_eq!(merged.conflicts[0].theirs, Some(8000.into()));
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn merge3(base: &Value, ours: &Value, theirs: &Value) -> ThreeWayMerge {
+    let mut conflicts = vec![];
+    let value = merge3_at(Some(base), Some(ours), Some(theirs), &mut vec![], &mut conflicts);
+    ThreeWayMerge {
+        value: value.unwrap_or(Value::Null),
+        conflicts,
+    }
+}
+
+/// Merges the values at a path, `None` meaning that the key is absent
+fn merge3_at(base: Option<&Value>, ours: Option<&Value>, theirs: Option<&Value>,
+             path: &mut Vec<String>, conflicts: &mut Vec<MergeConflict>) -> Option<Value> {
+    if ours == theirs || theirs == base {
+        return ours.cloned();
+    }
+    if ours == base {
+        return theirs.cloned();
+    }
+    let empty = Mapping::new();
+    let base_mapping = match base {
+        None => Some(&empty),
+        Some(b) => b.as_mapping(),
+    };
+    match (base_mapping, ours.and_then(|v| v.as_mapping()), theirs.and_then(|v| v.as_mapping())) {
+        (Some(b), Some(o), Some(t)) => {
+            let mut m = Mapping::new();
+            let keys = o.keys()
+                .chain(t.keys().filter(|k| !o.contains_key(*k)))
+                .chain(b.keys().filter(|k| !o.contains_key(*k) && !t.contains_key(*k)));
+            for k in keys {
+                path.push(key_token(k));
+                if let Some(v) = merge3_at(b.get(k), o.get(k), t.get(k), path, conflicts) {
+                    m.insert(k.clone(), v);
+                }
+                path.pop();
+            }
+            Some(Value::Mapping(m))
+        },
+        _ => {
+            conflicts.push(MergeConflict {
+                path: path.clone(),
+                base: base.cloned(),
+                ours: ours.cloned(),
+                theirs: theirs.cloned(),
+            });
+            ours.cloned()
+        },
     }
-    return Err(Error::Merge(format!("both arguments need to be mapping, found {:?} and {:?}", value, other))); 
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use pretty_assertions::{assert_eq, assert_ne};
+    use crate::yaml;
+    use pretty_assertions::assert_eq;
 
     #[test]
     fn merge_simple() {
@@ -52,4 +2077,715 @@ bar: true"#;
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn merger_options() {
+        let base = yaml("{a: {b: 1, c: [1]}, d: 2, e: 3}");
+        let overlay = yaml("{a: {c: [2], f: null}, d: null}");
+
+        let mut v = base.clone();
+        Merger::new().apply(&mut v, &overlay).unwrap();
+        assert_eq!(v, yaml("{a: {b: 1, c: [2], f: null}, d: null, e: 3}"));
+        assert_eq!(merged(&base, &overlay).unwrap(), v);
+        assert!(merged(&base, &yaml("1")).is_err());
+
+        let mut v = base.clone();
+        Merger::new()
+            .sequences(SequenceStrategy::Append)
+            .nulls(NullHandling::Ignore)
+            .apply(&mut v, &overlay)
+            .unwrap();
+        assert_eq!(v, yaml("{a: {b: 1, c: [1, 2]}, d: 2, e: 3}"));
+
+        let mut v = base.clone();
+        Merger::new()
+            .nulls(NullHandling::Delete)
+            .apply(&mut v, &overlay)
+            .unwrap();
+        assert_eq!(v, yaml("{a: {b: 1, c: [2]}, e: 3}"));
+
+        assert!(Merger::new().apply(&mut yaml("[1]"), &overlay).is_err());
+    }
+
+    #[test]
+    fn sequence_strategies() {
+        let base = yaml("l: [1, 2, {a: 1, b: 2}]");
+        let overlay = yaml("l: [3, 2, {a: 3}, 4]");
+        let merged = |strategy| {
+            let mut v = base.clone();
+            Merger::new()
+                .sequences(strategy)
+                .apply(&mut v, &overlay)
+                .unwrap();
+            v
+        };
+        assert_eq!(merged(SequenceStrategy::Replace), overlay);
+        assert_eq!(merged(SequenceStrategy::Append), yaml("l: [1, 2, {a: 1, b: 2}, 3, 2, {a: 3}, 4]"));
+        assert_eq!(merged(SequenceStrategy::Prepend), yaml("l: [3, 2, {a: 3}, 4, 1, 2, {a: 1, b: 2}]"));
+        assert_eq!(merged(SequenceStrategy::Union), yaml("l: [1, 2, {a: 1, b: 2}, 3, {a: 3}, 4]"));
+        assert_eq!(merged(SequenceStrategy::MergeByIndex), yaml("l: [3, 2, {a: 3, b: 2}, 4]"));
+        assert_eq!(merged(SequenceStrategy::MergeByKey("a".into())), yaml("l: [1, 2, {a: 1, b: 2}, 3, 2, {a: 3}, 4]"));
+
+        let base = yaml("l: [{id: 1, x: {y: 1}}, {id: 2}, {x: 1}]");
+        let overlay = yaml("l: [{id: 2, z: 3}, {x: 2}, {id: 1, x: {w: 2}}]");
+        let mut v = base.clone();
+        Merger::new()
+            .sequences(SequenceStrategy::MergeByKey("id".into()))
+            .apply(&mut v, &overlay)
+            .unwrap();
+        assert_eq!(v, yaml("l: [{id: 1, x: {y: 1, w: 2}}, {id: 2, z: 3}, {x: 1}, {x: 2}]"));
+    }
+
+    #[test]
+    fn sequence_item_paths() {
+        let base = yaml("l: [{id: a, x: 1}, {id: b, x: 2}]");
+        let overlay = yaml("l: [{id: b, x: [3]}]");
+        let err = Merger::new()
+            .sequences(SequenceStrategy::MergeByKey("id".into()))
+            .type_conflicts(TypeConflict::Error)
+            .apply(&mut base.clone(), &overlay)
+            .unwrap_err();
+        assert!(matches!(err, Error::TypeMismatch { ref path, .. } if path == "l.1.x"), "{err}");
+        let err = Merger::new()
+            .sequences(SequenceStrategy::MergeByIndex)
+            .type_conflicts(TypeConflict::Error)
+            .apply(&mut base.clone(), &overlay)
+            .unwrap_err();
+        assert!(matches!(err, Error::TypeMismatch { ref path, .. } if path == "l.0.x"), "{err}");
+
+        let mut v = base.clone();
+        Merger::new()
+            .sequences(SequenceStrategy::MergeByIndex)
+            .combine("l.1.x", |b, o| (b.as_i64().unwrap() + o.as_i64().unwrap()).into())
+            .apply(&mut v, &yaml("l: [{x: 10}, {x: 10}]"))
+            .unwrap();
+        assert_eq!(v, yaml("l: [{id: a, x: 10}, {id: b, x: 12}]"));
+    }
+
+    #[test]
+    fn precedence() {
+        let mut v = yaml("{a: {b: 1, c: [1]}, d: null, e: 3}");
+        let defaults = yaml("{a: {b: 2, c: [2], f: 4}, d: 5, e: null, g: null}");
+        Merger::new()
+            .precedence(Precedence::BaseWins)
+            .sequences(SequenceStrategy::Append)
+            .nulls(NullHandling::Delete)
+            .apply(&mut v, &defaults)
+            .unwrap();
+        assert_eq!(v, yaml("{a: {b: 1, c: [1], f: 4}, d: null, e: 3}"));
+    }
+
+    #[test]
+    fn shallow() {
+        let base = yaml("{a: {b: {c: 1}, d: 2}, l: [{x: 1}], e: 3}");
+        let overlay = yaml("{a: {b: {f: 4}}, l: [{y: 2}], g: 5}");
+        let mut v = base.clone();
+        Merger::new()
+            .shallow(true)
+            .sequences(SequenceStrategy::MergeByIndex)
+            .apply(&mut v, &overlay)
+            .unwrap();
+        assert_eq!(v, yaml("{a: {b: {f: 4}}, l: [{y: 2}], e: 3, g: 5}"));
+
+        let mut v = base.clone();
+        Merger::new()
+            .shallow(true)
+            .precedence(Precedence::BaseWins)
+            .apply(&mut v, &overlay)
+            .unwrap();
+        assert_eq!(v, yaml("{a: {b: {c: 1}, d: 2}, l: [{x: 1}], e: 3, g: 5}"));
+    }
+
+    #[test]
+    fn report() {
+        let mut v = yaml("{a: {b: 1, c: 2}, l: [1], m: [2], d: 3}");
+        let overlay = yaml("{a: {b: 1, c: 4, e: {f: 5}}, l: [1], m: [3], d: {g: 6}}");
+        let report = Merger::new()
+            .type_conflicts(TypeConflict::BaseWins)
+            .apply_with_report(&mut v.clone(), &overlay)
+            .unwrap();
+        assert_eq!(report.unchanged.last(), Some(&MergeChange {
+            path: vec!["d".to_owned()],
+            old: Some(3.into()),
+            new: Some(3.into()),
+        }));
+
+        let overlay = yaml("{a: {b: 1, c: 4, e: {f: 5}}, l: [1], m: [3], d: null}");
+        let report = Merger::new()
+            .sequences(SequenceStrategy::Append)
+            .nulls(NullHandling::Delete)
+            .apply_with_report(&mut v, &overlay)
+            .unwrap();
+        let change = |path: &str, old: Option<&str>, new: Option<&str>| MergeChange {
+            path: path.split('.').map(|s| s.to_owned()).collect(),
+            old: old.map(yaml),
+            new: new.map(yaml),
+        };
+        assert_eq!(report, MergeReport {
+            added: vec![change("a.e", None, Some("{f: 5}"))],
+            overwritten: vec![change("a.c", Some("2"), Some("4")),
+                              change("l", Some("[1]"), Some("[1, 1]")),
+                              change("m", Some("[2]"), Some("[2, 3]"))],
+            unchanged: vec![change("a.b", Some("1"), Some("1"))],
+            removed: vec![change("d", Some("3"), None)],
+            rejected: vec![],
+        });
+    }
+
+    #[test]
+    fn type_conflicts() {
+        let base = yaml("{a: 1, b: {c: 2}, d: [3], e: null, f: x}");
+        let overlay = yaml("{a: {x: 1}, b: 2, d: 3, e: {y: 2}, f: 4}");
+        let merged = |policy| {
+            let mut v = base.clone();
+            Merger::new()
+                .type_conflicts(policy)
+                .apply(&mut v, &overlay)
+                .map(|_| v)
+        };
+        assert_eq!(merged(TypeConflict::OverlayWins).unwrap(), yaml("{a: {x: 1}, b: 2, d: 3, e: {y: 2}, f: 4}"));
+        assert_eq!(merged(TypeConflict::BaseWins).unwrap(), yaml("{a: 1, b: {c: 2}, d: [3], e: {y: 2}, f: 4}"));
+        let err = merged(TypeConflict::Error).unwrap_err();
+        assert_eq!(err.to_string(), "impossible to merge YAML values at 'a': number in the base, mapping in the overlay");
+        // The default, which was an error before 0.3
+        assert_eq!(TypeConflict::default(), TypeConflict::OverlayWins);
+        let mut v = yaml("{a: 1}");
+        merge(&mut v, &yaml("{a: {b: 1}}")).unwrap();
+        assert_eq!(v, yaml("{a: {b: 1}}"));
+
+        let err = merge(&mut base.clone(), &yaml("[big, list]")).unwrap_err();
+        match err {
+            Error::TypeMismatch { path, base, overlay } => assert_eq!((path.as_str(), base.as_str(), overlay.as_str()),
+                                                                      ("", "mapping", "sequence")),
+            e => panic!("unexpected error {e:?}"),
+        }
+    }
+
+    #[test]
+    fn three_way() {
+        let base = yaml("{a: {b: 1, c: 2}, d: [1], e: 3, f: 4}");
+        let ours = yaml("{a: {b: 1, c: 5, g: 6}, d: [1, 2], f: 4}");
+        let theirs = yaml("{a: {b: 7, c: 5}, d: [1, 3], e: 3, h: 8}");
+        let merged = merge3(&base, &ours, &theirs);
+        assert_eq!(merged.value, yaml("{a: {b: 7, c: 5, g: 6}, d: [1, 2], h: 8}"));
+        assert_eq!(merged.conflicts, vec![MergeConflict {
+            path: vec!["d".to_owned()],
+            base: Some(yaml("[1]")),
+            ours: Some(yaml("[1, 2]")),
+            theirs: Some(yaml("[1, 3]")),
+        }]);
+
+        // Removed on one side, changed on the other
+        let merged = merge3(&yaml("{a: 1}"), &yaml("{}"), &yaml("{a: 2}"));
+        assert_eq!(merged.value, yaml("{}"));
+        assert_eq!(merged.conflicts[0].ours, None);
+    }
+
+    #[test]
+    fn shape() {
+        let shape = yaml("{a: {b: 1, c: null}, l: [{x: true}], e: [], t: !x {y: 1}}");
+        let value = yaml("{a: {b: null, c: [1], z: 1}, l: [{x: true}, {x: 1}, 2], e: [1, a], t: !x {y: a}}");
+        let violations: Vec<String> = shape_violations(&value, &shape)
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(violations, ["'l.1.x' should be a Bool, found a Number",
+                                "'l.2' should be a Mapping, found a Number",
+                                "'t.y' should be a Number, found a String"]);
+    }
+
+    #[test]
+    fn delete_tag() {
+        let base = yaml("{a: {b: 1, c: 2}, d: 3}");
+        let overlay = yaml("{a: {b: !delete , e: {f: !delete x, g: 1}}, d: !delete , h: !delete }");
+        let mut v = base.clone();
+        let report = Merger::new()
+            .apply_with_report(&mut v, &overlay)
+            .unwrap();
+        assert_eq!(v, yaml("{a: {c: 2, e: {g: 1}}}"));
+        assert_eq!(report.removed.len(), 2);
+
+        let mut v = base.clone();
+        Merger::new()
+            .delete_tag("")
+            .apply(&mut v, &yaml("{d: !delete }"))
+            .unwrap();
+        assert_eq!(v, yaml("{a: {b: 1, c: 2}, d: !delete }"));
+    }
+
+    #[test]
+    fn max_depth() {
+        let mut v = yaml("{a: {b: 1}}");
+        let overlay = yaml("{a: {b: {c: {d: 1}}}}");
+        let err = Merger::new()
+            .max_depth(3)
+            .apply(&mut v, &overlay)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "maximum depth of 3 exceeded at 'a.b.c.d'");
+        assert_eq!(v, yaml("{a: {b: 1}}"));
+    }
+
+    #[test]
+    fn tagged() {
+        let mut v = yaml("{storage: !s3 {bucket: a, region: eu}, cache: !redis {host: localhost}}");
+        let overlay = yaml("{storage: !s3 {bucket: b}, cache: !memory {size: 10}}");
+        Merger::new().apply(&mut v, &overlay).unwrap();
+        assert_eq!(v, yaml("{storage: !s3 {bucket: b, region: eu}, cache: !memory {size: 10}}"));
+
+        let err = Merger::new()
+            .type_conflicts(TypeConflict::Error)
+            .apply(&mut v, &yaml("{cache: !redis {host: localhost}}"))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "impossible to merge YAML values at 'cache': \
+                                     value tagged !memory in the base, value tagged !redis in the overlay");
+    }
+
+    #[test]
+    fn files() {
+        let dir = std::env::temp_dir().join(format!("yaml_extras_merge_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.yaml");
+        let b = dir.join("b.yaml");
+        std::fs::write(&a, "{x: {y: 1}}").unwrap();
+        std::fs::write(&b, "{x: {z: 2}}").unwrap();
+        let mut v = yaml("{w: 0}");
+        merge_files(&mut v, [&a, &b]).unwrap();
+        assert_eq!(v, yaml("{w: 0, x: {y: 1, z: 2}}"));
+
+        let missing = dir.join("missing.yaml");
+        let err = merge_files(&mut v, [&missing]).unwrap_err();
+        assert_eq!(err.to_string(), format!("impossible to read {}", missing.display()));
+        assert!(matches!(merge_str(&mut v, "{a: ["), Err(Error::Yaml(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn includes() {
+        let dir = std::env::temp_dir().join(format!("yaml_extras_includes_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("common")).unwrap();
+        std::fs::write(dir.join("common/base.yaml"), "{log: info, db: {host: localhost, port: 5432}, list: [1]}").unwrap();
+        std::fs::write(dir.join("common/tls.yaml"), "{extends: base.yaml, db: {tls: true}}").unwrap();
+        std::fs::write(dir.join("prod.yaml"), "{extends: [common/base.yaml, common/tls.yaml], db: {host: db}, list: [2]}").unwrap();
+        let merged = Merger::new()
+            .sequences(SequenceStrategy::Append)
+            .load(dir.join("prod.yaml"))
+            .unwrap();
+        assert_eq!(merged, yaml("{log: info, db: {host: db, port: 5432, tls: true}, list: [1, 1, 2]}"));
+
+        std::fs::write(dir.join("a.yaml"), "{extends: b.yaml}").unwrap();
+        std::fs::write(dir.join("b.yaml"), "{extends: [common/base.yaml, a.yaml]}").unwrap();
+        let err = Merger::new().load(dir.join("a.yaml")).unwrap_err();
+        let name = |f: &str| dir.join(f).display().to_string();
+        assert_eq!(err.to_string(), format!("impossible to mege YAML values: include cycle: {} -> {} -> {}",
+                                            name("a.yaml"), name("b.yaml"), name("a.yaml")));
+        assert_eq!(Merger::new().include_key("").load(dir.join("a.yaml")).unwrap(), yaml("{extends: b.yaml}"));
+        let err = Merger::new().max_include_depth(1).load(dir.join("prod.yaml")).unwrap_err();
+        assert_eq!(err.to_string(), format!("impossible to mege YAML values: more than 1 nested includes at {}",
+                                            dir.join("common/base.yaml").display()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stats() {
+        let v = yaml("{a: {b: 1, c: 2}, l: [1], m: [2], d: 3, n: 4}");
+        let overlay = yaml("{a: {b: 1, c: 4, e: {f: 5}}, l: [1], m: [3], d: {g: 6}, n: null}");
+        let stats = Merger::new()
+            .sequences(SequenceStrategy::MergeByIndex)
+            .nulls(NullHandling::Delete)
+            .type_conflicts(TypeConflict::BaseWins)
+            .apply_with_stats(&mut v.clone(), &overlay)
+            .unwrap();
+        assert_eq!(stats, MergeStats {
+            added: 1,
+            overwritten: 2,
+            unchanged: 3,
+            removed: 1,
+            type_conflicts: 1,
+            rejected: 0,
+        });
+
+        // Same classification as the full report
+        let report = Merger::new()
+            .apply_with_report(&mut v.clone(), &overlay)
+            .unwrap();
+        let stats = Merger::new()
+            .apply_with_stats(&mut v.clone(), &overlay)
+            .unwrap();
+        assert_eq!((stats.added, stats.overwritten, stats.unchanged, stats.removed),
+                   (report.added.len(), report.overwritten.len(), report.unchanged.len(), report.removed.len()));
+    }
+
+    #[test]
+    fn stats_in_sequences() {
+        let v = yaml("{a: 1, l: [{b: 1}, {c: [1]}]}");
+        let overlay = yaml("{a: [1], l: [{b: {d: 2}}, {c: [2]}]}");
+        let stats = Merger::new()
+            .sequences(SequenceStrategy::MergeByIndex)
+            .type_conflicts(TypeConflict::OverlayWins)
+            .apply_with_stats(&mut v.clone(), &overlay)
+            .unwrap();
+        // Items are counted as one overwritten sequence, but their type conflicts are counted
+        assert_eq!(stats, MergeStats {
+            added: 0,
+            overwritten: 2,
+            unchanged: 0,
+            removed: 0,
+            type_conflicts: 2,
+            rejected: 0,
+        });
+    }
+
+    #[test]
+    fn combinators() {
+        let mut v = yaml("{a: {b: x, c: 1}, d: 2, e: [f]}");
+        let overlay = yaml("{a: {b: y, c: 3}, d: 4, e: g}");
+        let merger = Merger::new()
+            .precedence(Precedence::BaseWins)
+            .combine("a.b", |b, o| format!("{}-{}", b.as_str().unwrap(), o.as_str().unwrap()).into())
+            .combine("a.c", |b, o| (b.as_i64().unwrap() + o.as_i64().unwrap()).into())
+            .combine("e", |_, _| unreachable!());
+        assert!(format!("{merger:?}").contains(r#"combinators: [["a", "b"], ["a", "c"], ["e"]]"#));
+        let report = merger.apply_with_report(&mut v, &overlay).unwrap();
+        assert_eq!(v, yaml("{a: {b: x-y, c: 4}, d: 2, e: [f]}"));
+        assert_eq!(report.overwritten.len(), 2);
+    }
+
+    #[test]
+    fn union_key() {
+        let mut v = yaml("[{id: 1, x: a}, {id: 2}]");
+        let overlay = yaml("[{id: 2, x: b}, {id: 3}, {id: 3, x: c}, 4]");
+        Merger::new()
+            .sequences(SequenceStrategy::Union)
+            .union_key(|item| item.get("id").cloned().unwrap_or_else(|| item.clone()))
+            .merge_sequence(v.as_sequence_mut().unwrap(), overlay.as_sequence().unwrap(), &mut State::default())
+            .unwrap();
+        assert_eq!(v, yaml("[{id: 1, x: a}, {id: 2}, {id: 3}, 4]"));
+    }
+
+    #[test]
+    fn key_order() {
+        let keys = |v: &Value| v.as_mapping()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str().unwrap().to_owned())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let v = yaml("{c: 1, a: {z: 1, y: 2}, b: 2, d: 3}");
+        let overlay = yaml("{e: 4, a: {x: 3, y: 0}, f: 5, b: null, g: 6, c: 0}");
+
+        let merged = Merger::new()
+            .nulls(NullHandling::Delete)
+            .merged(&v, &overlay)
+            .unwrap();
+        assert_eq!(keys(&merged), "c a d e f g");
+        assert_eq!(keys(&merged["a"]), "z y x");
+
+        let merged = Merger::new()
+            .nulls(NullHandling::Delete)
+            .key_order(KeyOrder::Relative)
+            .merged(&v, &overlay)
+            .unwrap();
+        assert_eq!(keys(&merged), "e c a f g d");
+        assert_eq!(keys(&merged["a"]), "x z y");
+    }
+
+    #[test]
+    fn parallel() {
+        let base = yaml("{a: {b: [1]}, c: 2, d: x}");
+        let overlays: Vec<Value> = (0..50)
+            .map(|i| yaml(&format!("{{a: {{b: [{i}], k{i}: {i}}}, e{}: {i}, d: null}}", i % 7)))
+            .collect();
+        let merger = Merger::new()
+            .sequences(SequenceStrategy::Append)
+            .nulls(NullHandling::Delete);
+        let mut expected = base.clone();
+        for overlay in &overlays {
+            merger.apply(&mut expected, overlay).unwrap();
+        }
+        let mut v = base.clone();
+        merger.par_apply_all(&mut v, &overlays).unwrap();
+        assert_eq!(v, expected);
+        assert_eq!(serde_yaml::to_string(&v).unwrap(), serde_yaml::to_string(&expected).unwrap());
+
+        let mut v = base.clone();
+        let err = Merger::new()
+            .type_conflicts(TypeConflict::Error)
+            .par_apply_all(&mut v, &[yaml("{c: 3}"), yaml("{c: [3]}")]);
+        assert!(err.is_err());
+        assert_eq!(v, base);
+        assert!(par_merge_all(&mut v, &[yaml("[1]")]).is_err());
+    }
+
+    #[test]
+    fn parallel_equivalence() {
+        let base = yaml("{Foo: {x: 1}, l: [{a: 1}], d: 2, s: x}");
+        let overlays = [
+            yaml("{foo: {y: 2}, l: [{b: 2}], n: null, d: null}"),
+            yaml("{FOO: {z: 3}, N: 1, l: [{c: 3}], s: !delete }"),
+            yaml("{new: 1, New: 2, s: [y], d: 4}"),
+        ];
+        let mergers = [
+            Merger::new(),
+            Merger::new().case_insensitive(true),
+            Merger::new().case_insensitive(true).nulls(NullHandling::Delete),
+            Merger::new().nulls(NullHandling::Delete).sequences(SequenceStrategy::MergeByIndex),
+            Merger::new().precedence(Precedence::BaseWins).sequences(SequenceStrategy::Append),
+            Merger::new().key_order(KeyOrder::Relative).type_conflicts(TypeConflict::BaseWins),
+            Merger::new().max_depth(2),
+        ];
+        for merger in &mergers {
+            for n in 1..=overlays.len() {
+                let mut expected = base.clone();
+                let expected_result = merger.apply_all(&mut expected, &overlays[..n]).map_err(|e| e.to_string());
+                let mut v = base.clone();
+                let result = merger.par_apply_all(&mut v, &overlays[..n]).map_err(|e| e.to_string());
+                assert_eq!(result, expected_result, "{merger:?}");
+                assert_eq!(serde_yaml::to_string(&v).unwrap(), serde_yaml::to_string(&expected).unwrap(),
+                           "{merger:?}");
+            }
+        }
+
+        for (base, overlay) in [(yaml("1"), yaml("{a: 1}")), (yaml("{a: 1}"), yaml("[2]")), (yaml("[1]"), yaml("[2]"))] {
+            let mut expected = base.clone();
+            let expected_result = Merger::new().apply_all(&mut expected, std::slice::from_ref(&overlay)).map_err(|e| e.to_string());
+            let mut v = base.clone();
+            let result = Merger::new().par_apply_all(&mut v, &[overlay]).map_err(|e| e.to_string());
+            assert_eq!(result, expected_result);
+            assert_eq!(v, expected);
+        }
+    }
+
+    #[test]
+    fn merge_at_path() {
+        let mut v = yaml("{a: {b: {c: 1}}, d: 2}");
+        merge_at(&mut v, "a.b", &yaml("{e: 3}")).unwrap();
+        merge_at(&mut v, "x.y", &yaml("4")).unwrap();
+        merge_at(&mut v, "", &yaml("{d: 5}")).unwrap();
+        assert_eq!(v, yaml("{a: {b: {c: 1, e: 3}}, d: 5, x: {y: 4}}"));
+        merge_at(&mut v, "/a/b.c", &yaml("6")).unwrap();
+        assert_eq!(v["a"]["b.c"], yaml("6"));
+        assert!(Merger::new().deny("/a/b.c").apply_at(&mut v, "/a", &yaml("{b.c: 7}")).is_err());
+
+        let err = Merger::new()
+            .type_conflicts(TypeConflict::Error)
+            .apply_at(&mut v, "d.f", &yaml("{g: 1}"))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "impossible to merge YAML values at 'd': number in the base, mapping in the overlay");
+    }
+
+    #[test]
+    fn protection() {
+        let base = yaml("{security: {tls: true, keys: [a]}, tenants: {a: {quota: 1}}, name: x}");
+        let overlay = yaml("{security: {tls: true, keys: null}, tenants: {a: {quota: 2}, b: {quota: 3}}, name: y}");
+        let merger = Merger::new()
+            .nulls(NullHandling::Delete)
+            .protection(Protection::Ignore)
+            .allow("tenants.*.quota")
+            .allow("name")
+            .deny("tenants.b");
+        let mut v = base.clone();
+        let report = merger.apply_with_report(&mut v, &overlay).unwrap();
+        assert_eq!(v, yaml("{security: {tls: true, keys: [a]}, tenants: {a: {quota: 2}}, name: y}"));
+        assert_eq!(report.rejected, vec![
+            MergeChange { path: vec!["security".into()], old: Some(base["security"].clone()), new: Some(overlay["security"].clone()) },
+            MergeChange { path: vec!["tenants".into(), "b".into()], old: None, new: Some(yaml("{quota: 3}")) },
+        ]);
+
+        // Nested mappings that are missing in the base are only added if they are allowed
+        let mut v = yaml("{}");
+        merger.apply(&mut v, &yaml("{tenants: {c: {quota: 4, admin: true}}, security: {tls: false}}")).unwrap();
+        assert_eq!(v, yaml("{tenants: {c: {quota: 4}}}"));
+
+        let merger = Merger::new().deny("*.password");
+        merge(&mut v, &yaml("{db: {password: secret}}")).unwrap();
+        merger.apply(&mut v.clone(), &yaml("{db: {password: secret, user: admin}, tenants: {c: {quota: 5}}}")).unwrap();
+        let err = merger.apply(&mut v, &yaml("{db: !delete }")).unwrap_err();
+        assert_eq!(err.to_string(), "impossible to mege YAML values: 'db' is write-protected");
+    }
+
+    #[test]
+    #[cfg(feature = "spans")]
+    fn anchors() {
+        let base = "---\nx: &x {a: 1}\ntext: |\n  line 1\n\n  line 2\nl: &l [1, 2]\n";
+        let overlay = "\ny: *x\nz: &z\n  - *l\nw: *z\n";
+        let merged = merged_with_anchors(base, overlay).unwrap();
+        assert_eq!(merged, yaml("{x: {a: 1}, text: \"line 1\\n\\nline 2\\n\", l: [1, 2], y: {a: 1}, z: [[1, 2]], w: [[1, 2]]}"));
+
+        assert!(merged_with_anchors("a: *nope", "b: 1").is_err());
+        assert!(merged_with_anchors("a: 1", "b: *nope").is_err());
+        assert!(merged_with_anchors("a: 1\n---\nb: 2", "b: 1").is_err());
+        assert!(merged_with_anchors("a: 1", "b: 1\n---\nb: 2").is_err());
+        let merged = merged_with_anchors("%YAML 1.2\n---\nd: &d é\n...\n", "%TAG !e! tag:example.org,2000:\n---\né: [*d, *d]\nt: !e!x *d\n");
+        assert!(merged.is_err());
+        let merged = merged_with_anchors("%YAML 1.2\n---\nd: &d é\n...\n", "%TAG !e! tag:example.org,2000:\n---\né: [*d, *d]\nt: !e!x x\n").unwrap();
+        assert_eq!(merged["é"], yaml("[é, é]"));
+        assert_eq!(merged["t"], "x");
+
+        // Block scalars, and aliases of the overlay's anchors containing aliases of the base
+        let base = "text: &text |\n  line 1\n   line 2\nfolded: &folded >-\n  a\n  b\n";
+        let overlay = "\tl:\n  - &pair [*text, *folded]\n  - *pair\n";
+        assert!(merged_with_anchors(base, overlay).is_err());
+        let overlay = "l:\n  - &pair [*text, *folded]\n  - *pair\nblock: |\n  *text\n";
+        let merged = merged_with_anchors(base, overlay).unwrap();
+        assert_eq!(merged["l"], yaml("[[\"line 1\\n line 2\\n\", a b], [\"line 1\\n line 2\\n\", a b]]"));
+        assert_eq!(merged["block"], yaml("\"*text\\n\""));
+
+        // Errors are located in their own document
+        let line = |err| match err {
+            Error::Yaml(err) => err.location().map(|l| (l.line(), l.column())),
+            Error::Located { line, column, .. } => Some((line, column)),
+            _ => None,
+        };
+        assert_eq!(line(merged_with_anchors("a: &a 1\nb: 2\n", "x: *a\ny: [1, *a\n").unwrap_err()), Some((3, 1)));
+        assert_eq!(line(merged_with_anchors("a: &a 1\nb: [\n", "x: *a\n").unwrap_err()), Some((3, 1)));
+        assert_eq!(line(merged_with_anchors("a: &a 1", "x: *a\ny: *b\n").unwrap_err()), Some((2, 4)));
+        let err = merged_with_anchors("a: &a 1", "? *a\n: 1\n").unwrap_err();
+        assert_eq!(err.to_string(), "impossible to mege YAML values: alias '*a' of the base can't be used in a key (line 1, column 3)");
+    }
+
+    #[test]
+    fn strict() {
+        let base = yaml("{a: {b: 1, c: !t {d: 2}}, e: [{f: 1}]}");
+        let overlay = yaml("{a: {b: 2, x: {y: 1}, c: !t {z: 3}}, e: [{g: 1}], h: !delete , i: 1}");
+        let paths = |v: Vec<Vec<String>>| v.iter().map(|p| p.join(".")).collect::<Vec<_>>();
+        assert_eq!(paths(unknown_keys(&base, &overlay)), ["a.x", "a.c.z", "i"]);
+        assert_eq!(paths(unknown_keys(&base, &yaml("{a: {b: 3}, e: []}"))), Vec::<String>::new());
+
+        let merger = Merger::new().strict(true);
+        let mut v = base.clone();
+        assert!(merger.apply(&mut v, &overlay).is_err());
+        assert_eq!(v, base);
+        merger.apply(&mut v, &yaml("{a: {b: 2, c: !t {d: 3}}, e: [{g: 1}]}")).unwrap();
+        assert_eq!(v, yaml("{a: {b: 2, c: !t {d: 3}}, e: [{g: 1}]}"));
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let base = yaml("{Env: {Path: a, HOME: b}, list: [1]}");
+        let overlay = yaml("{env: {PATH: c, Editor: vi}, LIST: [2], new: 1}");
+        let merger = Merger::new().case_insensitive(true);
+        assert_eq!(merger.merged(&base, &overlay).unwrap(), yaml("{Env: {Path: c, HOME: b, Editor: vi}, list: [2], new: 1}"));
+        let merged = merger.clone()
+            .key_order(KeyOrder::Relative)
+            .merged(&base, &overlay)
+            .unwrap();
+        assert_eq!(serde_yaml::to_string(&merged).unwrap(), "Env:\n  Path: c\n  Editor: vi\n  HOME: b\nlist:\n- 2\nnew: 1\n");
+        let err = merger.strict(true).merged(&base, &overlay).unwrap_err();
+        assert_eq!(err.to_string(), "impossible to mege YAML values: unknown keys in the overlay: Env.Editor, new");
+        assert_eq!(Merger::new().merged(&base, &overlay).unwrap().as_mapping().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn hooks() {
+        use std::sync::Mutex;
+
+        let calls = Mutex::new(vec![]);
+        let record = |when: &'static str| {
+            let calls = &calls;
+            move |path: &[String], v: Option<&Value>, o: &Value| {
+                calls.lock().unwrap().push((when, path.join("."), v.cloned(), o.clone()));
+            }
+        };
+        let merger = Merger::new()
+            .nulls(NullHandling::Delete)
+            .before_node(record("before"))
+            .after_node(record("after"));
+        let mut v = yaml("{a: 1, b: 2}");
+        merger.apply(&mut v, &yaml("{a: 3, b: null}")).unwrap();
+        assert_eq!(calls.lock().unwrap().clone(), vec![
+            ("before", "a".to_owned(), Some(yaml("1")), yaml("3")),
+            ("after", "a".to_owned(), Some(yaml("3")), yaml("3")),
+            ("before", "b".to_owned(), Some(yaml("2")), Value::Null),
+            ("after", "b".to_owned(), None, Value::Null),
+        ]);
+    }
+
+    #[test]
+    fn priority() {
+        let merged = Merger::new()
+            .nulls(NullHandling::Delete)
+            .merge_by_priority([
+                (1, yaml("{a: {b: 1, c: 2}, d: 3, e: {f: 1}}")),
+                (0, yaml("{a: {b: 0, g: 0}, h: 0}")),
+                (1, yaml("{a: {c: 2, i: 4}, e: null}")),
+            ])
+            .unwrap();
+        assert_eq!(merged.value, yaml("{a: {b: 1, g: 0, c: 2, i: 4}, h: 0, d: 3}"));
+        assert_eq!(merged.source(&["a", "b"]), Some(0));
+        assert_eq!(merged.source(&["a", "c"]), Some(2));
+        assert_eq!(merged.source(&["a", "g"]), Some(1));
+        assert_eq!(merged.source(&["a", "i"]), Some(2));
+        assert_eq!(merged.source(&["a"]), Some(1));
+        assert_eq!(merged.source(&["e", "f"]), None);
+        assert_eq!(merged.source(&["x"]), None);
+    }
+
+    #[test]
+    fn priority_ties() {
+        let merged = Merger::new()
+            .merge_by_priority([(0, yaml("{port: 80}")), (10, yaml("{port: 80}"))])
+            .unwrap();
+        assert_eq!(merged.source(&["port"]), Some(1));
+        let merged = Merger::new()
+            .merge_by_priority([(10, yaml("{port: 80, l: [1]}")), (0, yaml("{port: 80, l: [1]}")), (5, yaml("{port: 80}"))])
+            .unwrap();
+        assert_eq!(merged.source(&["port"]), Some(0));
+        assert_eq!(merged.source(&["l"]), Some(0));
+        // A value kept because of the precedence isn't provided by the overlay
+        let merged = Merger::new()
+            .precedence(Precedence::BaseWins)
+            .merge_by_priority([(0, yaml("{port: 80}")), (10, yaml("{port: 8080}"))])
+            .unwrap();
+        assert_eq!(merged.value, yaml("{port: 80}"));
+        assert_eq!(merged.source(&["port"]), Some(0));
+    }
+
+    #[test]
+    fn profiles() {
+        let base = yaml("{a: {b: 1, c: 2}, envs: {dev: {a: {b: 3}}, prod: {a: {c: null}, d: 4}}}");
+        let mut v = base.clone();
+        assert!(apply_profile(&mut v, "dev").is_err());
+        let merger = Merger::new()
+            .nulls(NullHandling::Delete)
+            .profiles_key("envs");
+        assert!(merger.apply_profile(&mut v, "staging").is_err());
+        assert_eq!(v, base);
+        merger.apply_profile(&mut v, "prod").unwrap();
+        assert_eq!(v, yaml("{a: {b: 1}, d: 4}"));
+    }
+
+    #[test]
+    fn defaults() {
+        let mut v = yaml("{a: null, b: {c: 1, d: [1]}, e: 2, f: {g: 3}}");
+        let defaults = yaml("{a: 1, b: {c: 2, d: [2, 3], x: null}, e: {h: 4}, f: 5, y: null, z: {w: !delete , u: 6}}");
+        apply_defaults(&mut v, &defaults).unwrap();
+        assert_eq!(v, yaml("{a: null, b: {c: 1, d: [1], x: null}, e: 2, f: {g: 3}, y: null, z: {u: 6}}"));
+
+        assert!(apply_defaults(&mut yaml("[1]"), &defaults).is_err());
+    }
+
+    #[test]
+    fn merge_keys() {
+        let source = "
+base: &base {a: {b: 1, c: 2}, d: [1]}
+other: &other {a: {b: 3}, e: 4}
+single: {<<: *base, d: [2]}
+list:
+  - <<: [*other, *base]
+    f: {<<: *other}
+";
+        let mut v = yaml(source);
+        expand_merge_keys(&mut v).unwrap();
+        assert_eq!(v["single"], yaml("{a: {b: 1, c: 2}, d: [2]}"));
+        assert_eq!(v["list"], yaml("[{a: {b: 3, c: 2}, d: [1], e: 4, f: {a: {b: 3}, e: 4}}]"));
+
+        let mut v = yaml(source);
+        Merger::new().shallow(true).expand_merge_keys(&mut v).unwrap();
+        assert_eq!(v["list"][0]["a"], yaml("{b: 3}"));
+
+        let mut v = yaml("{a: [{<<: 1}]}");
+        assert_eq!(expand_merge_keys(&mut v).unwrap_err().to_string(),
+                   "impossible to mege YAML values: '<<' at 'a.0' should be a mapping or a list of mappings");
+        assert_eq!(v, yaml("{a: [{<<: 1}]}"));
+    }
 }
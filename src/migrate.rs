@@ -0,0 +1,370 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::error::{Error, Result};
+use crate::path::{self, get_tokens, to_pointer, tokens};
+
+use serde_yaml::Value;
+
+use std::fmt;
+use std::sync::Arc;
+
+type StepFn<'m> = Arc<dyn Fn(&mut Value) -> Result<()> + Send + Sync + 'm>;
+
+/// What to do when a document contains both the old and the new path of a renamed key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenameConflict {
+    /// Returns an error (default)
+    #[default]
+    Error,
+    /// Keeps the value at the new path, the old one is removed
+    KeepNew,
+    /// Moves the value at the old path to the new one, replacing it
+    KeepOld,
+}
+
+/// A renamed key found in a document, e.g. to warn users that it is deprecated
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notice {
+    /// The old path, as given to `Migrator::rename`
+    pub old: String,
+    /// The new path
+    pub new: String,
+    /// The value that was dropped because both paths were present, if any
+    pub discarded: Option<Value>,
+}
+
+impl fmt::Display for Notice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "'{}' is deprecated, use '{}' instead", self.old, self.new)?;
+        if self.discarded.is_some() {
+            write!(f, " (both were present, only one value was kept)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Moves the values of renamed keys to their new paths, so that documents written for older
+/// versions of a program still work
+///
+/// Renames are applied in order; paths are dotted or JSON Pointers (see the `path` module).
+/// Mappings left empty by moving a value out of them are removed, unless they are
+/// items of a sequence.
+///
+/// # Example
+///
+/// ```
+/// let mut config: serde_yaml::Value = serde_yaml::from_str("{db: {host: localhost}, verbose: true}")?;
+/// let notices = yaml_extras::Migrator::new()
+///     .rename("db.host", "database.host")
+///     .rename("db.port", "database.port")
+///     .rename("verbose", "log.verbose")
+///     .apply(&mut config)?;
+/// assert_eq!(config, serde_yaml::from_str::<serde_yaml::Value>("{database: {host: localhost}, log: {verbose: true}}")?);
+/// assert_eq!(notices.len(), 2);
+/// assert_eq!(notices[0].to_string(), "'db.host' is deprecated, use 'database.host' instead");
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Migrator {
+    renames: Vec<(String, String)>,
+    conflicts: RenameConflict,
+}
+
+impl Migrator {
+    /// Creates a new Migrator, without any rename
+    pub fn new() -> Self {
+        Migrator::default()
+    }
+
+    /// Adds a rename from the `old` path to the `new` one
+    pub fn rename<S: Into<String>, T: Into<String>>(mut self, old: S, new: T) -> Self {
+        self.renames.push((old.into(), new.into()));
+        self
+    }
+
+    /// Sets what to do when both the old and the new path are present (default is
+    /// `RenameConflict::Error`)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use yaml_extras::{Migrator, RenameConflict};
+    ///
+    /// let mut config: serde_yaml::Value = serde_yaml::from_str("{port: 80, http: {port: 8080}}")?;
+    /// let migrator = Migrator::new()
+    ///     .rename("port", "http.port");
+    /// assert!(migrator.apply(&mut config.clone()).is_err());
+    /// let notices = migrator
+    ///     .conflicts(RenameConflict::KeepNew)
+    ///     .apply(&mut config)?;
+    /// assert_eq!(config, serde_yaml::from_str::<serde_yaml::Value>("{http: {port: 8080}}")?);
+    /// assert_eq!(notices[0].discarded, Some(80.into()));
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn conflicts(mut self, conflicts: RenameConflict) -> Self {
+        self.conflicts = conflicts;
+        self
+    }
+
+    /// Applies the renames to `value` and returns a notice for each old path that was found
+    ///
+    /// On error, `value` is left unmodified.
+    pub fn apply(&self, value: &mut Value) -> Result<Vec<Notice>> {
+        let mut migrated = value.clone();
+        let mut notices = vec![];
+        for (old, new) in &self.renames {
+            let old_keys = tokens(old);
+            if old_keys.is_empty() || get_tokens(&migrated, &old_keys).is_none() {
+                continue;
+            }
+            let present = get_tokens(&migrated, &tokens(new)).is_some();
+            if present && self.conflicts == RenameConflict::Error {
+                return Err(Error::Migrate(format!("both '{old}' and its new name '{new}' are present")));
+            }
+            let moved = remove(&mut migrated, &old_keys);
+            let discarded = if present && self.conflicts == RenameConflict::KeepNew {
+                Some(moved)
+            } else {
+                path::set(&mut migrated, new, moved)?
+                    .filter(|_| present)
+            };
+            notices.push(Notice {
+                old: old.clone(),
+                new: new.clone(),
+                discarded,
+            });
+        }
+        *value = migrated;
+        Ok(notices)
+    }
+}
+
+enum Step<'m> {
+    Renames(Migrator),
+    Custom(StepFn<'m>),
+}
+
+/// What `Migrations::apply` did
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MigrationReport {
+    /// The version of the document before the migration
+    pub from: u64,
+    /// The version of the document after the migration
+    pub to: u64,
+    /// The versions of the steps that were applied, in order
+    pub steps: Vec<u64>,
+    /// The notices of the renames of the applied steps
+    pub notices: Vec<Notice>,
+}
+
+/// Ordered migration steps, each bringing a document to a version given by a field of the
+/// document itself
+///
+/// The steps whose version is greater than the one of the document are applied in ascending
+/// order of version, then the version of the document is set to the last one. A document
+/// without a version is at version 0.
+///
+/// # Example
+///
+/// ```
+/// use yaml_extras::{Migrations, Migrator};
+///
+/// let migrations = Migrations::new()
+///     .step(2, Migrator::new().rename("db", "database"))
+///     .step_fn(3, |config| {
+///         yaml_extras::path::set(config, "database.pool", 10)?;
+///         Ok(())
+///     });
+///
+/// let mut config: serde_yaml::Value = serde_yaml::from_str("{version: 1, db: {host: localhost}}")?;
+/// let report = migrations.apply(&mut config)?;
+/// assert_eq!(config, serde_yaml::from_str::<serde_yaml::Value>("{version: 3, database: {host: localhost, pool: 10}}")?);
+/// assert_eq!((report.from, report.to, report.steps), (1, 3, vec![2, 3]));
+/// assert_eq!(report.notices[0].old, "db");
+///
+/// // Already up to date
+/// assert!(migrations.apply(&mut config)?.steps.is_empty());
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub struct Migrations<'m> {
+    version_key: String,
+    steps: Vec<(u64, Step<'m>)>,
+}
+
+impl fmt::Debug for Migrations<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Migrations")
+            .field("version_key", &self.version_key)
+            .field("steps", &self.steps.iter().map(|(version, _)| version).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Default for Migrations<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'m> Migrations<'m> {
+    /// Creates new Migrations, without any step
+    pub fn new() -> Self {
+        Migrations {
+            version_key: "version".to_owned(),
+            steps: vec![],
+        }
+    }
+
+    /// Sets the path of the version of the documents (default is `version`)
+    pub fn version_key<S: Into<String>>(mut self, version_key: S) -> Self {
+        self.version_key = version_key.into();
+        self
+    }
+
+    /// Adds a step applying the renames of `migrator` to reach `version`
+    pub fn step(mut self, version: u64, migrator: Migrator) -> Self {
+        self.steps.push((version, Step::Renames(migrator)));
+        self
+    }
+
+    /// Adds a step calling `f` to reach `version`
+    pub fn step_fn<F>(mut self, version: u64, f: F) -> Self
+    where F: Fn(&mut Value) -> Result<()> + Send + Sync + 'm {
+        self.steps.push((version, Step::Custom(Arc::new(f))));
+        self
+    }
+
+    /// The version of `value`
+    fn version(&self, value: &Value) -> Result<u64> {
+        match path::get(value, &self.version_key) {
+            None | Some(Value::Null) => Ok(0),
+            Some(v) => v.as_u64()
+                .ok_or_else(|| Error::Migrate(format!("invalid version at '{}'", self.version_key))),
+        }
+    }
+
+    /// Applies the pending steps to `value` and returns what was done
+    ///
+    /// On error, `value` is left unmodified.
+    pub fn apply(&self, value: &mut Value) -> Result<MigrationReport> {
+        let from = self.version(value)?;
+        let mut report = MigrationReport { from, to: from, ..Default::default() };
+        let mut steps: Vec<_> = self.steps.iter()
+            .filter(|(version, _)| *version > from)
+            .collect();
+        if steps.is_empty() {
+            return Ok(report);
+        }
+        steps.sort_by_key(|(version, _)| *version);
+        let mut migrated = value.clone();
+        for (version, step) in steps {
+            match step {
+                Step::Renames(migrator) => report.notices.extend(migrator.apply(&mut migrated)?),
+                Step::Custom(f) => f(&mut migrated)?,
+            }
+            report.steps.push(*version);
+            report.to = *version;
+        }
+        path::set(&mut migrated, &self.version_key, report.to)?;
+        *value = migrated;
+        Ok(report)
+    }
+}
+
+/// Removes the existing value at `keys`, and the mappings this leaves empty (unless they are
+/// items of a sequence)
+fn remove(value: &mut Value, keys: &[String]) -> Value {
+    let removed = path::remove(value, &to_pointer(keys))
+        .unwrap_or_default();
+    for i in (1..keys.len()).rev() {
+        let in_mapping = get_tokens(value, &keys[..i - 1]).is_some_and(Value::is_mapping);
+        match get_tokens(value, &keys[..i]) {
+            Some(Value::Mapping(m)) if m.is_empty() && in_mapping => {
+                path::remove(value, &to_pointer(&keys[..i]));
+            },
+            _ => break,
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yaml;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn renames() {
+        let mut v = yaml("{a: {b: {c: 1}, d: 2}, e: [{f: 3}], g: 4}");
+        let notices = Migrator::new()
+            .rename("a.b.c", "x.c")
+            .rename("e.0.f", "e.0.h")
+            .rename("missing", "y")
+            .rename("g", "g.value")
+            .apply(&mut v)
+            .unwrap();
+        assert_eq!(v, yaml("{a: {d: 2}, e: [{h: 3}], x: {c: 1}, g: {value: 4}}"));
+        assert_eq!(notices.iter().map(|n| n.old.as_str()).collect::<Vec<_>>(), ["a.b.c", "e.0.f", "g"]);
+        assert!(notices.iter().all(|n| n.discarded.is_none()));
+    }
+
+    #[test]
+    fn rename_conflicts() {
+        let v = yaml("{old: {a: 1}, new: {b: 2}, other: 3}");
+        let migrator = Migrator::new()
+            .rename("other", "moved")
+            .rename("old", "new");
+
+        let mut migrated = v.clone();
+        let err = migrator.clone().apply(&mut migrated).unwrap_err();
+        assert_eq!(err.to_string(), "impossible to migrate YAML value: both 'old' and its new name 'new' are present");
+        assert_eq!(migrated, v);
+
+        let mut migrated = v.clone();
+        let notices = migrator.clone().conflicts(RenameConflict::KeepOld).apply(&mut migrated).unwrap();
+        assert_eq!(migrated, yaml("{new: {a: 1}, moved: 3}"));
+        assert_eq!(notices[1].discarded, Some(yaml("{b: 2}")));
+
+        let mut migrated = v.clone();
+        let notices = migrator.conflicts(RenameConflict::KeepNew).apply(&mut migrated).unwrap();
+        assert_eq!(migrated, yaml("{new: {b: 2}, moved: 3}"));
+        assert_eq!(notices[1].discarded, Some(yaml("{a: 1}")));
+        assert_eq!(notices[1].to_string(), "'old' is deprecated, use 'new' instead (both were present, only one value was kept)");
+    }
+
+    #[test]
+    fn versions() {
+        let migrations = Migrations::new()
+            .version_key("meta.version")
+            .step(3, Migrator::new().rename("b", "c"))
+            .step(2, Migrator::new().rename("a", "b"))
+            .step_fn(4, |v| {
+                if v.get("c").is_none() {
+                    return Err(Error::Migrate("c is missing".into()));
+                }
+                Ok(())
+            });
+
+        let mut v = yaml("{a: 1}");
+        let report = migrations.apply(&mut v).unwrap();
+        assert_eq!(v, yaml("{c: 1, meta: {version: 4}}"));
+        assert_eq!(report.steps, [2, 3, 4]);
+        assert_eq!(report.notices.iter().map(|n| n.new.as_str()).collect::<Vec<_>>(), ["b", "c"]);
+
+        let mut v = yaml("{b: 1, meta: {version: 2}}");
+        assert_eq!(migrations.apply(&mut v).unwrap().steps, [3, 4]);
+        assert_eq!(v, yaml("{c: 1, meta: {version: 4}}"));
+
+        let mut v = yaml("{meta: {version: 3}}");
+        assert!(migrations.apply(&mut v).is_err());
+        assert_eq!(v, yaml("{meta: {version: 3}}"));
+
+        let mut v = yaml("{meta: {version: 5}}");
+        let report = migrations.apply(&mut v).unwrap();
+        assert_eq!((report.from, report.to), (5, 5));
+        assert!(migrations.apply(&mut yaml("{meta: {version: two}}")).is_err());
+    }
+}
@@ -0,0 +1,532 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! JSON Patch ([RFC 6902](https://www.rfc-editor.org/rfc/rfc6902)) for YAML values.
+//!
+//! Paths are JSON Pointers ([RFC 6901](https://www.rfc-editor.org/rfc/rfc6901)), e.g.
+//! `/server/ports/0`, where `~1` stands for `/` and `~0` for `~` inside a key.
+//!
+//! # Example
+//!
+//! ```
+//! use yaml_extras::patch::{self, PatchOp};
+//!
+//! let mut config: serde_yaml::Value = serde_yaml::from_str("{server: {port: 80}, debug: true}")?;
+//! let target: serde_yaml::Value = serde_yaml::from_str("{server: {port: 8080}}")?;
+//!
+//! let ops = patch::diff(&config, &target);
+//! assert_eq!(ops, vec![PatchOp::Replace { path: "/server/port".into(), value: 8080.into() },
+//!                      PatchOp::Remove { path: "/debug".into() }]);
+//!
+//! patch::apply(&mut config, &ops)?;
+//! assert_eq!(config, target);
+//! # Ok::<(), yaml_extras::Error>(())
+//! ```
+
+use crate::error::{Error, Result};
+use crate::path::{self, find_key, get_tokens, get_tokens_mut, key_token, parse_index, to_pointer, tokens};
+
+use serde_yaml::{Mapping, Value};
+
+/// An operation of a JSON Patch
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchOp {
+    /// Adds a value to a mapping, replacing the existing one, or inserts it in a sequence
+    /// (`-` as last token appends to the sequence)
+    Add { path: String, value: Value },
+    /// Removes a value, which must exist
+    Remove { path: String },
+    /// Replaces a value, which must exist
+    Replace { path: String, value: Value },
+    /// Removes a value and adds it at another path
+    Move { from: String, path: String },
+    /// Copies a value to another path
+    Copy { from: String, path: String },
+    /// Checks that the value at a path is equal to the given one
+    Test { path: String, value: Value },
+}
+
+impl PatchOp {
+    /// Reads an operation from its JSON Patch representation, e.g.
+    /// `{op: add, path: /a, value: 1}`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use yaml_extras::patch::PatchOp;
+    ///
+    /// let v: serde_yaml::Value = serde_yaml::from_str("{op: move, from: /a, path: /b}")?;
+    /// assert_eq!(PatchOp::from_value(&v)?, PatchOp::Move { from: "/a".into(), path: "/b".into() });
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn from_value(value: &Value) -> Result<PatchOp> {
+        let field = |name: &str| value.get(name)
+            .ok_or_else(|| error(format!("missing field '{name}' in operation {value:?}")));
+        let string = |name: &str| field(name).and_then(|v| v.as_str()
+                                                       .map(|s| s.to_owned())
+                                                       .ok_or_else(|| error(format!("field '{name}' is not a string"))));
+        let op = string("op")?;
+        let path = string("path")?;
+        match op.as_str() {
+            "add" => Ok(PatchOp::Add { path, value: field("value")?.clone() }),
+            "remove" => Ok(PatchOp::Remove { path }),
+            "replace" => Ok(PatchOp::Replace { path, value: field("value")?.clone() }),
+            "move" => Ok(PatchOp::Move { from: string("from")?, path }),
+            "copy" => Ok(PatchOp::Copy { from: string("from")?, path }),
+            "test" => Ok(PatchOp::Test { path, value: field("value")?.clone() }),
+            _ => Err(error(format!("unknown operation '{op}'"))),
+        }
+    }
+
+    /// Converts the operation to its JSON Patch representation
+    pub fn to_value(&self) -> Value {
+        let mut m = Mapping::new();
+        let (op, path) = match self {
+            PatchOp::Add { path, .. } => ("add", path),
+            PatchOp::Remove { path } => ("remove", path),
+            PatchOp::Replace { path, .. } => ("replace", path),
+            PatchOp::Move { path, .. } => ("move", path),
+            PatchOp::Copy { path, .. } => ("copy", path),
+            PatchOp::Test { path, .. } => ("test", path),
+        };
+        m.insert("op".into(), op.into());
+        match self {
+            PatchOp::Move { from, .. } | PatchOp::Copy { from, .. } => {
+                m.insert("from".into(), from.as_str().into());
+            },
+            _ => (),
+        }
+        m.insert("path".into(), path.as_str().into());
+        match self {
+            PatchOp::Add { value, .. } | PatchOp::Replace { value, .. } | PatchOp::Test { value, .. } => {
+                m.insert("value".into(), value.clone());
+            },
+            _ => (),
+        }
+        Value::Mapping(m)
+    }
+}
+
+fn error(msg: String) -> Error {
+    Error::Patch(msg)
+}
+
+/// Splits a JSON Pointer into unescaped tokens
+pub(crate) fn parse_pointer(pointer: &str) -> Result<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(vec![]);
+    }
+    let rest = pointer.strip_prefix('/')
+        .ok_or_else(|| error(format!("invalid pointer '{pointer}', it must start with '/'")))?;
+    Ok(rest.split('/')
+       .map(|t| t.replace("~1", "/").replace("~0", "~"))
+       .collect())
+}
+
+/// Parses a sequence index, which must not have leading zeros
+fn index(token: &str, len: usize, pointer: &str) -> Result<usize> {
+    parse_index(token, len).ok_or_else(|| error(format!("invalid index '{token}' in '{pointer}'")))
+}
+
+fn get_mut<'v>(value: &'v mut Value, tokens: &[String], pointer: &str) -> Result<&'v mut Value> {
+    get_tokens_mut(value, tokens).ok_or_else(|| error(format!("path '{pointer}' does not exist")))
+}
+
+/// The parent of the value at `pointer`, and the last token
+fn parent<'v>(value: &'v mut Value, pointer: &str) -> Result<(&'v mut Value, String)> {
+    let mut tokens = parse_pointer(pointer)?;
+    let last = tokens.pop()
+        .ok_or_else(|| error("the root can't be added or removed".to_owned()))?;
+    let mut parent = get_mut(value, &tokens, pointer)?;
+    if let Value::Tagged(tagged) = parent {
+        parent = &mut tagged.value;
+    }
+    Ok((parent, last))
+}
+
+fn add(value: &mut Value, pointer: &str, new: Value) -> Result<()> {
+    if pointer.is_empty() {
+        *value = new;
+        return Ok(());
+    }
+    let (parent, last) = parent(value, pointer)?;
+    match parent {
+        Value::Mapping(m) => {
+            let key = find_key(m, &last).unwrap_or_else(|| Value::from(last));
+            m.insert(key, new);
+        },
+        Value::Sequence(s) if last == "-" => s.push(new),
+        Value::Sequence(s) => {
+            // Inserting at the end is allowed
+            let i = index(&last, s.len() + 1, pointer)?;
+            s.insert(i, new);
+        },
+        _ => return Err(error(format!("parent of '{pointer}' is not a mapping or a sequence"))),
+    }
+    Ok(())
+}
+
+fn remove(value: &mut Value, pointer: &str) -> Result<Value> {
+    let (parent, last) = parent(value, pointer)?;
+    let removed = match parent {
+        Value::Mapping(m) => find_key(m, &last).and_then(|k| m.shift_remove(&k)),
+        Value::Sequence(s) => {
+            let i = index(&last, s.len(), pointer)?;
+            Some(s.remove(i))
+        },
+        _ => None,
+    };
+    removed.ok_or_else(|| error(format!("path '{pointer}' does not exist")))
+}
+
+/// Applies a single operation
+fn apply_op(value: &mut Value, op: &PatchOp) -> Result<()> {
+    match op {
+        PatchOp::Add { path, value: new } => add(value, path, new.clone()),
+        PatchOp::Remove { path } => remove(value, path).map(|_| ()),
+        PatchOp::Replace { path, value: new } => {
+            let tokens = parse_pointer(path)?;
+            *get_mut(value, &tokens, path)? = new.clone();
+            Ok(())
+        },
+        PatchOp::Move { from, path } => {
+            if from == path {
+                return Ok(());
+            }
+            if path.starts_with(&format!("{from}/")) {
+                return Err(error(format!("can't move '{from}' into one of its children")));
+            }
+            let moved = remove(value, from)?;
+            add(value, path, moved)
+        },
+        PatchOp::Copy { from, path } => {
+            let copied = get_tokens(value, &parse_pointer(from)?)
+                .ok_or_else(|| error(format!("path '{from}' does not exist")))?
+                .clone();
+            add(value, path, copied)
+        },
+        PatchOp::Test { path, value: expected } => {
+            match get_tokens(value, &parse_pointer(path)?) {
+                Some(v) if v == expected => Ok(()),
+                Some(v) => Err(error(format!("test failed at '{path}': expected {expected:?}, found {v:?}"))),
+                None => Err(error(format!("test failed: path '{path}' does not exist"))),
+            }
+        },
+    }
+}
+
+/// Applies a patch to a value.
+///
+/// The patch is applied atomically: if an operation fails, `value` is left unchanged.
+pub fn apply(value: &mut Value, ops: &[PatchOp]) -> Result<()> {
+    let mut patched = value.clone();
+    for op in ops {
+        apply_op(&mut patched, op)?;
+    }
+    *value = patched;
+    Ok(())
+}
+
+/// Same as `apply`, also returning the patch that restores the previous value, e.g. to undo
+/// the changes.
+///
+/// Keys that were removed are added back at the end of their mapping.
+///
+/// # Example
+///
+/// ```
+/// use yaml_extras::patch::{self, PatchOp};
+///
+/// let mut config: serde_yaml::Value = serde_yaml::from_str("{a: 1, b: [2]}")?;
+/// let original = config.clone();
+/// let undo = patch::apply_with_inverse(&mut config, &[
+///     PatchOp::Remove { path: "/a".into() },
+///     PatchOp::Add { path: "/b/-".into(), value: 3.into() },
+/// ])?;
+/// patch::apply(&mut config, &undo)?;
+/// assert_eq!(config, original);
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn apply_with_inverse(value: &mut Value, ops: &[PatchOp]) -> Result<Vec<PatchOp>> {
+    let old = value.clone();
+    apply(value, ops)?;
+    Ok(diff(value, &old))
+}
+
+/// Computes a patch that transforms `base` into `target`.
+///
+/// Mappings are compared key by key and sequences item by item, so the patch only touches
+/// the values that differ.
+pub fn diff(base: &Value, target: &Value) -> Vec<PatchOp> {
+    let mut ops = vec![];
+    diff_at(base, target, &mut vec![], &mut ops);
+    ops
+}
+
+fn diff_at(base: &Value, target: &Value, path: &mut Vec<String>, ops: &mut Vec<PatchOp>) {
+    if base == target {
+        return;
+    }
+    match (base, target) {
+        (Value::Mapping(b), Value::Mapping(t)) => {
+            for (k, t_val) in t {
+                path.push(key_token(k));
+                match b.get(k) {
+                    Some(b_val) => diff_at(b_val, t_val, path, ops),
+                    None => ops.push(PatchOp::Add { path: to_pointer(path), value: t_val.clone() }),
+                }
+                path.pop();
+            }
+            for k in b.keys().filter(|k| !t.contains_key(*k)) {
+                path.push(key_token(k));
+                ops.push(PatchOp::Remove { path: to_pointer(path) });
+                path.pop();
+            }
+        },
+        (Value::Sequence(b), Value::Sequence(t)) => {
+            for (i, (b_val, t_val)) in b.iter().zip(t).enumerate() {
+                path.push(i.to_string());
+                diff_at(b_val, t_val, path, ops);
+                path.pop();
+            }
+            for (i, t_val) in t.iter().enumerate().skip(b.len()) {
+                path.push(i.to_string());
+                ops.push(PatchOp::Add { path: to_pointer(path), value: t_val.clone() });
+                path.pop();
+            }
+            // Removed from the end so that indices stay valid
+            for i in (t.len()..b.len()).rev() {
+                path.push(i.to_string());
+                ops.push(PatchOp::Remove { path: to_pointer(path) });
+                path.pop();
+            }
+        },
+        _ => ops.push(PatchOp::Replace { path: to_pointer(path), value: target.clone() }),
+    }
+}
+
+/// A change made by a `Patch`
+#[derive(Debug, Clone, PartialEq)]
+enum Edit {
+    Set(String, Value),
+    Remove(String),
+    Append(String, Value),
+}
+
+/// A list of changes to a value, using dotted paths (e.g. `servers.0.host`) or JSON Pointers
+/// (see the `path` module), that are applied all at once.
+///
+/// Unlike JSON Patch operations, `set` and `append` create the missing mappings on their
+/// path.
+///
+/// # Example
+///
+/// ```
+/// use yaml_extras::patch::Patch;
+///
+/// let mut config: serde_yaml::Value = serde_yaml::from_str("{server: {port: 80}, debug: true}")?;
+/// Patch::new()
+///     .set("server.port", 8080)
+///     .set("server.tls.cert", "/etc/cert.pem")
+///     .append("server.aliases", "example.org")
+///     .remove("debug")
+///     .apply(&mut config)?;
+///
+/// let expected: serde_yaml::Value = serde_yaml::from_str("
+/// server:
+///   port: 8080
+///   tls: {cert: /etc/cert.pem}
+///   aliases: [example.org]")?;
+/// assert_eq!(config, expected);
+///
+/// // If a change fails, none is applied
+/// let res = Patch::new()
+///     .set("server.port", 80)
+///     .remove("missing")
+///     .apply(&mut config);
+/// assert!(res.is_err());
+/// assert_eq!(config, expected);
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Patch {
+    edits: Vec<Edit>,
+}
+
+impl Patch {
+    /// Creates an empty patch
+    pub fn new() -> Self {
+        Patch { edits: vec![] }
+    }
+
+    /// Sets the value at `path`, creating missing mappings
+    pub fn set<P: Into<String>, V: Into<Value>>(mut self, path: P, value: V) -> Self {
+        self.edits.push(Edit::Set(path.into(), value.into()));
+        self
+    }
+
+    /// Removes the value at `path`, which must exist
+    pub fn remove<P: Into<String>>(mut self, path: P) -> Self {
+        self.edits.push(Edit::Remove(path.into()));
+        self
+    }
+
+    /// Adds a value at the end of the sequence at `path`, which is created if it is missing
+    pub fn append<P: Into<String>, V: Into<Value>>(mut self, path: P, value: V) -> Self {
+        self.edits.push(Edit::Append(path.into(), value.into()));
+        self
+    }
+
+    /// Applies the changes in order. If one of them fails, `value` is left unchanged.
+    pub fn apply(&self, value: &mut Value) -> Result<()> {
+        let mut patched = value.clone();
+        for edit in &self.edits {
+            match edit {
+                Edit::Set(path, new) => *entry(&mut patched, path)? = new.clone(),
+                Edit::Remove(path) => {
+                    let tokens = tokens(path);
+                    let pointer = to_pointer(&tokens);
+                    remove(&mut patched, &pointer)?;
+                },
+                Edit::Append(path, new) => {
+                    let target = entry(&mut patched, path)?;
+                    if target.is_null() {
+                        *target = Value::Sequence(vec![]);
+                    }
+                    match target {
+                        Value::Sequence(s) => s.push(new.clone()),
+                        _ => return Err(error(format!("'{path}' is not a sequence"))),
+                    }
+                },
+            }
+        }
+        *value = patched;
+        Ok(())
+    }
+
+    /// Same as `apply`, also returning the JSON patch that restores the previous value (see
+    /// `apply_with_inverse`)
+    pub fn apply_with_inverse(&self, value: &mut Value) -> Result<Vec<PatchOp>> {
+        let old = value.clone();
+        self.apply(value)?;
+        Ok(diff(value, &old))
+    }
+}
+
+/// The value at a path, creating missing mappings (with a null value at the end)
+fn entry<'v>(value: &'v mut Value, path: &str) -> Result<&'v mut Value> {
+    path::entry(value, &tokens(path), path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yaml;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn pointers() {
+        assert_eq!(parse_pointer("").unwrap(), Vec::<String>::new());
+        assert_eq!(parse_pointer("/a~1b/~01/").unwrap(), vec!["a/b", "~1", ""]);
+        assert!(parse_pointer("a").is_err());
+        assert_eq!(to_pointer(&["a/b", "~1"]), "/a~1b/~01");
+    }
+
+    #[test]
+    fn operations() {
+        let mut v = yaml("{a: {b: [1, 2]}, 1: one, c: x}");
+        let ops = yaml("
+- {op: add, path: /a/b/1, value: 5}
+- {op: add, path: /a/b/-, value: 6}
+- {op: remove, path: /c}
+- {op: replace, path: /1, value: uno}
+- {op: copy, from: /a/b, path: /d}
+- {op: move, from: /a/b/0, path: /e}
+- {op: test, path: /d/1, value: 5}");
+        let ops: Vec<PatchOp> = ops.as_sequence().unwrap()
+            .iter()
+            .map(|op| PatchOp::from_value(op).unwrap())
+            .collect();
+        apply(&mut v, &ops).unwrap();
+        assert_eq!(v, yaml("{a: {b: [5, 2, 6]}, 1: uno, d: [1, 5, 2, 6], e: 1}"));
+        for op in &ops {
+            assert_eq!(&PatchOp::from_value(&op.to_value()).unwrap(), op);
+        }
+
+        let before = v.clone();
+        let failing = [PatchOp::Remove { path: "/e".into() },
+                       PatchOp::Test { path: "/a/b/0".into(), value: 1.into() }];
+        assert!(apply(&mut v, &failing).is_err());
+        assert_eq!(v, before);
+        for op in [PatchOp::Remove { path: "/z".into() },
+                   PatchOp::Replace { path: "/a/b/01".into(), value: 1.into() },
+                   PatchOp::Add { path: "/a/b/9".into(), value: 1.into() },
+                   PatchOp::Move { from: "/a".into(), path: "/a/c".into() }] {
+            assert!(apply(&mut v, &[op]).is_err());
+        }
+    }
+
+    #[test]
+    fn diff_roundtrip() {
+        let base = yaml("{a: {b: [1, 2, 3], c: x}, d: 1, e/f: {g: 1}}");
+        let target = yaml("{a: {b: [1, 4], h: y}, d: [1], e/f: {g: 2}, i: true}");
+        let ops = diff(&base, &target);
+        assert_eq!(ops, vec![
+            PatchOp::Replace { path: "/a/b/1".into(), value: 4.into() },
+            PatchOp::Remove { path: "/a/b/2".into() },
+            PatchOp::Add { path: "/a/h".into(), value: "y".into() },
+            PatchOp::Remove { path: "/a/c".into() },
+            PatchOp::Replace { path: "/d".into(), value: yaml("[1]") },
+            PatchOp::Replace { path: "/e~1f/g".into(), value: 2.into() },
+            PatchOp::Add { path: "/i".into(), value: true.into() },
+        ]);
+        let mut v = base.clone();
+        apply(&mut v, &ops).unwrap();
+        assert_eq!(v, target);
+        assert_eq!(diff(&target, &target), vec![]);
+    }
+
+    #[test]
+    fn patch_builder() {
+        let mut v = yaml("{a: [{b: 1}], c: 1, d: [1]}");
+        Patch::new()
+            .set("a.0.b", 2)
+            .set("x.y", yaml("{z: 1}"))
+            .append("d", 2)
+            .append("e", 1)
+            .remove("a.0")
+            .apply(&mut v)
+            .unwrap();
+        assert_eq!(v, yaml("{a: [], c: 1, d: [1, 2], x: {y: {z: 1}}, e: [1]}"));
+        for patch in [Patch::new().set("c.d", 1),
+                      Patch::new().set("a.1", 1),
+                      Patch::new().append("c", 1),
+                      Patch::new().remove("z")] {
+            assert!(patch.apply(&mut v).is_err());
+        }
+        Patch::new().set("", 1).apply(&mut v).unwrap();
+        assert_eq!(v, yaml("1"));
+    }
+
+    #[test]
+    fn inverse() {
+        let original = yaml("{a: {b: 1, c: [1, 2]}, d: x}");
+        let mut v = original.clone();
+        let undo = Patch::new()
+            .set("a.b", 2)
+            .remove("d")
+            .append("a.c", 3)
+            .set("e.f", true)
+            .apply_with_inverse(&mut v)
+            .unwrap();
+        assert_eq!(v, yaml("{a: {b: 2, c: [1, 2, 3]}, e: {f: true}}"));
+        apply(&mut v, &undo).unwrap();
+        assert_eq!(v, original);
+
+        // Nothing is returned on failure
+        let mut v = original.clone();
+        assert!(apply_with_inverse(&mut v, &[PatchOp::Remove { path: "/z".into() }]).is_err());
+        assert_eq!(v, original);
+    }
+}
@@ -0,0 +1,788 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Access to nested values with paths.
+//!
+//! Paths are either dotted, e.g. `servers.0.host`, or JSON Pointers
+//! ([RFC 6901](https://www.rfc-editor.org/rfc/rfc6901)) when they start with `/`, e.g.
+//! `/servers/0/host`, where `~1` stands for `/` and `~0` for `~` inside a key. Pointers allow
+//! keys containing dots; all the functions of this crate taking paths accept both.
+//!
+//! Each key of a path matches a string key of a mapping, or another scalar key displayed the
+//! same way (e.g. `8080` or `true`), and indexes sequences (without leading zeros). Tagged
+//! values are looked through.
+//!
+//! # Example
+//!
+//! ```
+//! use yaml_extras::path;
+//!
+//! let mut config: serde_yaml::Value = serde_yaml::from_str("{servers: [{host: localhost}]}")?;
+//! assert_eq!(path::get(&config, "servers.0.host"), Some(&"localhost".into()));
+//! assert_eq!(path::get(&config, "/servers/0/host"), Some(&"localhost".into()));
+//!
+//! path::set(&mut config, "servers.0.port", 8080)?;
+//! path::set(&mut config, "log.level", "debug")?;
+//! assert_eq!(path::remove(&mut config, "servers.0.host"), Some("localhost".into()));
+//! assert_eq!(config, serde_yaml::from_str::<serde_yaml::Value>(
+//!     "{servers: [{port: 8080}], log: {level: debug}}")?);
+//! # Ok::<(), yaml_extras::Error>(())
+//! ```
+
+use crate::de;
+use crate::diff::compare;
+use crate::error::{Error, Result};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_yaml::value::TaggedValue;
+use serde_yaml::{Mapping, Value};
+
+/// The token used for a mapping key in paths
+pub(crate) fn key_token(key: &Value) -> String {
+    match key {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+/// Splits a path, dotted or JSON Pointer, into unescaped keys
+///
+/// # Example
+///
+/// ```
+/// use yaml_extras::path;
+///
+/// assert_eq!(path::tokens("a.b.0"), ["a", "b", "0"]);
+/// assert_eq!(path::tokens("/a.b/c~1d/e~0f"), ["a.b", "c/d", "e~f"]);
+/// assert_eq!(path::tokens(""), Vec::<String>::new());
+/// ```
+pub fn tokens(path: &str) -> Vec<String> {
+    if let Some(pointer) = path.strip_prefix('/') {
+        pointer.split('/')
+            .map(|t| t.replace("~1", "/").replace("~0", "~"))
+            .collect()
+    } else if path.is_empty() {
+        vec![]
+    } else {
+        path.split('.').map(|s| s.to_owned()).collect()
+    }
+}
+
+/// Builds a JSON Pointer from unescaped keys
+///
+/// # Example
+///
+/// ```
+/// assert_eq!(yaml_extras::path::to_pointer(&["a.b", "c/d", "0"]), "/a.b/c~1d/0");
+/// ```
+pub fn to_pointer<S: AsRef<str>>(tokens: &[S]) -> String {
+    tokens.iter()
+        .map(|t| format!("/{}", t.as_ref().replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+/// The key of `m` matching a token: a string, or another scalar displayed the same way
+pub(crate) fn find_key(m: &Mapping, token: &str) -> Option<Value> {
+    let key = Value::from(token);
+    if m.contains_key(&key) {
+        return Some(key);
+    }
+    m.keys()
+        .find(|k| !k.is_string() && key_token(k) == token)
+        .cloned()
+}
+
+/// Parses a sequence index, which must not have leading zeros and be lower than `len`
+pub(crate) fn parse_index(token: &str, len: usize) -> Option<usize> {
+    let valid = !token.is_empty() && token.chars().all(|c| c.is_ascii_digit())
+        && (token == "0" || !token.starts_with('0'));
+    match token.parse::<usize>() {
+        Ok(i) if valid && i < len => Some(i),
+        _ => None,
+    }
+}
+
+/// The value at the path made of `tokens`
+pub(crate) fn get_tokens<'v, S: AsRef<str>>(value: &'v Value, tokens: &[S]) -> Option<&'v Value> {
+    tokens.iter().try_fold(value, |mut v, t| {
+        if let Value::Tagged(tagged) = v {
+            v = &tagged.value;
+        }
+        match v {
+            Value::Mapping(m) => find_key(m, t.as_ref()).and_then(|k| m.get(&k)),
+            Value::Sequence(s) => parse_index(t.as_ref(), s.len()).and_then(|i| s.get(i)),
+            _ => None,
+        }
+    })
+}
+
+/// Same as `get_tokens`, with a mutable reference
+pub(crate) fn get_tokens_mut<'v, S: AsRef<str>>(value: &'v mut Value, tokens: &[S]) -> Option<&'v mut Value> {
+    let mut v = value;
+    for t in tokens {
+        if let Value::Tagged(tagged) = v {
+            v = &mut tagged.value;
+        }
+        v = match v {
+            Value::Mapping(m) => {
+                let key = find_key(m, t.as_ref());
+                key.and_then(|k| m.get_mut(&k))
+            },
+            Value::Sequence(s) => {
+                let i = parse_index(t.as_ref(), s.len());
+                i.and_then(|i| s.get_mut(i))
+            },
+            _ => None,
+        }?;
+    }
+    Some(v)
+}
+
+/// The value at the path made of `tokens`, creating missing mappings (with a null value at
+/// the end). `path` is only used in errors.
+pub(crate) fn entry<'v>(value: &'v mut Value, tokens: &[String], path: &str) -> Result<&'v mut Value> {
+    let mut v = value;
+    for t in tokens {
+        if v.is_null() {
+            *v = Value::Mapping(Mapping::new());
+        }
+        if let Value::Tagged(tagged) = v {
+            v = &mut tagged.value;
+        }
+        v = match v {
+            Value::Mapping(m) => {
+                let key = find_key(m, t).unwrap_or_else(|| Value::from(t.as_str()));
+                m.entry(key).or_insert(Value::Null)
+            },
+            Value::Sequence(s) => {
+                let i = parse_index(t, s.len())
+                    .ok_or_else(|| Error::Path(format!("invalid index '{t}' in '{path}'")))?;
+                &mut s[i]
+            },
+            _ => return Err(Error::Path(format!("can't set '{path}': '{t}' is inside a scalar"))),
+        };
+    }
+    Ok(v)
+}
+
+/// The value at `path`, if it exists
+pub fn get<'v>(value: &'v Value, path: &str) -> Option<&'v Value> {
+    get_tokens(value, &tokens(path))
+}
+
+/// A mutable reference to the value at `path`, if it exists
+pub fn get_mut<'v>(value: &'v mut Value, path: &str) -> Option<&'v mut Value> {
+    get_tokens_mut(value, &tokens(path))
+}
+
+/// Deserializes the value at `path`
+///
+/// Errors give the full path of the value that couldn't be deserialized.
+///
+/// # Example
+///
+/// ```
+/// use serde_derive::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Cache {
+///     size: u32,
+/// }
+///
+/// let config: serde_yaml::Value = serde_yaml::from_str("{plugins: {cache: {size: 1024}}}")?;
+/// let cache: Cache = yaml_extras::path::get_as(&config, "plugins.cache")?;
+/// assert_eq!(cache.size, 1024);
+///
+/// let config: serde_yaml::Value = serde_yaml::from_str("{plugins: {cache: {size: big}}}")?;
+/// let err = yaml_extras::path::get_as::<Cache>(&config, "plugins.cache").err().unwrap();
+/// assert!(matches!(err, yaml_extras::Error::Deserialize { ref path, .. } if path == "plugins.cache.size"));
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn get_as<T: DeserializeOwned>(value: &Value, path: &str) -> Result<T> {
+    let keys = tokens(path);
+    let v = get_tokens(value, &keys).ok_or_else(|| Error::Path(format!("no value at '{path}'")))?;
+    de::from_value(v).map_err(|err| match err {
+        Error::Deserialize { path: inner, source } => Error::Deserialize {
+            path: keys.into_iter()
+                .chain((!inner.is_empty()).then_some(inner))
+                .collect::<Vec<_>>()
+                .join("."),
+            source,
+        },
+        err => err,
+    })
+}
+
+/// Sets the value at `path`, creating the missing mappings, and returns the previous value
+/// if there was one.
+///
+/// Null values on the way are replaced by mappings. Sequence items must already exist.
+pub fn set<V: Into<Value>>(value: &mut Value, path: &str, new: V) -> Result<Option<Value>> {
+    let mut keys = tokens(path);
+    let Some(last) = keys.pop() else {
+        return Ok(Some(std::mem::replace(value, new.into())));
+    };
+    let mut parent = entry(value, &keys, path)?;
+    if parent.is_null() {
+        *parent = Value::Mapping(Mapping::new());
+    }
+    if let Value::Tagged(tagged) = parent {
+        parent = &mut tagged.value;
+    }
+    match parent {
+        Value::Mapping(m) => {
+            let key = find_key(m, &last).unwrap_or_else(|| Value::from(last));
+            Ok(m.insert(key, new.into()))
+        },
+        Value::Sequence(s) => {
+            let i = parse_index(&last, s.len())
+                .ok_or_else(|| Error::Path(format!("invalid index '{last}' in '{path}'")))?;
+            Ok(Some(std::mem::replace(&mut s[i], new.into())))
+        },
+        _ => Err(Error::Path(format!("can't set '{path}': '{last}' is inside a scalar"))),
+    }
+}
+
+/// Serializes `new` and sets it at `path` like `set`, returning the previous value if there
+/// was one.
+///
+/// # Example
+///
+/// ```
+/// use serde_derive::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Cache {
+///     size: u32,
+/// }
+///
+/// let mut config = serde_yaml::Value::Null;
+/// yaml_extras::path::set_from(&mut config, "plugins.cache", &Cache { size: 1024 })?;
+/// assert_eq!(config, serde_yaml::from_str::<serde_yaml::Value>("{plugins: {cache: {size: 1024}}}")?);
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn set_from<T: Serialize + ?Sized>(value: &mut Value, path: &str, new: &T) -> Result<Option<Value>> {
+    set(value, path, serde_yaml::to_value(new)?)
+}
+
+/// Removes the value at `path` and returns it, if it exists. The root can't be removed.
+pub fn remove(value: &mut Value, path: &str) -> Option<Value> {
+    let mut keys = tokens(path);
+    let last = keys.pop()?;
+    let mut parent = get_tokens_mut(value, &keys)?;
+    if let Value::Tagged(tagged) = parent {
+        parent = &mut tagged.value;
+    }
+    match parent {
+        Value::Mapping(m) => find_key(m, &last).and_then(|k| m.shift_remove(&k)),
+        Value::Sequence(s) => parse_index(&last, s.len()).map(|i| s.remove(i)),
+        _ => None,
+    }
+}
+
+/// Whether a token of a path, possibly `*`, matches the token of a key
+fn token_matches(token: &str, key: &str) -> bool {
+    token == "*" || token == key
+}
+
+fn pick_tokens(value: &Value, paths: &[&[String]]) -> Option<Value> {
+    if paths.iter().any(|p| p.is_empty()) {
+        return Some(value.clone());
+    }
+    // Paths starting with the given key, without it
+    let nested = |key: &str| -> Vec<&[String]> {
+        paths.iter()
+            .filter(|p| token_matches(&p[0], key))
+            .map(|p| &p[1..])
+            .collect()
+    };
+    match value {
+        Value::Mapping(m) => {
+            let picked: Mapping = m.iter()
+                .filter_map(|(k, v)| {
+                    let paths = nested(&key_token(k));
+                    if paths.is_empty() {
+                        return None;
+                    }
+                    pick_tokens(v, &paths).map(|v| (k.clone(), v))
+                })
+                .collect();
+            (!picked.is_empty()).then_some(Value::Mapping(picked))
+        },
+        Value::Sequence(s) => {
+            let picked: Vec<Value> = s.iter()
+                .enumerate()
+                .filter_map(|(i, v)| {
+                    let paths = nested(&i.to_string());
+                    if paths.is_empty() {
+                        return None;
+                    }
+                    pick_tokens(v, &paths)
+                })
+                .collect();
+            (!picked.is_empty()).then_some(Value::Sequence(picked))
+        },
+        Value::Tagged(t) => pick_tokens(&t.value, paths).map(|value| {
+            Value::Tagged(Box::new(TaggedValue { tag: t.tag.clone(), value }))
+        }),
+        _ => None,
+    }
+}
+
+/// Returns a copy of `value` with only the values at `paths`, in their original place. A
+/// `*` in a path matches any key.
+///
+/// Items of sequences that aren't picked are left out, so the indices of the others may
+/// change. If no path exists, the result is an empty mapping.
+///
+/// # Example
+///
+/// ```
+/// use yaml_extras::path;
+///
+/// let config: serde_yaml::Value = serde_yaml::from_str("
+/// db: {host: localhost, password: secret}
+/// servers: [{name: a, token: x}, {name: b, token: y}]
+/// debug: true")?;
+/// let picked = path::pick(&config, &["db.host", "servers.*.name"]);
+/// assert_eq!(picked, serde_yaml::from_str::<serde_yaml::Value>("
+/// db: {host: localhost}
+/// servers: [{name: a}, {name: b}]")?);
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn pick<S: AsRef<str>>(value: &Value, paths: &[S]) -> Value {
+    let paths: Vec<Vec<String>> = paths.iter()
+        .map(|p| tokens(p.as_ref()))
+        .collect();
+    let paths: Vec<&[String]> = paths.iter()
+        .map(|p| p.as_slice())
+        .collect();
+    pick_tokens(value, &paths).unwrap_or_else(|| Value::Mapping(Mapping::new()))
+}
+
+/// Removes the values at `paths` (unescaped tokens) and returns how many were removed
+fn remove_tokens(value: &mut Value, paths: &[&[String]]) -> usize {
+    let nested = |key: &str| -> (bool, Vec<&[String]>) {
+        let matching: Vec<&[String]> = paths.iter()
+            .filter(|p| !p.is_empty() && token_matches(&p[0], key))
+            .map(|p| &p[1..])
+            .collect();
+        (matching.iter().any(|p| p.is_empty()), matching)
+    };
+    let mut removed = 0;
+    match value {
+        Value::Mapping(m) => m.retain(|k, v| match nested(&key_token(k)) {
+            (true, _) => {
+                removed += 1;
+                false
+            },
+            (false, paths) => {
+                if !paths.is_empty() {
+                    removed += remove_tokens(v, &paths);
+                }
+                true
+            },
+        }),
+        Value::Sequence(s) => {
+            let mut i = 0;
+            s.retain_mut(|v| {
+                let keep = match nested(&i.to_string()) {
+                    (true, _) => {
+                        removed += 1;
+                        false
+                    },
+                    (false, paths) => {
+                        if !paths.is_empty() {
+                            removed += remove_tokens(v, &paths);
+                        }
+                        true
+                    },
+                };
+                i += 1;
+                keep
+            });
+        },
+        Value::Tagged(t) => removed += remove_tokens(&mut t.value, paths),
+        _ => (),
+    }
+    removed
+}
+
+/// Removes the values at `paths` from `value`, a `*` in a path matching any key, and
+/// returns how many were removed. The root can't be removed.
+///
+/// Indices of sequences refer to the items before any removal.
+///
+/// # Example
+///
+/// ```
+/// use yaml_extras::path;
+///
+/// let mut config: serde_yaml::Value = serde_yaml::from_str("
+/// db: {host: localhost, password: secret}
+/// servers: [{name: a, token: x}, {name: b, token: y}]")?;
+/// assert_eq!(path::remove_paths(&mut config, &["*.password", "servers.*.token"]), 3);
+/// assert_eq!(config, serde_yaml::from_str::<serde_yaml::Value>("
+/// db: {host: localhost}
+/// servers: [{name: a}, {name: b}]")?);
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn remove_paths<S: AsRef<str>>(value: &mut Value, paths: &[S]) -> usize {
+    let paths: Vec<Vec<String>> = paths.iter()
+        .map(|p| tokens(p.as_ref()))
+        .collect();
+    let paths: Vec<&[String]> = paths.iter()
+        .map(|p| p.as_slice())
+        .collect();
+    remove_tokens(value, &paths)
+}
+
+/// Returns a copy of `value` without the values at `paths`, a `*` in a path matching any
+/// key. This is the complement of `pick`, see `remove_paths`.
+pub fn omit<S: AsRef<str>>(value: &Value, paths: &[S]) -> Value {
+    let mut omitted = value.clone();
+    remove_paths(&mut omitted, paths);
+    omitted
+}
+
+/// Sorts the sequence at `path` by the value at `key` (a path too) inside each of its items
+///
+/// Values are compared by type (null, booleans, numbers, strings, sequences, mappings, then
+/// tagged values), then by value; numbers are compared whatever their representation. Items
+/// without `key` come last, and the sort is stable.
+///
+/// # Example
+///
+/// ```
+/// let mut config: serde_yaml::Value = serde_yaml::from_str("
+/// services:
+///   - {name: web, port: 8080}
+///   - {name: db, port: 5432}
+///   - {name: cache, port: 6379.0}
+/// ")?;
+/// yaml_extras::path::sort_sequence_by(&mut config, "services", "port")?;
+/// assert_eq!(config["services"][0]["name"], "db");
+/// assert_eq!(config["services"][1]["name"], "cache");
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn sort_sequence_by(value: &mut Value, path: &str, key: &str) -> Result<()> {
+    let key = tokens(key);
+    let mut v = get_mut(value, path).ok_or_else(|| Error::Path(format!("no value at '{path}'")))?;
+    if let Value::Tagged(tagged) = v {
+        v = &mut tagged.value;
+    }
+    let Value::Sequence(s) = v else {
+        return Err(Error::Path(format!("'{path}' is not a sequence")));
+    };
+    s.sort_by(|a, b| match (get_tokens(a, &key), get_tokens(b, &key)) {
+        (Some(a), Some(b)) => compare(a, b),
+        (a, b) => b.is_some().cmp(&a.is_some()),
+    });
+    Ok(())
+}
+
+/// What `walk_mut` does after visiting a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VisitAction {
+    /// Visits the children of the value (default)
+    #[default]
+    Continue,
+    /// Doesn't visit the children of the value, but continues with its siblings
+    SkipChildren,
+    /// Stops the traversal
+    Stop,
+}
+
+fn walk_at<F: FnMut(&[String], &Value)>(value: &Value, path: &mut Vec<String>, f: &mut F) {
+    f(path, value);
+    let value = match value {
+        Value::Tagged(t) => &t.value,
+        v => v,
+    };
+    match value {
+        Value::Mapping(m) => for (k, v) in m {
+            path.push(key_token(k));
+            walk_at(v, path, f);
+            path.pop();
+        },
+        Value::Sequence(s) => for (i, v) in s.iter().enumerate() {
+            path.push(i.to_string());
+            walk_at(v, path, f);
+            path.pop();
+        },
+        _ => (),
+    }
+}
+
+/// Calls `f` with the path and the value of `value` and all the values nested in it, parents
+/// first. Tagged values are visited once, with their tag.
+///
+/// # Example
+///
+/// ```
+/// let config: serde_yaml::Value = serde_yaml::from_str("{db: {port: 5432}, ports: [80, 443]}")?;
+/// let mut numbers = vec![];
+/// yaml_extras::path::walk(&config, |path, value| if value.is_number() {
+///     numbers.push(path.join("."));
+/// });
+/// assert_eq!(numbers, ["db.port", "ports.0", "ports.1"]);
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn walk<F: FnMut(&[String], &Value)>(value: &Value, mut f: F) {
+    walk_at(value, &mut vec![], &mut f);
+}
+
+/// Returns `false` if the traversal was stopped
+fn walk_mut_at<F>(value: &mut Value, path: &mut Vec<String>, f: &mut F) -> bool
+where F: FnMut(&[String], &mut Value) -> VisitAction {
+    match f(path, value) {
+        VisitAction::Continue => (),
+        VisitAction::SkipChildren => return true,
+        VisitAction::Stop => return false,
+    }
+    let value = match value {
+        Value::Tagged(t) => &mut t.value,
+        v => v,
+    };
+    match value {
+        Value::Mapping(m) => for (k, v) in m.iter_mut() {
+            path.push(key_token(k));
+            let go_on = walk_mut_at(v, path, f);
+            path.pop();
+            if !go_on {
+                return false;
+            }
+        },
+        Value::Sequence(s) => for (i, v) in s.iter_mut().enumerate() {
+            path.push(i.to_string());
+            let go_on = walk_mut_at(v, path, f);
+            path.pop();
+            if !go_on {
+                return false;
+            }
+        },
+        _ => (),
+    }
+    true
+}
+
+/// Same as `walk`, but `f` can modify the values and choose whether to visit the children
+/// of a value. The children are the ones of the value after `f` was called.
+///
+/// # Example
+///
+/// ```
+/// use yaml_extras::path::{self, VisitAction};
+///
+/// let mut config: serde_yaml::Value = serde_yaml::from_str("{a: 1, secrets: {b: 2}, c: 3}")?;
+/// path::walk_mut(&mut config, |path, value| {
+///     if path == ["secrets"] {
+///         return VisitAction::SkipChildren;
+///     }
+///     if let Some(n) = value.as_u64() {
+///         *value = (n * 10).into();
+///     }
+///     VisitAction::Continue
+/// });
+/// assert_eq!(config, serde_yaml::from_str::<serde_yaml::Value>("{a: 10, secrets: {b: 2}, c: 30}")?);
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn walk_mut<F>(value: &mut Value, mut f: F)
+where F: FnMut(&[String], &mut Value) -> VisitAction {
+    walk_mut_at(value, &mut vec![], &mut f);
+}
+
+/// Replaces each value for which `predicate` returns `true` by the result of `replacer`, and
+/// returns the paths of the replaced values. The replacements themselves are not visited.
+///
+/// # Example
+///
+/// ```
+/// let mut config: serde_yaml::Value = serde_yaml::from_str("
+/// web: {image: old.registry/web}
+/// db: {image: old.registry/db, ratio: 0.333}
+/// ")?;
+/// let replaced = yaml_extras::path::replace_where(
+///     &mut config,
+///     |_, value| value.as_str().is_some_and(|s| s.starts_with("old.registry/")),
+///     |_, value| value.as_str().unwrap().replace("old.registry/", "new.registry/").into(),
+/// );
+/// assert_eq!(replaced, [vec!["web", "image"], vec!["db", "image"]]);
+/// assert_eq!(config["db"]["image"], "new.registry/db");
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn replace_where<P, R>(value: &mut Value, mut predicate: P, mut replacer: R) -> Vec<Vec<String>>
+where P: FnMut(&[String], &Value) -> bool,
+      R: FnMut(&[String], &Value) -> Value {
+    let mut replaced = vec![];
+    walk_mut(value, |path, value| {
+        if !predicate(path, value) {
+            return VisitAction::Continue;
+        }
+        *value = replacer(path, value);
+        replaced.push(path.to_vec());
+        VisitAction::SkipChildren
+    });
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yaml;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn accessors() {
+        let mut v = yaml("{a: [{b: 1}, !t {c: 2}], 8080: x, true: y, n: null}");
+        assert_eq!(get(&v, "a.0.b"), Some(&yaml("1")));
+        assert_eq!(get(&v, "a.1.c"), Some(&yaml("2")));
+        assert_eq!(get(&v, "8080"), Some(&yaml("x")));
+        assert_eq!(get(&v, "true"), Some(&yaml("y")));
+        assert_eq!(get(&v, "a.01"), None);
+        assert_eq!(get(&v, "a.2"), None);
+        assert_eq!(get(&v, ""), Some(&v));
+
+        *get_mut(&mut v, "a.0.b").unwrap() = 3.into();
+        assert_eq!(set(&mut v, "a.1.c", 4).unwrap(), Some(yaml("2")));
+        assert_eq!(set(&mut v, "n.o.p", 5).unwrap(), None);
+        assert_eq!(set(&mut v, "a.0", "z").unwrap(), Some(yaml("{b: 3}")));
+        assert!(set(&mut v, "a.5", 1).is_err());
+        assert!(set(&mut v, "8080.q", 1).is_err());
+        assert_eq!(remove(&mut v, "true"), Some(yaml("y")));
+        assert_eq!(remove(&mut v, "a.0"), Some(yaml("z")));
+        assert_eq!(remove(&mut v, "missing.key"), None);
+        assert_eq!(remove(&mut v, ""), None);
+        assert_eq!(v, yaml("{a: [!t {c: 4}], 8080: x, n: {o: {p: 5}}}"));
+
+        assert_eq!(set(&mut v, "", 1).unwrap(), Some(yaml("{a: [!t {c: 4}], 8080: x, n: {o: {p: 5}}}")));
+        assert_eq!(v, yaml("1"));
+
+        let mut v = yaml("{a.b: {c/d: 1}, e: [~]}");
+        assert_eq!(get(&v, "/a.b/c~1d"), Some(&yaml("1")));
+        set(&mut v, "/e/0/f~0g", 2).unwrap();
+        assert_eq!(remove(&mut v, "/a.b"), Some(yaml("{c/d: 1}")));
+        assert_eq!(v, yaml("{e: [{f~g: 2}]}"));
+    }
+
+    #[test]
+    fn picking() {
+        let v = yaml("{a: {b: 1, c: 2}, l: [{x: 1, y: 2}, {y: 3}, 4], t: !t {u: 1, w: 2}, 8: z}");
+        assert_eq!(pick(&v, &["a.b", "/l/0/x", "t.u", "8", "missing.key"]), yaml("{a: {b: 1}, l: [{x: 1}], t: !t {u: 1}, 8: z}"));
+        assert_eq!(pick(&v, &["l.*.y"]), yaml("{l: [{y: 2}, {y: 3}]}"));
+        assert_eq!(pick(&v, &["a", "a.b"]), yaml("{a: {b: 1, c: 2}}"));
+        assert_eq!(pick(&v, &[""]), v);
+        assert_eq!(pick(&v, &["a.b.c"]), yaml("{}"));
+        assert_eq!(pick::<&str>(&v, &[]), yaml("{}"));
+    }
+
+    #[test]
+    fn omitting() {
+        let v = yaml("{a: {b: 1, c: 2}, l: [{x: 1, y: 2}, {y: 3}, 4], t: !t {u: 1, w: 2}, 8: z}");
+        assert_eq!(omit(&v, &["a.b", "/l/0/x", "l.2", "t.u", "8", "missing.key"]), yaml("{a: {c: 2}, l: [{y: 2}, {y: 3}], t: !t {w: 2}}"));
+        assert_eq!(omit(&v, &["*.*.y", "l.1"]), yaml("{a: {b: 1, c: 2}, l: [{x: 1}, 4], t: !t {u: 1, w: 2}, 8: z}"));
+        assert_eq!(omit(&v, &[""]), v);
+        let mut w = v.clone();
+        assert_eq!(remove_paths(&mut w, &["*"]), 4);
+        assert_eq!(w, yaml("{}"));
+    }
+
+    #[test]
+    fn walking() {
+        let mut v = yaml("{a: [1, !t {b: 2}], c: {d: 3}, e: 4}");
+        let mut visited = vec![];
+        walk(&v, |path, _| visited.push(path.join(".")));
+        assert_eq!(visited, ["", "a", "a.0", "a.1", "a.1.b", "c", "c.d", "e"]);
+
+        let mut visited = vec![];
+        walk_mut(&mut v, |path, value| {
+            visited.push(path.join("."));
+            match path.join(".").as_str() {
+                "a" => {
+                    *value = yaml("[5]");
+                    VisitAction::Continue
+                },
+                "c.d" => VisitAction::Stop,
+                _ => VisitAction::Continue,
+            }
+        });
+        assert_eq!(visited, ["", "a", "a.0", "c", "c.d"]);
+        assert_eq!(v, yaml("{a: [5], c: {d: 3}, e: 4}"));
+    }
+
+    #[test]
+    fn replacing() {
+        let mut v = yaml("{a: 1.26, b: [2.5, {c: 3}], d: {e: 1.0}}");
+        let replaced = replace_where(&mut v, |_, v| v.is_f64(), |_, v| v.as_f64().unwrap().round().into());
+        assert_eq!(replaced.len(), 3);
+        assert_eq!(replaced[1], ["b", "0"]);
+        assert_eq!(v, yaml("{a: 1.0, b: [3.0, {c: 3}], d: {e: 1.0}}"));
+
+        // Replacements are not visited again
+        let mut v = yaml("{a: {b: 1}}");
+        let replaced = replace_where(&mut v, |_, v| v.is_mapping(), |_, v| yaml("{wrapped: x}").as_mapping().map(|m| {
+            let mut m = m.clone();
+            m.insert("inner".into(), v.clone());
+            Value::Mapping(m)
+        }).unwrap());
+        assert_eq!(replaced, [Vec::<String>::new()]);
+        assert_eq!(v, yaml("{wrapped: x, inner: {a: {b: 1}}}"));
+    }
+
+    #[test]
+    fn typed_access() {
+        #[derive(serde_derive::Deserialize, Debug, PartialEq)]
+        struct Server {
+            port: u16,
+        }
+
+        let v = yaml("{servers: [{port: 80}, {port: http}], a.b: {port: 8080}}");
+        assert_eq!(get_as::<Server>(&v, "servers.0").unwrap(), Server { port: 80 });
+        assert_eq!(get_as::<Server>(&v, "/a.b").unwrap(), Server { port: 8080 });
+        assert_eq!(get_as::<u16>(&v, "servers.0.port").unwrap(), 80);
+        match get_as::<Server>(&v, "servers.1") {
+            Err(Error::Deserialize { path, .. }) => assert_eq!(path, "servers.1.port"),
+            r => panic!("unexpected result {r:?}"),
+        }
+        match get_as::<Vec<u8>>(&v, "servers") {
+            Err(Error::Deserialize { path, .. }) => assert_eq!(path, "servers.0"),
+            r => panic!("unexpected result {r:?}"),
+        }
+        assert_eq!(get_as::<u16>(&v, "servers.2").unwrap_err().to_string(), "impossible to access YAML path: no value at 'servers.2'");
+    }
+
+    #[test]
+    fn typed_insertion() {
+        #[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, PartialEq)]
+        struct Server {
+            port: u16,
+        }
+
+        let mut v = yaml("{servers: [{port: 80}]}");
+        assert_eq!(set_from(&mut v, "servers.0", &Server { port: 81 }).unwrap(), Some(yaml("{port: 80}")));
+        assert_eq!(set_from(&mut v, "/backup/a.b", &Server { port: 82 }).unwrap(), None);
+        set_from(&mut v, "tags", &["a", "b"][..]).unwrap();
+        assert_eq!(v, yaml("{servers: [{port: 81}], backup: {a.b: {port: 82}}, tags: [a, b]}"));
+        assert_eq!(get_as::<Server>(&v, "/backup/a.b").unwrap(), Server { port: 82 });
+        assert!(set_from(&mut v, "tags.0.x", &1).is_err());
+    }
+
+    #[test]
+    fn sorting() {
+        let mut v = yaml("{a: {list: [{n: b}, {x: 1}, {n: 10}, {n: a, m: 1}, {n: 2.5}, {n: a, m: 0}, 3]}}");
+        sort_sequence_by(&mut v, "a.list", "n").unwrap();
+        assert_eq!(v, yaml("{a: {list: [{n: 2.5}, {n: 10}, {n: a, m: 1}, {n: a, m: 0}, {n: b}, {x: 1}, 3]}}"));
+        sort_sequence_by(&mut v, "/a/list", "m").unwrap();
+        assert_eq!(v["a"]["list"][0], yaml("{n: a, m: 0}"));
+
+        let mut v = yaml("{a: {b: 1}, c: !t [{k: {l: 2}}, {k: {l: 1}}]}");
+        sort_sequence_by(&mut v, "c", "k.l").unwrap();
+        assert_eq!(v["c"], yaml("!t [{k: {l: 1}}, {k: {l: 2}}]"));
+        assert_eq!(sort_sequence_by(&mut v, "a", "b").unwrap_err().to_string(), "impossible to access YAML path: 'a' is not a sequence");
+        assert!(sort_sequence_by(&mut v, "x", "b").is_err());
+    }
+}
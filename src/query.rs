@@ -0,0 +1,547 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Queries with a subset of [JSONPath](https://www.rfc-editor.org/rfc/rfc9535).
+//!
+//! Supported syntax, from the root `$`:
+//!
+//! - `.key` or `['key']` for a key of a mapping, `.*` or `[*]` for all the children;
+//! - `[0]` for an item of a sequence, negative indices counting from the end;
+//! - `[start:end:step]` for slices of a sequence, each part being optional;
+//! - `[0, 2]` or `['a', 'b']` for unions;
+//! - `..key`, `..*` or `..[...]` to apply a selector to a value and all its descendants;
+//! - `[?(...)]` to keep the children matching a filter, which can compare relative paths
+//!   (`@.enabled`) or literals (numbers, strings, `true`, `false` and `null`) with `==`,
+//!   `!=`, `<`, `<=`, `>` and `>=`, check that a path exists (`@.name`), and combine these
+//!   with `&&`, `||`, `!` and parentheses.
+//!
+//! # Example
+//!
+//! ```
+//! use yaml_extras::query;
+//!
+//! let config: serde_yaml::Value = serde_yaml::from_str("
+//! plugins:
+//!   - {name: auth, enabled: true, priority: 1}
+//!   - {name: cache, enabled: false}
+//!   - {name: log, enabled: true, priority: 5}")?;
+//! let matches = query::query(&config, "$.plugins[?(@.enabled == true && @.priority > 2)].name")?;
+//! assert_eq!(matches.len(), 1);
+//! assert_eq!(matches[0].path, ["plugins", "2", "name"]);
+//! assert_eq!(matches[0].value, "log");
+//! # Ok::<(), yaml_extras::Error>(())
+//! ```
+
+use crate::error::{Error, Result};
+use crate::path::{find_key, key_token};
+
+use serde_yaml::Value;
+
+use std::cmp::Ordering;
+
+/// A value matched by a query
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match<'v> {
+    /// The keys leading to the value, with sequence indices displayed as numbers
+    pub path: Vec<String>,
+    /// The matched value, borrowed from the queried document
+    pub value: &'v Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    Key(String),
+    Index(i64),
+    Wildcard,
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    Union(Vec<Selector>),
+    Filter(Filter),
+    Descendant(Box<Selector>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    /// A path relative to the filtered value
+    Path(Vec<Selector>),
+    Literal(Value),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Or(Box<Filter>, Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Exists(Vec<Selector>),
+    Compare(Operand, Op, Operand),
+}
+
+struct Parser<'e> {
+    expr: &'e str,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+/// Whether a character can be used in a key after a dot
+fn is_name_char(c: char) -> bool {
+    !c.is_whitespace() && !matches!(c, '.' | '[' | ']' | '(' | ')' | '=' | '!' | '<' | '>' | '&'
+                                    | '|' | ',' | '?' | '@' | '$' | '\'' | '"')
+}
+
+impl Parser<'_> {
+    fn error(&self, msg: &str) -> Error {
+        Error::Path(format!("invalid JSONPath '{}' at character {}: {msg}", self.expr, self.pos))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consumes `s` if it comes next, after spaces
+    fn eat(&mut self, s: &str) -> bool {
+        self.skip_spaces();
+        let len = s.chars().count();
+        if self.chars[self.pos..].iter().take(len).copied().eq(s.chars()) {
+            self.pos += len;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, s: &str) -> Result<()> {
+        if self.eat(s) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{s}'")))
+        }
+    }
+
+    /// Selectors following the root (`$` or `@`)
+    fn selectors(&mut self) -> Result<Vec<Selector>> {
+        let mut selectors = vec![];
+        loop {
+            if self.eat("..") {
+                let selector = if self.eat("[") {
+                    self.bracket()?
+                } else {
+                    self.dotted()?
+                };
+                selectors.push(Selector::Descendant(Box::new(selector)));
+            } else if self.eat(".") {
+                selectors.push(self.dotted()?);
+            } else if self.eat("[") {
+                selectors.push(self.bracket()?);
+            } else {
+                return Ok(selectors);
+            }
+        }
+    }
+
+    /// A key or `*` after a dot
+    fn dotted(&mut self) -> Result<Selector> {
+        if self.eat("*") {
+            return Ok(Selector::Wildcard);
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(is_name_char) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(self.error("expected a key"));
+        }
+        Ok(Selector::Key(self.chars[start..self.pos].iter().collect()))
+    }
+
+    /// The content of brackets, after `[`
+    fn bracket(&mut self) -> Result<Selector> {
+        if self.eat("?") {
+            let filter = self.or()?;
+            self.expect("]")?;
+            return Ok(Selector::Filter(filter));
+        }
+        let mut selectors = vec![self.bracket_item()?];
+        while self.eat(",") {
+            selectors.push(self.bracket_item()?);
+        }
+        self.expect("]")?;
+        Ok(if selectors.len() == 1 {
+            selectors.remove(0)
+        } else {
+            Selector::Union(selectors)
+        })
+    }
+
+    /// A key, index, slice or wildcard between brackets
+    fn bracket_item(&mut self) -> Result<Selector> {
+        self.skip_spaces();
+        match self.peek() {
+            Some('*') => {
+                self.pos += 1;
+                Ok(Selector::Wildcard)
+            },
+            Some(q @ ('\'' | '"')) => {
+                self.pos += 1;
+                self.string(q).map(Selector::Key)
+            },
+            _ => {
+                let start = self.integer()?;
+                if !self.eat(":") {
+                    return start.map(Selector::Index).ok_or_else(|| self.error("expected an index"));
+                }
+                let end = self.integer()?;
+                let step = if self.eat(":") { self.integer()? } else { None };
+                Ok(Selector::Slice(start, end, step))
+            },
+        }
+    }
+
+    /// An optional integer
+    fn integer(&mut self) -> Result<Option<i64>> {
+        self.skip_spaces();
+        let start = self.pos;
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let s: String = self.chars[start..self.pos].iter().collect();
+        match s.as_str() {
+            "" => Ok(None),
+            s => s.parse().map(Some).map_err(|_| self.error("invalid integer")),
+        }
+    }
+
+    /// A quoted string, after the opening quote
+    fn string(&mut self, quote: char) -> Result<String> {
+        let mut s = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some('\\') => {
+                    self.pos += 1;
+                    let c = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+                    s.push(match c {
+                        'n' => '\n',
+                        't' => '\t',
+                        c => c,
+                    });
+                },
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(s);
+                },
+                Some(c) => s.push(c),
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn or(&mut self) -> Result<Filter> {
+        let mut filter = self.and()?;
+        while self.eat("||") {
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter> {
+        let mut filter = self.comparison()?;
+        while self.eat("&&") {
+            filter = Filter::And(Box::new(filter), Box::new(self.comparison()?));
+        }
+        Ok(filter)
+    }
+
+    fn comparison(&mut self) -> Result<Filter> {
+        if self.eat("!") {
+            return Ok(Filter::Not(Box::new(self.comparison()?)));
+        }
+        if self.eat("(") {
+            let filter = self.or()?;
+            self.expect(")")?;
+            return Ok(filter);
+        }
+        let left = self.operand()?;
+        let ops = [("==", Op::Eq), ("!=", Op::Ne), ("<=", Op::Le), (">=", Op::Ge), ("<", Op::Lt), (">", Op::Gt)];
+        let Some(op) = ops.iter().find(|(s, _)| self.eat(s)).map(|(_, op)| *op) else {
+            return match left {
+                Operand::Path(path) => Ok(Filter::Exists(path)),
+                Operand::Literal(_) => Err(self.error("expected a comparison")),
+            };
+        };
+        Ok(Filter::Compare(left, op, self.operand()?))
+    }
+
+    fn operand(&mut self) -> Result<Operand> {
+        self.skip_spaces();
+        match self.peek() {
+            Some('@') => {
+                self.pos += 1;
+                self.selectors().map(Operand::Path)
+            },
+            Some(q @ ('\'' | '"')) => {
+                self.pos += 1;
+                self.string(q).map(|s| Operand::Literal(s.into()))
+            },
+            _ => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.' | '_')) {
+                    self.pos += 1;
+                }
+                let word: String = self.chars[start..self.pos].iter().collect();
+                match word.as_str() {
+                    "true" => Ok(Operand::Literal(true.into())),
+                    "false" => Ok(Operand::Literal(false.into())),
+                    "null" => Ok(Operand::Literal(Value::Null)),
+                    w => match serde_yaml::from_str::<Value>(w) {
+                        Ok(n @ Value::Number(_)) => Ok(Operand::Literal(n)),
+                        _ => {
+                            self.pos = start;
+                            Err(self.error("expected a path or a literal"))
+                        },
+                    },
+                }
+            },
+        }
+    }
+}
+
+fn parse(expr: &str) -> Result<Vec<Selector>> {
+    let mut parser = Parser { expr, chars: expr.trim().chars().collect(), pos: 0 };
+    parser.expect("$")?;
+    let selectors = parser.selectors()?;
+    parser.skip_spaces();
+    if parser.pos < parser.chars.len() {
+        return Err(parser.error("unexpected character"));
+    }
+    Ok(selectors)
+}
+
+/// The children of a value, with their key
+fn children(value: &Value) -> Vec<(String, &Value)> {
+    match value {
+        Value::Mapping(m) => m.iter().map(|(k, v)| (key_token(k), v)).collect(),
+        Value::Sequence(s) => s.iter().enumerate().map(|(i, v)| (i.to_string(), v)).collect(),
+        Value::Tagged(t) => children(&t.value),
+        _ => vec![],
+    }
+}
+
+/// The indices of a slice of a sequence of length `len`
+fn slice_indices(start: Option<i64>, end: Option<i64>, step: Option<i64>, len: usize) -> Vec<usize> {
+    let len = len as i64;
+    let step = step.unwrap_or(1);
+    let normalize = |i: i64| if i < 0 { len + i } else { i };
+    let mut indices = vec![];
+    if step > 0 {
+        let start = normalize(start.unwrap_or(0)).clamp(0, len);
+        let end = normalize(end.unwrap_or(len)).clamp(0, len);
+        let mut i = start;
+        while i < end {
+            indices.push(i as usize);
+            match i.checked_add(step) {
+                Some(n) => i = n,
+                None => break,
+            }
+        }
+    } else if step < 0 {
+        let start = start.map_or(len - 1, normalize).clamp(-1, len - 1);
+        let end = end.map_or(-1, normalize).clamp(-1, len - 1);
+        let mut i = start;
+        while i > end {
+            indices.push(i as usize);
+            match i.checked_add(step) {
+                Some(n) => i = n,
+                None => break,
+            }
+        }
+    }
+    indices
+}
+
+fn select<'v>(selector: &Selector, path: &[String], value: &'v Value, out: &mut Vec<Match<'v>>) {
+    let value = match value {
+        Value::Tagged(t) => &t.value,
+        v => v,
+    };
+    let mut push = |key: String, value: &'v Value| {
+        let mut path = path.to_vec();
+        path.push(key);
+        out.push(Match { path, value });
+    };
+    match (selector, value) {
+        (Selector::Key(k), Value::Mapping(m)) => if let Some(key) = find_key(m, k) {
+            push(key_token(&key), &m[&key]);
+        },
+        (Selector::Index(i), Value::Sequence(s)) => {
+            let i = if *i < 0 { s.len() as i64 + i } else { *i };
+            if let Some(item) = usize::try_from(i).ok().and_then(|i| s.get(i)) {
+                push(i.to_string(), item);
+            }
+        },
+        (Selector::Slice(start, end, step), Value::Sequence(s)) => {
+            for i in slice_indices(*start, *end, *step, s.len()) {
+                push(i.to_string(), &s[i]);
+            }
+        },
+        (Selector::Wildcard, _) => for (k, v) in children(value) {
+            push(k, v);
+        },
+        (Selector::Filter(filter), _) => for (k, v) in children(value) {
+            if matches(filter, v) {
+                push(k, v);
+            }
+        },
+        (Selector::Union(selectors), _) => for selector in selectors {
+            select(selector, path, value, out);
+        },
+        (Selector::Descendant(selector), _) => {
+            select(selector, path, value, out);
+            for (k, v) in children(value) {
+                let mut path = path.to_vec();
+                path.push(k);
+                select(&Selector::Descendant(selector.clone()), &path, v, out);
+            }
+        },
+        _ => (),
+    }
+}
+
+fn evaluate<'v>(selectors: &[Selector], value: &'v Value) -> Vec<Match<'v>> {
+    let mut matches = vec![Match { path: vec![], value }];
+    for selector in selectors {
+        let mut next = vec![];
+        for m in &matches {
+            select(selector, &m.path, m.value, &mut next);
+        }
+        matches = next;
+    }
+    matches
+}
+
+/// The value of an operand of a comparison, i.e. the first match of a path
+fn operand_value<'v>(operand: &'v Operand, value: &'v Value) -> Option<&'v Value> {
+    match operand {
+        Operand::Literal(l) => Some(l),
+        Operand::Path(selectors) => evaluate(selectors, value).first().map(|m| m.value),
+    }
+}
+
+fn compare(a: Option<&Value>, op: Op, b: Option<&Value>) -> bool {
+    let ordering = match (a, b) {
+        (None, None) => Some(Ordering::Equal),
+        (Some(Value::Number(a)), Some(Value::Number(b))) => a.as_f64().partial_cmp(&b.as_f64()),
+        (Some(Value::String(a)), Some(Value::String(b))) => Some(a.cmp(b)),
+        (Some(a), Some(b)) if a == b => Some(Ordering::Equal),
+        _ => None,
+    };
+    match op {
+        Op::Eq => ordering == Some(Ordering::Equal),
+        Op::Ne => ordering != Some(Ordering::Equal),
+        Op::Lt => ordering == Some(Ordering::Less),
+        Op::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        Op::Gt => ordering == Some(Ordering::Greater),
+        Op::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+    }
+}
+
+fn matches(filter: &Filter, value: &Value) -> bool {
+    match filter {
+        Filter::Or(a, b) => matches(a, value) || matches(b, value),
+        Filter::And(a, b) => matches(a, value) && matches(b, value),
+        Filter::Not(f) => !matches(f, value),
+        Filter::Exists(selectors) => !evaluate(selectors, value).is_empty(),
+        Filter::Compare(a, op, b) => compare(operand_value(a, value), *op, operand_value(b, value)),
+    }
+}
+
+/// Returns the values matching a JSONPath expression, in document order for each selector.
+///
+/// Returns `Error::Path` if the expression is invalid.
+pub fn query<'v>(value: &'v Value, expr: &str) -> Result<Vec<Match<'v>>> {
+    Ok(evaluate(&parse(expr)?, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yaml;
+    use pretty_assertions::assert_eq;
+
+    fn paths(value: &Value, expr: &str) -> Vec<String> {
+        query(value, expr).unwrap()
+            .iter()
+            .map(|m| m.path.join("."))
+            .collect()
+    }
+
+    #[test]
+    fn selectors() {
+        let v = yaml("{a: {b: [10, 11, 12, 13], c: !t {b: x}}, d.e: 1, 8: y}");
+        assert_eq!(paths(&v, "$"), [""]);
+        assert_eq!(paths(&v, "$.a.b[1]"), ["a.b.1"]);
+        assert_eq!(paths(&v, "$['a'][\"b\"][-1]"), ["a.b.3"]);
+        assert_eq!(paths(&v, "$['d.e']"), ["d.e"]);
+        assert_eq!(paths(&v, "$.8"), ["8"]);
+        assert_eq!(paths(&v, "$.a.b[1:3]"), ["a.b.1", "a.b.2"]);
+        assert_eq!(paths(&v, "$.a.b[::-2]"), ["a.b.3", "a.b.1"]);
+        assert_eq!(paths(&v, "$.a.b[-2:]"), ["a.b.2", "a.b.3"]);
+        assert_eq!(paths(&v, "$.a.b[0, 2]"), ["a.b.0", "a.b.2"]);
+        assert_eq!(paths(&v, "$.a.*"), ["a.b", "a.c"]);
+        assert_eq!(paths(&v, "$..b"), ["a.b", "a.c.b"]);
+        assert_eq!(paths(&v, "$..[0]"), ["a.b.0"]);
+        assert_eq!(paths(&v, "$.missing.b"), Vec::<String>::new());
+        assert_eq!(query(&v, "$.a.c.b").unwrap()[0].value, &yaml("x"));
+
+        for invalid in ["a.b", "$.", "$[", "$[1", "$['a]", "$.a b", "$[?(@.a ==)]", "$[?(1)]"] {
+            assert!(matches!(query(&v, invalid), Err(Error::Path(_))), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn filters() {
+        let v = yaml("
+items:
+  - {name: a, enabled: true, size: 1}
+  - {name: b, enabled: false, size: 5.5}
+  - {name: c, size: 10, tags: [x]}
+  - plain");
+        assert_eq!(paths(&v, "$.items[?(@.enabled == true)].name"), ["items.0.name"]);
+        assert_eq!(paths(&v, "$.items[?(@.enabled != true)]"), ["items.1", "items.2", "items.3"]);
+        assert_eq!(paths(&v, "$.items[?(@.size >= 5.5)]"), ["items.1", "items.2"]);
+        assert_eq!(paths(&v, "$.items[?(@.size < 10 && !@.enabled)]"), Vec::<String>::new());
+        assert_eq!(paths(&v, "$.items[?(@.size < 10 && !(@.enabled == true))]"), ["items.1"]);
+        assert_eq!(paths(&v, "$.items[?(@.tags || @.name == 'a')]"), ["items.0", "items.2"]);
+        assert_eq!(paths(&v, "$.items[?@.tags[0] == \"x\"]"), ["items.2"]);
+        assert_eq!(paths(&v, "$.items[?(@ == 'plain')]"), ["items.3"]);
+        assert_eq!(paths(&v, "$.items[?(@.name > 'a')].size"), ["items.1.size", "items.2.size"]);
+        assert_eq!(paths(&v, "$[?(@[0].size == 1)]"), ["items"]);
+    }
+
+    #[test]
+    fn large_steps() {
+        let v = yaml("{a: [10, 11, 12]}");
+        assert_eq!(paths(&v, "$.a[1::9223372036854775807]"), ["a.1"]);
+        assert_eq!(paths(&v, "$.a[::-9223372036854775807]"), ["a.2"]);
+        assert_eq!(slice_indices(Some(1), None, Some(i64::MAX), 3), [1]);
+        assert_eq!(slice_indices(None, None, Some(i64::MIN + 1), 3), [2]);
+    }
+}
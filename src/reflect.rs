@@ -0,0 +1,718 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Reflection of a type's serde attributes, by watching how it deserializes itself.
+
+use crate::document::ValueType;
+use crate::error::Result;
+use crate::path::key_token;
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+
+use std::cell::RefCell;
+
+/// Implements `deserialize_*` methods of `Tracer` so that probed null values are replaced
+/// by made up values of the right type
+macro_rules! forward_nulls {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+                if self.is_probed() {
+                    self.probe().$method(visitor)
+                } else {
+                    self.deserialize_any(visitor)
+                }
+            }
+        )*
+    };
+}
+
+/// Implements `deserialize_*` methods of `Probe` for integers
+macro_rules! probe_integers {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(mut self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+                self.record(Some(ValueType::Number));
+                visitor.visit_u64(0)
+            }
+        )*
+    };
+}
+
+/// The paths of optional fields, with the type they contain if it could be found
+pub(crate) type OptionalFields = Vec<(Vec<String>, Option<ValueType>)>;
+
+/// What was learned about a type while deserializing it
+#[derive(Debug, Default)]
+struct Trace {
+    /// For each path corresponding to a struct, the names of the fields it accepts
+    structs: Vec<(Vec<String>, &'static [&'static str])>,
+    /// Whether null values should be replaced by a `Probe`, to find optional fields
+    probe_nulls: bool,
+    /// The index of the null option whose type is probed, in the order they are found
+    probe_index: usize,
+    /// The number of null options found so far
+    options_seen: usize,
+    optional: OptionalFields,
+}
+
+/// A deserializer over a `Value` that records what the deserialized type asks for
+struct Tracer<'a> {
+    value: &'a Value,
+    path: Vec<String>,
+    trace: &'a RefCell<Trace>,
+}
+
+impl<'a> Tracer<'a> {
+    fn is_probed(&self) -> bool {
+        self.value.is_null() && self.trace.borrow().probe_nulls
+    }
+
+    fn probe(&self) -> Probe<'a> {
+        Probe {
+            path: None,
+            trace: self.trace,
+        }
+    }
+
+    fn child(&self, value: &'a Value, segment: String) -> Tracer<'a> {
+        let mut path = self.path.clone();
+        path.push(segment);
+        Tracer {
+            value,
+            path,
+            trace: self.trace,
+        }
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for Tracer<'a> {
+    type Error = serde_yaml::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        match self.value {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(b) => visitor.visit_bool(*b),
+            Value::Number(n) => {
+                if let Some(u) = n.as_u64() {
+                    visitor.visit_u64(u)
+                } else if let Some(i) = n.as_i64() {
+                    visitor.visit_i64(i)
+                } else {
+                    visitor.visit_f64(n.as_f64().unwrap_or(f64::NAN))
+                }
+            },
+            Value::String(s) => visitor.visit_str(s),
+            Value::Sequence(s) => visitor.visit_seq(SeqTracer {
+                iter: s.iter().enumerate(),
+                parent: &self,
+            }),
+            Value::Mapping(m) => visitor.visit_map(MapTracer {
+                iter: m.iter(),
+                pending: None,
+                parent: &self,
+            }),
+            // Tagged values are enums, which are not traced
+            Value::Tagged(_) => self.value.clone().deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        match self.value {
+            Value::Null if self.is_probed() => {
+                let index = {
+                    let mut trace = self.trace.borrow_mut();
+                    trace.options_seen += 1;
+                    trace.options_seen - 1
+                };
+                if index == self.trace.borrow().probe_index {
+                    // The probe records the inner type
+                    visitor.visit_some(Probe {
+                        path: Some(self.path),
+                        trace: self.trace,
+                    })
+                } else {
+                    visitor.visit_none()
+                }
+            },
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, name: &'static str, fields: &'static [&'static str], visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        if self.is_probed() {
+            return self.probe().deserialize_struct(name, fields, visitor);
+        }
+        self.trace.borrow_mut().structs.push((self.path.clone(), fields));
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.value.clone().deserialize_enum(name, variants, visitor)
+    }
+
+    forward_nulls! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char deserialize_str
+        deserialize_string deserialize_bytes deserialize_byte_buf deserialize_unit
+        deserialize_seq deserialize_map
+    }
+
+    serde::forward_to_deserialize_any! {
+        unit_struct tuple tuple_struct identifier ignored_any
+    }
+}
+
+/// A deserializer that makes up a value of whatever type is asked for, used in place of
+/// null values to find out what optional fields contain
+struct Probe<'a> {
+    /// The path of the optional field whose type should be recorded, if any
+    path: Option<Vec<String>>,
+    trace: &'a RefCell<Trace>,
+}
+
+impl Probe<'_> {
+    fn record(&mut self, ty: Option<ValueType>) {
+        if let Some(path) = self.path.take() {
+            self.trace.borrow_mut().optional.push((path, ty));
+        }
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for Probe<'a> {
+    type Error = serde_yaml::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(mut self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.record(None);
+        visitor.visit_unit()
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(mut self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.record(Some(ValueType::Bool));
+        visitor.visit_bool(false)
+    }
+
+    probe_integers! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(mut self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.record(Some(ValueType::Number));
+        visitor.visit_f32(0.0)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(mut self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.record(Some(ValueType::Number));
+        visitor.visit_f64(0.0)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(mut self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.record(Some(ValueType::String));
+        visitor.visit_char(' ')
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(mut self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.record(Some(ValueType::String));
+        visitor.visit_str("")
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(mut self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.record(Some(ValueType::List));
+        visitor.visit_bytes(&[])
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(mut self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.record(None);
+        visitor.visit_none()
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(mut self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.record(Some(ValueType::Null));
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(mut self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.record(Some(ValueType::List));
+        visitor.visit_seq(ProbeFields {
+            fields: [].iter(),
+            trace: self.trace,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, _len: usize, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(mut self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.record(Some(ValueType::Mapping));
+        visitor.visit_map(ProbeFields {
+            fields: [].iter(),
+            trace: self.trace,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(mut self, _name: &'static str, fields: &'static [&'static str], visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.record(Some(ValueType::Mapping));
+        visitor.visit_map(ProbeFields {
+            fields: fields.iter(),
+            trace: self.trace,
+        })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(mut self, _name: &'static str, variants: &'static [&'static str], visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.record(Some(ValueType::Tagged));
+        let variant = variants.first()
+            .ok_or_else(|| <serde_yaml::Error as de::Error>::custom("enum without variants"))?;
+        visitor.visit_enum(ProbeVariant {
+            variant,
+            trace: self.trace,
+        })
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        visitor.visit_str("")
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+}
+
+/// The fields of a made up struct, each with a made up value; empty for lists and maps
+struct ProbeFields<'a, I> {
+    fields: I,
+    trace: &'a RefCell<Trace>,
+}
+
+impl<'de, 'a, I> MapAccess<'de> for ProbeFields<'a, I>
+where I: Iterator<Item = &'a &'static str> {
+    type Error = serde_yaml::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> std::result::Result<Option<K::Value>, Self::Error> {
+        match self.fields.next() {
+            Some(field) => seed.deserialize(Value::from(*field)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> std::result::Result<V::Value, Self::Error> {
+        seed.deserialize(Probe {
+            path: None,
+            trace: self.trace,
+        })
+    }
+}
+
+impl<'de, 'a, I> SeqAccess<'de> for ProbeFields<'a, I>
+where I: Iterator<Item = &'a &'static str> {
+    type Error = serde_yaml::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, _seed: T) -> std::result::Result<Option<T::Value>, Self::Error> {
+        Ok(None)
+    }
+}
+
+/// The first variant of an enum, with made up content
+struct ProbeVariant<'a> {
+    variant: &'static str,
+    trace: &'a RefCell<Trace>,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for ProbeVariant<'a> {
+    type Error = serde_yaml::Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> std::result::Result<(V::Value, Self), Self::Error> {
+        let variant = seed.deserialize(Value::from(self.variant))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a> de::VariantAccess<'de> for ProbeVariant<'a> {
+    type Error = serde_yaml::Error;
+
+    fn unit_variant(self) -> std::result::Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> std::result::Result<T::Value, Self::Error> {
+        seed.deserialize(Probe {
+            path: None,
+            trace: self.trace,
+        })
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        Probe {
+            path: None,
+            trace: self.trace,
+        }.deserialize_seq(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        Probe {
+            path: None,
+            trace: self.trace,
+        }.deserialize_struct("", fields, visitor)
+    }
+}
+
+struct SeqTracer<'a, 'p, I> {
+    iter: I,
+    parent: &'p Tracer<'a>,
+}
+
+impl<'de, 'a, 'p, I> SeqAccess<'de> for SeqTracer<'a, 'p, I>
+where I: Iterator<Item = (usize, &'a Value)> {
+    type Error = serde_yaml::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> std::result::Result<Option<T::Value>, Self::Error> {
+        match self.iter.next() {
+            Some((i, v)) => seed.deserialize(self.parent.child(v, i.to_string())).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+struct MapTracer<'a, 'p, I> {
+    iter: I,
+    pending: Option<(&'a Value, &'a Value)>,
+    parent: &'p Tracer<'a>,
+}
+
+impl<'de, 'a, 'p, I> MapAccess<'de> for MapTracer<'a, 'p, I>
+where I: Iterator<Item = (&'a Value, &'a Value)> {
+    type Error = serde_yaml::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> std::result::Result<Option<K::Value>, Self::Error> {
+        match self.iter.next() {
+            Some((k, v)) => {
+                self.pending = Some((k, v));
+                seed.deserialize(k.clone()).map(Some)
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> std::result::Result<V::Value, Self::Error> {
+        let (k, v) = self.pending.take()
+            .ok_or_else(|| <serde_yaml::Error as de::Error>::custom("value requested before key"))?;
+        seed.deserialize(self.parent.child(v, key_token(k)))
+    }
+}
+
+/// Lists the optional fields of a type in its value (see `type_value`), with the type they
+/// contain if it could be found.
+///
+/// This deserializes the value again, with null values replaced by made up ones, to see
+/// which of them are deserialized as options and what they contain. Made up values may be
+/// rejected (e.g. because of aliases or by a custom `Deserialize` implementation), so each
+/// option is probed by its own deserialization, whose errors are ignored.
+fn optional_fields<T: DeserializeOwned>(value: &Value) -> OptionalFields {
+    let mut optional = vec![];
+    for probe_index in 0.. {
+        let trace = RefCell::new(Trace {
+            probe_nulls: true,
+            probe_index,
+            ..Trace::default()
+        });
+        let _ = T::deserialize(Tracer {
+            value,
+            path: vec![],
+            trace: &trace,
+        });
+        let trace = trace.into_inner();
+        if trace.options_seen <= probe_index {
+            break;
+        }
+        optional.extend(trace.optional);
+    }
+    optional
+}
+
+pub(crate) fn value_at<'v>(value: &'v Value, path: &[String]) -> Option<&'v Value> {
+    let mut current = value;
+    for segment in path {
+        current = match current {
+            Value::Mapping(m) => m.get(segment.as_str())?,
+            Value::Sequence(s) => s.get(segment.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+fn value_at_mut<'v>(value: &'v mut Value, path: &[String]) -> Option<&'v mut Value> {
+    let mut current = value;
+    for segment in path {
+        current = match current {
+            Value::Mapping(m) => m.get_mut(segment.as_str())?,
+            Value::Sequence(s) => s.get_mut(segment.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+/// Checks if a field that is accepted by a struct is actually an alias of a field that is
+/// already present, since deserializing both would fail with a duplicate field error
+fn is_alias<T: DeserializeOwned>(serialized: &Value, path: &[String], field: &str) -> bool {
+    let mut probe = serialized.clone();
+    if let Some(m) = value_at_mut(&mut probe, path).and_then(Value::as_mapping_mut) {
+        m.insert(Value::from(field), Value::Null);
+    }
+    serde_yaml::from_value::<T>(probe)
+        .err()
+        .is_some_and(|e| e.to_string().contains("duplicate field"))
+}
+
+/// Computes the YAML value corresponding to the default of a type, so that keys match what
+/// deserialization accepts.
+///
+/// The value is first obtained by serializing `T::default()`, which takes care of
+/// `#[serde(rename)]`, `#[serde(flatten)]` or `#[serde(skip)]`. Then, for each struct:
+///
+/// * fields that are serialized but not deserialized (e.g. `#[serde(skip_deserializing)]`)
+///   are removed;
+/// * fields that are accepted but not serialized (e.g. `#[serde(skip_serializing_if = "...")]`)
+///   are added with a null value.
+///
+/// Structs that contain a flattened field can't be inspected and are left as serialized.
+///
+/// Keys are in declaration order: the fields of a struct are sorted as they are declared,
+/// including the ones that are added, and flattened fields are inserted where the
+/// `#[serde(flatten)]` field is declared. This order is kept by `Documenter::apply_type`.
+///
+/// # Example
+///
+/// ```
+/// use serde_derive::{Serialize, Deserialize};
+///
+/// #[derive(Serialize, Deserialize, Default)]
+/// struct Config {
+///     #[serde(rename = "server-name")]
+///     name: String,
+///     #[serde(skip_serializing_if = "Option::is_none")]
+///     port: Option<u16>,
+///     #[serde(skip_deserializing)]
+///     computed: bool,
+/// }
+///
+/// let value = yaml_extras::document::type_value::<Config>()?;
+/// assert_eq!(value, serde_yaml::from_str::<serde_yaml::Value>("{server-name: '', port: null}")?);
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn type_value<T>() -> Result<Value>
+where T: Serialize + DeserializeOwned + Default {
+    Ok(type_info::<T>()?.0)
+}
+
+/// Same as `type_value`, but also lists the optional fields with the type they contain
+pub(crate) fn type_info<T>() -> Result<(Value, OptionalFields)>
+where T: Serialize + DeserializeOwned + Default {
+    let serialized = serde_yaml::to_value(T::default())?;
+    let trace = RefCell::new(Trace::default());
+    T::deserialize(Tracer {
+        value: &serialized,
+        path: vec![],
+        trace: &trace,
+    })?;
+
+    // Parents are traced before their children, so they are rebuilt first
+    let mut value = serialized.clone();
+    for (path, fields) in trace.into_inner().structs {
+        let Some(original) = value_at(&serialized, &path).and_then(Value::as_mapping) else {
+            continue;
+        };
+        let mut m = Mapping::new();
+        for field in fields {
+            if let Some(v) = original.get(*field) {
+                m.insert(Value::from(*field), v.clone());
+            } else if !is_alias::<T>(&serialized, &path, field) {
+                m.insert(Value::from(*field), Value::Null);
+            }
+        }
+        if let Some(target) = value_at_mut(&mut value, &path) {
+            *target = Value::Mapping(m);
+        }
+    }
+    let optional = optional_fields::<T>(&value);
+    Ok((value, optional))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_derive::{Serialize, Deserialize};
+
+    #[derive(Serialize, Deserialize, Default)]
+    struct Inner {
+        #[serde(alias = "size")]
+        length: u32,
+        #[serde(skip_serializing_if = "Vec::is_empty", default)]
+        items: Vec<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Default)]
+    #[allow(dead_code)]
+    struct Outer {
+        #[serde(rename = "renamed")]
+        name: String,
+        #[serde(skip)]
+        skipped: bool,
+        #[serde(skip_deserializing)]
+        output_only: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        maybe: Option<i32>,
+        inner: Inner,
+    }
+
+    #[test]
+    fn serde_attributes() {
+        let expected = r#"
+renamed: ""
+maybe: null
+inner:
+    length: 0
+    items: null
+"#;
+        let expected: Value = serde_yaml::from_str(expected).unwrap();
+        let actual = type_value::<Outer>().unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn flatten() {
+        #[derive(Serialize, Deserialize, Default)]
+        struct Flattened {
+            first: bool,
+            #[serde(flatten)]
+            inner: Inner,
+            last: bool,
+        }
+
+        let expected = r#"
+first: false
+length: 0
+last: false
+"#;
+        let expected: Value = serde_yaml::from_str(expected).unwrap();
+        let actual = type_value::<Flattened>().unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn field_order() {
+        #[derive(Serialize, Deserialize, Default)]
+        struct Ordered {
+            zeta: u8,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            alpha: Option<u8>,
+            inner: Inner,
+            mu: u8,
+        }
+
+        let keys = |v: &Value| -> Vec<String> {
+            v.as_mapping()
+                .unwrap()
+                .keys()
+                .map(|k| k.as_str().unwrap().to_owned())
+                .collect()
+        };
+        let value = type_value::<Ordered>().unwrap();
+        assert_eq!(keys(&value), vec!["zeta", "alpha", "inner", "mu"]);
+        assert_eq!(keys(&value["inner"]), vec!["length", "items"]);
+
+        #[derive(Serialize, Deserialize, Default)]
+        struct Outer {
+            last: u8,
+            #[serde(flatten)]
+            ordered: Ordered,
+            first: u8,
+        }
+        let value = type_value::<Outer>().unwrap();
+        assert_eq!(keys(&value), vec!["last", "zeta", "inner", "mu", "first"]);
+    }
+
+    #[test]
+    fn optional_fields() {
+        #[derive(Serialize, Deserialize, Default)]
+        enum Mode {
+            #[default]
+            Fast,
+        }
+
+        #[derive(Serialize, Deserialize, Default)]
+        struct Options {
+            // Added as null, but not an option
+            #[serde(skip_serializing_if = "Vec::is_empty", default)]
+            list: Vec<u8>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            port: Option<u16>,
+            name: Option<String>,
+            inner: Option<Inner>,
+            mode: Option<Mode>,
+            items: Option<Vec<String>>,
+            set: Option<u8>,
+        }
+
+        let (_, optional) = type_info::<Options>().unwrap();
+        let path = |s: &str| vec![s.to_owned()];
+        assert_eq!(optional, vec![
+            (path("port"), Some(ValueType::Number)),
+            (path("name"), Some(ValueType::String)),
+            (path("inner"), Some(ValueType::Mapping)),
+            (path("mode"), Some(ValueType::Tagged)),
+            (path("items"), Some(ValueType::List)),
+            (path("set"), Some(ValueType::Number)),
+        ]);
+    }
+
+    #[test]
+    fn optional_fields_in_maps() {
+        #[derive(Serialize, Deserialize)]
+        struct Ports {
+            ports: std::collections::BTreeMap<u16, Option<String>>,
+        }
+
+        impl Default for Ports {
+            fn default() -> Self {
+                Ports { ports: [(80, None)].into_iter().collect() }
+            }
+        }
+
+        let (_, optional) = type_info::<Ports>().unwrap();
+        assert_eq!(optional, vec![(vec!["ports".to_owned(), "80".to_owned()], Some(ValueType::String))]);
+    }
+}
@@ -4,6 +4,7 @@
 
 use crate::error::Result;
 use crate::error::Error;
+use crate::error::{check_depth, DEFAULT_MAX_DEPTH};
 
 /// Restructure a YAML map so that keys containing dots are transformed into appropriate
 /// fields of sub-maps.
@@ -41,14 +42,23 @@ use crate::error::Error;
 pub struct Restructurer<'r> {
     recursive: bool,
     ignore: Vec<&'r str>,
+    max_depth: usize,
 }
 
+impl<'r> Default for Restructurer<'r> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
 impl<'r> Restructurer<'r> {
     /// Creates a new Restructurer with default values
     pub fn new() -> Self {
         Restructurer {
             recursive: true,
             ignore: vec![],
+            max_depth: DEFAULT_MAX_DEPTH,
         }
     }
 
@@ -101,6 +111,22 @@ impl<'r> Restructurer<'r> {
         self
     }
 
+    /// Sets the maximum depth of the restructured value, including the levels created by
+    /// dotted keys, beyond which `Error::DepthExceeded` is returned (default is 128)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let res = yaml_extras::Restructurer::new()
+    ///     .max_depth(2)
+    ///     .apply_str("a.b.c: 1");
+    /// assert!(matches!(res, Err(yaml_extras::Error::DepthExceeded { .. })));
+    /// ```
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
     /// Restructures a `serde::yaml` value containing your YAML structure.
     ///
     /// # Example
@@ -124,6 +150,12 @@ impl<'r> Restructurer<'r> {
     /// # Ok::<(), yaml_extras::Error>(())
     /// ```
     pub fn apply_value(self: &Self, value: &mut serde_yaml::Value) -> Result<()> {
+        check_depth(value, self.max_depth)?;
+        self.apply_at(value, &mut vec![])
+    }
+
+    /// Restructures a value found at `path`
+    fn apply_at(&self, value: &mut serde_yaml::Value, path: &mut Vec<String>) -> Result<()> {
         use serde_yaml::Value;
         let m = value.as_mapping_mut()
             .ok_or(Error::Restructure("not a mapping".into()))?;
@@ -144,7 +176,7 @@ impl<'r> Restructurer<'r> {
                  .to_owned())
             .collect();
         for k in dotted_keys {
-            self.restructure_key(m, &k)?;
+            self.restructure_key(m, &k, path)?;
         }
         
         if self.recursive {
@@ -153,9 +185,10 @@ impl<'r> Restructurer<'r> {
                 .map(|(k, _)| k.as_str().unwrap().to_owned())
                 .collect();
             for k in map_keys {
-                self.apply_value(m.get_mut(&k)
-                                    .unwrap())?;
-                
+                path.push(k);
+                self.apply_at(m.get_mut(path.last().unwrap())
+                                 .unwrap(), path)?;
+                path.pop();
             }
         }
         
@@ -193,7 +226,9 @@ impl<'r> Restructurer<'r> {
     
     /// Restructure a key inside a mapping so that if it's dotted it will be inserted
     /// to submap.
-    fn restructure_key(self: &Self, m: &mut serde_yaml::Mapping, k: &str) -> Result<()> {
+    ///
+    /// `path` is the path of `m`.
+    fn restructure_key(self: &Self, m: &mut serde_yaml::Mapping, k: &str, path: &mut Vec<String>) -> Result<()> {
         use serde_yaml::Value;
 
         if let Some((mut prefix, mut suffix)) = k.split_once('.') {
@@ -201,7 +236,7 @@ impl<'r> Restructurer<'r> {
             for i in &self.ignore {
                 if k.starts_with(i) {
                     // k is in ignore list, revamp prefix and suffix
-                    if let Some((p, s)) = k.split_once(&format!("{i}.")) {
+                    if let Some((_, s)) = k.split_once(&format!("{i}.")) {
                         prefix = i;
                         suffix = s;
                         break;
@@ -217,9 +252,19 @@ impl<'r> Restructurer<'r> {
                 return Ok(());
             }
 
+            // The value is moved one level deeper, inside `prefix`
+            if path.len() + 2 > self.max_depth {
+                let mut keys = path.clone();
+                keys.push(k.to_owned());
+                return Err(Error::DepthExceeded {
+                    path: keys.join("."),
+                    limit: self.max_depth,
+                });
+            }
+
         
             
-            let val = m.remove(&k).unwrap();
+            let val = m.remove(k).unwrap();
 
             if !m.contains_key(prefix) {
                 m.insert(Value::String(prefix.into()),
@@ -233,7 +278,10 @@ impl<'r> Restructurer<'r> {
             inner.insert(Value::String(suffix.into()),
                          val);
             // Check the inner map and the suffix to see if it still contains dots
-            self.restructure_key(inner, suffix)?;
+            path.push(prefix.to_owned());
+            let result = self.restructure_key(inner, suffix, path);
+            path.pop();
+            result?;
         }
     
     Ok(())
@@ -328,7 +376,7 @@ foo.bar.baz: true
 "#;
         let v1: Value = serde_yaml::from_str(s1).unwrap();
         let v2: Value = Restructurer::new()
-            .apply_str(&s2)
+            .apply_str(s2)
             .unwrap();
         assert_eq!(v1, v2);
     }
@@ -347,9 +395,30 @@ foo.ignored.key.baz: true
         let v1: Value = serde_yaml::from_str(s1).unwrap();
         let v2: Value = Restructurer::new()
             .ignore(vec!["ignored.key"])
-            .apply_str(&s2)
+            .apply_str(s2)
             .unwrap();
         assert_eq!(v1, v2);
     }
-}
 
+    #[test]
+    fn max_depth() {
+        let err = Restructurer::new()
+            .max_depth(3)
+            .apply_str("a:\n  b:\n    c.d.e: 1\n")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "maximum depth of 3 exceeded at 'a.b.c.d.e'");
+        let err = Restructurer::new()
+            .max_depth(3)
+            .apply_str("a:\n  b.c.d: 1\n")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "maximum depth of 3 exceeded at 'a.b.c.d'");
+
+        // Ignored prefixes are a single level
+        let v = Restructurer::new()
+            .max_depth(2)
+            .ignore(vec!["x.y.z"])
+            .apply_str("x.y.z.w: 1")
+            .unwrap();
+        assert_eq!(v, serde_yaml::from_str::<Value>("x.y.z: {w: 1}").unwrap());
+    }
+}
@@ -0,0 +1,208 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::path::key_token;
+
+use serde_yaml::{Mapping, Value};
+
+/// Options of `to_json_schema`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaOptions {
+    /// Whether objects accept properties that are not in the value (default is `false`)
+    pub additional_properties: bool,
+    /// The title of the schema, if any
+    pub title: Option<String>,
+}
+
+fn type_schema(name: &str) -> Mapping {
+    let mut schema = Mapping::new();
+    schema.insert("type".into(), name.into());
+    schema
+}
+
+/// The schema of a value, without the top-level keywords
+fn schema_of(value: &Value, options: &SchemaOptions) -> Mapping {
+    match value {
+        // Null values don't tell anything about the type
+        Value::Null => Mapping::new(),
+        Value::Bool(_) => type_schema("boolean"),
+        Value::Number(n) if n.is_f64() => type_schema("number"),
+        Value::Number(_) => type_schema("integer"),
+        Value::String(_) => type_schema("string"),
+        Value::Sequence(s) => {
+            let mut schema = type_schema("array");
+            if let Some(items) = s.iter().map(|item| schema_of(item, options)).reduce(unify) {
+                schema.insert("items".into(), Value::Mapping(items));
+            }
+            schema
+        },
+        Value::Mapping(m) => {
+            let mut schema = type_schema("object");
+            let properties: Mapping = m.iter()
+                .map(|(k, v)| (Value::String(key_token(k)), Value::Mapping(schema_of(v, options))))
+                .collect();
+            let required: Vec<Value> = m.iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, _)| Value::String(key_token(k)))
+                .collect();
+            schema.insert("properties".into(), Value::Mapping(properties));
+            if !required.is_empty() {
+                schema.insert("required".into(), Value::Sequence(required));
+            }
+            schema.insert("additionalProperties".into(), options.additional_properties.into());
+            schema
+        },
+        Value::Tagged(t) => schema_of(&t.value, options),
+    }
+}
+
+/// A schema accepting the values of both `a` and `b`
+fn unify(a: Mapping, b: Mapping) -> Mapping {
+    if a == b || a.is_empty() {
+        return a;
+    }
+    if b.is_empty() {
+        return b;
+    }
+    let ty = |s: &Mapping| s.get("type").and_then(Value::as_str).map(str::to_owned);
+    match (ty(&a).as_deref(), ty(&b).as_deref()) {
+        (Some("integer"), Some("number")) => return b,
+        (Some("number"), Some("integer")) => return a,
+        (Some("array"), Some("array")) => {
+            let mut a = a;
+            let items = match (a.remove("items"), b.get("items")) {
+                (Some(Value::Mapping(x)), Some(Value::Mapping(y))) => Some(unify(x, y.clone())),
+                (x, y) => x.or(y.cloned()).and_then(|v| v.as_mapping().cloned()),
+            };
+            if let Some(items) = items {
+                a.insert("items".into(), Value::Mapping(items));
+            }
+            return a;
+        },
+        (Some("object"), Some("object")) => {
+            let mut a = a;
+            let mut properties = a.remove("properties")
+                .and_then(|p| p.as_mapping().cloned())
+                .unwrap_or_default();
+            for (k, v) in b.get("properties").and_then(Value::as_mapping).into_iter().flatten() {
+                let unified = match (properties.remove(k), v) {
+                    (Some(Value::Mapping(x)), Value::Mapping(y)) => Value::Mapping(unify(x, y.clone())),
+                    (_, v) => v.clone(),
+                };
+                properties.insert(k.clone(), unified);
+            }
+            let required_b = b.get("required").and_then(Value::as_sequence).cloned().unwrap_or_default();
+            let required: Vec<Value> = a.remove("required")
+                .and_then(|r| r.as_sequence().cloned())
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|k| required_b.contains(k))
+                .collect();
+            let additional = a.remove("additionalProperties");
+            a.insert("properties".into(), Value::Mapping(properties));
+            if !required.is_empty() {
+                a.insert("required".into(), Value::Sequence(required));
+            }
+            if let Some(additional) = additional {
+                a.insert("additionalProperties".into(), additional);
+            }
+            return a;
+        },
+        _ => (),
+    }
+    // Alternatives
+    let mut alternatives = match a.get("anyOf") {
+        Some(Value::Sequence(alternatives)) if a.len() == 1 => alternatives.clone(),
+        _ => vec![Value::Mapping(a)],
+    };
+    let b = Value::Mapping(b);
+    if !alternatives.contains(&b) {
+        alternatives.push(b);
+    }
+    let mut schema = Mapping::new();
+    schema.insert("anyOf".into(), Value::Sequence(alternatives));
+    schema
+}
+
+/// Generates a JSON Schema (draft 2020-12) describing the structure of `value`, e.g. to let
+/// editors validate documents that should look like it
+///
+/// The keys with a non-null value are required, while null values accept anything. The
+/// items of sequences share a schema accepting all of them. Tags are ignored.
+///
+/// # Example
+///
+/// ```
+/// use yaml_extras::SchemaOptions;
+///
+/// let defaults: serde_yaml::Value = serde_yaml::from_str("{host: localhost, port: 80, proxy: null}")?;
+/// let schema = yaml_extras::to_json_schema(&defaults, SchemaOptions::default());
+/// assert_eq!(schema, serde_yaml::from_str::<serde_yaml::Value>(r#"
+/// $schema: https://json-schema.org/draft/2020-12/schema
+/// type: object
+/// properties:
+///   host: {type: string}
+///   port: {type: integer}
+///   proxy: {}
+/// required: [host, port]
+/// additionalProperties: false
+/// "#)?);
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn to_json_schema(value: &Value, options: SchemaOptions) -> Value {
+    let mut root = Mapping::new();
+    root.insert("$schema".into(), "https://json-schema.org/draft/2020-12/schema".into());
+    if let Some(title) = &options.title {
+        root.insert("title".into(), title.as_str().into());
+    }
+    root.extend(schema_of(value, &options));
+    Value::Mapping(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yaml;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn schemas() {
+        let options = SchemaOptions {
+            additional_properties: true,
+            title: Some("Config".into()),
+        };
+        let schema = to_json_schema(&yaml("
+servers:
+  - {host: a, port: 80, tags: []}
+  - {host: b, port: 80.5, tls: true, tags: [x]}
+mixed: [1, a, !t 2, null]
+8080: []
+"), options);
+        assert_eq!(schema, yaml(r#"
+$schema: https://json-schema.org/draft/2020-12/schema
+title: Config
+type: object
+properties:
+  servers:
+    type: array
+    items:
+      type: object
+      properties:
+        host: {type: string}
+        port: {type: number}
+        tags: {type: array, items: {type: string}}
+        tls: {type: boolean}
+      required: [host, port, tags]
+      additionalProperties: true
+  mixed:
+    type: array
+    items: {}
+  "8080": {type: array}
+required: [servers, mixed, "8080"]
+additionalProperties: true
+"#));
+        assert_eq!(to_json_schema(&yaml("[1, a, true]"), SchemaOptions::default())["items"],
+                   yaml("{anyOf: [{type: integer}, {type: string}, {type: boolean}]}"));
+    }
+}
@@ -0,0 +1,246 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Parsing that remembers where each value comes from in the source (requires the `spans`
+//! feature).
+//!
+//! `Spanned::parse` gives both the value and the location of each of its nodes, so that
+//! errors about a path can be reported with a line and a column.
+//!
+//! # Example
+//!
+//! ```
+//! use yaml_extras::span::Spanned;
+//!
+//! let overlay = Spanned::parse("
+//! server:
+//!   port: [8080]
+//! ")?;
+//! let base: serde_yaml::Value = serde_yaml::from_str("server: {port: 80}")?;
+//! let err = yaml_extras::Merger::new()
+//!     .type_conflicts(yaml_extras::TypeConflict::Error)
+//!     .merged(&base, overlay.value())
+//!     .unwrap_err();
+//! assert_eq!(overlay.locate(err).to_string(),
+//!     "impossible to merge YAML values at 'server.port': number in the base, sequence in the overlay (line 3, column 9)");
+//! # Ok::<(), yaml_extras::Error>(())
+//! ```
+
+use crate::error::{Error, Result};
+use crate::events;
+use crate::path::tokens;
+
+pub use crate::events::{Location, Span};
+
+use serde_yaml::Value;
+
+use std::collections::BTreeMap;
+
+/// A parsed document with the location of its nodes
+#[derive(Debug, Clone)]
+pub struct Spanned {
+    value: Value,
+    spans: BTreeMap<Vec<String>, Span>,
+    keys: BTreeMap<Vec<String>, Span>,
+}
+
+impl Spanned {
+    /// Parses a YAML document
+    pub fn parse(source: &str) -> Result<Self> {
+        let value = serde_yaml::from_str(source)?;
+        let nodes = events::nodes(source)?;
+        Ok(Spanned {
+            value,
+            spans: nodes.spans,
+            keys: nodes.keys,
+        })
+    }
+
+    /// The parsed value
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Returns the parsed value
+    pub fn into_value(self) -> Value {
+        self.value
+    }
+
+    /// The span of the value at `keys`
+    #[cfg(feature = "edit")]
+    pub(crate) fn value_span(&self, keys: &[String]) -> Option<Span> {
+        self.spans.get(keys).copied()
+    }
+
+    /// The span of the key of the value at `keys`, if it is in a mapping
+    #[cfg(feature = "edit")]
+    pub(crate) fn key_span(&self, keys: &[String]) -> Option<Span> {
+        self.keys.get(keys).copied()
+    }
+
+    /// The end of the last node nested in the value at `keys`
+    #[cfg(feature = "edit")]
+    pub(crate) fn descendants_end(&self, keys: &[String]) -> Option<Location> {
+        self.spans.range(keys.to_vec()..)
+            .take_while(|(path, _)| path.starts_with(keys))
+            .filter(|(path, _)| path.len() > keys.len())
+            .map(|(_, span)| span.end)
+            .max()
+    }
+
+    /// The span of the value at `path` (dotted or a JSON Pointer), if it exists
+    ///
+    /// Values that are inside an alias don't have a span of their own.
+    pub fn span(&self, path: &str) -> Option<Span> {
+        self.spans.get(&tokens(path)).copied()
+    }
+
+    /// The location to show for `path`: the span of its key when it is in a mapping, else the
+    /// span of its value
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let spanned = yaml_extras::span::Spanned::parse("server:\n  port: 80\n")?;
+    /// let span = spanned.location_of("server.port").unwrap();
+    /// assert_eq!((span.start.line, span.start.column), (2, 3));
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn location_of(&self, path: &str) -> Option<Span> {
+        let keys = tokens(path);
+        self.keys.get(&keys)
+            .or_else(|| self.spans.get(&keys))
+            .copied()
+    }
+
+    /// The path of the innermost key or value at `line` and `column` (starting at 1), if any
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let spanned = yaml_extras::span::Spanned::parse("server:\n  port: 80\n")?;
+    /// assert_eq!(spanned.path_at(2, 4), Some(vec!["server".to_owned(), "port".to_owned()]));
+    /// assert_eq!(spanned.path_at(1, 1), Some(vec!["server".to_owned()]));
+    /// assert_eq!(spanned.path_at(5, 1), None);
+    /// # Ok::<(), yaml_extras::Error>(())
+    /// ```
+    pub fn path_at(&self, line: usize, column: usize) -> Option<Vec<String>> {
+        let contains = |span: &Span| (span.start.line, span.start.column) <= (line, column)
+            && (line, column) < (span.end.line, span.end.column);
+        self.keys.iter()
+            .chain(&self.spans)
+            .filter(|(_, span)| contains(span))
+            .map(|(path, _)| path)
+            .max_by_key(|path| path.len())
+            .cloned()
+    }
+
+    /// Adds the location of the value an error is about to it, when it is known
+    ///
+    /// If there is no value at the path of the error, the location of its closest existing
+    /// parent is used.
+    pub fn locate(&self, err: Error) -> Error {
+        let path = match &err {
+            Error::TypeMismatch { path, .. } | Error::Deserialize { path, .. } | Error::DepthExceeded { path, .. } => path,
+            _ => return err,
+        };
+        let mut keys = tokens(path);
+        loop {
+            if let Some(span) = self.spans.get(&keys) {
+                return Error::Located {
+                    line: span.start.line,
+                    column: span.start.column,
+                    source: Box::new(err),
+                };
+            }
+            if keys.pop().is_none() {
+                return err;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn start(spanned: &Spanned, path: &str) -> Option<(usize, usize)> {
+        spanned.span(path).map(|span| (span.start.line, span.start.column))
+    }
+
+    #[test]
+    fn spans() {
+        let spanned = Spanned::parse("\
+a:
+  b: 1
+  'c': [x, {d: y}]
+? [complex, key]
+: {e: 2}
+f: &anchor
+  g: 3
+h: *anchor
+8080: port
+").unwrap();
+        assert_eq!(start(&spanned, ""), Some((1, 1)));
+        assert_eq!(start(&spanned, "a"), Some((2, 3)));
+        assert_eq!(start(&spanned, "a.b"), Some((2, 6)));
+        assert_eq!(start(&spanned, "a.c"), Some((3, 8)));
+        assert_eq!(start(&spanned, "a.c.1.d"), Some((3, 16)));
+        assert_eq!(start(&spanned, "f.g"), Some((7, 6)));
+        assert_eq!(start(&spanned, "h"), Some((8, 4)));
+        assert_eq!(start(&spanned, "8080"), Some((9, 7)));
+        assert_eq!(start(&spanned, "h.g"), None);
+        assert_eq!(start(&spanned, "e"), None);
+        let span = spanned.span("a.c.0").unwrap();
+        assert_eq!((span.start.index, span.end.index), (18, 19));
+    }
+
+    #[test]
+    fn located_errors() {
+        let spanned = Spanned::parse("servers:\n  - {host: a, port: eighty}\n").unwrap();
+        #[derive(serde_derive::Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Server {
+            host: String,
+            port: u16,
+        }
+        let err = crate::path::get_as::<Vec<Server>>(spanned.value(), "servers").unwrap_err();
+        match spanned.locate(err) {
+            Error::Located { line, column, source } => {
+                assert_eq!((line, column), (2, 21));
+                assert!(matches!(*source, Error::Deserialize { .. }));
+            },
+            err => panic!("unexpected error {err:?}"),
+        }
+        let err = spanned.locate(Error::DepthExceeded { path: "servers.0.missing.x".into(), limit: 1 });
+        assert!(matches!(err, Error::Located { line: 2, column: 5, .. }));
+        assert!(matches!(spanned.locate(Error::Path("x".into())), Error::Path(_)));
+    }
+
+    #[test]
+    fn locations() {
+        let spanned = Spanned::parse("\
+a:
+  b: [1, {c: 2}]
+  d:
+    e: 3
+").unwrap();
+        let loc = |path| spanned.location_of(path).map(|span| (span.start.line, span.start.column));
+        assert_eq!(loc("a.b"), Some((2, 3)));
+        assert_eq!(loc("a.b.1"), Some((2, 10)));
+        assert_eq!(loc("a.b.1.c"), Some((2, 11)));
+        assert_eq!(loc(""), Some((1, 1)));
+        assert_eq!(loc("a.x"), None);
+
+        let path_at = |line, column| spanned.path_at(line, column).map(|path| path.join("."));
+        assert_eq!(path_at(2, 3).as_deref(), Some("a.b"));
+        assert_eq!(path_at(2, 7).as_deref(), Some("a.b.0"));
+        assert_eq!(path_at(2, 14).as_deref(), Some("a.b.1.c"));
+        assert_eq!(path_at(2, 15).as_deref(), Some("a.b.1"));
+        assert_eq!(path_at(2, 16).as_deref(), Some("a.b"));
+        assert_eq!(path_at(4, 8).as_deref(), Some("a.d.e"));
+        assert_eq!(path_at(3, 1).as_deref(), Some("a"));
+    }
+}
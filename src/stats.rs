@@ -0,0 +1,97 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::document::ValueType;
+use crate::path::walk;
+
+use serde_yaml::Value;
+
+use std::collections::BTreeMap;
+
+/// Statistics about a value, see `stats`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Stats {
+    /// The number of keys, in all the mappings
+    pub keys: usize,
+    /// The number of values that are scalars or empty collections
+    pub leaves: usize,
+    /// The depth of the most nested value, the root being at depth 0
+    pub max_depth: usize,
+    /// The number of values of each type, tagged values being only counted as such
+    pub types: BTreeMap<ValueType, usize>,
+    /// The path whose dotted form is the longest
+    pub longest_path: Vec<String>,
+}
+
+/// Computes statistics about a value, e.g. to check that a generated document is not too
+/// complex
+///
+/// # Example
+///
+/// ```
+/// use yaml_extras::document::ValueType;
+///
+/// let config: serde_yaml::Value = serde_yaml::from_str("{server: {host: localhost, ports: [80, 443]}, debug: false}")?;
+/// let stats = yaml_extras::stats(&config);
+/// assert_eq!(stats.keys, 4);
+/// assert_eq!(stats.leaves, 4);
+/// assert_eq!(stats.max_depth, 3);
+/// assert_eq!(stats.types[&ValueType::Number], 2);
+/// assert_eq!(stats.longest_path, ["server", "ports", "0"]);
+/// # Ok::<(), yaml_extras::Error>(())
+/// ```
+pub fn stats(value: &Value) -> Stats {
+    let mut stats = Stats::default();
+    let mut longest = 0;
+    walk(value, |path, v| {
+        *stats.types.entry(ValueType::from(v)).or_default() += 1;
+        let v = match v {
+            Value::Tagged(t) => &t.value,
+            v => v,
+        };
+        match v {
+            Value::Mapping(m) if !m.is_empty() => stats.keys += m.len(),
+            Value::Sequence(s) if !s.is_empty() => (),
+            _ => stats.leaves += 1,
+        }
+        stats.max_depth = stats.max_depth.max(path.len());
+        let len = path.iter().map(|key| key.chars().count() + 1).sum::<usize>();
+        if len > longest {
+            longest = len;
+            stats.longest_path = path.to_vec();
+        }
+    });
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yaml;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn statistics() {
+        let s = stats(&yaml("{a: [1, {}, []], bb: !t {c: null, dddd: x}, e: [[[1.5]]]}"));
+        assert_eq!(s, Stats {
+            keys: 5,
+            leaves: 6,
+            max_depth: 4,
+            types: [
+                (ValueType::Null, 1),
+                (ValueType::Number, 2),
+                (ValueType::String, 1),
+                (ValueType::List, 5),
+                (ValueType::Mapping, 2),
+                (ValueType::Tagged, 1),
+            ].into_iter().collect(),
+            longest_path: vec!["bb".into(), "dddd".into()],
+        });
+        assert_eq!(stats(&yaml("1")), Stats {
+            leaves: 1,
+            types: [(ValueType::Number, 1)].into_iter().collect(),
+            ..Default::default()
+        });
+    }
+}
@@ -0,0 +1,352 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! A small template language, using a subset of Jinja's syntax, to render a documentation
+//! tree.
+//!
+//! Supported constructs are:
+//!
+//! * `{{ var.field }}` to display a field of a node;
+//! * `{% for var in list %}…{% endfor %}`, `var` being an identifier, optionally filtered with
+//!   `if [not] var.field`;
+//! * `{% if [not] var.field %}…{% else %}…{% endif %}`;
+//! * `{#…#}` for comments;
+//! * a `-` just inside a delimiter (e.g. `{%-` or `-%}`) to strip the whitespace on that side.
+//!
+//! This is a deliberately small engine, written here rather than depending on a full template
+//! crate such as `minijinja`, since only node fields and lists need to be rendered. It does
+//! *not* support filters (`{{ x | upper }}`), expressions or comparisons, `elif`, `set`,
+//! macros, includes, template inheritance or loop variables. Nothing is escaped: values are
+//! written as they are, so the template is responsible for producing valid HTML or Markdown.
+//!
+//! Malformed templates (unclosed delimiters, unknown or unbalanced statements, invalid
+//! variables or loop variables) are rejected when parsed, and unknown variables or fields
+//! when rendered, with an `Error::Document` starting with "invalid template".
+
+use crate::document::DocNode;
+use crate::error::{Error, Result};
+
+/// A dotted variable, e.g. `leaf.description`
+type Var = Vec<String>;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Condition {
+    negated: bool,
+    var: Var,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Text(String),
+    Var(Var),
+    For {
+        name: String,
+        list: Var,
+        filter: Option<Condition>,
+        body: Vec<Node>,
+    },
+    If {
+        condition: Condition,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Text(String),
+    Expr(String),
+    Stmt(String),
+}
+
+fn error(msg: String) -> Error {
+    Error::Document(format!("invalid template: {msg}"))
+}
+
+/// Splits a template between text, `{{ expressions }}` and `{% statements %}`
+fn tokenize(template: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut rest = template;
+    let mut trim_next = false;
+    loop {
+        let start = ["{{", "{%", "{#"].iter()
+            .filter_map(|d| rest.find(d))
+            .min();
+        let Some(start) = start else {
+            break;
+        };
+        let mut text = &rest[..start];
+        if trim_next {
+            text = text.trim_start();
+        }
+        let open = &rest[start..start + 2];
+        let close = match open {
+            "{{" => "}}",
+            "{%" => "%}",
+            _ => "#}",
+        };
+        let inner_start = start + 2;
+        let end = rest[inner_start..].find(close)
+            .ok_or_else(|| error(format!("unclosed '{open}'")))?;
+        let mut inner = &rest[inner_start..inner_start + end];
+        if let Some(s) = inner.strip_prefix('-') {
+            text = text.trim_end();
+            inner = s;
+        }
+        trim_next = false;
+        if let Some(s) = inner.strip_suffix('-') {
+            trim_next = true;
+            inner = s;
+        }
+        if !text.is_empty() {
+            tokens.push(Token::Text(text.to_owned()));
+        }
+        let inner = inner.trim().to_owned();
+        match open {
+            "{{" => tokens.push(Token::Expr(inner)),
+            "{%" => tokens.push(Token::Stmt(inner)),
+            _ => (),
+        }
+        rest = &rest[inner_start + end + 2..];
+    }
+    let text = if trim_next { rest.trim_start() } else { rest };
+    if !text.is_empty() {
+        tokens.push(Token::Text(text.to_owned()));
+    }
+    Ok(tokens)
+}
+
+fn parse_var(s: &str) -> Result<Var> {
+    let valid = !s.is_empty() && s.split('.')
+        .all(|p| !p.is_empty() && p.chars().all(|c| c.is_alphanumeric() || c == '_'));
+    if !valid {
+        return Err(error(format!("invalid variable '{s}'")));
+    }
+    Ok(s.split('.').map(|s| s.to_owned()).collect())
+}
+
+/// Parses the name of a loop variable, which must be an identifier
+fn parse_name(s: &str) -> Result<String> {
+    let mut chars = s.chars();
+    let valid = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+        && !["for", "in", "if", "not", "else"].contains(&s);
+    if !valid {
+        return Err(error(format!("invalid loop variable '{s}'")));
+    }
+    Ok(s.to_owned())
+}
+
+fn parse_condition(words: &[&str]) -> Result<Condition> {
+    match words {
+        ["not", var] => Ok(Condition { negated: true, var: parse_var(var)? }),
+        [var] => Ok(Condition { negated: false, var: parse_var(var)? }),
+        _ => Err(error(format!("invalid condition '{}'", words.join(" ")))),
+    }
+}
+
+/// Parses tokens until one of the `until` statements, which is returned
+fn parse_block<I>(tokens: &mut I, until: &[&str]) -> Result<(Vec<Node>, Option<String>)>
+where I: Iterator<Item = Token> {
+    let mut nodes = vec![];
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Text(s) => nodes.push(Node::Text(s)),
+            Token::Expr(s) => nodes.push(Node::Var(parse_var(&s)?)),
+            Token::Stmt(s) => {
+                let words: Vec<&str> = s.split_whitespace().collect();
+                match words.as_slice() {
+                    [w] if until.contains(w) => return Ok((nodes, Some(s))),
+                    ["for", name, "in", list, filter @ ..] => {
+                        let name = parse_name(name)?;
+                        let list = parse_var(list)?;
+                        let filter = match filter {
+                            [] => None,
+                            ["if", condition @ ..] => Some(parse_condition(condition)?),
+                            _ => return Err(error(format!("invalid statement '{s}'"))),
+                        };
+                        let (body, _) = parse_block(tokens, &["endfor"])?;
+                        nodes.push(Node::For { name, list, filter, body });
+                    },
+                    ["if", condition @ ..] => {
+                        let condition = parse_condition(condition)?;
+                        let (then, end) = parse_block(tokens, &["else", "endif"])?;
+                        let otherwise = if end.as_deref() == Some("else") {
+                            parse_block(tokens, &["endif"])?.0
+                        } else {
+                            vec![]
+                        };
+                        nodes.push(Node::If { condition, then, otherwise });
+                    },
+                    _ => return Err(error(format!("unexpected statement '{s}'"))),
+                }
+            },
+        }
+    }
+    if until.is_empty() {
+        Ok((nodes, None))
+    } else {
+        Err(error(format!("missing '{}'", until.last().unwrap_or(&""))))
+    }
+}
+
+/// What a variable evaluates to
+enum Resolved<'n> {
+    Text(String),
+    Nodes(Vec<&'n DocNode>),
+}
+
+impl Resolved<'_> {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Resolved::Text(s) => !s.is_empty(),
+            Resolved::Nodes(n) => !n.is_empty(),
+        }
+    }
+}
+
+/// A parsed template
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    pub(crate) fn parse(template: &str) -> Result<Template> {
+        let mut tokens = tokenize(template)?.into_iter();
+        let (nodes, _) = parse_block(&mut tokens, &[])?;
+        Ok(Template { nodes })
+    }
+
+    /// Renders the template with `root` bound to the given node. Fields other than
+    /// `children` and `leaves` are obtained with `field`, which returns `None` for unknown
+    /// fields.
+    pub(crate) fn render(&self, root: &DocNode, field: &dyn Fn(&DocNode, &str) -> Option<String>) -> Result<String> {
+        let mut out = String::new();
+        let mut scope = vec![("root".to_owned(), root)];
+        render_nodes(&self.nodes, &mut scope, field, &mut out)?;
+        Ok(out)
+    }
+}
+
+fn resolve<'n>(var: &Var, scope: &[(String, &'n DocNode)], field: &dyn Fn(&DocNode, &str) -> Option<String>) -> Result<Resolved<'n>> {
+    let node = scope.iter()
+        .rev()
+        .find(|(name, _)| *name == var[0])
+        .map(|(_, node)| *node)
+        .ok_or_else(|| error(format!("unknown variable '{}'", var[0])))?;
+    match &var[1..] {
+        [] => Ok(Resolved::Nodes(vec![node])),
+        [f] if f == "children" => Ok(Resolved::Nodes(node.children.iter().collect())),
+        [f] if f == "leaves" => Ok(Resolved::Nodes(node.leaves())),
+        [f] => field(node, f)
+            .map(Resolved::Text)
+            .ok_or_else(|| error(format!("unknown field '{}'", var.join(".")))),
+        _ => Err(error(format!("unknown field '{}'", var.join(".")))),
+    }
+}
+
+fn check(condition: &Condition, scope: &[(String, &DocNode)], field: &dyn Fn(&DocNode, &str) -> Option<String>) -> Result<bool> {
+    let truthy = resolve(&condition.var, scope, field)?.is_truthy();
+    Ok(truthy != condition.negated)
+}
+
+fn render_nodes<'n>(nodes: &[Node], scope: &mut Vec<(String, &'n DocNode)>, field: &dyn Fn(&DocNode, &str) -> Option<String>, out: &mut String) -> Result<()> {
+    for node in nodes {
+        match node {
+            Node::Text(s) => out.push_str(s),
+            Node::Var(var) => match resolve(var, scope, field)? {
+                Resolved::Text(s) => out.push_str(&s),
+                // A node is displayed as its path
+                Resolved::Nodes(nodes) => {
+                    let paths: Vec<String> = nodes.iter()
+                        .map(|n| n.path.join("."))
+                        .collect();
+                    out.push_str(&paths.join(", "));
+                },
+            },
+            Node::For { name, list, filter, body } => {
+                let Resolved::Nodes(items) = resolve(list, scope, field)? else {
+                    return Err(error(format!("'{}' is not a list", list.join("."))));
+                };
+                for item in items {
+                    scope.push((name.clone(), item));
+                    let keep = match filter {
+                        Some(condition) => check(condition, scope, field)?,
+                        None => true,
+                    };
+                    if keep {
+                        render_nodes(body, scope, field, out)?;
+                    }
+                    scope.pop();
+                }
+            },
+            Node::If { condition, then, otherwise } => {
+                if check(condition, scope, field)? {
+                    render_nodes(then, scope, field, out)?;
+                } else {
+                    render_nodes(otherwise, scope, field, out)?;
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn tokens() {
+        let tokens = tokenize("a {{ x }}\n  {%- if y -%}  b{# comment #}").unwrap();
+        assert_eq!(tokens, vec![Token::Text("a ".to_owned()),
+                                Token::Expr("x".to_owned()),
+                                Token::Stmt("if y".to_owned()),
+                                Token::Text("b".to_owned())]);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(Template::parse("{{ x").is_err());
+        assert!(Template::parse("{% for x in y %}").is_err());
+        assert!(Template::parse("{% if a b %}{% endif %}").is_err());
+        assert!(Template::parse("{% endfor %}").is_err());
+        assert!(Template::parse("{{ a..b }}").is_err());
+    }
+
+    #[test]
+    fn malformed() {
+        let message = |template| match Template::parse(template) {
+            Err(Error::Document(msg)) => msg,
+            result => panic!("unexpected result for {template:?}: {result:?}"),
+        };
+        assert_eq!(message("a {% if x"), "invalid template: unclosed '{%'");
+        assert_eq!(message("a {# comment"), "invalid template: unclosed '{#'");
+        assert_eq!(message("{{ x | upper }}"), "invalid template: invalid variable 'x | upper'");
+        assert_eq!(message("{{ }}"), "invalid template: invalid variable ''");
+        assert_eq!(message("{% if x %}a"), "invalid template: missing 'endif'");
+        assert_eq!(message("{% if x %}{% else %}a"), "invalid template: missing 'endif'");
+        assert_eq!(message("{% if x %}{% elif y %}{% endif %}"), "invalid template: unexpected statement 'elif y'");
+        assert_eq!(message("{% if x == 1 %}{% endif %}"), "invalid template: invalid condition 'x == 1'");
+        assert_eq!(message("{% for x in y %}{% endif %}"), "invalid template: unexpected statement 'endif'");
+        assert_eq!(message("{% for x y %}{% endfor %}"), "invalid template: unexpected statement 'for x y'");
+        assert_eq!(message("{% for x in y else %}{% endfor %}"), "invalid template: invalid statement 'for x in y else'");
+        assert_eq!(message("{% for x.y in z %}{% endfor %}"), "invalid template: invalid loop variable 'x.y'");
+        assert_eq!(message("{% for 1x in z %}{% endfor %}"), "invalid template: invalid loop variable '1x'");
+        assert_eq!(message("{% for a, b in z %}{% endfor %}"), "invalid template: unexpected statement 'for a, b in z'");
+        assert_eq!(message("{% for a,b in z %}{% endfor %}"), "invalid template: invalid loop variable 'a,b'");
+        assert_eq!(message("{% for in in z %}{% endfor %}"), "invalid template: invalid loop variable 'in'");
+        assert_eq!(message("{% for x in y..z %}{% endfor %}"), "invalid template: invalid variable 'y..z'");
+        assert_eq!(message("{% for x in %}{% endfor %}"), "invalid template: unexpected statement 'for x in'");
+        assert_eq!(message("{% for x in y if %}{% endfor %}"), "invalid template: invalid condition ''");
+        // The header is checked before the body
+        assert_eq!(message("{% for x- in y %}"), "invalid template: invalid loop variable 'x-'");
+        assert_eq!(message("{% else %}"), "invalid template: unexpected statement 'else'");
+        assert_eq!(message("{% set x = 1 %}"), "invalid template: unexpected statement 'set x = 1'");
+        // Delimiters in plain text are not statements
+        assert_eq!(Template::parse("a }} b %}").unwrap().nodes, vec![Node::Text("a }} b %}".to_owned())]);
+    }
+}
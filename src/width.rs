@@ -0,0 +1,202 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Display width of strings in a terminal, so that CJK text and colors don't break alignment.
+//!
+//! This is an approximation, with tables of the most common ranges rather than the full
+//! Unicode data (as the `unicode-width` crate has), and terminals don't all agree anyway.
+//! Grapheme clusters are only handled for combining marks, emoji joined with a zero width
+//! joiner and skin tone modifiers, which are counted with the character before them.
+
+/// Ranges of characters that are displayed on two columns (East Asian Wide and Fullwidth,
+/// including emoji with an emoji presentation)
+const WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115F),
+    (0x231A, 0x231B),
+    (0x2329, 0x232A),
+    (0x23E9, 0x23EC),
+    (0x23F0, 0x23F0),
+    (0x23F3, 0x23F3),
+    (0x25FD, 0x25FE),
+    (0x2614, 0x2615),
+    (0x2648, 0x2653),
+    (0x267F, 0x267F),
+    (0x2693, 0x2693),
+    (0x26A1, 0x26A1),
+    (0x26AA, 0x26AB),
+    (0x26BD, 0x26BE),
+    (0x26C4, 0x26C5),
+    (0x26CE, 0x26CE),
+    (0x26D4, 0x26D4),
+    (0x26EA, 0x26EA),
+    (0x26F2, 0x26F3),
+    (0x26F5, 0x26F5),
+    (0x26FA, 0x26FA),
+    (0x26FD, 0x26FD),
+    (0x2705, 0x2705),
+    (0x270A, 0x270B),
+    (0x2728, 0x2728),
+    (0x274C, 0x274C),
+    (0x274E, 0x274E),
+    (0x2753, 0x2755),
+    (0x2757, 0x2757),
+    (0x2795, 0x2797),
+    (0x27B0, 0x27B0),
+    (0x27BF, 0x27BF),
+    (0x2B1B, 0x2B1C),
+    (0x2B50, 0x2B50),
+    (0x2B55, 0x2B55),
+    (0x2E80, 0x303E),
+    (0x3041, 0x33FF),
+    (0x3400, 0x4DBF),
+    (0x4E00, 0x9FFF),
+    (0xA000, 0xA4CF),
+    (0xA960, 0xA97F),
+    (0xAC00, 0xD7A3),
+    (0xF900, 0xFAFF),
+    (0xFE10, 0xFE19),
+    (0xFE30, 0xFE6F),
+    (0xFF00, 0xFF60),
+    (0xFFE0, 0xFFE6),
+    (0x1F004, 0x1F004),
+    (0x1F0CF, 0x1F0CF),
+    (0x1F18E, 0x1F18E),
+    (0x1F191, 0x1F19A),
+    (0x1F200, 0x1F202),
+    (0x1F210, 0x1F23B),
+    (0x1F240, 0x1F248),
+    (0x1F250, 0x1F251),
+    (0x1F260, 0x1F265),
+    (0x1F300, 0x1F64F),
+    (0x1F680, 0x1F6C5),
+    (0x1F6CC, 0x1F6CC),
+    (0x1F6D0, 0x1F6D2),
+    (0x1F6D5, 0x1F6D7),
+    (0x1F6DC, 0x1F6DF),
+    (0x1F6EB, 0x1F6EC),
+    (0x1F6F4, 0x1F6FC),
+    (0x1F7E0, 0x1F7EB),
+    (0x1F7F0, 0x1F7F0),
+    (0x1F90C, 0x1F9FF),
+    (0x1FA70, 0x1FAFF),
+    (0x20000, 0x2FFFD),
+    (0x30000, 0x3FFFD),
+];
+
+/// Ranges of characters that are not displayed on their own (combining marks, zero width
+/// spaces and joiners, variation selectors, tags)
+const ZERO: &[(u32, u32)] = &[
+    (0x0300, 0x036F),
+    (0x0483, 0x0489),
+    (0x0591, 0x05BD),
+    (0x0610, 0x061A),
+    (0x064B, 0x065F),
+    (0x1AB0, 0x1AFF),
+    (0x1DC0, 0x1DFF),
+    (0x200B, 0x200F),
+    (0x20D0, 0x20FF),
+    (0x302A, 0x302F),
+    (0x3099, 0x309A),
+    (0xFE00, 0xFE0F),
+    (0xFE20, 0xFE2F),
+    (0xFEFF, 0xFEFF),
+    (0xE0020, 0xE007F),
+    (0xE0100, 0xE01EF),
+];
+
+/// The zero width joiner, which joins emoji into a single one
+const ZWJ: char = '\u{200D}';
+
+/// Whether `c` is an emoji skin tone modifier, displayed with the emoji before it
+fn is_emoji_modifier(c: char) -> bool {
+    ('\u{1F3FB}'..='\u{1F3FF}').contains(&c)
+}
+
+fn in_ranges(c: char, ranges: &[(u32, u32)]) -> bool {
+    let c = c as u32;
+    ranges.binary_search_by(|&(start, end)| {
+        if end < c {
+            std::cmp::Ordering::Less
+        } else if start > c {
+            std::cmp::Ordering::Greater
+        } else {
+            std::cmp::Ordering::Equal
+        }
+    }).is_ok()
+}
+
+/// Number of columns used to display a character
+pub(crate) fn char_width(c: char) -> usize {
+    if c.is_control() || in_ranges(c, ZERO) {
+        0
+    } else if in_ranges(c, WIDE) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Number of columns used to display a string, ignoring ANSI escape sequences
+pub(crate) fn display_width(s: &str) -> usize {
+    let mut width = 0;
+    // Whether the next character is part of the previous one
+    let mut joined = false;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip until the final byte of the sequence, e.g. 'm' for colors
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else if joined || (width > 0 && is_emoji_modifier(c)) {
+            joined = false;
+        } else {
+            width += char_width(c);
+            joined = c == ZWJ && width > 0;
+        }
+    }
+    width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn widths() {
+        assert_eq!(display_width("port"), 4);
+        assert_eq!(display_width("ポート"), 6);
+        assert_eq!(display_width("설정"), 4);
+        assert_eq!(display_width("e\u{301}te\u{301}"), 3);
+        assert_eq!(display_width("\x1b[1;31mkey\x1b[0m"), 3);
+    }
+
+    #[test]
+    fn emoji() {
+        // Transport and map symbols
+        assert_eq!(display_width("\u{1F680}"), 2);
+        assert_eq!(display_width("\u{1F6A7}\u{1F6D1}"), 4);
+        assert_eq!(display_width("\u{1F6C6}"), 1);
+        // Miscellaneous symbols and dingbats, only some of which are wide
+        assert_eq!(display_width("\u{2614}\u{26A1}\u{2705}\u{274C}"), 8);
+        assert_eq!(display_width("\u{2600}\u{2713}"), 2);
+        assert_eq!(display_width("\u{2764}\u{FE0F}"), 1);
+        // Grapheme clusters
+        assert_eq!(display_width("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}"), 2);
+        assert_eq!(display_width("\u{1F44D}\u{1F3FD}a"), 3);
+        assert_eq!(display_width("\u{1F3FD}"), 2);
+        assert_eq!(display_width("\u{200D}a"), 1);
+    }
+
+    #[test]
+    fn sorted_tables() {
+        for ranges in [WIDE, ZERO] {
+            assert!(ranges.iter().all(|(start, end)| start <= end));
+            assert!(ranges.windows(2).all(|w| w[0].1 < w[1].0));
+        }
+    }
+}